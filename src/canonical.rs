@@ -0,0 +1,192 @@
+use serde_json::{Map, Number, Value};
+
+use crate::coordinate::Coordinate;
+use crate::session::Session;
+
+// CANONICAL SESSION FILES
+// Sessions are saved in a canonical form, so saving the same session twice gives the same
+// bytes and editing a cell changes about one line of the file, which keeps the diffs of
+// session files under version control small:
+// - maps keyed by coordinates (cells, conditional formatting, comments) are written in
+//   coordinate order, a grid before the cells nested in it and the cells of a grid row by
+//   row, every other object has its keys sorted
+// - numbers are always written the same way, see `write_number`
+// - the file is indented, and with `Layout::CellPerLine` every entry of a map keyed by
+//   coordinates is written on a single line
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Layout {
+    Indented,
+    CellPerLine,
+}
+
+pub fn canonical_json(session: &Session, layout: Layout) -> Result<String, serde_json::Error> {
+    let value = serde_json::to_value(session)?;
+    let mut out = String::new();
+    write_value(&mut out, &value, Some(0), layout);
+    out.push('\n');
+    Ok(out)
+}
+
+// writes `value` indented at `depth`, or on a single line if `depth` is None
+fn write_value(out: &mut String, value: &Value, depth: Option<usize>, layout: Layout) {
+    match value {
+        Value::Number(number) => write_number(out, number),
+        Value::Array(items) if !items.is_empty() => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_break(out, depth.map(|d| d + 1));
+                write_value(out, item, depth.map(|d| d + 1), layout);
+            }
+            write_break(out, depth);
+            out.push(']');
+        }
+        Value::Object(map) if !map.is_empty() => {
+            let (entries, by_coordinate) = sorted_entries(map);
+            out.push('{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_break(out, depth.map(|d| d + 1));
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push_str(": ");
+                let item_depth = match layout {
+                    Layout::CellPerLine if by_coordinate => None,
+                    _ => depth.map(|d| d + 1),
+                };
+                write_value(out, item, item_depth, layout);
+            }
+            write_break(out, depth);
+            out.push('}');
+        }
+        // null, booleans, strings and empty arrays or objects
+        _ => out.push_str(&value.to_string()),
+    }
+}
+
+// a new line indented at `depth`, or a space between the items of a single line
+fn write_break(out: &mut String, depth: Option<usize>) {
+    match depth {
+        Some(depth) => {
+            out.push('\n');
+            out.push_str(&"  ".repeat(depth));
+        }
+        None => out.push(' '),
+    }
+}
+
+// integers are written as they are, and floats in their shortest form that reads back as
+// the same float, with a fraction even when it's zero (`90.0`), an exponent when they're
+// very large or small (`1e21`) and without the sign of -0.0. The floats are written by
+// `ryu` directly so the output doesn't change with the version of serde_json.
+fn write_number(out: &mut String, number: &Number) {
+    if let Some(integer) = number.as_u64() {
+        out.push_str(&integer.to_string());
+    } else if let Some(integer) = number.as_i64() {
+        out.push_str(&integer.to_string());
+    } else if let Some(float) = number.as_f64() {
+        let float = if float == 0.0 { 0.0 } else { float };
+        out.push_str(ryu::Buffer::new().format_finite(float));
+    }
+}
+
+// the entries of an object in canonical order, and whether its keys are all coordinates
+fn sorted_entries(map: &Map<String, Value>) -> (Vec<(&String, &Value)>, bool) {
+    let coordinates: Option<Vec<Coordinate>> = map.keys().map(|k| Coordinate::parse(k)).collect();
+    match coordinates {
+        Some(coordinates) => {
+            let mut entries: Vec<(Coordinate, (&String, &Value))> =
+                coordinates.into_iter().zip(map.iter()).collect();
+            entries.sort_by(|(a, _), (b, _)| a.row_cols.cmp(&b.row_cols));
+            (entries.into_iter().map(|(_, entry)| entry).collect(), true)
+        }
+        None => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            (entries, false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate::{CoordinateParser, Rule};
+    use crate::grammar::Grammar;
+    use crate::grammar_map::{build_grammar_map, MapEntry};
+    use crate::util::non_zero_u32_tuple;
+    use crate::{coord, g, grid};
+    use pest::Parser;
+    use std::collections::HashMap;
+    use std::num::NonZeroU32;
+
+    fn grammars() -> HashMap<Coordinate, Grammar> {
+        let mut map = HashMap::new();
+        build_grammar_map(
+            &mut map,
+            coord!("root"),
+            grid![
+                [
+                    g!(Grammar::input("", "a")),
+                    grid![[g!(Grammar::input("", "b"))]]
+                ],
+                [g!(Grammar::input("", "c")), g!(Grammar::input("", "d"))]
+            ],
+        );
+        map
+    }
+
+    #[test]
+    fn test_byte_identical() {
+        let first = Session::of_grammars("canonical", grammars());
+        // the same cells inserted in another order
+        let mut reversed = HashMap::new();
+        let mut cells: Vec<(Coordinate, Grammar)> = grammars().into_iter().collect();
+        cells.sort_by(|(a, _), (b, _)| b.row_cols.cmp(&a.row_cols));
+        reversed.extend(cells);
+        let second = Session::of_grammars("canonical", reversed);
+        for &layout in &[Layout::Indented, Layout::CellPerLine] {
+            let json = canonical_json(&first, layout).unwrap();
+            assert_eq!(json, canonical_json(&second, layout).unwrap());
+            // and it reads back as the same session
+            let read: Session = serde_json::from_str(&json).unwrap();
+            assert_eq!(read.grammars, first.grammars);
+            assert_eq!(canonical_json(&read, layout).unwrap(), json);
+        }
+    }
+
+    #[test]
+    fn test_coordinate_order() {
+        let json = canonical_json(
+            &Session::of_grammars("canonical", grammars()),
+            Layout::CellPerLine,
+        )
+        .unwrap();
+        let order = [
+            "\"root\": {",
+            "\"root-A1\": {",
+            "\"root-B1\": {",
+            "\"root-B1-A1\": {",
+            "\"root-A2\": {",
+            "\"root-B2\": {",
+        ];
+        let positions: Vec<usize> = order.iter().map(|key| json.find(key).unwrap()).collect();
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+        // one cell per line
+        let line = json.lines().find(|l| l.contains("\"root-B2\"")).unwrap();
+        assert!(line.trim_start().starts_with("\"root-B2\": {") && line.ends_with('}'));
+        assert!(line.contains("\"kind\": { \"Input\": \"d\" }"));
+    }
+
+    #[test]
+    fn test_numbers() {
+        let mut out = String::new();
+        let value = serde_json::json!([90.0, -0.0, 0.1, 3, -2, 1e21]);
+        write_value(&mut out, &value, None, Layout::Indented);
+        assert_eq!(out, "[ 90.0, 0.0, 0.1, 3, -2, 1e21 ]");
+    }
+}
@@ -1,13 +1,24 @@
 use crate::coordinate::*;
-use stdweb::web::{html_element::TextAreaElement, IHtmlElement};
+use stdweb::web::html_element::TextAreaElement;
+use stdweb::Value;
 use yew::prelude::*;
 
+// default syntax highlighting mode of editors
+pub const DEFAULT_EDITOR_MODE: &str = "python";
+
+// modes loaded in static/index.html, shown in the mode dropdown of editors
+pub const EDITOR_MODES: [&str; 2] = ["python", "javascript"];
+
 pub struct CodeMirror {
     code: String,
     mode: String,
     coordinate: Coordinate,
+    onchange: Callback<String>,
     link: ComponentLink<Self>,
     node_ref: NodeRef,
+    // the CodeMirror instance replacing the textarea once it's mounted, when the
+    // CodeMirror library isn't loaded the plain textarea is used instead
+    editor: Option<Value>,
 }
 
 pub enum Msg {
@@ -19,6 +30,19 @@ pub struct Props {
     pub content: String,
     pub mode: String,
     pub coordinate: Coordinate,
+    // called with the new content every time the code is edited
+    #[props(required)]
+    pub onchange: Callback<String>,
+}
+
+impl CodeMirror {
+    fn mode(mode: &str) -> String {
+        if mode.is_empty() {
+            DEFAULT_EDITOR_MODE.to_string()
+        } else {
+            mode.to_string()
+        }
+    }
 }
 
 impl Component for CodeMirror {
@@ -28,40 +52,76 @@ impl Component for CodeMirror {
     fn create(props: Self::Properties, link: ComponentLink<Self>) -> Self {
         CodeMirror {
             code: props.content,
-            mode: props.mode,
+            mode: CodeMirror::mode(&props.mode),
             coordinate: props.coordinate,
+            onchange: props.onchange,
             link,
             node_ref: NodeRef::default(),
+            editor: None,
         }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
             Msg::UpdateCode(code) => {
-                self.code = code;
-                true
+                if code != self.code {
+                    self.code = code.clone();
+                    self.onchange.emit(code);
+                }
+                // the editor (or textarea) already shows the new code
+                false
+            }
+        }
+    }
+
+    fn change(&mut self, props: Self::Properties) -> ShouldRender {
+        let mode = CodeMirror::mode(&props.mode);
+        if mode != self.mode {
+            self.mode = mode;
+            if let Some(editor) = &self.editor {
+                js! { @{editor.clone()}.setOption("mode", @{self.mode.clone()}); };
+            }
+        }
+        // content changed outside of the editor, e.g. a session was loaded
+        let content_changed = props.content != self.code;
+        if content_changed {
+            self.code = props.content;
+            if let Some(editor) = &self.editor {
+                js! { @{editor.clone()}.setValue(@{self.code.clone()}); };
             }
         }
+        self.coordinate = props.coordinate;
+        self.onchange = props.onchange;
+        self.editor.is_none() && content_changed
     }
 
     fn mounted(&mut self) -> ShouldRender {
-        /// TODO: this code runs the codemirror instantiation, but it doesn't work too well.
-        /// leave it disabled for now, so we'll just have a regular textarea in it's place
-        // if let Some(input) = self.node_ref.cast::<TextAreaElement>() {
-        //     input.focus();
-        // }
-        // let element_id = format! {"codemirror-{}", self.coordinate.to_string()};
-        // js! {
-        //     let codeMirrorInstance = CodeMirror.fromTextArea(document.getElementById(@{element_id}), {
-        //         lineNumbers: true,
-        //         mode: "python"
-        //     });
-        //     codeMirrorInstance.setValue(@{&self.code});
-        //     setTimeout(function() {
-        //         codeMirrorInstance.refresh();
-        //         console.log("codemirror instance setup finished!");
-        //     },1);
-        // }
+        let textarea = match self.node_ref.cast::<TextAreaElement>() {
+            Some(textarea) => textarea,
+            None => return false,
+        };
+        let on_change = self.link.callback(Msg::UpdateCode);
+        let editor = js! {
+            if (typeof CodeMirror === "undefined") {
+                return null;
+            }
+            let editor = CodeMirror.fromTextArea(@{textarea}, {
+                lineNumbers: true,
+                mode: @{self.mode.clone()},
+            });
+            editor.setValue(@{self.code.clone()});
+            editor.onChangeCallback = @{move |code: String| on_change.emit(code)};
+            editor.on("change", function (instance) {
+                instance.onChangeCallback(instance.getValue());
+            });
+            // the size of the cell is only known once it's laid out
+            setTimeout(function () { editor.refresh(); }, 1);
+            return editor;
+        };
+        self.editor = match editor {
+            Value::Null | Value::Undefined => None,
+            editor => Some(editor),
+        };
         false
     }
 
@@ -75,4 +135,14 @@ impl Component for CodeMirror {
             </textarea>
         }
     }
+
+    fn destroy(&mut self) {
+        if let Some(editor) = self.editor.take() {
+            js! {
+                let editor = @{editor};
+                editor.onChangeCallback.drop();
+                editor.toTextArea();
+            };
+        }
+    }
 }
@@ -0,0 +1,879 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::num::NonZeroU32;
+
+use crate::coordinate::{Coordinate, Line};
+use crate::grammar::{Grammar, Kind};
+use crate::session::Session;
+use crate::util::merge_cells;
+
+// COLLABORATION
+// A session can be edited by several people at once through a relay (static/relay.js), a
+// small WebSocket server that numbers every app connected to it (a "site"), puts the
+// operations the sites send in one order and forwards them to every site. The relay doesn't
+// know about grids: each site applies the operations in the relay's order to the session
+// that was shared, so they all end up with the same cells.
+// - a site applies its edits right away and sends them, they're pending until the relay
+//   sends them back in its order
+// - an operation is made without the operations of other sites the relay ordered after the
+//   last one its site had seen (its `base`), so it's transformed over them before it's
+//   applied, e.g. a cell set in row 3 is set in row 4 if a row was inserted above it
+// - it's made after the pending operations of its site though, which the operations of the
+//   other sites are transformed over first (see `Bridge`)
+// - the pending operations are transformed the same way over the operations of other
+//   sites, and applied again on top of them
+// Cursors and selections are sent to the other sites as they move, they aren't ordered.
+
+// where a relay listens unless it's given another host and port, keep in sync with
+// static/relay.js
+pub const DEFAULT_RELAY_HOST: &str = "127.0.0.1";
+pub const DEFAULT_RELAY_PORT: u16 = 8765;
+
+// the URL of a relay listening where it does by default, the relay only accepts sockets
+// with its token added to it though (`?token=...`)
+pub fn default_relay_url() -> String {
+    format! {"ws://{}:{}", DEFAULT_RELAY_HOST, DEFAULT_RELAY_PORT}
+}
+
+// an edit of the cells of a shared session
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Op {
+    // set-cell: the new grammar of a cell, or None once it's removed with what's nested in it
+    SetCell(Coordinate, Option<Box<Grammar>>),
+    // insert-row and insert-col: a row or column of empty cells before `index` in a grid
+    InsertLine(
+        /* grid */ Coordinate,
+        Line,
+        /* index */ NonZeroU32,
+    ),
+    // nest-grid: turns a cell into a grid of empty cells
+    NestGrid(
+        Coordinate,
+        /* rows */ NonZeroU32,
+        /* cols */ NonZeroU32,
+    ),
+    // merge: merges the range between two corners of a grid, see `merge_cells`
+    Merge(
+        /* grid */ Coordinate,
+        (NonZeroU32, NonZeroU32),
+        (NonZeroU32, NonZeroU32),
+    ),
+}
+
+// where someone is in the session, shown in the grids of the other sites
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Presence {
+    pub name: String,
+    pub cursor: Option<Coordinate>,
+    // the corners of the selected range
+    pub selection: Option<(Coordinate, Coordinate)>,
+}
+
+// what a site sends to the relay
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ClientMessage {
+    // replaces the shared session
+    Share(Box<Session>),
+    Op { base: usize, op: Op },
+    Presence(Presence),
+}
+
+// what the relay sends to the sites, `Op::seq` counts the operations since the session was
+// shared
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum RelayMessage {
+    Welcome {
+        site: u32,
+    },
+    Shared {
+        site: u32,
+        session: Box<Session>,
+    },
+    Op {
+        seq: usize,
+        site: u32,
+        base: usize,
+        op: Op,
+    },
+    Presence {
+        site: u32,
+        presence: Presence,
+    },
+    Left {
+        site: u32,
+    },
+}
+
+// what becomes of the shared tab after a message from the relay
+pub enum Received {
+    // another site shared its session, it replaces the one of the tab
+    Session(Box<Session>),
+    // the cells of the tab once the operation is applied
+    Cells(HashMap<Coordinate, Grammar>),
+    // a site joined, moved or left
+    Presence,
+    Nothing,
+}
+
+#[derive(Debug)]
+pub struct Collab {
+    pub url: String,
+    // the name shown with this site's cursor
+    pub name: String,
+    // the tab that is edited with the other sites
+    pub tab: usize,
+    // the number the relay gave this site, None until it's connected
+    pub site: Option<u32>,
+    pub peers: BTreeMap<u32, Presence>,
+    // the cells with the operations the relay ordered, the same on every site
+    confirmed: HashMap<Coordinate, Grammar>,
+    // the operations the relay ordered by the site they come from, as they were applied
+    log: Vec<(u32, Option<Op>)>,
+    // the operations of this site the relay hasn't sent back yet, as they apply after `log`
+    // (None once another site removed what they change)
+    pending: Vec<Option<Op>>,
+    // what the other sites had seen when they sent their last operation
+    bridges: HashMap<u32, Bridge>,
+    // the cells as of the last operation, edits are found by comparing the tab with them
+    shadow: HashMap<Coordinate, Grammar>,
+    // the presence sent last
+    presence: Presence,
+}
+
+impl Collab {
+    pub fn new(
+        url: String,
+        name: String,
+        tab: usize,
+        grammars: &HashMap<Coordinate, Grammar>,
+    ) -> Collab {
+        Collab {
+            url,
+            name,
+            tab,
+            site: None,
+            peers: BTreeMap::new(),
+            confirmed: grammars.clone(),
+            log: vec![],
+            pending: vec![],
+            bridges: HashMap::new(),
+            shadow: grammars.clone(),
+            presence: Presence::default(),
+        }
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.site.is_some()
+    }
+
+    // the operations that aren't ordered by the relay yet
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    // shares `session` with the other sites, which open it instead of theirs
+    pub fn share(&mut self, session: &Session) -> ClientMessage {
+        self.confirmed = session.grammars.clone();
+        self.shadow = session.grammars.clone();
+        self.log.clear();
+        self.pending.clear();
+        self.bridges.clear();
+        ClientMessage::Share(Box::new(session.clone()))
+    }
+
+    // the operations of the edits made to the tab since the last ones
+    pub fn local_edits(&mut self, grammars: &HashMap<Coordinate, Grammar>) -> Vec<ClientMessage> {
+        let ops = cell_ops(&self.shadow, grammars);
+        if ops.is_empty() {
+            return vec![];
+        }
+        self.shadow = grammars.clone();
+        ops.into_iter().map(|op| self.send(op)).collect()
+    }
+
+    // the operation of an edit about to be made to the tab (after the edits made before it),
+    // the differences between the edit and `op` are sent with the next edits
+    pub fn local_op(
+        &mut self,
+        grammars: &HashMap<Coordinate, Grammar>,
+        op: Op,
+    ) -> Vec<ClientMessage> {
+        let mut messages = self.local_edits(grammars);
+        apply(&mut self.shadow, &op);
+        messages.push(self.send(op));
+        messages
+    }
+
+    fn send(&mut self, op: Op) -> ClientMessage {
+        self.pending.push(Some(op.clone()));
+        ClientMessage::Op {
+            base: self.log.len(),
+            op,
+        }
+    }
+
+    // the presence to send if it changed
+    pub fn move_to(
+        &mut self,
+        cursor: Option<Coordinate>,
+        selection: Option<(Coordinate, Coordinate)>,
+    ) -> Option<ClientMessage> {
+        let presence = Presence {
+            name: self.name.clone(),
+            cursor,
+            selection,
+        };
+        if !self.is_connected() || presence == self.presence {
+            return None;
+        }
+        self.presence = presence.clone();
+        Some(ClientMessage::Presence(presence))
+    }
+
+    pub fn receive(&mut self, message: RelayMessage) -> Received {
+        match message {
+            RelayMessage::Welcome { site } => {
+                self.site = Some(site);
+                // the other sites get this site's presence again
+                self.presence = Presence::default();
+                Received::Presence
+            }
+            // this site already shared it
+            RelayMessage::Shared { site, .. } if Some(site) == self.site => Received::Nothing,
+            RelayMessage::Shared { session, .. } => {
+                self.confirmed = session.grammars.clone();
+                self.shadow = session.grammars.clone();
+                self.log.clear();
+                self.pending.clear();
+                self.bridges.clear();
+                Received::Session(session)
+            }
+            // an operation from before the session was shared, or sent twice
+            RelayMessage::Op { seq, .. } if seq != self.log.len() + 1 => Received::Nothing,
+            // this site's own operation, already transformed over the ones ordered before it
+            RelayMessage::Op { site, .. } if Some(site) == self.site => {
+                let op = if self.pending.is_empty() {
+                    None
+                } else {
+                    self.pending.remove(0)
+                };
+                self.ordered(site, op)
+            }
+            RelayMessage::Op { site, base, op, .. } => {
+                let bridge = self.bridges.entry(site).or_default();
+                bridge.advance(site, &self.log, base);
+                bridge.pending.push(Some(op));
+                // as if the site got the operations ordered since it sent it
+                let mut ordered = bridge.clone();
+                ordered.advance(site, &self.log, self.log.len());
+                let op = ordered.pending.pop().flatten();
+                if let Some(done) = &op {
+                    self.pending = transform_all(&self.pending, done);
+                }
+                self.ordered(site, op)
+            }
+            RelayMessage::Presence { site, .. } if Some(site) == self.site => Received::Nothing,
+            RelayMessage::Presence { site, presence } => {
+                self.peers.insert(site, presence);
+                Received::Presence
+            }
+            RelayMessage::Left { site } => {
+                self.peers.remove(&site);
+                Received::Presence
+            }
+        }
+    }
+
+    // applies an operation in the relay's order, as it applies after the ones before it
+    fn ordered(&mut self, site: u32, op: Option<Op>) -> Received {
+        if let Some(op) = &op {
+            apply(&mut self.confirmed, op);
+        }
+        self.log.push((site, op));
+        let mut cells = self.confirmed.clone();
+        for pending in self.pending.iter().flatten() {
+            apply(&mut cells, pending);
+        }
+        self.shadow = cells.clone();
+        Received::Cells(cells)
+    }
+}
+
+// what another site had seen when it sent an operation: the length of the log it had, and
+// its operations ordered after it as they applied on the site, which is what the site
+// transformed the operations of the other sites over before it made the operation
+#[derive(Debug, Clone, Default)]
+struct Bridge {
+    seen: usize,
+    pending: Vec<Option<Op>>,
+}
+
+impl Bridge {
+    // the bridge once `site` got the operations of the log up to `seen`, as it does in
+    // `Collab::receive`
+    fn advance(&mut self, site: u32, log: &[(u32, Option<Op>)], seen: usize) {
+        for (other, done) in log.iter().take(seen).skip(self.seen) {
+            if *other == site {
+                if !self.pending.is_empty() {
+                    self.pending.remove(0);
+                }
+            } else if let Some(done) = done {
+                self.pending = transform_all(&self.pending, done);
+            }
+        }
+        self.seen = self.seen.max(seen);
+    }
+}
+
+// the color of the cursor and selection of a site
+pub fn peer_color(site: u32) -> &'static str {
+    const COLORS: [&str; 6] = [
+        "#e6194b", "#3cb44b", "#4363d8", "#f58231", "#911eb4", "#42d4f4",
+    ];
+    COLORS[site as usize % COLORS.len()]
+}
+
+pub fn apply(grammars: &mut HashMap<Coordinate, Grammar>, op: &Op) {
+    match op {
+        Op::SetCell(coord, Some(grammar)) => {
+            grammars.insert(coord.clone(), (**grammar).clone());
+        }
+        Op::SetCell(coord, None) => {
+            grammars.retain(|c, _| !c.row_cols.starts_with(&coord.row_cols));
+        }
+        Op::InsertLine(grid, line, index) => insert_line(grammars, grid, *line, *index),
+        Op::NestGrid(coord, rows, cols) => {
+            let sub_coords = match grammars.get_mut(coord) {
+                Some(cell) => {
+                    cell.kind = Grammar::as_grid(*rows, *cols).kind;
+                    match &cell.kind {
+                        Kind::Grid(sub_coords) => sub_coords.clone(),
+                        _ => return,
+                    }
+                }
+                None => return,
+            };
+            for sub_coord in sub_coords {
+                grammars.insert(Coordinate::child_of(coord, sub_coord), Grammar::default());
+            }
+        }
+        Op::Merge(grid, start, end) => merge_cells(grammars, grid, *start, *end),
+    }
+}
+
+// inserts a line of empty cells in the grid, the ranges merged over the line grow over it
+fn insert_line(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    grid: &Coordinate,
+    line: Line,
+    index: NonZeroU32,
+) {
+    let sub_coords = match grammars.get(grid).map(|g| &g.kind) {
+        Some(Kind::Grid(sub_coords)) => sub_coords.clone(),
+        _ => return,
+    };
+    let moved: Vec<Coordinate> = grammars
+        .keys()
+        .filter(|c| c.after_line_inserted(grid, line, index) != **c)
+        .cloned()
+        .collect();
+    let cells: Vec<(Coordinate, Grammar)> = moved
+        .iter()
+        .filter_map(|c| grammars.remove_entry(c))
+        .collect();
+    for (coord, grammar) in cells {
+        grammars.insert(coord.after_line_inserted(grid, line, index), grammar);
+    }
+    let (sub_coords, inserted) = inserted_sub_coords(&sub_coords, line, index);
+    for sub_coord in sub_coords.iter() {
+        if let Some(cell) = grammars.get_mut(&Coordinate::child_of(grid, *sub_coord)) {
+            let span = match line {
+                Line::Row => &mut cell.style.row_span,
+                Line::Col => &mut cell.style.col_span,
+            };
+            if span.0 != 0 {
+                *span = (shift(span.0, index), shift(span.1, index));
+            }
+        }
+    }
+    for sub_coord in inserted.iter() {
+        // the cell after the new one, which is merged with it if its range starts before
+        let mut next = *sub_coord;
+        match line {
+            Line::Row => next.0 = NonZeroU32::new(next.0.get() + 1).unwrap(),
+            Line::Col => next.1 = NonZeroU32::new(next.1.get() + 1).unwrap(),
+        }
+        let mut cell = Grammar::default();
+        if let Some(next) = grammars.get(&Coordinate::child_of(grid, next)) {
+            let span = match line {
+                Line::Row => next.style.row_span,
+                Line::Col => next.style.col_span,
+            };
+            if span.0 != 0 && span.0 < index.get() {
+                cell.style.row_span = next.style.row_span;
+                cell.style.col_span = next.style.col_span;
+                cell.style.display = false;
+            }
+        }
+        grammars.insert(Coordinate::child_of(grid, *sub_coord), cell);
+    }
+    if let Some(cell) = grammars.get_mut(grid) {
+        cell.kind = Kind::Grid(sub_coords.into_iter().chain(inserted).collect());
+    }
+}
+
+type SubCoords = Vec<(NonZeroU32, NonZeroU32)>;
+
+// the sub-coordinates of a grid once a line is inserted before `index`, and the ones of the
+// new line (one for each row or column of the grid)
+fn inserted_sub_coords(
+    sub_coords: &[(NonZeroU32, NonZeroU32)],
+    line: Line,
+    index: NonZeroU32,
+) -> (SubCoords, SubCoords) {
+    let last = sub_coords
+        .iter()
+        .map(|(row, col)| match line {
+            Line::Row => row.get(),
+            Line::Col => col.get(),
+        })
+        .max()
+        .unwrap_or(0);
+    // no gaps after the last line
+    let index = NonZeroU32::new(index.get().min(last + 1)).unwrap();
+    let mut across: Vec<NonZeroU32> = sub_coords
+        .iter()
+        .map(|(row, col)| match line {
+            Line::Row => *col,
+            Line::Col => *row,
+        })
+        .collect();
+    across.sort();
+    across.dedup();
+    let moved = sub_coords
+        .iter()
+        .map(|(row, col)| match line {
+            Line::Row => (NonZeroU32::new(shift(row.get(), index)).unwrap(), *col),
+            Line::Col => (*row, NonZeroU32::new(shift(col.get(), index)).unwrap()),
+        })
+        .collect();
+    let inserted = across
+        .into_iter()
+        .map(|other| match line {
+            Line::Row => (index, other),
+            Line::Col => (other, index),
+        })
+        .collect();
+    (moved, inserted)
+}
+
+// a row or column number once a line is inserted before `index`
+fn shift(position: u32, index: NonZeroU32) -> u32 {
+    if position >= index.get() {
+        position + 1
+    } else {
+        position
+    }
+}
+
+// `op` as it applies after `done`, which was made without it, or None if `done` removed what
+// it changes. `done_first` is whether the relay ordered `done` before `op`: a line inserted
+// at the same place as another one goes after it if it was ordered after it, and a cell set
+// by both has the value of the one ordered last.
+pub fn transform(op: &Op, done: &Op, done_first: bool) -> Option<Op> {
+    match done {
+        Op::InsertLine(grid, line, index) => {
+            Some(after_line_inserted(op, grid, *line, *index, done_first))
+        }
+        Op::SetCell(removed, None) if changes_within(op, removed) => None,
+        Op::SetCell(set, _) if !done_first => match op {
+            Op::SetCell(coord, _) if coord == set => None,
+            _ => Some(op.clone()),
+        },
+        _ => Some(op.clone()),
+    }
+}
+
+// `ops`, made one after the other without `done`, as they apply after `done`, which was
+// ordered before them: each of them is transformed over `done` as it applies after the ones
+// before it
+fn transform_all(ops: &[Option<Op>], done: &Op) -> Vec<Option<Op>> {
+    let mut done = Some(done.clone());
+    let mut transformed = vec![];
+    for op in ops {
+        match (op, done.take()) {
+            (Some(op), Some(d)) => {
+                transformed.push(transform(op, &d, true));
+                done = transform(&d, op, false);
+            }
+            (op, d) => {
+                transformed.push(op.clone());
+                done = d;
+            }
+        }
+    }
+    transformed
+}
+
+fn after_line_inserted(
+    op: &Op,
+    grid: &Coordinate,
+    line: Line,
+    index: NonZeroU32,
+    done_first: bool,
+) -> Op {
+    let moved = |coord: &Coordinate| coord.after_line_inserted(grid, line, index);
+    let shifted = |position: NonZeroU32| NonZeroU32::new(shift(position.get(), index)).unwrap();
+    match op {
+        // the grid is set as it was before the line, which it keeps
+        Op::SetCell(coord, Some(grammar)) if coord == grid => {
+            let mut grammar = grammar.clone();
+            if let Kind::Grid(sub_coords) = &grammar.kind {
+                let (sub_coords, inserted) = inserted_sub_coords(sub_coords, line, index);
+                grammar.kind = Kind::Grid(sub_coords.into_iter().chain(inserted).collect());
+            }
+            Op::SetCell(coord.clone(), Some(grammar))
+        }
+        Op::SetCell(coord, grammar) => Op::SetCell(moved(coord), grammar.clone()),
+        Op::NestGrid(coord, rows, cols) => Op::NestGrid(moved(coord), *rows, *cols),
+        // a line inserted at the same place goes after the one ordered before it
+        Op::InsertLine(other, other_line, other_index) => {
+            let other = moved(other);
+            if other == *grid && *other_line == line && (done_first || *other_index != index) {
+                Op::InsertLine(other, line, shifted(*other_index))
+            } else {
+                Op::InsertLine(other, *other_line, *other_index)
+            }
+        }
+        // a range grows over the line inserted in it
+        Op::Merge(other, start, end) => {
+            let other = moved(other);
+            if other != *grid {
+                return Op::Merge(other, *start, *end);
+            }
+            let (start, end) = match line {
+                Line::Row => ((shifted(start.0), start.1), (shifted(end.0), end.1)),
+                Line::Col => ((start.0, shifted(start.1)), (end.0, shifted(end.1))),
+            };
+            Op::Merge(other, start, end)
+        }
+    }
+}
+
+// whether `op` changes cells nested in `coord`
+fn changes_within(op: &Op, coord: &Coordinate) -> bool {
+    let nested = |c: &Coordinate| {
+        c.row_cols.len() > coord.row_cols.len() && c.row_cols.starts_with(&coord.row_cols)
+    };
+    match op {
+        Op::SetCell(c, _) | Op::NestGrid(c, _, _) => nested(c),
+        Op::InsertLine(grid, _, _) | Op::Merge(grid, _, _) => grid == coord || nested(grid),
+    }
+}
+
+// the set-cell operations that turn the cells `before` into the cells `after`, a grid before
+// the cells nested in it
+pub fn cell_ops(
+    before: &HashMap<Coordinate, Grammar>,
+    after: &HashMap<Coordinate, Grammar>,
+) -> Vec<Op> {
+    let mut coords: Vec<&Coordinate> = after
+        .iter()
+        .filter(|(coord, grammar)| before.get(coord) != Some(grammar))
+        .map(|(coord, _)| coord)
+        .chain(before.keys().filter(|coord| !after.contains_key(coord)))
+        .collect();
+    coords.sort_by(|a, b| a.row_cols.cmp(&b.row_cols));
+    coords
+        .into_iter()
+        .map(|coord| Op::SetCell(coord.clone(), after.get(coord).cloned().map(Box::new)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate::{CoordinateParser, Rule};
+    use crate::grammar_map::{build_grammar_map, input_grid, input_value, MapEntry};
+    use crate::util::non_zero_u32_tuple;
+    use crate::{coord, g, grid};
+    use pest::Parser;
+
+    fn sub_coords(map: &HashMap<Coordinate, Grammar>, coord: Coordinate) -> usize {
+        match map.get(&coord).map(|g| &g.kind) {
+            Some(Kind::Grid(sub_coords)) => sub_coords.len(),
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn test_insert_line() {
+        let mut cells = input_grid(vec![vec!["a", "b"], vec!["c", "d"]]);
+        apply(
+            &mut cells,
+            &Op::NestGrid(
+                coord!("root-B2"),
+                NonZeroU32::new(1).unwrap(),
+                NonZeroU32::new(1).unwrap(),
+            ),
+        );
+        apply(
+            &mut cells,
+            &Op::Merge(
+                coord!("root"),
+                non_zero_u32_tuple((1, 2)),
+                non_zero_u32_tuple((2, 2)),
+            ),
+        );
+        apply(
+            &mut cells,
+            &Op::InsertLine(coord!("root"), Line::Row, NonZeroU32::new(2).unwrap()),
+        );
+        assert_eq!(sub_coords(&cells, coord!("root")), 6);
+        assert_eq!(
+            input_value(&cells, coord!("root-A1")),
+            Some("a".to_string())
+        );
+        assert_eq!(input_value(&cells, coord!("root-A2")), Some(String::new()));
+        assert_eq!(
+            input_value(&cells, coord!("root-A3")),
+            Some("c".to_string())
+        );
+        // the merged range grows over the new row
+        let merged = &cells[&coord!("root-B1")].style;
+        assert_eq!(merged.row_span, (1, 3));
+        assert!(!cells[&coord!("root-B2")].style.display);
+        assert_eq!(cells[&coord!("root-B2")].style.row_span, (1, 3));
+        assert_eq!(cells[&coord!("root-B3")].style.row_span, (1, 3));
+    }
+
+    #[test]
+    fn test_transform() {
+        let index = NonZeroU32::new(1).unwrap();
+        let row = Op::InsertLine(coord!("root"), Line::Row, index);
+        let set = Op::SetCell(coord!("root-B1-A1"), None);
+        assert_eq!(
+            transform(&set, &row, true),
+            Some(Op::SetCell(coord!("root-B2-A1"), None))
+        );
+        // the same place, after the line ordered first
+        assert_eq!(
+            transform(&row, &row, true),
+            Some(Op::InsertLine(
+                coord!("root"),
+                Line::Row,
+                NonZeroU32::new(2).unwrap()
+            ))
+        );
+        assert_eq!(transform(&row, &row, false), Some(row.clone()));
+        let col = Op::InsertLine(coord!("root"), Line::Col, index);
+        assert_eq!(transform(&row, &col, true), Some(row.clone()));
+        // nothing is changed in a removed cell
+        let removed = Op::SetCell(coord!("root-B1"), None);
+        assert_eq!(transform(&set, &removed, true), None);
+        assert_eq!(
+            transform(
+                &Op::InsertLine(coord!("root-B1"), Line::Row, index),
+                &removed,
+                true
+            ),
+            None
+        );
+        assert_eq!(transform(&removed, &set, true), Some(removed.clone()));
+        // a cell set by both has the value of the one ordered last
+        let restored = Op::SetCell(coord!("root-B1"), Some(Box::new(Grammar::default())));
+        assert_eq!(transform(&removed, &restored, false), None);
+        assert_eq!(transform(&restored, &removed, true), Some(restored.clone()));
+    }
+
+    #[test]
+    fn test_relay_messages() {
+        // as static/relay.js reads and writes them
+        let message = ClientMessage::Op {
+            base: 0,
+            op: Op::SetCell(coord!("root-B1"), None),
+        };
+        assert_eq!(
+            serde_json::to_string(&message).unwrap(),
+            r#"{"Op":{"base":0,"op":{"SetCell":["root-B1",null]}}}"#
+        );
+        let json = r#"{"Op":{"seq":2,"site":3,"base":1,"op":{"InsertLine":["root-A1","Row",2]}}}"#;
+        match serde_json::from_str::<RelayMessage>(json).unwrap() {
+            RelayMessage::Op {
+                seq,
+                site,
+                base,
+                op,
+            } => {
+                assert_eq!((seq, site, base), (2, 3, 1));
+                let index = NonZeroU32::new(2).unwrap();
+                assert_eq!(op, Op::InsertLine(coord!("root-A1"), Line::Row, index));
+            }
+            message => panic!("unexpected {:?}", message),
+        }
+    }
+
+    // a relay as in static/relay.js
+    #[derive(Default)]
+    struct Relay {
+        messages: Vec<RelayMessage>,
+        seq: usize,
+    }
+
+    impl Relay {
+        fn send(&mut self, site: u32, messages: Vec<ClientMessage>) {
+            for message in messages {
+                self.messages.push(match message {
+                    ClientMessage::Share(session) => {
+                        self.seq = 0;
+                        RelayMessage::Shared { site, session }
+                    }
+                    ClientMessage::Op { base, op } => {
+                        self.seq += 1;
+                        RelayMessage::Op {
+                            seq: self.seq,
+                            site,
+                            base,
+                            op,
+                        }
+                    }
+                    ClientMessage::Presence(presence) => RelayMessage::Presence { site, presence },
+                });
+            }
+        }
+    }
+
+    struct Site {
+        collab: Collab,
+        cells: HashMap<Coordinate, Grammar>,
+        received: usize,
+    }
+
+    impl Site {
+        fn new(site: u32, cells: HashMap<Coordinate, Grammar>) -> Site {
+            let mut collab = Collab::new(default_relay_url(), format! {"site {}", site}, 0, &cells);
+            collab.receive(RelayMessage::Welcome { site });
+            Site {
+                collab,
+                cells,
+                received: 0,
+            }
+        }
+
+        fn receive(&mut self, relay: &Relay) {
+            for message in relay.messages[self.received..].iter().cloned() {
+                match self.collab.receive(message) {
+                    Received::Session(session) => self.cells = session.grammars,
+                    Received::Cells(cells) => self.cells = cells,
+                    Received::Presence | Received::Nothing => (),
+                }
+            }
+            self.received = relay.messages.len();
+        }
+
+        fn edit(&mut self, coord: Coordinate, value: &str) -> Vec<ClientMessage> {
+            self.cells.insert(coord, Grammar::input("", value));
+            self.collab.local_edits(&self.cells)
+        }
+
+        fn op(&mut self, op: Op) -> Vec<ClientMessage> {
+            let messages = self.collab.local_op(&self.cells, op.clone());
+            apply(&mut self.cells, &op);
+            messages
+        }
+    }
+
+    #[test]
+    fn test_concurrent_edits() {
+        let shared = input_grid(vec![vec!["a", "b"], vec!["c", "d"]]);
+        let session = Session::of_grammars("shared", shared.clone());
+        let mut relay = Relay::default();
+        let mut first = Site::new(1, shared);
+        let mut second = Site::new(2, input_grid(vec![vec!["other"]]));
+        let share = first.collab.share(&session);
+        relay.send(1, vec![share]);
+        second.receive(&relay);
+        first.receive(&relay);
+        assert_eq!(
+            input_value(&second.cells, coord!("root-B2")),
+            Some("d".to_string())
+        );
+
+        // both insert a row in the middle, and a column at the end, at the same time
+        let row = NonZeroU32::new(2).unwrap();
+        let col = NonZeroU32::new(3).unwrap();
+        let ops = first.op(Op::InsertLine(coord!("root"), Line::Row, row));
+        relay.send(1, ops);
+        let ops = second.op(Op::InsertLine(coord!("root"), Line::Col, col));
+        relay.send(2, ops);
+        let ops = second.op(Op::InsertLine(coord!("root"), Line::Row, row));
+        relay.send(2, ops);
+        let edits = second.edit(coord!("root-B3"), "e");
+        relay.send(2, edits);
+        let edits = first.edit(coord!("root-A1"), "f");
+        relay.send(1, edits);
+        assert_eq!(second.collab.pending(), 3);
+
+        first.receive(&relay);
+        second.receive(&relay);
+        assert_eq!(first.cells, second.cells);
+        assert_eq!(first.collab.pending() + second.collab.pending(), 0);
+        // two new rows, with a cell in the new column each
+        assert_eq!(sub_coords(&first.cells, coord!("root")), 12);
+        assert_eq!(
+            input_value(&first.cells, coord!("root-C2")),
+            Some(String::new())
+        );
+        assert_eq!(
+            input_value(&first.cells, coord!("root-C3")),
+            Some(String::new())
+        );
+        // the edits stay in their cell
+        assert_eq!(
+            input_value(&first.cells, coord!("root-A1")),
+            Some("f".to_string())
+        );
+        assert_eq!(
+            input_value(&first.cells, coord!("root-B4")),
+            Some("e".to_string())
+        );
+        assert_eq!(
+            input_value(&first.cells, coord!("root-B3")),
+            Some(String::new())
+        );
+        assert_eq!(
+            input_value(&first.cells, coord!("root-A4")),
+            Some("c".to_string())
+        );
+
+        // a row inserted by the other site is ordered before a row and an edit of this site
+        let ops = second.op(Op::InsertLine(coord!("root"), Line::Row, row));
+        relay.send(2, ops);
+        let first_row = NonZeroU32::new(1).unwrap();
+        let ops = first.op(Op::InsertLine(coord!("root"), Line::Row, first_row));
+        relay.send(1, ops);
+        let edits = first.edit(coord!("root-A2"), "g");
+        relay.send(1, edits);
+        first.receive(&relay);
+        second.receive(&relay);
+        assert_eq!(first.cells, second.cells);
+        assert_eq!(sub_coords(&first.cells, coord!("root")), 18);
+        // the edit is in the row it was made in, below the row of this site
+        assert_eq!(
+            input_value(&first.cells, coord!("root-A1")),
+            Some(String::new())
+        );
+        assert_eq!(
+            input_value(&first.cells, coord!("root-A2")),
+            Some("g".to_string())
+        );
+        assert_eq!(
+            input_value(&first.cells, coord!("root-A3")),
+            Some(String::new())
+        );
+        assert_eq!(
+            input_value(&first.cells, coord!("root-A6")),
+            Some("c".to_string())
+        );
+    }
+}
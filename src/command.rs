@@ -0,0 +1,262 @@
+use crate::navigation::{Direction, Move};
+
+// COMMANDS
+// Commands are the user-facing actions that only need the state of the model (the active
+// cell, the selection, the view root) to run, which is what lets them be bound to keys
+// (see src/keymap.rs). `Model::command_action` turns a command into its `Action`.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    NestGrid,
+    InsertRow,
+    InsertCol,
+    DeleteRow,
+    DeleteCol,
+    MergeCells,
+    UnmergeCells,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    Save,
+    NewEditor,
+    RunEditor,
+    ToggleLookup,
+    Evaluate,
+    ZoomIntoGrid,
+    ViewBack,
+    ViewForward,
+    AddDefinition,
+    ExportCSV,
+    ExportHTML,
+    CopyMarkdown,
+    ImportJSON,
+    ExportJSON,
+    OpenWorkbook,
+    ExportWorkbook,
+    Reset,
+    CommandPalette,
+    Navigate(Move, /* extend the selection */ bool),
+}
+
+const DIRECTIONS: [Direction; 4] = [
+    Direction::Up,
+    Direction::Down,
+    Direction::Left,
+    Direction::Right,
+];
+
+impl Command {
+    pub fn all() -> Vec<Command> {
+        let mut commands = vec![
+            Command::NestGrid,
+            Command::InsertRow,
+            Command::InsertCol,
+            Command::DeleteRow,
+            Command::DeleteCol,
+            Command::MergeCells,
+            Command::UnmergeCells,
+            Command::ZoomIn,
+            Command::ZoomOut,
+            Command::ZoomReset,
+            Command::Save,
+            Command::NewEditor,
+            Command::RunEditor,
+            Command::ToggleLookup,
+            Command::Evaluate,
+            Command::ZoomIntoGrid,
+            Command::ViewBack,
+            Command::ViewForward,
+            Command::AddDefinition,
+            Command::ExportCSV,
+            Command::ExportHTML,
+            Command::CopyMarkdown,
+            Command::ImportJSON,
+            Command::ExportJSON,
+            Command::OpenWorkbook,
+            Command::ExportWorkbook,
+            Command::Reset,
+            Command::CommandPalette,
+        ];
+        let mut moves: Vec<Move> = vec![];
+        moves.extend(DIRECTIONS.iter().map(|d| Move::Step(*d)));
+        moves.extend(DIRECTIONS.iter().map(|d| Move::Edge(*d)));
+        moves.extend(vec![
+            Move::RowStart,
+            Move::RowEnd,
+            Move::Next,
+            Move::Previous,
+        ]);
+        for extend in &[false, true] {
+            commands.extend(moves.iter().map(|m| Command::Navigate(*m, *extend)));
+        }
+        commands
+    }
+
+    // the name commands are bound by in the keymap file, e.g. "nest_grid" or "select_up"
+    pub fn name(self) -> String {
+        let name = match self {
+            Command::NestGrid => "nest_grid",
+            Command::InsertRow => "insert_row",
+            Command::InsertCol => "insert_col",
+            Command::DeleteRow => "delete_row",
+            Command::DeleteCol => "delete_col",
+            Command::MergeCells => "merge_cells",
+            Command::UnmergeCells => "unmerge_cells",
+            Command::ZoomIn => "zoom_in",
+            Command::ZoomOut => "zoom_out",
+            Command::ZoomReset => "zoom_reset",
+            Command::Save => "save",
+            Command::NewEditor => "new_editor",
+            Command::RunEditor => "run_editor",
+            Command::ToggleLookup => "toggle_lookup",
+            Command::Evaluate => "evaluate",
+            Command::ZoomIntoGrid => "zoom_into_grid",
+            Command::ViewBack => "view_back",
+            Command::ViewForward => "view_forward",
+            Command::AddDefinition => "add_definition",
+            Command::ExportCSV => "export_csv",
+            Command::ExportHTML => "export_html",
+            Command::CopyMarkdown => "copy_markdown",
+            Command::ImportJSON => "import_json",
+            Command::ExportJSON => "export_json",
+            Command::OpenWorkbook => "open_workbook",
+            Command::ExportWorkbook => "export_workbook",
+            Command::Reset => "reset",
+            Command::CommandPalette => "command_palette",
+            Command::Navigate(movement, extend) => {
+                let name = match movement {
+                    Move::Step(direction) => format! {"move_{}", direction_name(direction)},
+                    Move::Edge(direction) => format! {"jump_{}", direction_name(direction)},
+                    Move::RowStart => "row_start".to_string(),
+                    Move::RowEnd => "row_end".to_string(),
+                    Move::Next => "next_cell".to_string(),
+                    Move::Previous => "previous_cell".to_string(),
+                };
+                // extending versions are "select_up" rather than "select_move_up"
+                return match (extend, movement) {
+                    (true, Move::Step(direction)) => {
+                        format! {"select_{}", direction_name(direction)}
+                    }
+                    (true, _) => format! {"select_{}", name},
+                    (false, _) => name,
+                };
+            }
+        };
+        name.to_string()
+    }
+
+    pub fn from_name(name: &str) -> Option<Command> {
+        Command::all().into_iter().find(|c| c.name() == name)
+    }
+
+    // the short name of the command in the command palette, e.g. "Nest grid"
+    pub fn title(self) -> String {
+        let title = match self {
+            Command::InsertCol => "Insert column",
+            Command::DeleteCol => "Delete column",
+            Command::RunEditor => "Run editor",
+            Command::ExportCSV => "Export CSV",
+            Command::ExportHTML => "Export HTML",
+            Command::CopyMarkdown => "Copy as Markdown",
+            Command::ImportJSON => "Import JSON",
+            Command::ExportJSON => "Export JSON",
+            Command::OpenWorkbook => "Open workbook",
+            Command::ExportWorkbook => "Export workbook",
+            Command::Evaluate => "Evaluate with driver",
+            _ => {
+                let name = self.name().replace("_", " ");
+                let mut chars = name.chars();
+                return match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => name,
+                };
+            }
+        };
+        title.to_string()
+    }
+
+    pub fn description(self) -> String {
+        let description = match self {
+            Command::NestGrid => "Nest a grid in the active cell",
+            Command::InsertRow => "Insert a row",
+            Command::InsertCol => "Insert a column",
+            Command::DeleteRow => "Delete the row of the focused cell",
+            Command::DeleteCol => "Delete the column of the focused cell",
+            Command::MergeCells => "Merge the selected cells",
+            Command::UnmergeCells => "Unmerge the active cell",
+            Command::ZoomIn => "Zoom in",
+            Command::ZoomOut => "Zoom out",
+            Command::ZoomReset => "Reset the zoom",
+            Command::Save => "Save the session",
+            Command::NewEditor => "Turn the active cell into a code editor",
+            Command::RunEditor => "Run the active editor",
+            Command::ToggleLookup => "Toggle the active cell between an input and a lookup",
+            Command::Evaluate => "Evaluate the active cell with its driver",
+            Command::ZoomIntoGrid => "Zoom the view into the grid of the active cell",
+            Command::ViewBack => "Go back to the previous view root",
+            Command::ViewForward => "Go forward to the next view root",
+            Command::AddDefinition => "Define a grammar from the selected grid",
+            Command::ExportCSV => "Export the grid of the active cell to CSV",
+            Command::ExportHTML => "Export the grid of the active cell as an HTML page",
+            Command::CopyMarkdown => "Copy the grid of the active cell as a Markdown table",
+            Command::ImportJSON => "Replace the active cell with a JSON document",
+            Command::ExportJSON => "Save the grid of the active cell as a JSON document",
+            Command::OpenWorkbook => "Open the sheets of an .xlsx or .ods workbook in new tabs",
+            Command::ExportWorkbook => "Save the session as an .xlsx workbook",
+            Command::Reset => "Reset the grid to its initial cells",
+            Command::CommandPalette => "Search and run commands",
+            Command::Navigate(movement, extend) => {
+                let target = match movement {
+                    Move::Step(Direction::Up) => "the cell above",
+                    Move::Step(Direction::Down) => "the cell below",
+                    Move::Step(Direction::Left) => "the cell on the left",
+                    Move::Step(Direction::Right) => "the cell on the right",
+                    Move::Edge(Direction::Up) => "the top edge of the data",
+                    Move::Edge(Direction::Down) => "the bottom edge of the data",
+                    Move::Edge(Direction::Left) => "the left edge of the data",
+                    Move::Edge(Direction::Right) => "the right edge of the data",
+                    Move::RowStart => "the start of the row",
+                    Move::RowEnd => "the end of the row",
+                    Move::Next => "the next cell",
+                    Move::Previous => "the previous cell",
+                };
+                return if extend {
+                    format! {"Extend the selection to {}", target}
+                } else {
+                    format! {"Move to {}", target}
+                };
+            }
+        };
+        description.to_string()
+    }
+}
+
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Up => "up",
+        Direction::Down => "down",
+        Direction::Left => "left",
+        Direction::Right => "right",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        assert_eq!(Command::NestGrid.name(), "nest_grid");
+        assert_eq!(
+            Command::Navigate(Move::Edge(Direction::Left), true).name(),
+            "select_jump_left"
+        );
+        // every command can be found by its name
+        for command in Command::all() {
+            assert_eq!(Command::from_name(&command.name()), Some(command));
+        }
+        assert_eq!(Command::from_name("fly"), None);
+        assert_eq!(Command::ZoomIntoGrid.title(), "Zoom into grid");
+        assert_eq!(Command::ExportCSV.title(), "Export CSV");
+    }
+}
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+
+use crate::coordinate::{Coordinate, Line};
+
+// COMMENTS
+// Comments are notes attached to a cell, stored in `Session::comments` by the coordinate
+// of the cell. Each comment starts a thread that others can reply to, and can be resolved
+// once it's been dealt with. Comments follow their cell when the structure changes: they
+// are copied along with the cell by `util::move_grammar` and moved up or left when a row
+// or column before them is deleted (see `delete_line`). New rows and columns are always
+// added after the existing ones, so inserting never moves a comment.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Reply {
+    pub author: String,
+    // ISO 8601, e.g. "2020-05-01T12:30:00.000Z"
+    pub timestamp: String,
+    pub text: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub author: String,
+    pub timestamp: String,
+    pub text: String,
+    #[serde(default)]
+    pub replies: Vec<Reply>,
+    #[serde(default)]
+    pub resolved: bool,
+}
+
+impl Comment {
+    pub fn new(author: String, timestamp: String, text: String) -> Comment {
+        Comment {
+            author,
+            timestamp,
+            text,
+            replies: vec![],
+            resolved: false,
+        }
+    }
+}
+
+pub type Comments = HashMap<Coordinate, Vec<Comment>>;
+
+pub const DEFAULT_COMMENT_AUTHOR: &str = "anonymous";
+
+// whether the cell has comments that aren't resolved yet
+pub fn has_open_comments(comments: &Comments, coord: &Coordinate) -> bool {
+    comments
+        .get(coord)
+        .map_or(false, |thread| thread.iter().any(|c| !c.resolved))
+}
+
+// copies the comments of `source` and of the cells nested in it over to `dest`
+pub fn copy_comments(comments: &mut Comments, source: &Coordinate, dest: &Coordinate) {
+    if source == dest {
+        return;
+    }
+    let copied: Vec<(Coordinate, Vec<Comment>)> = comments
+        .iter()
+        .filter(|(coord, _)| coord.row_cols.starts_with(&source.row_cols))
+        .map(|(coord, thread)| {
+            let mut row_cols = dest.row_cols.clone();
+            row_cols.extend_from_slice(&coord.row_cols[source.row_cols.len()..]);
+            (Coordinate { row_cols }, thread.clone())
+        })
+        .collect();
+    for (coord, thread) in copied {
+        comments.insert(coord, thread);
+    }
+}
+
+// removes the comments in row/column `index` of the grid at `parent` (and the cells
+// nested in it), and moves the comments of the rows/columns after it back by one
+pub fn delete_line(comments: &mut Comments, parent: &Coordinate, line: Line, index: NonZeroU32) {
+    let threads: Vec<(Coordinate, Vec<Comment>)> = comments.drain().collect();
+    for (coord, thread) in threads {
+        if let Some(coord) = coord.after_line_deleted(parent, line, index) {
+            comments.insert(coord, thread);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coord;
+    use crate::coordinate::{CoordinateParser, Rule};
+    use crate::util::non_zero_u32_tuple;
+    use pest::Parser;
+
+    fn comment(text: &str) -> Comment {
+        Comment::new(
+            "ada".to_string(),
+            "2020-05-01T12:30:00.000Z".to_string(),
+            text.to_string(),
+        )
+    }
+
+    fn comments() -> Comments {
+        let mut comments = Comments::new();
+        comments.insert(coord!("root-A1"), vec![comment("a1")]);
+        comments.insert(coord!("root-A2"), vec![comment("a2")]);
+        comments.insert(coord!("root-B3-A1"), vec![comment("nested")]);
+        comments
+    }
+
+    #[test]
+    fn test_copy_comments() {
+        let mut comments = comments();
+        copy_comments(&mut comments, &coord!("root-B3"), &coord!("meta-A4"));
+        assert_eq!(comments[&coord!("meta-A4-A1")], vec![comment("nested")]);
+        assert!(comments.contains_key(&coord!("root-B3-A1")));
+    }
+
+    #[test]
+    fn test_delete_row() {
+        let mut comments = comments();
+        delete_line(
+            &mut comments,
+            &coord!("root"),
+            Line::Row,
+            NonZeroU32::new(1).unwrap(),
+        );
+        assert_eq!(comments.len(), 2);
+        assert_eq!(comments[&coord!("root-A1")], vec![comment("a2")]);
+        assert_eq!(comments[&coord!("root-B2-A1")], vec![comment("nested")]);
+    }
+
+    #[test]
+    fn test_delete_col() {
+        let mut comments = comments();
+        delete_line(
+            &mut comments,
+            &coord!("root"),
+            Line::Col,
+            NonZeroU32::new(2).unwrap(),
+        );
+        assert_eq!(comments.len(), 2);
+        assert!(!comments.contains_key(&coord!("root-B3-A1")));
+    }
+
+    #[test]
+    fn test_open_comments() {
+        let mut comments = comments();
+        assert!(has_open_comments(&comments, &coord!("root-A1")));
+        comments.get_mut(&coord!("root-A1")).unwrap()[0].resolved = true;
+        assert!(!has_open_comments(&comments, &coord!("root-A1")));
+        assert!(!has_open_comments(&comments, &coord!("root-C1")));
+    }
+}
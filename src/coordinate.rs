@@ -229,6 +229,66 @@ impl Coordinate {
 
         None
     }
+
+    // parses a coordinate typed in by the user, unlike `coord!` this doesn't panic
+    pub fn parse(s: &str) -> Option<Coordinate> {
+        let s = s.trim();
+        match CoordinateParser::parse(Rule::coordinate, s) {
+            Ok(pairs) if pairs.as_str() == s && !s.is_empty() => Some(coord!(s)),
+            _ => None,
+        }
+    }
+
+    // where this coordinate ends up once row/column `index` of the grid at `parent` is
+    // deleted: `None` if it's in the deleted row/column (or nested in it), otherwise it's
+    // moved back by one if it comes after it
+    pub fn after_line_deleted(
+        &self,
+        parent: &Coordinate,
+        line: Line,
+        index: NonZeroU32,
+    ) -> Option<Coordinate> {
+        let depth = parent.row_cols.len();
+        if self.row_cols.len() <= depth || !self.row_cols.starts_with(&parent.row_cols) {
+            return Some(self.clone());
+        }
+        let mut moved = self.clone();
+        let position = match line {
+            Line::Row => &mut moved.row_cols[depth].0,
+            Line::Col => &mut moved.row_cols[depth].1,
+        };
+        if *position == index {
+            return None;
+        }
+        if *position > index {
+            *position = NonZeroU32::new(position.get() - 1).unwrap();
+        }
+        Some(moved)
+    }
+
+    // where this coordinate ends up once a row/column is inserted before `index` in the grid
+    // at `parent`: moved forward by one if it's at or after `index` (or nested in a cell
+    // that is)
+    pub fn after_line_inserted(
+        &self,
+        parent: &Coordinate,
+        line: Line,
+        index: NonZeroU32,
+    ) -> Coordinate {
+        let depth = parent.row_cols.len();
+        let mut moved = self.clone();
+        if self.row_cols.len() <= depth || !self.row_cols.starts_with(&parent.row_cols) {
+            return moved;
+        }
+        let position = match line {
+            Line::Row => &mut moved.row_cols[depth].0,
+            Line::Col => &mut moved.row_cols[depth].1,
+        };
+        if *position >= index {
+            *position = NonZeroU32::new(position.get() + 1).unwrap();
+        }
+        moved
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Hash)]
@@ -259,6 +319,13 @@ impl PartialEq for Col {
 
 impl Eq for Col {}
 
+// a row or a column of a grid
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum Line {
+    Row,
+    Col,
+}
+
 // macro for easily defining a coordinate
 // either absolutely or relative to it's parent coordinate
 // TODO: this code is messy, can be optimized more later
@@ -334,6 +401,54 @@ mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
 
+    #[test]
+    fn test_parse() {
+        assert_eq!(Coordinate::parse(" root-A1-B2 "), Some(coord!("root-A1-B2")));
+        assert_eq!(Coordinate::parse("root-A1-"), None);
+        assert_eq!(Coordinate::parse("1A"), None);
+        assert_eq!(Coordinate::parse(""), None);
+    }
+
+    #[test]
+    fn test_after_line_deleted() {
+        let index = NonZeroU32::new(2).unwrap();
+        let cell = coord!("root-B3-A1");
+        assert_eq!(
+            cell.after_line_deleted(&coord!("root"), Line::Row, index),
+            Some(coord!("root-B2-A1"))
+        );
+        assert_eq!(
+            cell.after_line_deleted(&coord!("root"), Line::Col, index),
+            None
+        );
+        assert_eq!(
+            cell.after_line_deleted(&coord!("root-A1"), Line::Row, index),
+            Some(cell.clone())
+        );
+    }
+
+    #[test]
+    fn test_after_line_inserted() {
+        let index = NonZeroU32::new(2).unwrap();
+        let cell = coord!("root-B2-A1");
+        assert_eq!(
+            cell.after_line_inserted(&coord!("root"), Line::Row, index),
+            coord!("root-B3-A1")
+        );
+        assert_eq!(
+            cell.after_line_inserted(&coord!("root"), Line::Col, index),
+            coord!("root-C2-A1")
+        );
+        assert_eq!(
+            cell.after_line_inserted(&coord!("root-B2"), Line::Row, index),
+            cell
+        );
+        assert_eq!(
+            coord!("root").after_line_inserted(&coord!("root"), Line::Row, index),
+            coord!("root")
+        );
+    }
+
     #[test]
     fn test_row() {
         assert_eq!(coord!("root-A1-B2-B3").row().get(), 3);
@@ -0,0 +1,593 @@
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+
+use crate::coordinate::Coordinate;
+use crate::grammar::{Grammar, Kind};
+use crate::session::Session;
+use crate::style::Style;
+
+// SESSION DIFFS
+// Two sessions are compared cell by cell: a diff lists the cells added, removed or
+// changed (in their name, style or kind) from one session to the other, by coordinate.
+// The grid shows the diff of the current session against a session file by outlining the
+// cells that changed, the removed ones are only listed since they aren't in the grid.
+// Three sessions are merged the same way. `merge_sessions` takes the changes made on
+// either side since the base both sides started from: a cell changed on one side only
+// takes that change, a cell changed on both sides is merged part by part (its name, each
+// field of its style and its kind, the cells of a grid being merged as a set) and parts
+// changed differently on both sides are conflicts, left as they are on our side until
+// they're resolved.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CellChange {
+    Added(Grammar),
+    Removed(Grammar),
+    Changed(/* before */ Grammar, /* after */ Grammar),
+}
+
+impl CellChange {
+    // e.g. "changed: kind, style.font_color"
+    pub fn describe(&self) -> String {
+        match self {
+            CellChange::Added(_) => "added".to_string(),
+            CellChange::Removed(_) => "removed".to_string(),
+            CellChange::Changed(before, after) => {
+                format! {"changed: {}", changed_parts(before, after).join(", ")}
+            }
+        }
+    }
+
+    // the class the cell is outlined with in the grid
+    pub fn class(&self) -> &'static str {
+        match self {
+            CellChange::Added(_) => "diff-added",
+            CellChange::Removed(_) => "diff-removed",
+            CellChange::Changed(_, _) => "diff-changed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SessionDiff {
+    // the cells that changed, parents before the cells nested in them and row by row
+    pub cells: Vec<(Coordinate, CellChange)>,
+}
+
+impl SessionDiff {
+    pub fn get(&self, coord: &Coordinate) -> Option<&CellChange> {
+        self.cells
+            .iter()
+            .find(|(c, _)| c == coord)
+            .map(|(_, change)| change)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+}
+
+// the diff of a tab against a session file, shown in the grid of that tab
+#[derive(Debug)]
+pub struct ShownDiff {
+    pub tab: usize,
+    // the path of the session file
+    pub against: String,
+    pub diff: SessionDiff,
+}
+
+pub fn diff_sessions(before: &Session, after: &Session) -> SessionDiff {
+    diff_grammars(&before.grammars, &after.grammars)
+}
+
+pub fn diff_grammars(
+    before: &HashMap<Coordinate, Grammar>,
+    after: &HashMap<Coordinate, Grammar>,
+) -> SessionDiff {
+    let cells = sorted_coords(&[before, after])
+        .into_iter()
+        .filter_map(|coord| {
+            let change = match (before.get(&coord), after.get(&coord)) {
+                (None, Some(added)) => CellChange::Added(added.clone()),
+                (Some(removed), None) => CellChange::Removed(removed.clone()),
+                (Some(b), Some(a)) if b != a => CellChange::Changed(b.clone(), a.clone()),
+                _ => return None,
+            };
+            Some((coord, change))
+        })
+        .collect();
+    SessionDiff { cells }
+}
+
+// the coordinates of all the cells, parents first and row by row
+fn sorted_coords(maps: &[&HashMap<Coordinate, Grammar>]) -> Vec<Coordinate> {
+    let coords: HashSet<&Coordinate> = maps.iter().flat_map(|map| map.keys()).collect();
+    let mut coords: Vec<Coordinate> = coords.into_iter().cloned().collect();
+    coords.sort_by(|a, b| a.row_cols.cmp(&b.row_cols));
+    coords
+}
+
+// the parts of a cell that differ, "name", "kind" or "style." and the style field
+pub fn changed_parts(before: &Grammar, after: &Grammar) -> Vec<String> {
+    let mut parts = vec![];
+    if before.name != after.name {
+        parts.push("name".to_string());
+    }
+    let (before_style, after_style) = (style_fields(&before.style), style_fields(&after.style));
+    for (field, value) in after_style.iter() {
+        if before_style.get(field) != Some(value) {
+            parts.push(format! {"style.{}", field});
+        }
+    }
+    if before.kind != after.kind {
+        parts.push("kind".to_string());
+    }
+    parts
+}
+
+fn style_fields(style: &Style) -> Map<String, Value> {
+    match serde_json::to_value(style) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::new(),
+    }
+}
+
+// MERGES
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Base,
+    Ours,
+    Theirs,
+}
+
+// a cell changed differently on both sides, `None` where the cell isn't there
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub coord: Coordinate,
+    pub base: Option<Grammar>,
+    pub ours: Option<Grammar>,
+    pub theirs: Option<Grammar>,
+}
+
+impl Conflict {
+    pub fn side(&self, side: Side) -> Option<&Grammar> {
+        match side {
+            Side::Base => self.base.as_ref(),
+            Side::Ours => self.ours.as_ref(),
+            Side::Theirs => self.theirs.as_ref(),
+        }
+    }
+
+    // resolves the conflict with the cell of `side`, which is listed in its grid (or taken
+    // out of it, along with what's nested in it, if the cell isn't there on that side)
+    pub fn resolve(&self, grammars: &mut HashMap<Coordinate, Grammar>, side: Side) {
+        let row_col = self.coord.row_col();
+        let parent = self
+            .coord
+            .parent()
+            .and_then(|parent| grammars.get_mut(&parent));
+        if let Some(Grammar {
+            kind: Kind::Grid(sub_coords),
+            ..
+        }) = parent
+        {
+            sub_coords.retain(|c| *c != row_col);
+            if self.side(side).is_some() {
+                sub_coords.push(row_col);
+            }
+        }
+        match self.side(side) {
+            Some(grammar) => {
+                grammars.insert(self.coord.clone(), grammar.clone());
+            }
+            None => {
+                let row_cols = &self.coord.row_cols;
+                grammars.retain(|c, _| !c.row_cols.starts_with(row_cols));
+            }
+        }
+    }
+}
+
+// a cell of a conflict in a few words, e.g. "\"42\"" or "grid of 4 cells"
+pub fn cell_summary(grammar: Option<&Grammar>) -> String {
+    match grammar.map(|g| &g.kind) {
+        None => "no cell".to_string(),
+        Some(Kind::Input(value)) | Some(Kind::Text(value)) | Some(Kind::Lookup(value, _)) => {
+            format! {"\"{}\"", value}
+        }
+        Some(Kind::Grid(cells)) => format! {"grid of {} cells", cells.len()},
+        Some(Kind::Editor(content, _, _)) => {
+            format! {"editor of {} lines", content.lines().count()}
+        }
+        Some(Kind::Defn(name, _, _)) => format! {"definition {}", name},
+        Some(Kind::Interactive(name, _)) => format! {"interactive {}", name},
+    }
+}
+
+// the conflicts left from merging into a tab, see `merge_sessions`
+#[derive(Debug)]
+pub struct MergeConflicts {
+    pub tab: usize,
+    pub conflicts: Vec<Conflict>,
+}
+
+pub struct Merge {
+    pub session: Session,
+    pub conflicts: Vec<Conflict>,
+}
+
+// merges the changes made to `base` in `ours` and in `theirs`, conflicting cells are left
+// as they are in `ours`
+pub fn merge_sessions(base: &Session, ours: &Session, theirs: &Session) -> Merge {
+    let (grammars, conflicts) = merge_grammars(&base.grammars, &ours.grammars, &theirs.grammars);
+    // the rest of the session is taken from the side that changed it, ours if both did
+    let mut session = ours.clone();
+    session.grammars = grammars;
+    session.title = pick(&base.title, &ours.title, &theirs.title);
+    session.formatting = pick(&base.formatting, &ours.formatting, &theirs.formatting);
+    session.style_classes = pick(
+        &base.style_classes,
+        &ours.style_classes,
+        &theirs.style_classes,
+    );
+    session.theme = pick(&base.theme, &ours.theme, &theirs.theme);
+    session.validations = pick(&base.validations, &ours.validations, &theirs.validations);
+    session.comments = pick(&base.comments, &ours.comments, &theirs.comments);
+    session.names = pick(&base.names, &ours.names, &theirs.names);
+    Merge { session, conflicts }
+}
+
+// the base of a merge of two sessions that don't have a common one: their session
+// without its cells, so the cells that are on both sides and differ are conflicts
+pub fn empty_base(theirs: &Session) -> Session {
+    Session {
+        grammars: HashMap::new(),
+        ..theirs.clone()
+    }
+}
+
+fn pick<T: PartialEq + Clone>(base: &T, ours: &T, theirs: &T) -> T {
+    if ours == base {
+        theirs.clone()
+    } else {
+        ours.clone()
+    }
+}
+
+pub fn merge_grammars(
+    base: &HashMap<Coordinate, Grammar>,
+    ours: &HashMap<Coordinate, Grammar>,
+    theirs: &HashMap<Coordinate, Grammar>,
+) -> (HashMap<Coordinate, Grammar>, Vec<Conflict>) {
+    let mut merged = HashMap::new();
+    let mut conflicts = vec![];
+    for coord in sorted_coords(&[base, ours, theirs]) {
+        let (b, o, t) = (base.get(&coord), ours.get(&coord), theirs.get(&coord));
+        let cell = if o == t || b == t {
+            Some(o.cloned())
+        } else if b == o {
+            Some(t.cloned())
+        } else {
+            match (o, t) {
+                (Some(o), Some(t)) => merge_grammar(b, o, t).map(Some),
+                _ => None,
+            }
+        };
+        let grammar = match cell {
+            Some(grammar) => grammar,
+            None => {
+                conflicts.push(Conflict {
+                    coord: coord.clone(),
+                    base: b.cloned(),
+                    ours: o.cloned(),
+                    theirs: t.cloned(),
+                });
+                o.cloned()
+            }
+        };
+        if let Some(grammar) = grammar {
+            merged.insert(coord, grammar);
+        }
+    }
+    // cells of grids that are gone go with them
+    let orphans: Vec<Coordinate> = merged
+        .keys()
+        .filter(|coord| match coord.parent() {
+            Some(parent) => !matches!(merged.get(&parent).map(|g| &g.kind), Some(Kind::Grid(_))),
+            None => false,
+        })
+        .cloned()
+        .collect();
+    for orphan in orphans {
+        merged.retain(|c, _| !c.row_cols.starts_with(&orphan.row_cols));
+    }
+    (merged, conflicts)
+}
+
+// the side that changed, `None` if both changed it differently
+fn merge3<T: PartialEq + Clone>(base: Option<&T>, ours: &T, theirs: &T) -> Option<T> {
+    if ours == theirs || base == Some(theirs) {
+        Some(ours.clone())
+    } else if base == Some(ours) {
+        Some(theirs.clone())
+    } else {
+        None
+    }
+}
+
+// a cell changed on both sides merged part by part, `None` if a part conflicts
+fn merge_grammar(base: Option<&Grammar>, ours: &Grammar, theirs: &Grammar) -> Option<Grammar> {
+    let name = merge3(base.map(|b| &b.name), &ours.name, &theirs.name)?;
+    let kind = match (&ours.kind, &theirs.kind) {
+        (Kind::Grid(our_cells), Kind::Grid(their_cells)) => {
+            let base_cells = match base.map(|b| &b.kind) {
+                Some(Kind::Grid(base_cells)) => base_cells.clone(),
+                _ => vec![],
+            };
+            // the cells either side added, but none of those either side removed
+            let mut cells = our_cells.clone();
+            cells.extend(their_cells.iter().filter(|c| !our_cells.contains(c)));
+            cells.retain(|c| {
+                !base_cells.contains(c) || (our_cells.contains(c) && their_cells.contains(c))
+            });
+            Kind::Grid(cells)
+        }
+        _ => merge3(base.map(|b| &b.kind), &ours.kind, &theirs.kind)?,
+    };
+    // grids that grew on both sides take the larger size
+    let is_grid = matches!(kind, Kind::Grid(_));
+    let base_style = base.map(|b| style_fields(&b.style));
+    let (our_style, their_style) = (style_fields(&ours.style), style_fields(&theirs.style));
+    let mut style = our_style.clone();
+    for (field, their_value) in their_style.iter() {
+        let our_value = our_style.get(field).unwrap_or(&Value::Null);
+        let base_value = base_style.as_ref().and_then(|b| b.get(field));
+        let value = match merge3(base_value, our_value, their_value) {
+            Some(value) => value,
+            None if is_grid && (field == "width" || field == "height") => {
+                let (o, t) = (our_value.as_f64()?, their_value.as_f64()?);
+                Value::from(o.max(t))
+            }
+            None => return None,
+        };
+        style.insert(field.clone(), value);
+    }
+    Some(Grammar {
+        name,
+        style: serde_json::from_value(Value::Object(style)).ok()?,
+        kind,
+    })
+}
+
+// GIT CONFLICTS
+// A session file merged by git with conflicting changes has conflict markers in it, its
+// two (or three, with `merge.conflictStyle diff3`) sides are taken back apart to be
+// merged as sessions.
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConflictedText {
+    pub ours: String,
+    pub base: Option<String>,
+    pub theirs: String,
+}
+
+pub fn split_conflict_markers(text: &str) -> Option<ConflictedText> {
+    #[derive(PartialEq)]
+    enum Section {
+        Common,
+        Ours,
+        Base,
+        Theirs,
+    }
+    let mut section = Section::Common;
+    let (mut ours, mut base, mut theirs) = (String::new(), String::new(), String::new());
+    let (mut conflicted, mut has_base) = (false, false);
+    for line in text.lines() {
+        let next = if line.starts_with("<<<<<<<") && section == Section::Common {
+            Some(Section::Ours)
+        } else if line.starts_with("|||||||") && section == Section::Ours {
+            Some(Section::Base)
+        } else if line.trim_end() == "=======" && section != Section::Common {
+            Some(Section::Theirs)
+        } else if line.starts_with(">>>>>>>") && section == Section::Theirs {
+            Some(Section::Common)
+        } else {
+            None
+        };
+        if let Some(next) = next {
+            conflicted = true;
+            has_base |= next == Section::Base;
+            section = next;
+            continue;
+        }
+        let sides: Vec<&mut String> = match section {
+            Section::Common => vec![&mut ours, &mut base, &mut theirs],
+            Section::Ours => vec![&mut ours],
+            Section::Base => vec![&mut base],
+            Section::Theirs => vec![&mut theirs],
+        };
+        for side in sides {
+            side.push_str(line);
+            side.push('\n');
+        }
+    }
+    if !conflicted || section != Section::Common {
+        return None;
+    }
+    Some(ConflictedText {
+        ours,
+        base: if has_base { Some(base) } else { None },
+        theirs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate::{CoordinateParser, Rule};
+    use crate::grammar_map::{build_grammar_map, input_grid, input_value, MapEntry};
+    use crate::util::non_zero_u32_tuple;
+    use crate::{coord, g, grid};
+    use pest::Parser;
+    use std::num::NonZeroU32;
+
+    #[test]
+    fn test_diff() {
+        let before = input_grid(vec![vec!["a", "b"], vec!["c", "d"]]);
+        let mut after = input_grid(vec![vec!["a", "B"], vec!["c", "d"], vec!["e", "f"]]);
+        after.get_mut(&coord!("root-A1")).unwrap().style.font_color = "red".to_string();
+        after.remove(&coord!("root-A2"));
+        let diff = diff_grammars(&before, &after);
+        let changes: Vec<(String, String)> = diff
+            .cells
+            .iter()
+            .map(|(coord, change)| (coord.to_string(), change.describe()))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (
+                    "root".to_string(),
+                    "changed: style.height, kind".to_string()
+                ),
+                (
+                    "root-A1".to_string(),
+                    "changed: style.font_color".to_string()
+                ),
+                ("root-B1".to_string(), "changed: kind".to_string()),
+                ("root-A2".to_string(), "removed".to_string()),
+                ("root-A3".to_string(), "added".to_string()),
+                ("root-B3".to_string(), "added".to_string()),
+            ]
+        );
+        assert_eq!(diff.get(&coord!("root-B2")), None);
+        assert_eq!(
+            diff.get(&coord!("root-A3")).map(|c| c.class()),
+            Some("diff-added")
+        );
+        assert!(diff_grammars(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn test_merge() {
+        let base = input_grid(vec![vec!["a", "b"], vec!["c", "d"]]);
+        let mut ours = base.clone();
+        let mut theirs = base.clone();
+        // each side edits its own cells, and both edit B2
+        ours.insert(coord!("root-A1"), Grammar::input("", "ours"));
+        ours.insert(coord!("root-B2"), Grammar::input("", "ours"));
+        theirs
+            .get_mut(&coord!("root-A1"))
+            .unwrap()
+            .style
+            .font_weight = 700;
+        theirs.insert(coord!("root-B1"), Grammar::input("", "theirs"));
+        theirs.insert(coord!("root-B2"), Grammar::input("", "theirs"));
+        // both add a row
+        for (map, value) in [(&mut ours, "o"), (&mut theirs, "t")].iter_mut() {
+            map.insert(coord!("root-A3"), Grammar::input("", *value));
+            map.insert(coord!("root-B3"), Grammar::input("", "same"));
+            let root = map.get_mut(&coord!("root")).unwrap();
+            root.style.height += 30.0;
+            if let Kind::Grid(cells) = &mut root.kind {
+                cells.push(non_zero_u32_tuple((3, 1)));
+                cells.push(non_zero_u32_tuple((3, 2)));
+            }
+        }
+        theirs.get_mut(&coord!("root")).unwrap().style.height += 30.0;
+
+        let (merged, conflicts) = merge_grammars(&base, &ours, &theirs);
+        assert_eq!(
+            input_value(&merged, coord!("root-A1")),
+            Some("ours".to_string())
+        );
+        assert_eq!(merged[&coord!("root-A1")].style.font_weight, 700);
+        assert_eq!(
+            input_value(&merged, coord!("root-B1")),
+            Some("theirs".to_string())
+        );
+        assert_eq!(
+            input_value(&merged, coord!("root-B3")),
+            Some("same".to_string())
+        );
+        assert_eq!(
+            merged[&coord!("root")].style.height,
+            theirs[&coord!("root")].style.height
+        );
+        match &merged[&coord!("root")].kind {
+            Kind::Grid(cells) => assert_eq!(cells.len(), 6),
+            _ => panic!("root should be a grid"),
+        }
+
+        let conflicted: Vec<String> = conflicts.iter().map(|c| c.coord.to_string()).collect();
+        assert_eq!(conflicted, vec!["root-B2", "root-A3"]);
+        assert_eq!(
+            input_value(&merged, coord!("root-B2")),
+            Some("ours".to_string())
+        );
+
+        assert_eq!(cell_summary(conflicts[0].side(Side::Base)), "\"d\"");
+        assert_eq!(cell_summary(conflicts[1].side(Side::Base)), "no cell");
+
+        let mut resolved = merged.clone();
+        conflicts[0].resolve(&mut resolved, Side::Theirs);
+        assert_eq!(
+            input_value(&resolved, coord!("root-B2")),
+            Some("theirs".to_string())
+        );
+        // A3 wasn't in the base
+        conflicts[1].resolve(&mut resolved, Side::Base);
+        assert_eq!(resolved.get(&coord!("root-A3")), None);
+        match &resolved[&coord!("root")].kind {
+            Kind::Grid(cells) => assert!(!cells.contains(&non_zero_u32_tuple((3, 1)))),
+            _ => panic!("root should be a grid"),
+        }
+    }
+
+    #[test]
+    fn test_merge_removed_grid() {
+        let mut base = input_grid(vec![vec!["a", "b"]]);
+        build_grammar_map(
+            &mut base,
+            coord!("root-B1"),
+            grid![[g!(Grammar::input("", "x"))]],
+        );
+        // ours removes the nested grid, theirs edits in it
+        let mut ours = base.clone();
+        ours.retain(|c, _| c.row_cols.len() < 3);
+        ours.insert(coord!("root-B1"), Grammar::input("", "b"));
+        let mut theirs = base.clone();
+        theirs.insert(coord!("root-B1-A1"), Grammar::input("", "y"));
+
+        let (merged, conflicts) = merge_grammars(&base, &ours, &theirs);
+        assert_eq!(
+            input_value(&merged, coord!("root-B1")),
+            Some("b".to_string())
+        );
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].coord, coord!("root-B1-A1"));
+        // the cell in conflict isn't left without its grid
+        assert_eq!(merged.get(&coord!("root-B1-A1")), None);
+    }
+
+    #[test]
+    fn test_split_conflict_markers() {
+        let text = "{\n<<<<<<< HEAD\n  \"a\": 1\n||||||| base\n  \"a\": 0\n=======\n  \"a\": 2\n>>>>>>> other\n}\n";
+        assert_eq!(
+            split_conflict_markers(text),
+            Some(ConflictedText {
+                ours: "{\n  \"a\": 1\n}\n".to_string(),
+                base: Some("{\n  \"a\": 0\n}\n".to_string()),
+                theirs: "{\n  \"a\": 2\n}\n".to_string(),
+            })
+        );
+        let two_sides = split_conflict_markers("<<<<<<< HEAD\n1\n=======\n2\n>>>>>>> b\n").unwrap();
+        assert_eq!(two_sides.base, None);
+        assert_eq!(split_conflict_markers("{\"a\": 1}"), None);
+        // an unfinished conflict
+        assert_eq!(
+            split_conflict_markers("<<<<<<< HEAD\n1\n=======\n2\n"),
+            None
+        );
+    }
+}
@@ -0,0 +1,566 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::coordinate::Coordinate;
+use crate::grammar::Grammar;
+
+// Drivers are JS plugins that give meaning to the grammars defined in the sheet.
+// The contract between a driver and the sheet is the `ISE` object set up in
+// static/driver_api.js:
+//
+//   ISE.registerDriver({
+//     name: "js",                         // unique name of the driver
+//     definitions: ["js_grammar"],        // names of the definitions it evaluates
+//     evaluate: (request) => value,       // may also return a Promise
+//   });
+//
+// Every registration is reported back to the `Model` as a `DriverInfo`, and every
+// evaluation goes out as a `DriverRequest` and comes back as a `DriverResponse`.
+// Both messages cross the JS boundary as JSON.
+
+// DriverInfo is what the sheet knows about a registered driver
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DriverInfo {
+    pub name: String,
+    pub definitions: Vec<String>,
+}
+
+// DriverRequest is passed to the `evaluate` entry point of a driver
+#[derive(Serialize, Debug, Clone)]
+pub struct DriverRequest {
+    pub id: u32,
+    pub driver: String,
+    pub definition: String,
+    pub root: Coordinate,
+    // the grammar at `root` and all the grammars nested under it,
+    // keyed by their coordinate string
+    pub grammars: HashMap<String, Grammar>,
+}
+
+// DriverResponse is sent back by static/driver_api.js once `evaluate` has settled
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct DriverResponse {
+    pub id: u32,
+    pub result: DriverResult,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum DriverResult {
+    Value(serde_json::Value),
+    Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DriverError {
+    // no grammar at or above the coordinate is handled by a registered driver
+    NoDriver(Coordinate),
+    // a response came back for a request that isn't pending (anymore)
+    UnknownRequest(u32),
+    // the driver threw, rejected, or returned something that couldn't be read
+    Evaluation { driver: String, message: String },
+}
+
+impl fmt::Display for DriverError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DriverError::NoDriver(coord) => write!(
+                f,
+                "no driver handles the grammar at {}",
+                coord.to_string()
+            ),
+            DriverError::UnknownRequest(id) => write!(f, "no pending driver request #{}", id),
+            DriverError::Evaluation { driver, message } => {
+                write!(f, "driver \"{}\" failed: {}", driver, message)
+            }
+        }
+    }
+}
+
+// A pending request remembers where its result has to go
+#[derive(Debug, Clone)]
+pub struct PendingRequest {
+    pub driver: String,
+    pub root: Coordinate,
+    pub output: Coordinate,
+}
+
+// DriverRegistry keeps track of the loaded drivers and of the requests in flight
+#[derive(Debug, Default)]
+pub struct DriverRegistry {
+    pub drivers: Vec<DriverInfo>,
+    pending: HashMap<u32, PendingRequest>,
+    next_id: u32,
+}
+
+impl DriverRegistry {
+    // registering a driver with the same name again replaces the previous one,
+    // so that reloading a driver directory picks up the new definitions
+    pub fn register(&mut self, info: DriverInfo) {
+        if let Some(existing) = self.drivers.iter_mut().find(|d| d.name == info.name) {
+            *existing = info;
+        } else {
+            self.drivers.push(info);
+        }
+    }
+
+    pub fn driver_for(&self, definition: &str) -> Option<&DriverInfo> {
+        self.drivers
+            .iter()
+            .find(|d| d.definitions.iter().any(|defn| defn == definition))
+    }
+
+    // find the closest grammar (the coordinate itself or one of its parents) whose
+    // name is a definition handled by one of the registered drivers
+    pub fn find_root(
+        &self,
+        grammars: &HashMap<Coordinate, Grammar>,
+        coord: &Coordinate,
+    ) -> Option<(Coordinate, DriverInfo)> {
+        let mut current = Some(coord.clone());
+        while let Some(c) = current {
+            if let Some(driver) = grammars.get(&c).and_then(|g| self.driver_for(&g.name)) {
+                return Some((c, driver.clone()));
+            }
+            current = c.parent();
+        }
+        None
+    }
+
+    pub fn request(
+        &mut self,
+        grammars: &HashMap<Coordinate, Grammar>,
+        coord: &Coordinate,
+        output: Coordinate,
+    ) -> Result<DriverRequest, DriverError> {
+        let (root, driver) = self
+            .find_root(grammars, coord)
+            .ok_or_else(|| DriverError::NoDriver(coord.clone()))?;
+        self.next_id += 1;
+        let id = self.next_id;
+        self.pending.insert(
+            id,
+            PendingRequest {
+                driver: driver.name.clone(),
+                root: root.clone(),
+                output,
+            },
+        );
+        Ok(DriverRequest {
+            id,
+            driver: driver.name,
+            definition: grammars
+                .get(&root)
+                .map(|g| g.name.clone())
+                .unwrap_or_default(),
+            grammars: subtree(grammars, &root),
+            root,
+        })
+    }
+
+    // match a response with its request. The pending request is returned either way so
+    // the caller knows which cell to write the value (or the error) to.
+    pub fn resolve(
+        &mut self,
+        response: DriverResponse,
+    ) -> Result<(PendingRequest, serde_json::Value), (Option<PendingRequest>, DriverError)> {
+        match self.pending.remove(&response.id) {
+            None => Err((None, DriverError::UnknownRequest(response.id))),
+            Some(pending) => match response.result {
+                DriverResult::Value(value) => Ok((pending, value)),
+                DriverResult::Error(message) => {
+                    let error = DriverError::Evaluation {
+                        driver: pending.driver.clone(),
+                        message,
+                    };
+                    Err((Some(pending), error))
+                }
+            },
+        }
+    }
+}
+
+// all the grammars at or nested under `root`, keyed by their coordinate string
+pub fn subtree(
+    grammars: &HashMap<Coordinate, Grammar>,
+    root: &Coordinate,
+) -> HashMap<String, Grammar> {
+    grammars
+        .iter()
+        .filter(|(c, _)| c.row_cols.starts_with(&root.row_cols))
+        .map(|(c, g)| (c.to_string(), g.clone()))
+        .collect()
+}
+
+// drivers can return any JSON value, strings are written into cells as-is
+pub fn value_to_string(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Null => String::new(),
+        v => v.to_string(),
+    }
+}
+
+// DRIVER DIRECTORY VALIDATION
+// A driver directory is selected as a whole in the "Settings" menu. Before anything in it
+// is loaded, it is checked against the following rules:
+// - there is exactly one main file, `{directory}/{directory}.js`
+// - there is at most one manifest, `{directory}/driver.json`
+// - every file has one of the `DRIVER_FILE_TYPES` extensions
+// - no file is bigger than `MAX_DRIVER_FILE_SIZE`, and the whole directory isn't
+//   bigger than `MAX_DRIVER_DIRECTORY_SIZE`
+pub const DRIVER_MANIFEST_NAME: &str = "driver.json";
+// driver files are passed to the main process as text, so only text formats are allowed
+pub const DRIVER_FILE_TYPES: [&str; 9] = ["js", "json", "css", "html", "txt", "md", "py", "svg", "csv"];
+pub const MAX_DRIVER_FILE_SIZE: u64 = 5 * 1024 * 1024;
+pub const MAX_DRIVER_DIRECTORY_SIZE: u64 = 20 * 1024 * 1024;
+
+// path (relative to the selected directory's parent, i.e. the `webkitRelativePath`)
+// and size in bytes of a file in a driver directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct DriverFileInfo {
+    pub path: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DriverIssue {
+    NoFiles,
+    NoMainFile { expected: String },
+    MultipleMainFiles(Vec<String>),
+    MultipleDirectories(Vec<String>),
+    MultipleManifests(Vec<String>),
+    InvalidManifest { path: String, message: String },
+    FileTypeNotAllowed(String),
+    FileTooLarge { path: String, size: u64 },
+    DirectoryTooLarge { size: u64 },
+    UnreadableFile(String),
+    UploadRejected(String),
+}
+
+impl fmt::Display for DriverIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DriverIssue::NoFiles => write!(f, "the selected directory is empty"),
+            DriverIssue::NoMainFile { expected } => {
+                write!(f, "missing main file, expected {}", expected)
+            }
+            DriverIssue::MultipleMainFiles(paths) => {
+                write!(f, "more than one main file: {}", paths.join(", "))
+            }
+            DriverIssue::MultipleDirectories(dirs) => write!(
+                f,
+                "files come from more than one directory: {}",
+                dirs.join(", ")
+            ),
+            DriverIssue::MultipleManifests(paths) => {
+                write!(f, "more than one manifest: {}", paths.join(", "))
+            }
+            DriverIssue::InvalidManifest { path, message } => {
+                write!(f, "invalid manifest {}: {}", path, message)
+            }
+            DriverIssue::FileTypeNotAllowed(path) => write!(
+                f,
+                "{} is not an allowed file type (allowed: {})",
+                path,
+                DRIVER_FILE_TYPES.join(", ")
+            ),
+            DriverIssue::FileTooLarge { path, size } => write!(
+                f,
+                "{} is {} bytes, the limit is {} bytes",
+                path, size, MAX_DRIVER_FILE_SIZE
+            ),
+            DriverIssue::DirectoryTooLarge { size } => write!(
+                f,
+                "the driver directory is {} bytes, the limit is {} bytes",
+                size, MAX_DRIVER_DIRECTORY_SIZE
+            ),
+            DriverIssue::UnreadableFile(path) => write!(f, "{} could not be read as text", path),
+            DriverIssue::UploadRejected(path) => {
+                write!(f, "{} was rejected by the main process", path)
+            }
+        }
+    }
+}
+
+// Optional manifest of a driver directory
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct DriverManifest {
+    pub name: String,
+    #[serde(default)]
+    pub version: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+// DriverValidationReport is the result of checking a driver directory,
+// it is shown in the "Settings" menu
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DriverValidationReport {
+    pub directory: String,
+    pub main_file: Option<String>,
+    pub manifest: Option<String>,
+    pub manifest_info: Option<DriverManifest>,
+    pub misc_files: Vec<String>,
+    pub issues: Vec<DriverIssue>,
+}
+
+impl DriverValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+fn file_extension(path: &str) -> Option<String> {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    let mut parts = file_name.rsplitn(2, '.');
+    match (parts.next(), parts.next()) {
+        (Some(extension), Some(stem)) if stem != "" => Some(extension.to_lowercase()),
+        _ => None,
+    }
+}
+
+pub fn validate_driver_files(files: &[DriverFileInfo]) -> DriverValidationReport {
+    let mut report = DriverValidationReport::default();
+    if files.is_empty() {
+        report.issues.push(DriverIssue::NoFiles);
+        return report;
+    }
+
+    let mut directories: Vec<String> = files
+        .iter()
+        .map(|f| f.path.split('/').next().unwrap_or("").to_string())
+        .collect();
+    directories.sort();
+    directories.dedup();
+    if directories.len() > 1 {
+        report
+            .issues
+            .push(DriverIssue::MultipleDirectories(directories.clone()));
+    }
+    report.directory = directories.first().cloned().unwrap_or_default();
+
+    let main_file_path = format! {"{}/{}.js", report.directory, report.directory};
+    let mut main_files = vec![];
+    let mut manifests = vec![];
+    let mut total_size = 0;
+    for file in files {
+        total_size += file.size;
+        let path_parts: Vec<&str> = file.path.split('/').collect();
+        match (path_parts.first(), path_parts.last(), path_parts.len()) {
+            (Some(directory), Some(file_name), 2)
+                if format! {"{}.js", directory} == file_name.to_string() =>
+            {
+                main_files.push(file.path.clone())
+            }
+            (_, Some(file_name), 2) if *file_name == DRIVER_MANIFEST_NAME => {
+                manifests.push(file.path.clone())
+            }
+            _ => report.misc_files.push(file.path.clone()),
+        }
+        match file_extension(&file.path) {
+            Some(ref extension) if DRIVER_FILE_TYPES.contains(&extension.as_str()) => (),
+            _ => report
+                .issues
+                .push(DriverIssue::FileTypeNotAllowed(file.path.clone())),
+        }
+        if file.size > MAX_DRIVER_FILE_SIZE {
+            report.issues.push(DriverIssue::FileTooLarge {
+                path: file.path.clone(),
+                size: file.size,
+            });
+        }
+    }
+    if total_size > MAX_DRIVER_DIRECTORY_SIZE {
+        report
+            .issues
+            .push(DriverIssue::DirectoryTooLarge { size: total_size });
+    }
+
+    match main_files.len() {
+        0 => report.issues.push(DriverIssue::NoMainFile {
+            expected: main_file_path,
+        }),
+        1 => report.main_file = main_files.pop(),
+        _ => report.issues.push(DriverIssue::MultipleMainFiles(main_files)),
+    }
+    match manifests.len() {
+        0 => (),
+        1 => report.manifest = manifests.pop(),
+        _ => report.issues.push(DriverIssue::MultipleManifests(manifests)),
+    }
+
+    report
+}
+
+pub fn parse_driver_manifest(path: &str, contents: &str) -> Result<DriverManifest, DriverIssue> {
+    serde_json::from_str::<DriverManifest>(contents).map_err(|e| DriverIssue::InvalidManifest {
+        path: path.to_string(),
+        message: e.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate::CoordinateParser;
+    use crate::coordinate::Rule;
+    use crate::util::non_zero_u32_tuple;
+    use crate::{coord, g, grid};
+    use crate::grammar_map::*;
+    use pest::Parser;
+    use std::num::NonZeroU32;
+
+    fn js_grammars() -> HashMap<Coordinate, Grammar> {
+        let mut map = HashMap::new();
+        build_grammar_map(
+            &mut map,
+            coord!("root-A1"),
+            grid![[g!(Grammar::input("", "1")), g!(Grammar::input("", "2"))]],
+        );
+        map.get_mut(&coord!("root-A1")).unwrap().name = "js_grammar".to_string();
+        map.insert(coord!("root-B1"), Grammar::default());
+        map
+    }
+
+    fn js_driver() -> DriverInfo {
+        DriverInfo {
+            name: "js".to_string(),
+            definitions: vec!["js_grammar".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_register_replaces_driver_with_same_name() {
+        let mut registry = DriverRegistry::default();
+        registry.register(js_driver());
+        registry.register(DriverInfo {
+            name: "js".to_string(),
+            definitions: vec!["other".to_string()],
+        });
+        assert_eq!(registry.drivers.len(), 1);
+        assert!(registry.driver_for("js_grammar").is_none());
+        assert!(registry.driver_for("other").is_some());
+    }
+
+    #[test]
+    fn test_request_uses_closest_defined_parent() {
+        let mut registry = DriverRegistry::default();
+        registry.register(js_driver());
+        let grammars = js_grammars();
+        let request = registry
+            .request(&grammars, &coord!("root-A1-B1"), coord!("root-B1"))
+            .unwrap();
+        assert_eq!(request.root, coord!("root-A1"));
+        assert_eq!(request.definition, "js_grammar");
+        assert_eq!(request.grammars.len(), 3);
+        assert!(request.grammars.contains_key("root-A1-B1"));
+        assert!(!request.grammars.contains_key("root-B1"));
+    }
+
+    #[test]
+    fn test_request_without_driver() {
+        let mut registry = DriverRegistry::default();
+        let grammars = js_grammars();
+        assert_eq!(
+            registry
+                .request(&grammars, &coord!("root-A1-B1"), coord!("root-B1"))
+                .unwrap_err(),
+            DriverError::NoDriver(coord!("root-A1-B1"))
+        );
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut registry = DriverRegistry::default();
+        registry.register(js_driver());
+        let grammars = js_grammars();
+        let request = registry
+            .request(&grammars, &coord!("root-A1"), coord!("root-B1"))
+            .unwrap();
+        let (pending, value) = registry
+            .resolve(DriverResponse {
+                id: request.id,
+                result: DriverResult::Value(serde_json::json!(3)),
+            })
+            .unwrap();
+        assert_eq!(pending.output, coord!("root-B1"));
+        assert_eq!(value_to_string(&value), "3");
+        // a request can only be resolved once
+        assert_eq!(
+            registry
+                .resolve(DriverResponse {
+                    id: request.id,
+                    result: DriverResult::Value(serde_json::json!(3)),
+                })
+                .unwrap_err()
+                .1,
+            DriverError::UnknownRequest(request.id)
+        );
+    }
+
+    fn file(path: &str, size: u64) -> DriverFileInfo {
+        DriverFileInfo {
+            path: path.to_string(),
+            size,
+        }
+    }
+
+    #[test]
+    fn test_validate_driver_files() {
+        let report = validate_driver_files(&[
+            file("python/python.js", 100),
+            file("python/driver.json", 10),
+            file("python/lib/helpers.js", 10),
+        ]);
+        assert!(report.is_valid());
+        assert_eq!(report.directory, "python");
+        assert_eq!(report.main_file, Some("python/python.js".to_string()));
+        assert_eq!(report.manifest, Some("python/driver.json".to_string()));
+        assert_eq!(report.misc_files, vec!["python/lib/helpers.js".to_string()]);
+    }
+
+    #[test]
+    fn test_validate_driver_files_without_main_file() {
+        let report = validate_driver_files(&[file("python/main.js", 100)]);
+        assert!(!report.is_valid());
+        assert_eq!(
+            report.issues,
+            vec![DriverIssue::NoMainFile {
+                expected: "python/python.js".to_string()
+            }]
+        );
+        assert_eq!(validate_driver_files(&[]).issues, vec![DriverIssue::NoFiles]);
+    }
+
+    #[test]
+    fn test_validate_driver_files_types_and_sizes() {
+        let report = validate_driver_files(&[
+            file("python/python.js", MAX_DRIVER_FILE_SIZE + 1),
+            file("python/run.exe", 10),
+            file("python/Makefile", 10),
+        ]);
+        assert_eq!(
+            report.issues,
+            vec![
+                DriverIssue::FileTooLarge {
+                    path: "python/python.js".to_string(),
+                    size: MAX_DRIVER_FILE_SIZE + 1
+                },
+                DriverIssue::FileTypeNotAllowed("python/run.exe".to_string()),
+                DriverIssue::FileTypeNotAllowed("python/Makefile".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_driver_manifest() {
+        assert_eq!(
+            parse_driver_manifest("python/driver.json", r#"{"name": "python"}"#)
+                .unwrap()
+                .name,
+            "python"
+        );
+        assert!(parse_driver_manifest("python/driver.json", "{").is_err());
+    }
+}
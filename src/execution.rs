@@ -0,0 +1,528 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use stdweb::unstable::TryInto;
+use yew::Callback;
+
+use crate::coordinate::Coordinate;
+
+// Code in the sheet (e.g. from an editor cell) is run by an execution backend.
+// `Executions` keeps track of the requests sent to the backend and where their results
+// should go, while the backend is only responsible for running the code and calling back
+// with an `ExecutionResult` exactly once per request.
+//
+// Timeouts and cancellation are handled by `Executions`: a timed out or cancelled request
+// is resolved right away and the backend is asked to stop it (if it can), any result that
+// comes in late for it is ignored.
+
+// default time a script is allowed to run for before it's reported as timed out
+pub const DEFAULT_TIMEOUT_MS: u32 = 10_000;
+
+// PYTHON_RUNNER is run before any python code, by every python backend. It defines
+// `__ise_run`, which takes a JSON encoded `ExecutionRequest` and returns a JSON encoded
+// `ExecutionResult`, capturing stdout/stderr and the value of the last expression.
+pub const PYTHON_RUNNER: &str = r#"
+import ast, io, json, sys, traceback
+
+def __ise_to_json(value):
+    try:
+        json.dumps(value)
+        return value
+    except (TypeError, ValueError):
+        return repr(value)
+
+def __ise_run(request_json):
+    request = json.loads(request_json)
+    result = {"id": request["id"], "stdout": "", "stderr": ""}
+    stdout, stderr = io.StringIO(), io.StringIO()
+    real_stdout, real_stderr = sys.stdout, sys.stderr
+    sys.stdout, sys.stderr = stdout, stderr
+    scope = {"__name__": "__main__"}
+    scope.update(request.get("globals", {}))
+    try:
+        exec(compile(request.get("prelude", ""), "<prelude>", "exec"), scope)
+        tree = ast.parse(request["code"], "<cell>", "exec")
+        last = None
+        if tree.body and isinstance(tree.body[-1], ast.Expr):
+            last = ast.Expression(tree.body.pop().value)
+        exec(compile(tree, "<cell>", "exec"), scope)
+        value = eval(compile(last, "<cell>", "eval"), scope) if last is not None else None
+        result["outcome"] = {"Ok": __ise_to_json(value)}
+    except SyntaxError as e:
+        result["outcome"] = {"Err": {
+            "kind": "Syntax", "message": str(e), "traceback": traceback.format_exc(),
+        }}
+    except BaseException as e:
+        result["outcome"] = {"Err": {
+            "kind": "Runtime",
+            "message": "{}: {}".format(type(e).__name__, e),
+            "traceback": traceback.format_exc(),
+        }}
+    finally:
+        sys.stdout, sys.stderr = real_stdout, real_stderr
+    result["stdout"] = stdout.getvalue()
+    result["stderr"] = stderr.getvalue()
+    return json.dumps(result)
+"#;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum BackendKind {
+    // python compiled to wasm, running in the renderer process
+    Pyodide,
+    // a python interpreter started by the electron main process
+    NodePython,
+    // in-process stand-in, used for testing
+    Mock,
+}
+
+impl BackendKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BackendKind::Pyodide => "Pyodide",
+            BackendKind::NodePython => "Python (local)",
+            BackendKind::Mock => "Mock",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ExecutionRequest {
+    pub id: u32,
+    pub code: String,
+    // values made available to the code as global variables
+    pub globals: serde_json::Map<String, serde_json::Value>,
+    // code run before `code` in the same scope, e.g. to wrap the globals in helpers
+    pub prelude: String,
+    pub timeout_ms: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ExecutionErrorKind {
+    Syntax,
+    Runtime,
+    Timeout,
+    Cancelled,
+    // the backend itself failed, e.g. pyodide isn't loaded or python isn't installed
+    Backend,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExecutionError {
+    pub kind: ExecutionErrorKind,
+    pub message: String,
+    #[serde(default)]
+    pub traceback: String,
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ExecutionErrorKind::Syntax => write!(f, "syntax error: {}", self.message),
+            ExecutionErrorKind::Runtime => write!(f, "{}", self.message),
+            ExecutionErrorKind::Timeout => write!(f, "timed out: {}", self.message),
+            ExecutionErrorKind::Cancelled => write!(f, "cancelled"),
+            ExecutionErrorKind::Backend => write!(f, "backend error: {}", self.message),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ExecutionResult {
+    pub id: u32,
+    #[serde(default)]
+    pub stdout: String,
+    #[serde(default)]
+    pub stderr: String,
+    // value of the last expression of the code
+    pub outcome: Result<serde_json::Value, ExecutionError>,
+}
+
+impl ExecutionResult {
+    pub fn error(id: u32, kind: ExecutionErrorKind, message: String) -> ExecutionResult {
+        ExecutionResult {
+            id,
+            stdout: String::new(),
+            stderr: String::new(),
+            outcome: Err(ExecutionError {
+                kind,
+                message,
+                traceback: String::new(),
+            }),
+        }
+    }
+
+    // results come back from JS as JSON, anything unreadable is a backend error
+    pub fn from_json(id: u32, json: &str) -> ExecutionResult {
+        serde_json::from_str(json).unwrap_or_else(|e| {
+            ExecutionResult::error(
+                id,
+                ExecutionErrorKind::Backend,
+                format! {"unreadable result: {}", e},
+            )
+        })
+    }
+}
+
+pub trait ExecutionBackend: fmt::Debug {
+    fn kind(&self) -> BackendKind;
+
+    // starts running `request`, `done` has to be called exactly once with its result
+    fn execute(&mut self, request: ExecutionRequest, done: Callback<ExecutionResult>);
+
+    // asks the backend to stop running a request, if it's able to
+    fn cancel(&mut self, id: u32);
+}
+
+pub fn new_backend(kind: BackendKind) -> Box<dyn ExecutionBackend> {
+    match kind {
+        BackendKind::Pyodide => Box::new(PyodideBackend::default()),
+        BackendKind::NodePython => Box::new(NodePythonBackend::default()),
+        BackendKind::Mock => Box::new(MockBackend::default()),
+    }
+}
+
+// Pyodide runs synchronously on the renderer thread, so it can't be interrupted: a
+// timed out or cancelled request keeps running but its result is dropped.
+#[derive(Debug, Default)]
+pub struct PyodideBackend {
+    runner_loaded: bool,
+}
+
+impl ExecutionBackend for PyodideBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Pyodide
+    }
+
+    fn execute(&mut self, request: ExecutionRequest, done: Callback<ExecutionResult>) {
+        let id = request.id;
+        let request_json = serde_json::to_string(&request).unwrap();
+        let runner = if self.runner_loaded {
+            ""
+        } else {
+            PYTHON_RUNNER
+        };
+        let result_json: String = js! {
+            try {
+                if (typeof pyodide === "undefined") {
+                    throw new Error("pyodide is not loaded");
+                }
+                let runner = @{runner};
+                if (runner !== "") {
+                    pyodide.runPython(runner);
+                }
+                return pyodide.globals.__ise_run(@{request_json});
+            } catch (e) {
+                return JSON.stringify({
+                    id: @{id},
+                    outcome: { Err: { kind: "Backend", message: String(e), traceback: "" } },
+                });
+            }
+        }
+        .try_into()
+        .unwrap_or_default();
+        let result = ExecutionResult::from_json(id, &result_json);
+        // the runner only failing to load is a backend error
+        self.runner_loaded = match &result.outcome {
+            Err(ExecutionError {
+                kind: ExecutionErrorKind::Backend,
+                ..
+            }) => false,
+            _ => true,
+        };
+        done.emit(result);
+    }
+
+    fn cancel(&mut self, _id: u32) {}
+}
+
+// NodePythonBackend sends the code to the electron main process (see the "run-python"
+// handler in static/main.js), which runs it in a python child process
+#[derive(Debug, Default)]
+pub struct NodePythonBackend;
+
+impl ExecutionBackend for NodePythonBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::NodePython
+    }
+
+    fn execute(&mut self, request: ExecutionRequest, done: Callback<ExecutionResult>) {
+        use stdweb::Once;
+        let id = request.id;
+        let request_json = serde_json::to_string(&request).unwrap();
+        let respond = move |result_json: String| {
+            done.emit(ExecutionResult::from_json(id, &result_json));
+        };
+        js! {
+            let respond = @{Once(respond)};
+            require("electron").ipcRenderer
+                .invoke("run-python", @{request_json}, @{PYTHON_RUNNER})
+                .then(respond, function (e) {
+                    respond(JSON.stringify({
+                        id: @{id},
+                        outcome: { Err: { kind: "Backend", message: String(e), traceback: "" } },
+                    }));
+                });
+        };
+    }
+
+    fn cancel(&mut self, id: u32) {
+        js! {
+            require("electron").ipcRenderer.send("cancel-python", @{id});
+        };
+    }
+}
+
+// MockBackend answers from a table of canned results, keyed by code. Code without a
+// canned result is left running, which is useful to test timeouts and cancellation.
+#[derive(Debug, Default)]
+pub struct MockBackend {
+    pub responses: HashMap<String, Result<serde_json::Value, ExecutionError>>,
+    pub executed: Vec<ExecutionRequest>,
+    pub cancelled: Vec<u32>,
+}
+
+impl MockBackend {
+    pub fn respond(&mut self, code: &str, outcome: Result<serde_json::Value, ExecutionError>) {
+        self.responses.insert(code.to_string(), outcome);
+    }
+}
+
+impl ExecutionBackend for MockBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Mock
+    }
+
+    fn execute(&mut self, request: ExecutionRequest, done: Callback<ExecutionResult>) {
+        self.executed.push(request.clone());
+        if let Some(outcome) = self.responses.get(&request.code) {
+            done.emit(ExecutionResult {
+                id: request.id,
+                stdout: String::new(),
+                stderr: String::new(),
+                outcome: outcome.clone(),
+            });
+        }
+    }
+
+    fn cancel(&mut self, id: u32) {
+        self.cancelled.push(id);
+    }
+}
+
+// where the result of a running request should be written to
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingExecution {
+    pub output: Coordinate,
+}
+
+#[derive(Debug)]
+pub struct Executions {
+    pub backend: Box<dyn ExecutionBackend>,
+    pending: HashMap<u32, PendingExecution>,
+    next_id: u32,
+    pub timeout_ms: u32,
+    // sent along with every request, see `ExecutionRequest::prelude`
+    pub prelude: String,
+    // last result, shown in the "Home" menu with its stdout and stderr
+    pub last_result: Option<ExecutionResult>,
+}
+
+impl Executions {
+    pub fn new(backend: Box<dyn ExecutionBackend>) -> Executions {
+        Executions {
+            backend,
+            pending: HashMap::new(),
+            next_id: 0,
+            timeout_ms: DEFAULT_TIMEOUT_MS,
+            prelude: String::new(),
+            last_result: None,
+        }
+    }
+
+    pub fn set_backend(&mut self, backend: Box<dyn ExecutionBackend>) {
+        // requests running on the old backend are cancelled
+        let ids: Vec<u32> = self.pending.keys().cloned().collect();
+        for id in ids {
+            self.cancel(id);
+        }
+        self.backend = backend;
+    }
+
+    pub fn is_running(&self, id: u32) -> bool {
+        self.pending.contains_key(&id)
+    }
+
+    pub fn running(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = self.pending.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    // sends code to the backend, and returns the id of the request
+    pub fn start(
+        &mut self,
+        code: String,
+        globals: serde_json::Map<String, serde_json::Value>,
+        output: Coordinate,
+        done: Callback<ExecutionResult>,
+    ) -> u32 {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.pending.insert(id, PendingExecution { output });
+        self.backend.execute(
+            ExecutionRequest {
+                id,
+                code,
+                globals,
+                prelude: self.prelude.clone(),
+                timeout_ms: self.timeout_ms,
+            },
+            done,
+        );
+        id
+    }
+
+    // matches a result with its request, results of requests that aren't running
+    // anymore (e.g. cancelled or timed out) are ignored
+    pub fn finish(
+        &mut self,
+        result: ExecutionResult,
+    ) -> Option<(PendingExecution, ExecutionResult)> {
+        let pending = self.pending.remove(&result.id)?;
+        self.last_result = Some(result.clone());
+        Some((pending, result))
+    }
+
+    pub fn cancel(&mut self, id: u32) -> Option<(PendingExecution, ExecutionResult)> {
+        self.stop(
+            id,
+            ExecutionErrorKind::Cancelled,
+            "cancelled by user".to_string(),
+        )
+    }
+
+    pub fn time_out(&mut self, id: u32) -> Option<(PendingExecution, ExecutionResult)> {
+        let message = format! {"did not finish within {}ms", self.timeout_ms};
+        self.stop(id, ExecutionErrorKind::Timeout, message)
+    }
+
+    fn stop(
+        &mut self,
+        id: u32,
+        kind: ExecutionErrorKind,
+        message: String,
+    ) -> Option<(PendingExecution, ExecutionResult)> {
+        if !self.pending.contains_key(&id) {
+            return None;
+        }
+        self.backend.cancel(id);
+        self.finish(ExecutionResult::error(id, kind, message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coord;
+    use crate::coordinate::{CoordinateParser, Rule};
+    use crate::util::non_zero_u32_tuple;
+    use pest::Parser;
+    use std::cell::RefCell;
+    use std::num::NonZeroU32;
+    use std::rc::Rc;
+
+    fn collect() -> (Callback<ExecutionResult>, Rc<RefCell<Vec<ExecutionResult>>>) {
+        let results = Rc::new(RefCell::new(vec![]));
+        let results_ref = results.clone();
+        (
+            Callback::from(move |r| results_ref.borrow_mut().push(r)),
+            results,
+        )
+    }
+
+    #[test]
+    fn test_mock_execution() {
+        let mut mock = MockBackend::default();
+        mock.respond("1 + 2", Ok(serde_json::json!(3)));
+        let mut executions = Executions::new(Box::new(mock));
+        let (done, results) = collect();
+        let id = executions.start(
+            "1 + 2".to_string(),
+            serde_json::Map::new(),
+            coord!("root-A1"),
+            done,
+        );
+        let result = results.borrow()[0].clone();
+        assert_eq!(result.id, id);
+        let (pending, result) = executions.finish(result).unwrap();
+        assert_eq!(pending.output, coord!("root-A1"));
+        assert_eq!(result.outcome, Ok(serde_json::json!(3)));
+        assert!(!executions.is_running(id));
+    }
+
+    #[test]
+    fn test_cancelled_execution_ignores_late_result() {
+        let mut executions = Executions::new(Box::new(MockBackend::default()));
+        let (done, results) = collect();
+        let id = executions.start(
+            "while True: pass".to_string(),
+            serde_json::Map::new(),
+            coord!("root-A1"),
+            done,
+        );
+        assert!(results.borrow().is_empty());
+        assert!(executions.is_running(id));
+
+        let (_, result) = executions.cancel(id).unwrap();
+        assert_eq!(
+            result.outcome.unwrap_err().kind,
+            ExecutionErrorKind::Cancelled
+        );
+        assert!(executions
+            .finish(ExecutionResult::error(
+                id,
+                ExecutionErrorKind::Runtime,
+                "late".to_string()
+            ))
+            .is_none());
+        assert!(executions.time_out(id).is_none());
+    }
+
+    #[test]
+    fn test_timed_out_execution() {
+        let mut executions = Executions::new(Box::new(MockBackend::default()));
+        let (done, _) = collect();
+        let id = executions.start(
+            "while True: pass".to_string(),
+            serde_json::Map::new(),
+            coord!("root-A1"),
+            done,
+        );
+        let (_, result) = executions.time_out(id).unwrap();
+        assert_eq!(
+            result.outcome.unwrap_err().kind,
+            ExecutionErrorKind::Timeout
+        );
+        assert_eq!(executions.running(), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn test_result_from_json() {
+        let result = ExecutionResult::from_json(
+            1,
+            r#"{"id": 1, "stdout": "hi\n", "stderr": "", "outcome": {"Err": {"kind": "Syntax", "message": "invalid syntax"}}}"#,
+        );
+        assert_eq!(result.stdout, "hi\n");
+        assert_eq!(
+            result.outcome.unwrap_err().to_string(),
+            "syntax error: invalid syntax"
+        );
+        assert_eq!(
+            ExecutionResult::from_json(2, "not json")
+                .outcome
+                .unwrap_err()
+                .kind,
+            ExecutionErrorKind::Backend
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+
+// FILE EXPLORER
+// The "File Explorer" side menu lists the session files of a workspace directory chosen
+// by the user, along with the files opened recently. The files themselves are only ever
+// touched by the Electron main process (see static/main.js), this keeps track of what it
+// answered. The workspace and the recent files are kept in `EXPLORER_FILE` in the user
+// config directory, the main process keeps its own record of them and only touches the
+// files of the workspace and the recent ones.
+
+pub const EXPLORER_FILE: &str = "explorer.json";
+
+// keep in sync with SESSION_FILE_TYPES in static/main.js
+pub const SESSION_FILE_TYPES: &[&str] = &[".ise", ".json"];
+
+pub const MAX_RECENT_FILES: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Explorer {
+    pub workspace: Option<String>,
+    // paths of the files opened recently, the latest first
+    pub recent: Vec<String>,
+}
+
+// a session file as listed by the main process
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct SessionFile {
+    pub name: String,
+    pub path: String,
+    // milliseconds since the epoch
+    pub modified: f64,
+    // the title of the session, `None` if the file isn't a session
+    pub title: Option<String>,
+}
+
+impl Explorer {
+    pub fn opened(&mut self, path: &str) {
+        self.recent.retain(|p| p != path);
+        self.recent.insert(0, path.to_string());
+        self.recent.truncate(MAX_RECENT_FILES);
+    }
+
+    // keeps the recent files in step with a file renamed (`Some`) or deleted (`None`)
+    pub fn moved(&mut self, path: &str, new_path: Option<String>) {
+        self.recent = self
+            .recent
+            .drain(..)
+            .filter_map(|p| if p == path { new_path.clone() } else { Some(p) })
+            .collect();
+    }
+}
+
+pub fn is_session_file(name: &str) -> bool {
+    let name = name.to_lowercase();
+    SESSION_FILE_TYPES.iter().any(|t| name.ends_with(t))
+}
+
+// the session files of a listing, sorted by name
+pub fn parse_listing(listing: &str) -> Result<Vec<SessionFile>, String> {
+    let mut files: Vec<SessionFile> =
+        serde_json::from_str(listing).map_err(|e| format! {"cannot read the workspace: {}", e})?;
+    files.retain(|f| is_session_file(&f.name));
+    files.sort_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+    Ok(files)
+}
+
+// the name a file is renamed to: a plain file name, with ".json" added if it isn't a
+// session file name already
+pub fn file_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() || name == "." || name == ".." {
+        return Err("the file name is empty".to_string());
+    }
+    if name.contains('/') || name.contains('\\') {
+        return Err(format! {"\"{}\" should be a file name, not a path", name});
+    }
+    if is_session_file(name) {
+        Ok(name.to_string())
+    } else {
+        Ok(format! {"{}.json", name})
+    }
+}
+
+// the name of a copy of `name` that isn't one of `existing`, e.g. "budget copy.json",
+// then "budget copy 2.json"
+pub fn duplicate_name(name: &str, existing: &[String]) -> String {
+    let (stem, extension) = match name.rfind('.') {
+        Some(i) if i > 0 => name.split_at(i),
+        _ => (name, ""),
+    };
+    let mut copy = format! {"{} copy{}", stem, extension};
+    let mut n = 2;
+    while existing.contains(&copy) {
+        copy = format! {"{} copy {}{}", stem, n, extension};
+        n += 1;
+    }
+    copy
+}
+
+// the last part of a path, which is how recent files are shown
+pub fn path_name(path: &str) -> &str {
+    path.rsplit(|c| c == '/' || c == '\\')
+        .next()
+        .unwrap_or(path)
+}
+
+// the modified time of a file as "2020-05-17 14:03" (UTC)
+pub fn format_modified(millis: f64) -> String {
+    let seconds = (millis / 1000.0).floor() as i64;
+    let days = seconds.div_euclid(86400);
+    let time = seconds.rem_euclid(86400);
+    // days to the civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format! {"{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, time / 3600, time % 3600 / 60}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_files() {
+        let mut explorer = Explorer::default();
+        for i in 0..12 {
+            explorer.opened(&format! {"/w/{}.json", i});
+        }
+        explorer.opened("/w/5.json");
+        assert_eq!(explorer.recent.len(), MAX_RECENT_FILES);
+        assert_eq!(explorer.recent[0], "/w/5.json");
+        assert_eq!(explorer.recent[1], "/w/11.json");
+        assert_eq!(
+            explorer.recent.iter().filter(|p| *p == "/w/5.json").count(),
+            1
+        );
+
+        explorer.moved("/w/5.json", Some("/w/five.json".to_string()));
+        assert_eq!(explorer.recent[0], "/w/five.json");
+        explorer.moved("/w/11.json", None);
+        assert_eq!(explorer.recent[1], "/w/10.json");
+    }
+
+    #[test]
+    fn test_parse_listing() {
+        let files = parse_listing(
+            r#"[
+                {"name": "b.ise", "path": "/w/b.ise", "modified": 0, "title": "B"},
+                {"name": "notes.txt", "path": "/w/notes.txt", "modified": 0, "title": null},
+                {"name": "A.json", "path": "/w/A.json", "modified": 0, "title": null}
+            ]"#,
+        )
+        .unwrap();
+        let names: Vec<&str> = files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["A.json", "b.ise"]);
+        assert_eq!(files[1].title, Some("B".to_string()));
+        assert!(parse_listing("nope").is_err());
+    }
+
+    #[test]
+    fn test_file_names() {
+        assert_eq!(file_name(" budget "), Ok("budget.json".to_string()));
+        assert_eq!(file_name("budget.ise"), Ok("budget.ise".to_string()));
+        assert!(file_name("../budget").is_err());
+        assert!(file_name("").is_err());
+
+        let existing = vec!["budget copy.json".to_string()];
+        assert_eq!(duplicate_name("budget.json", &[]), "budget copy.json");
+        assert_eq!(
+            duplicate_name("budget.json", &existing),
+            "budget copy 2.json"
+        );
+        assert_eq!(path_name("/home/me/budget.json"), "budget.json");
+    }
+
+    #[test]
+    fn test_format_modified() {
+        assert_eq!(format_modified(0.0), "1970-01-01 00:00");
+        assert_eq!(format_modified(1589724180000.0), "2020-05-17 14:03");
+    }
+}
@@ -0,0 +1,412 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::num::NonZeroU32;
+
+use crate::coordinate::{Coordinate, Line};
+use crate::grammar::{Grammar, Kind};
+use crate::style::{Style, DEFAULT_BORDER_COLOR, DEFAULT_FONT_COLOR, DEFAULT_FONT_WEIGHT};
+use crate::value::CellValue;
+
+// CONDITIONAL FORMATTING
+// Each grid can have a list of formatting rules (stored in `Session::formatting` by the
+// coordinate of the grid). A rule applies to every cell nested in its grid and, when its
+// condition holds, overrides parts of the cell's `Style`. Rules are applied in order,
+// starting with the outermost grid, so later rules and rules of inner grids win.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Condition {
+    // the cell's value is a number greater/less than the threshold
+    GreaterThan(f64),
+    LessThan(f64),
+    // the cell's value is exactly this text
+    Equals(String),
+    // the cell's value matches the regular expression
+    Matches(String),
+    // the cell is of this kind, e.g. "Lookup" (see `kind_name`)
+    KindIs(String),
+    // the cell failed validation
+    Invalid,
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Condition::GreaterThan(n) => write!(f, "value > {}", n),
+            Condition::LessThan(n) => write!(f, "value < {}", n),
+            Condition::Equals(text) => write!(f, "value is \"{}\"", text),
+            Condition::Matches(pattern) => write!(f, "value matches /{}/", pattern),
+            Condition::KindIs(name) => write!(f, "kind is {}", name),
+            Condition::Invalid => write!(f, "validation failed"),
+        }
+    }
+}
+
+// Style fields set by a formatting rule, everything else is left as it is
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct StyleOverride {
+    #[serde(default)]
+    pub font_color: Option<String>,
+    #[serde(default)]
+    pub font_weight: Option<i32>,
+    #[serde(default)]
+    pub border_color: Option<String>,
+}
+
+impl StyleOverride {
+    // fields set in `other` replace the ones set here
+    pub fn merge(&mut self, other: &StyleOverride) {
+        if other.font_color.is_some() {
+            self.font_color = other.font_color.clone();
+        }
+        if other.font_weight.is_some() {
+            self.font_weight = other.font_weight;
+        }
+        if other.border_color.is_some() {
+            self.border_color = other.border_color.clone();
+        }
+    }
+
+    // only sets the fields that `style` leaves at their defaults
+    pub fn apply_to_defaults(&self, style: &mut Style) {
+        if style.font_color == DEFAULT_FONT_COLOR {
+            if let Some(font_color) = &self.font_color {
+                style.font_color = font_color.clone();
+            }
+        }
+        if style.font_weight == DEFAULT_FONT_WEIGHT {
+            if let Some(font_weight) = self.font_weight {
+                style.font_weight = font_weight;
+            }
+        }
+        if style.border_color == DEFAULT_BORDER_COLOR {
+            if let Some(border_color) = &self.border_color {
+                style.border_color = border_color.clone();
+            }
+        }
+    }
+
+    pub fn apply(&self, style: &mut Style) {
+        if let Some(font_color) = &self.font_color {
+            style.font_color = font_color.clone();
+        }
+        if let Some(font_weight) = self.font_weight {
+            style.font_weight = font_weight;
+        }
+        if let Some(border_color) = &self.border_color {
+            style.border_color = border_color.clone();
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FormatRule {
+    pub condition: Condition,
+    pub style: StyleOverride,
+}
+
+impl FormatRule {
+    pub fn applies_to(&self, grammar: &Grammar, invalid: bool) -> bool {
+        match &self.condition {
+            Condition::GreaterThan(threshold) => {
+                number_value(grammar).map_or(false, |n| n > *threshold)
+            }
+            Condition::LessThan(threshold) => {
+                number_value(grammar).map_or(false, |n| n < *threshold)
+            }
+            Condition::Equals(text) => text_value(grammar).map_or(false, |v| v == text),
+            // invalid expressions never match, `validate_rule` reports them when the
+            // rule is added
+            Condition::Matches(pattern) => match (regex(pattern), text_value(grammar)) {
+                (Ok(re), Some(v)) => re.is_match(v),
+                _ => false,
+            },
+            Condition::KindIs(name) => kind_name(&grammar.kind) == name,
+            Condition::Invalid => invalid,
+        }
+    }
+}
+
+// moves the rules of the grids after row/column `index` of the grid at `parent` back by one
+// once it's deleted, the rules of the grids in the deleted line are dropped
+pub fn delete_line(
+    formatting: &mut HashMap<Coordinate, Vec<FormatRule>>,
+    parent: &Coordinate,
+    line: Line,
+    index: NonZeroU32,
+) {
+    let rules: Vec<(Coordinate, Vec<FormatRule>)> = formatting.drain().collect();
+    for (grid, rules) in rules {
+        if let Some(grid) = grid.after_line_deleted(parent, line, index) {
+            formatting.insert(grid, rules);
+        }
+    }
+}
+
+thread_local! {
+    // the regular expressions of `Matches` rules (here and in src/validation.rs) by their
+    // pattern, they're checked for every cell each time the grid is drawn
+    static REGEXES: RefCell<HashMap<String, Result<Regex, String>>> = RefCell::new(HashMap::new());
+}
+
+// the regular expression of a pattern, compiled the first time it's used
+pub fn regex(pattern: &str) -> Result<Regex, String> {
+    REGEXES.with(|regexes| {
+        if let Some(regex) = regexes.borrow().get(pattern) {
+            return regex.clone();
+        }
+        let regex = Regex::new(pattern).map_err(|e| format! {"invalid regular expression: {}", e});
+        regexes
+            .borrow_mut()
+            .insert(pattern.to_string(), regex.clone());
+        regex
+    })
+}
+
+// checks a rule before it's added to a grid
+pub fn validate_rule(rule: &FormatRule) -> Result<(), String> {
+    match &rule.condition {
+        Condition::Matches(pattern) => regex(pattern).map(|_| ()),
+        Condition::KindIs(name) if !KIND_NAMES.contains(&name.as_str()) => Err(format! {
+            "unknown kind \"{}\", expected one of {}", name, KIND_NAMES.join(", ")
+        }),
+        _ => Ok(()),
+    }
+}
+
+pub const CONDITION_NAMES: [&str; 6] = [
+    "GreaterThan",
+    "LessThan",
+    "Equals",
+    "Matches",
+    "KindIs",
+    "Invalid",
+];
+
+// FormatRuleDraft holds the fields of the "add rule" form while they're filled in
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatRuleDraft {
+    // one of CONDITION_NAMES
+    pub condition: String,
+    pub value: String,
+    pub font_color: String,
+    pub font_weight: String,
+    pub border_color: String,
+}
+
+impl Default for FormatRuleDraft {
+    fn default() -> FormatRuleDraft {
+        FormatRuleDraft {
+            condition: CONDITION_NAMES[0].to_string(),
+            value: String::new(),
+            font_color: String::new(),
+            font_weight: String::new(),
+            border_color: String::new(),
+        }
+    }
+}
+
+impl FormatRuleDraft {
+    pub fn to_rule(&self) -> Result<FormatRule, String> {
+        let number = || {
+            self.value
+                .trim()
+                .parse::<f64>()
+                .map_err(|_| format! {"\"{}\" is not a number", self.value})
+        };
+        let condition = match self.condition.as_str() {
+            "GreaterThan" => Condition::GreaterThan(number()?),
+            "LessThan" => Condition::LessThan(number()?),
+            "Equals" => Condition::Equals(self.value.clone()),
+            "Matches" => Condition::Matches(self.value.clone()),
+            "KindIs" => Condition::KindIs(self.value.trim().to_string()),
+            "Invalid" => Condition::Invalid,
+            other => return Err(format! {"unknown condition \"{}\"", other}),
+        };
+        let text = |s: &str| {
+            if s.trim().is_empty() {
+                None
+            } else {
+                Some(s.trim().to_string())
+            }
+        };
+        let font_weight = match text(&self.font_weight) {
+            Some(weight) => Some(
+                weight
+                    .parse::<i32>()
+                    .map_err(|_| format! {"\"{}\" is not a font weight", weight})?,
+            ),
+            None => None,
+        };
+        let rule = FormatRule {
+            condition,
+            style: StyleOverride {
+                font_color: text(&self.font_color),
+                font_weight,
+                border_color: text(&self.border_color),
+            },
+        };
+        if rule.style == StyleOverride::default() {
+            return Err("the rule doesn't change the style".to_string());
+        }
+        validate_rule(&rule)?;
+        Ok(rule)
+    }
+}
+
+pub const KIND_NAMES: [&str; 7] = [
+    "Text",
+    "Input",
+    "Grid",
+    "Interactive",
+    "Lookup",
+    "Defn",
+    "Editor",
+];
+
+pub fn kind_name(kind: &Kind) -> &'static str {
+    match kind {
+        Kind::Text(_) => "Text",
+        Kind::Input(_) => "Input",
+        Kind::Grid(_) => "Grid",
+        Kind::Interactive(_, _) => "Interactive",
+        Kind::Lookup(_, _) => "Lookup",
+        Kind::Defn(_, _, _) => "Defn",
+        Kind::Editor(_, _, _) => "Editor",
+    }
+}
+
+fn text_value(grammar: &Grammar) -> Option<&str> {
+    match &grammar.kind {
+        Kind::Text(s) | Kind::Input(s) | Kind::Lookup(s, _) => Some(s),
+        _ => None,
+    }
+}
+
+fn number_value(grammar: &Grammar) -> Option<f64> {
+    text_value(grammar).and_then(|s| CellValue::parse(s).as_number())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coord;
+    use crate::coordinate::{CoordinateParser, Rule};
+    use crate::util::non_zero_u32_tuple;
+    use pest::Parser;
+
+    fn input(value: &str) -> Grammar {
+        Grammar {
+            kind: Kind::Input(value.to_string()),
+            ..Grammar::default()
+        }
+    }
+
+    fn rule(condition: Condition) -> FormatRule {
+        FormatRule {
+            condition,
+            style: StyleOverride {
+                font_color: Some("red".to_string()),
+                ..StyleOverride::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_conditions() {
+        assert!(rule(Condition::GreaterThan(10.0)).applies_to(&input(" 11 "), false));
+        assert!(!rule(Condition::GreaterThan(10.0)).applies_to(&input("10"), false));
+        assert!(!rule(Condition::GreaterThan(10.0)).applies_to(&input("eleven"), false));
+        assert!(rule(Condition::LessThan(0.0)).applies_to(&input("-1.5"), false));
+        assert!(rule(Condition::GreaterThan(1000.0)).applies_to(&input("$1,200"), false));
+        assert!(rule(Condition::LessThan(0.5)).applies_to(&input("20%"), false));
+        assert!(rule(Condition::Equals("ok".to_string())).applies_to(&input("ok"), false));
+        assert!(rule(Condition::Matches("^[A-Z]+$".to_string())).applies_to(&input("ABC"), false));
+        assert!(!rule(Condition::Matches("^[A-Z]+$".to_string())).applies_to(&input("abc"), false));
+        assert!(rule(Condition::KindIs("Input".to_string())).applies_to(&input(""), false));
+        assert!(!rule(Condition::KindIs("Lookup".to_string())).applies_to(&input(""), false));
+        assert!(rule(Condition::Invalid).applies_to(&input(""), true));
+        assert!(!rule(Condition::Invalid).applies_to(&input(""), false));
+    }
+
+    #[test]
+    fn test_validate_rule() {
+        assert!(validate_rule(&rule(Condition::Matches("(".to_string()))).is_err());
+        // the same pattern is compiled once
+        assert!(regex("^[a-z]+$").unwrap().is_match("abc"));
+        REGEXES.with(|regexes| assert!(regexes.borrow().contains_key("^[a-z]+$")));
+        assert!(validate_rule(&rule(Condition::KindIs("Cell".to_string()))).is_err());
+        assert!(validate_rule(&rule(Condition::KindIs("Lookup".to_string()))).is_ok());
+    }
+
+    #[test]
+    fn test_delete_line() {
+        let mut formatting = HashMap::new();
+        formatting.insert(coord!("root"), vec![rule(Condition::Invalid)]);
+        formatting.insert(coord!("root-A2"), vec![rule(Condition::Invalid)]);
+        formatting.insert(coord!("root-B3-A1"), vec![rule(Condition::Invalid)]);
+        delete_line(
+            &mut formatting,
+            &coord!("root"),
+            Line::Row,
+            NonZeroU32::new(2).unwrap(),
+        );
+        let mut grids: Vec<String> = formatting.keys().map(|c| c.to_string()).collect();
+        grids.sort();
+        assert_eq!(grids, vec!["root", "root-B2-A1"]);
+    }
+
+    #[test]
+    fn test_draft_to_rule() {
+        let draft = FormatRuleDraft {
+            value: "10".to_string(),
+            font_weight: "700".to_string(),
+            ..FormatRuleDraft::default()
+        };
+        assert_eq!(
+            draft.to_rule(),
+            Ok(FormatRule {
+                condition: Condition::GreaterThan(10.0),
+                style: StyleOverride {
+                    font_weight: Some(700),
+                    ..StyleOverride::default()
+                },
+            })
+        );
+        assert!(FormatRuleDraft {
+            value: "ten".to_string(),
+            ..draft.clone()
+        }
+        .to_rule()
+        .is_err());
+        assert!(FormatRuleDraft {
+            font_weight: String::new(),
+            ..draft.clone()
+        }
+        .to_rule()
+        .is_err());
+        assert!(FormatRuleDraft {
+            condition: "Matches".to_string(),
+            value: "[".to_string(),
+            ..draft
+        }
+        .to_rule()
+        .is_err());
+    }
+
+    #[test]
+    fn test_style_override() {
+        let mut style = Style::default();
+        StyleOverride {
+            font_weight: Some(700),
+            border_color: Some("blue".to_string()),
+            ..StyleOverride::default()
+        }
+        .apply(&mut style);
+        assert_eq!(style.font_weight, 700);
+        assert_eq!(style.border_color, "blue".to_string());
+        assert_eq!(style.font_color, Style::default().font_color);
+    }
+}
@@ -22,7 +22,7 @@ pub struct CoordinateParser;
 
 // Grammar is the main data-type representing
 // the contents of a cell
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Grammar {
     pub name: String,
     pub style: Style,
@@ -61,7 +61,13 @@ pub enum Kind {
         /* rule names and coordinates */ Vec<(String, Coordinate)>,
     ),
 
-    Editor(/* content */ String),
+    // Code editor grammar, running it writes the result to the output coordinate
+    // (the cell to the right when it isn't set)
+    Editor(
+        /* content */ String,
+        /* mode */ String,
+        /* output */ Option<Coordinate>,
+    ),
 }
 js_serializable!(Kind);
 js_deserializable!(Kind);
@@ -166,6 +172,14 @@ impl Grammar {
         }
     }
 
+    // an empty input cell of this size
+    pub fn sized(width: f64, height: f64) -> Grammar {
+        Grammar {
+            style: Style::sized(width, height),
+            ..Grammar::default()
+        }
+    }
+
     pub fn default_button() -> Grammar {
         Grammar {
             name: "button".to_string(),
@@ -252,7 +266,7 @@ mod tests {
     fn test_grammar_style() {
         assert_eq!(
             Grammar::default().style(&coord!("root-A1")),
-            format! {"/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\ngrid-area: cell-root-A1;\n"}
+            format! {"border-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\ngrid-area: cell-root-A1;\n"}
         );
         assert_ne!(
             Grammar::default().style(&coord!("root-A1")),
@@ -267,7 +281,7 @@ mod tests {
         assert_ne!(
             Grammar::as_grid(NonZeroU32::new(1).unwrap(), NonZeroU32::new(2).unwrap())
                 .style(&coord!("root-A1")),
-            format! {"/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\ngrid-area: cell-root-A1;\n"}
+            format! {"border-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\ngrid-area: cell-root-A1;\n"}
         );
     }
 
@@ -301,6 +315,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_editor_serialization() {
+        let kind = Kind::Editor(
+            "print(1)".to_string(),
+            "python".to_string(),
+            Some(coord!("root-B1")),
+        );
+        let json = serde_json::to_string(&kind).unwrap();
+        assert_eq!(serde_json::from_str::<Kind>(&json).unwrap(), kind);
+        let kind = Kind::Editor(String::new(), "python".to_string(), None);
+        let json = serde_json::to_string(&kind).unwrap();
+        assert_eq!(serde_json::from_str::<Kind>(&json).unwrap(), kind);
+    }
+
     #[test]
     fn test_default_button() {
         assert_eq!(Grammar::default_button().name, "button".to_string());
@@ -56,8 +56,8 @@ pub fn build_grammar_map(
                     name: String::new(),
                     style: {
                         let mut s = Style::default();
-                        s.width = 90.0 * (num_cols as f64);
-                        s.height = 30.0 * (num_rows as f64);
+                        s.width *= num_cols as f64;
+                        s.height *= num_rows as f64;
                         s
                     },
                     kind: Kind::Grid(sub_coords),
@@ -67,6 +67,32 @@ pub fn build_grammar_map(
     }
 }
 
+// a grid of input cells at the root, for tests
+#[cfg(test)]
+pub fn input_grid(rows: Vec<Vec<&str>>) -> HashMap<Coordinate, Grammar> {
+    let entry = MapEntry::Grid(
+        rows.into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|value| Box::new(MapEntry::G(Grammar::input("", value))))
+                    .collect()
+            })
+            .collect(),
+    );
+    let mut map = HashMap::new();
+    build_grammar_map(&mut map, coord!("root"), entry);
+    map
+}
+
+// the text of an input cell, for tests
+#[cfg(test)]
+pub fn input_value(map: &HashMap<Coordinate, Grammar>, coord: Coordinate) -> Option<String> {
+    match map.get(&coord).map(|g| &g.kind) {
+        Some(Kind::Input(value)) => Some(value.clone()),
+        _ => None,
+    }
+}
+
 #[macro_export]
 macro_rules! g {
     ( $grammar:expr ) => {
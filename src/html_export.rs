@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+
+use crate::coordinate::{Col, Coordinate, Row};
+use crate::grammar::{Grammar, Interactive, Kind};
+use crate::style::{get_style, StyleContext};
+use crate::theme::Theme;
+
+// HTML EXPORT
+// A grid (or any cell) exported as a standalone HTML page, which can be opened in any
+// browser without the app. Cells are laid out with the same CSS grid the sheet uses (see
+// `Grammar::style` and `style::get_style`), so merged cells, nested grids and styling
+// look the way they do in the sheet. The few rules of static/styles.css the layout relies
+// on are embedded in the page as `EXPORT_CSS`, everything else is inline.
+
+pub const EXPORT_CSS: &str = "body {
+  margin: 16px;
+  background-color: var(--theme-background);
+  color: var(--theme-font-color);
+  font-family: var(--theme-font-family);
+}
+
+.cell {
+  margin-top: 1px;
+  margin-left: 1px;
+  box-shadow: 1px 0px 0px rgba(0, 0, 0, 0.1);
+  border-collapse: collapse;
+  background: var(--theme-grid-color);
+}
+
+.cell-data {
+  box-sizing: border-box;
+  width: 100%;
+  height: 100%;
+  overflow: hidden;
+  font-size: 20px;
+  background-color: var(--theme-cell-background);
+}
+
+.cell-data.editor {
+  margin: 0;
+  font-size: 14px;
+  white-space: pre-wrap;
+}
+";
+
+// the page of the cell at `coord`, `None` if there's no such cell. `context` gives the
+// style context of a cell, as `Model::style_context` does.
+pub fn export_html<'a>(
+    grammars: &HashMap<Coordinate, Grammar>,
+    coord: &Coordinate,
+    col_widths: &HashMap<Col, f64>,
+    row_heights: &HashMap<Row, f64>,
+    theme: &Theme,
+    title: &str,
+    context: &dyn Fn(&Coordinate) -> StyleContext<'a>,
+) -> Option<String> {
+    grammars.get(coord)?;
+    let mut cells = String::new();
+    write_cell(
+        grammars,
+        coord,
+        col_widths,
+        row_heights,
+        context,
+        1,
+        &mut cells,
+    );
+    Some(format! {
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>\n{}</style>\n</head>\n<body style=\"{}\">\n<div class=\"grid-wrapper\">\n{}</div>\n\
+         </body>\n</html>\n",
+        escape_html(title),
+        EXPORT_CSS,
+        escape_html(&theme.css_variables()),
+        cells,
+    })
+}
+
+fn write_cell<'a>(
+    grammars: &HashMap<Coordinate, Grammar>,
+    coord: &Coordinate,
+    col_widths: &HashMap<Col, f64>,
+    row_heights: &HashMap<Row, f64>,
+    context: &dyn Fn(&Coordinate) -> StyleContext<'a>,
+    depth: usize,
+    html: &mut String,
+) {
+    let grammar = match grammars.get(coord) {
+        Some(grammar) => grammar,
+        None => return,
+    };
+    // cells merged into another one are hidden, the merged cell spans over them
+    if !grammar.style.display {
+        return;
+    }
+    let indent = "  ".repeat(depth);
+    let style = get_style(grammar, col_widths, row_heights, coord, &context(coord));
+    let id = escape_html(&coord.to_string());
+    if let Kind::Grid(sub_coords) = &grammar.kind {
+        html.push_str(&format! {
+            "{}<div class=\"cell grid\" id=\"cell-{}\" style=\"{}\">\n",
+            indent, id, escape_html(&style),
+        });
+        let mut sub_coords = sub_coords.clone();
+        sub_coords.sort();
+        for row_col in sub_coords {
+            let sub_coord = Coordinate::child_of(coord, row_col);
+            write_cell(
+                grammars,
+                &sub_coord,
+                col_widths,
+                row_heights,
+                context,
+                depth + 1,
+                html,
+            );
+        }
+        html.push_str(&format! {"{}</div>\n", indent});
+        return;
+    }
+    html.push_str(&format! {
+        "{}<div class=\"cell\" id=\"cell-{}\" style=\"{}\">{}</div>\n",
+        indent, id, escape_html(&style), cell_content(grammar),
+    });
+}
+
+// the markup inside of a cell that isn't a grid
+fn cell_content(grammar: &Grammar) -> String {
+    match &grammar.kind {
+        Kind::Input(value) => {
+            let shown = match &grammar.style.format {
+                Some(format) => format.display(value),
+                None => value.clone(),
+            };
+            format! {"<div class=\"cell-data\">{}</div>", escape_html(&shown)}
+        }
+        Kind::Text(value) | Kind::Lookup(value, _) | Kind::Defn(value, _, _) => {
+            format! {"<div class=\"cell-data\">{}</div>", escape_html(value)}
+        }
+        Kind::Editor(content, _, _) => {
+            format! {"<pre class=\"cell-data editor\">{}</pre>", escape_html(content)}
+        }
+        Kind::Interactive(name, Interactive::Button()) => {
+            format! {"<button disabled>{}</button>", escape_html(name)}
+        }
+        Kind::Interactive(name, Interactive::Slider(value, min, max)) => format! {
+            "<input type=\"range\" disabled min=\"{}\" max=\"{}\" value=\"{}\" title=\"{}\">",
+            min, max, value, escape_html(name),
+        },
+        Kind::Interactive(name, Interactive::Toggle(checked)) => format! {
+            "<input type=\"checkbox\" disabled{} title=\"{}\">",
+            if *checked { " checked" } else { "" },
+            escape_html(name),
+        },
+        Kind::Grid(_) => String::new(),
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate::{CoordinateParser, Rule};
+    use crate::style::Style;
+    use crate::util::non_zero_u32_tuple;
+    use crate::{coord, row_col_vec};
+    use pest::Parser;
+    use std::num::NonZeroU32;
+
+    fn grid(rows_cols: Vec<(NonZeroU32, NonZeroU32)>) -> Grammar {
+        Grammar {
+            name: String::new(),
+            style: Style::default(),
+            kind: Kind::Grid(rows_cols),
+        }
+    }
+
+    // root is a 2x2 grid: "a & b" and a nested 1x2 grid on the first row, the second
+    // row merged into a single cell
+    fn sheet() -> HashMap<Coordinate, Grammar> {
+        let mut merged = Grammar::input("", "<total>");
+        merged.style.col_span = (1, 2);
+        let mut hidden = Grammar::input("", "hidden");
+        hidden.style.display = false;
+        let mut nested = Grammar::input("", "0.5");
+        nested.style.font_color = "red".to_string();
+        hashmap! {
+            coord!("root") => grid(row_col_vec![(1, 1), (1, 2), (2, 1), (2, 2)]),
+            coord!("root-A1") => Grammar::input("", "a & b"),
+            coord!("root-B1") => grid(row_col_vec![(1, 1), (1, 2)]),
+            coord!("root-B1-A1") => nested,
+            coord!("root-B1-B1") => Grammar::text("", "text"),
+            coord!("root-A2") => merged,
+            coord!("root-B2") => hidden,
+        }
+    }
+
+    fn export(grammars: &HashMap<Coordinate, Grammar>, coord: Coordinate) -> Option<String> {
+        export_html(
+            grammars,
+            &coord,
+            &HashMap::new(),
+            &HashMap::new(),
+            &Theme::light(),
+            "Budget <2020>",
+            &|_| StyleContext::default(),
+        )
+    }
+
+    #[test]
+    fn test_export_html() {
+        let grammars = sheet();
+        let html = export(&grammars, coord!("root")).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>\n"));
+        assert!(html.contains("<title>Budget &lt;2020&gt;</title>"));
+        // nothing is loaded from elsewhere
+        assert!(!html.contains("<script") && !html.contains("<link"));
+
+        // cells are nested the way grids are
+        let cells: Vec<&str> = html
+            .lines()
+            .filter(|line| line.trim_start().starts_with("<div class=\"cell"))
+            .map(|line| line.split("style=").next().unwrap())
+            .collect();
+        assert_eq!(
+            cells,
+            vec![
+                "  <div class=\"cell grid\" id=\"cell-root\" ",
+                "    <div class=\"cell\" id=\"cell-root-A1\" ",
+                "    <div class=\"cell grid\" id=\"cell-root-B1\" ",
+                "      <div class=\"cell\" id=\"cell-root-B1-A1\" ",
+                "      <div class=\"cell\" id=\"cell-root-B1-B1\" ",
+                "    <div class=\"cell\" id=\"cell-root-A2\" ",
+            ]
+        );
+        assert!(html.contains("<div class=\"cell-data\">a &amp; b</div>"));
+        assert!(html.contains("<div class=\"cell-data\">&lt;total&gt;</div>"));
+        assert!(!html.contains("id=\"cell-root-B2\""));
+
+        // the styles are the ones of the sheet
+        let style = get_style(
+            &grammars[&coord!("root-A2")],
+            &HashMap::new(),
+            &HashMap::new(),
+            &coord!("root-A2"),
+            &StyleContext::default(),
+        );
+        assert!(style.contains("grid-column: 1 / span 2;"));
+        assert!(html.contains(&escape_html(&style)));
+        assert!(html.contains("grid-template-areas: \n&quot;cell-root-A1 cell-root-B1&quot;"));
+        assert!(html.contains("color: red;"));
+
+        // the same sheet is always exported the same
+        assert_eq!(export(&grammars, coord!("root")), Some(html));
+    }
+
+    #[test]
+    fn test_export_nested_cell() {
+        let grammars = sheet();
+        let html = export(&grammars, coord!("root-B1")).unwrap();
+        assert!(html.contains("id=\"cell-root-B1\""));
+        assert!(!html.contains("id=\"cell-root-A1\""));
+        assert!(html.contains("<div class=\"cell-data\">text</div>"));
+        assert_eq!(export(&grammars, coord!("root-C9")), None);
+    }
+}
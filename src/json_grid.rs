@@ -0,0 +1,294 @@
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use crate::coordinate::Coordinate;
+use crate::grammar::{Grammar, Kind};
+use crate::grammar_map::MapEntry;
+use crate::sheet::{cell_value, grid_size};
+use crate::util::non_zero_u32_tuple;
+
+// JSON DOCUMENTS
+// A JSON document is edited as nested grids:
+// - an object is a grid of two columns, its keys as text cells in the first column (in the
+//   order of the document) and their values in the second one
+// - an array of objects that all have the same keys is a table, the keys as text cells in
+//   the first row and an object per row below it
+// - any other array is a grid of one column, an element per row
+// - scalars are input cells, strings as they are and other values as JSON (so `42` is a
+//   number and `"42"` a string)
+// Going back, a grid is read as a table or an object if its text cells say it is one, and
+// as an array otherwise, a grid of several columns being an array of rows. Empty objects
+// and arrays can't be grids, they're the input cells `{}` and `[]`.
+
+pub fn parse_json(text: &str) -> Result<MapEntry, String> {
+    serde_json::from_str::<Value>(text)
+        .map(|value| json_entry(&value))
+        .map_err(|e| format! {"cannot read the JSON document: {}", e})
+}
+
+// the grid of a JSON value, to be built with `build_grammar_map`
+pub fn json_entry(value: &Value) -> MapEntry {
+    match value {
+        Value::Object(map) if !map.is_empty() => MapEntry::Grid(
+            map.iter()
+                .map(|(key, value)| vec![Box::new(text(key)), Box::new(json_entry(value))])
+                .collect(),
+        ),
+        Value::Array(items) if !items.is_empty() => match table_keys(items) {
+            Some(keys) => {
+                let mut rows = vec![keys.iter().map(|key| Box::new(text(key))).collect()];
+                rows.extend(items.iter().map(|item| {
+                    keys.iter()
+                        .map(|key| Box::new(json_entry(&item[key.as_str()])))
+                        .collect()
+                }));
+                MapEntry::Grid(rows)
+            }
+            None => MapEntry::Grid(
+                items
+                    .iter()
+                    .map(|item| vec![Box::new(json_entry(item))])
+                    .collect(),
+            ),
+        },
+        Value::String(s) if serde_json::from_str::<Value>(s).is_ok() || s.starts_with('"') => {
+            MapEntry::G(Grammar::input("".to_string(), value.to_string()))
+        }
+        Value::String(s) => MapEntry::G(Grammar::input("", s.as_str())),
+        value => MapEntry::G(Grammar::input("".to_string(), value.to_string())),
+    }
+}
+
+fn text(key: &str) -> MapEntry {
+    MapEntry::G(Grammar::text("", key))
+}
+
+// the keys of an array of objects that all have the same keys
+fn table_keys(items: &[Value]) -> Option<Vec<String>> {
+    let keys: Vec<String> = match items.first()? {
+        Value::Object(first) if !first.is_empty() => first.keys().cloned().collect(),
+        _ => return None,
+    };
+    let uniform = items.iter().all(|item| match item {
+        Value::Object(map) => map.len() == keys.len() && keys.iter().all(|k| map.contains_key(k)),
+        _ => false,
+    });
+    if uniform {
+        Some(keys)
+    } else {
+        None
+    }
+}
+
+// the JSON value of the cell at `coord`
+pub fn grid_json(grammars: &HashMap<Coordinate, Grammar>, coord: &Coordinate) -> Value {
+    let grammar = match grammars.get(coord) {
+        Some(grammar) => grammar,
+        None => return Value::Null,
+    };
+    let sub_coords = match &grammar.kind {
+        Kind::Grid(sub_coords) => sub_coords,
+        Kind::Input(raw) => {
+            return serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.clone()))
+        }
+        Kind::Text(text) => return Value::String(text.clone()),
+        _ => return cell_value(grammars, coord),
+    };
+    let (rows, cols) = grid_size(sub_coords);
+    let cell = |row: u32, col: u32| {
+        let cell_coord = Coordinate::child_of(coord, non_zero_u32_tuple((row, col)));
+        match grammars.get(&cell_coord) {
+            Some(grammar) if grammar.style.display => Some((cell_coord, grammar)),
+            _ => None,
+        }
+    };
+    let key = |row: u32, col: u32| match cell(row, col) {
+        Some((
+            _,
+            Grammar {
+                kind: Kind::Text(key),
+                ..
+            },
+        )) => Some(key.clone()),
+        _ => None,
+    };
+    let value = |row: u32, col: u32| match cell(row, col) {
+        Some((cell_coord, _)) => grid_json(grammars, &cell_coord),
+        None => Value::Null,
+    };
+
+    let headers: Option<Vec<String>> = (1..=cols).map(|col| key(1, col)).collect();
+    if let (Some(headers), true) = (headers, rows > 1) {
+        return Value::Array(
+            (2..=rows)
+                .map(|row| {
+                    let object: Map<String, Value> = headers
+                        .iter()
+                        .enumerate()
+                        .map(|(i, header)| (header.clone(), value(row, i as u32 + 1)))
+                        .collect();
+                    Value::Object(object)
+                })
+                .collect(),
+        );
+    }
+
+    // keys typed in rows added to an object are inputs
+    let input_key = |row: u32| match cell(row, 1) {
+        Some((
+            _,
+            Grammar {
+                kind: Kind::Input(key),
+                ..
+            },
+        )) => Some(key.clone()),
+        _ => key(row, 1),
+    };
+    if cols == 2 && key(1, 1).is_some() {
+        if let Some(keys) = (1..=rows).map(input_key).collect::<Option<Vec<String>>>() {
+            return Value::Object(
+                keys.into_iter()
+                    .enumerate()
+                    .map(|(i, key)| (key, value(i as u32 + 1, 2)))
+                    .collect(),
+            );
+        }
+    }
+
+    Value::Array(
+        (1..=rows)
+            .map(|row| match cols {
+                1 => value(row, 1),
+                _ => Value::Array((1..=cols).map(|col| value(row, col)).collect()),
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate::{CoordinateParser, Rule};
+    use crate::grammar_map::build_grammar_map;
+    use crate::{coord, g, grid};
+    use pest::Parser;
+    use serde_json::json;
+    use std::num::NonZeroU32;
+
+    fn build(value: &Value) -> HashMap<Coordinate, Grammar> {
+        let mut map = HashMap::new();
+        build_grammar_map(&mut map, coord!("root"), json_entry(value));
+        map
+    }
+
+    fn kind(map: &HashMap<Coordinate, Grammar>, coord: Coordinate) -> Kind {
+        map[&coord].kind.clone()
+    }
+
+    #[test]
+    fn test_json_entry() {
+        let map = build(&json!({
+            "name": "ise",
+            "version": "1.0",
+            "private": true,
+            "tags": ["grid", 42],
+            "people": [{"name": "Ada", "age": 36}, {"name": "Alan", "age": 41}],
+            "empty": {},
+        }));
+        // keys stay in the order of the document
+        assert_eq!(
+            kind(&map, coord!("root-A1")),
+            Kind::Text("name".to_string())
+        );
+        assert_eq!(
+            kind(&map, coord!("root-A6")),
+            Kind::Text("empty".to_string())
+        );
+        assert_eq!(kind(&map, coord!("root-B6")), Kind::Input("{}".to_string()));
+        // a table
+        assert_eq!(
+            kind(&map, coord!("root-B5-A1")),
+            Kind::Text("name".to_string())
+        );
+        assert_eq!(
+            kind(&map, coord!("root-B5-B1")),
+            Kind::Text("age".to_string())
+        );
+        assert_eq!(
+            kind(&map, coord!("root-B5-B3")),
+            Kind::Input("41".to_string())
+        );
+        assert_eq!(
+            kind(&map, coord!("root-B5-A2")),
+            Kind::Input("Ada".to_string())
+        );
+        assert_eq!(
+            kind(&map, coord!("root-B3")),
+            Kind::Input("true".to_string())
+        );
+        // an array
+        assert_eq!(
+            kind(&map, coord!("root-B4-A1")),
+            Kind::Input("grid".to_string())
+        );
+        assert_eq!(
+            kind(&map, coord!("root-B4-A2")),
+            Kind::Input("42".to_string())
+        );
+        // strings that read as other values are quoted
+        assert_eq!(
+            kind(&map, coord!("root-B2")),
+            Kind::Input("\"1.0\"".to_string())
+        );
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let values = vec![
+            json!({"a": {"b": [1, 2, {"c": null}]}, "d": "text", "e": "\"quoted\""}),
+            json!([{"x": 1, "y": 2}, {"y": 3, "x": [4]}]),
+            json!([{"x": 1}, {"y": 2}]),
+            json!([[1, 2], [3, 4]]),
+            json!([{}, [], "", "null"]),
+            json!("scalar"),
+        ];
+        for value in values {
+            assert_eq!(grid_json(&build(&value), &coord!("root")), value);
+        }
+    }
+
+    #[test]
+    fn test_edited_grids() {
+        // rows added to an object have their key typed in an input
+        let mut map = HashMap::new();
+        build_grammar_map(
+            &mut map,
+            coord!("root"),
+            grid![
+                [g!(Grammar::text("", "a")), g!(Grammar::input("", "1"))],
+                [g!(Grammar::input("", "b")), g!(Grammar::input("", "two"))]
+            ],
+        );
+        assert_eq!(
+            grid_json(&map, &coord!("root")),
+            json!({"a": 1, "b": "two"})
+        );
+
+        // grids that aren't from a JSON document are arrays of rows
+        let mut map = HashMap::new();
+        build_grammar_map(
+            &mut map,
+            coord!("root"),
+            grid![
+                [g!(Grammar::input("", "1")), g!(Grammar::input("", "b"))],
+                [g!(Grammar::input("", "")), g!(Grammar::default())]
+            ],
+        );
+        assert_eq!(
+            grid_json(&map, &coord!("root")),
+            json!([[1, "b"], ["", ""]])
+        );
+
+        assert!(parse_json("{\"a\": ").is_err());
+    }
+}
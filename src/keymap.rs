@@ -0,0 +1,302 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::command::Command;
+
+// KEYMAP
+// The keymap binds key combinations, as written by `key_combination` in src/model.rs
+// (e.g. "Ctrl-Shift-ArrowUp"), to commands. It starts from `DEFAULT_KEYMAP` and applies
+// the user's overrides from `KEYMAP_FILE` in the user config directory, a JSON object of
+// key combinations to command names where `null` removes a binding:
+//   { "Ctrl-m": "merge_cells", "Ctrl-g": null }
+// Overrides that can't be applied, or that take a key from another command, are reported
+// as issues in the "Settings" side menu.
+
+pub const KEYMAP_FILE: &str = "keymap.json";
+
+pub const DEFAULT_KEYMAP: &[(&str, &str)] = &[
+    ("Ctrl-g", "nest_grid"),
+    ("Ctrl-s", "save"),
+    ("Ctrl-Shift-P", "command_palette"),
+    ("Ctrl-=", "zoom_in"),
+    ("Ctrl--", "zoom_out"),
+    ("Ctrl-0", "zoom_reset"),
+    ("Alt-ArrowLeft", "view_back"),
+    ("Alt-ArrowRight", "view_forward"),
+    ("ArrowUp", "move_up"),
+    ("ArrowDown", "move_down"),
+    ("ArrowLeft", "move_left"),
+    ("ArrowRight", "move_right"),
+    ("Enter", "move_down"),
+    ("Shift-Enter", "move_up"),
+    ("Home", "row_start"),
+    ("End", "row_end"),
+    ("Ctrl-ArrowUp", "jump_up"),
+    ("Ctrl-ArrowDown", "jump_down"),
+    ("Ctrl-ArrowLeft", "jump_left"),
+    ("Ctrl-ArrowRight", "jump_right"),
+    ("Shift-ArrowUp", "select_up"),
+    ("Shift-ArrowDown", "select_down"),
+    ("Shift-ArrowLeft", "select_left"),
+    ("Shift-ArrowRight", "select_right"),
+    ("Shift-Home", "select_row_start"),
+    ("Shift-End", "select_row_end"),
+    ("Ctrl-Shift-ArrowUp", "select_jump_up"),
+    ("Ctrl-Shift-ArrowDown", "select_jump_down"),
+    ("Ctrl-Shift-ArrowLeft", "select_jump_left"),
+    ("Ctrl-Shift-ArrowRight", "select_jump_right"),
+];
+
+// keys the cells handle themselves (Tab order, lookups and deleting the selection)
+const RESERVED_KEYS: &[&str] = &["Tab", "Shift-Tab", "Space", "Backspace", "Delete"];
+
+const MODIFIERS: &[&str] = &["Meta", "Ctrl", "Alt", "Shift"];
+
+const NAMED_KEYS: &[&str] = &[
+    "ArrowUp",
+    "ArrowDown",
+    "ArrowLeft",
+    "ArrowRight",
+    "Enter",
+    "Escape",
+    "Home",
+    "End",
+    "PageUp",
+    "PageDown",
+    "Insert",
+    "Delete",
+    "Backspace",
+    "Tab",
+    "Space",
+    "F1",
+    "F2",
+    "F3",
+    "F4",
+    "F5",
+    "F6",
+    "F7",
+    "F8",
+    "F9",
+    "F10",
+    "F11",
+    "F12",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: BTreeMap<String, Command>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        let bindings = DEFAULT_KEYMAP
+            .iter()
+            .map(|(keys, name)| {
+                let command = Command::from_name(name).expect("unknown command in DEFAULT_KEYMAP");
+                (keys.to_string(), command)
+            })
+            .collect();
+        Keymap { bindings }
+    }
+}
+
+impl Keymap {
+    pub fn command(&self, keys: &str) -> Option<Command> {
+        self.bindings.get(keys).cloned()
+    }
+
+    pub fn keys_for(&self, command: Command) -> Vec<&str> {
+        self.bindings
+            .iter()
+            .filter(|(_, c)| **c == command)
+            .map(|(keys, _)| keys.as_str())
+            .collect()
+    }
+
+    // the key combinations of the command as shown next to it in menus, e.g. "Ctrl-g"
+    pub fn shortcut(&self, command: Command) -> Option<String> {
+        let keys = self.keys_for(command);
+        if keys.is_empty() {
+            None
+        } else {
+            Some(keys.join(", "))
+        }
+    }
+
+    pub fn bindings(&self) -> impl Iterator<Item = (&String, &Command)> {
+        self.bindings.iter()
+    }
+
+    // applies the overrides of a keymap file, returns what couldn't be applied and the
+    // bindings that were taken from other commands
+    pub fn apply_overrides(&mut self, overrides: &str) -> Vec<String> {
+        let overrides = match serde_json::from_str::<Value>(overrides) {
+            Ok(Value::Object(overrides)) => overrides,
+            Ok(_) => return vec!["the keymap should be an object of keys to commands".to_string()],
+            Err(e) => return vec![format! {"cannot read the keymap: {}", e}],
+        };
+        let mut issues = vec![];
+        // the first override of a key combination wins over the ones written differently
+        let mut overridden: BTreeMap<String, String> = BTreeMap::new();
+        for (raw_keys, value) in overrides.iter() {
+            let keys = match normalize_keys(raw_keys) {
+                Ok(keys) => keys,
+                Err(e) => {
+                    issues.push(e);
+                    continue;
+                }
+            };
+            if RESERVED_KEYS.contains(&keys.as_str()) {
+                issues.push(format! {"{} is used by the cells and can't be bound", keys});
+                continue;
+            }
+            if let Some(first) = overridden.get(&keys) {
+                issues.push(format! {
+                    "\"{}\" and \"{}\" are the same keys, \"{}\" is ignored", first, raw_keys, raw_keys
+                });
+                continue;
+            }
+            overridden.insert(keys.clone(), raw_keys.clone());
+            let command = match value {
+                Value::Null => {
+                    self.bindings.remove(&keys);
+                    continue;
+                }
+                Value::String(name) => match Command::from_name(name) {
+                    Some(command) => command,
+                    None => {
+                        issues
+                            .push(format! {"{} is bound to an unknown command \"{}\"", keys, name});
+                        continue;
+                    }
+                },
+                _ => {
+                    issues.push(format! {"{} should be bound to a command name or null", keys});
+                    continue;
+                }
+            };
+            if let Some(previous) = self.bindings.insert(keys.clone(), command) {
+                if previous != command {
+                    issues.push(format! {
+                        "{} runs {} instead of {}", keys, command.name(), previous.name()
+                    });
+                }
+            }
+        }
+        issues
+    }
+}
+
+// writes a key combination the way `key_combination` does: modifiers in the order
+// Meta, Ctrl, Alt, Shift, letters in upper case only with Shift, e.g. "ctrl+shift+a" is
+// "Ctrl-Shift-A"
+pub fn normalize_keys(keys: &str) -> Result<String, String> {
+    let mut modifiers = vec![];
+    let mut rest = keys.trim();
+    loop {
+        let split = rest
+            .char_indices()
+            .find(|(_, c)| *c == '-' || *c == '+')
+            .map(|(i, _)| i)
+            .filter(|i| *i > 0 && *i + 1 < rest.len());
+        let modifier = split.and_then(|i| match rest[..i].to_lowercase().as_str() {
+            "meta" | "cmd" | "command" | "super" => Some("Meta"),
+            "ctrl" | "control" => Some("Ctrl"),
+            "alt" | "option" => Some("Alt"),
+            "shift" => Some("Shift"),
+            _ => None,
+        });
+        match (split, modifier) {
+            (Some(i), Some(modifier)) => {
+                modifiers.push(modifier);
+                rest = &rest[i + 1..];
+            }
+            _ => break,
+        }
+    }
+    let shift = modifiers.contains(&"Shift");
+    let key = if rest.chars().count() == 1 {
+        if shift {
+            rest.to_uppercase()
+        } else {
+            rest.to_lowercase()
+        }
+    } else {
+        match NAMED_KEYS.iter().find(|k| k.eq_ignore_ascii_case(rest)) {
+            Some(key) => key.to_string(),
+            None => return Err(format! {"\"{}\" isn't a key", keys}),
+        }
+    };
+    let mut combination: Vec<String> = MODIFIERS
+        .iter()
+        .filter(|m| modifiers.contains(m))
+        .map(|m| m.to_string())
+        .collect();
+    combination.push(key);
+    Ok(combination.join("-"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::navigation::{Direction, Move};
+
+    #[test]
+    fn test_normalize_keys() {
+        assert_eq!(
+            normalize_keys("ctrl+shift+a"),
+            Ok("Ctrl-Shift-A".to_string())
+        );
+        assert_eq!(
+            normalize_keys("Shift-Ctrl-arrowup"),
+            Ok("Ctrl-Shift-ArrowUp".to_string())
+        );
+        assert_eq!(normalize_keys("Ctrl-G"), Ok("Ctrl-g".to_string()));
+        assert_eq!(normalize_keys("Ctrl--"), Ok("Ctrl--".to_string()));
+        assert!(normalize_keys("Ctrl-Banana").is_err());
+    }
+
+    #[test]
+    fn test_default_keymap() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.command("Ctrl-g"), Some(Command::NestGrid));
+        assert_eq!(
+            keymap.command("Ctrl-Shift-ArrowLeft"),
+            Some(Command::Navigate(Move::Edge(Direction::Left), true))
+        );
+        assert_eq!(
+            keymap.shortcut(Command::Navigate(Move::Step(Direction::Down), false)),
+            Some("ArrowDown, Enter".to_string())
+        );
+        // the defaults are written the way `normalize_keys` writes them
+        for (keys, _) in DEFAULT_KEYMAP {
+            assert_eq!(normalize_keys(keys).as_ref().map(|k| k.as_str()), Ok(*keys));
+        }
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut keymap = Keymap::default();
+        let issues = keymap.apply_overrides(
+            r#"{
+                "ctrl+m": "merge_cells",
+                "Ctrl-g": "unmerge_cells",
+                "Home": null,
+                "Tab": "save",
+                "Ctrl-k": "fly",
+                "Ctrl-Banana": "save"
+            }"#,
+        );
+        assert_eq!(keymap.command("Ctrl-m"), Some(Command::MergeCells));
+        assert_eq!(keymap.command("Ctrl-g"), Some(Command::UnmergeCells));
+        assert_eq!(keymap.command("Home"), None);
+        assert_eq!(keymap.command("Tab"), None);
+        assert_eq!(keymap.command("Ctrl-k"), None);
+        assert_eq!(issues.len(), 4);
+        assert!(issues.contains(&"Ctrl-g runs unmerge_cells instead of nest_grid".to_string()));
+
+        let issues = Keymap::default().apply_overrides(r#"{"Ctrl-m": "save", "ctrl-M": "save"}"#);
+        assert_eq!(issues.len(), 1);
+        assert!(!Keymap::default().apply_overrides("[]").is_empty());
+    }
+}
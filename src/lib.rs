@@ -24,6 +24,28 @@ pub mod style;
 pub mod util;
 pub mod view;
 pub mod codemirror;
+pub mod driver;
+pub mod execution;
+pub mod sheet;
+pub mod formatting;
+pub mod theme;
+pub mod value;
+pub mod validation;
+pub mod comments;
+pub mod names;
+pub mod navigation;
+pub mod command;
+pub mod keymap;
+pub mod palette;
+pub mod settings;
+pub mod explorer;
+pub mod html_export;
+pub mod markdown;
+pub mod json_grid;
+pub mod workbook;
+pub mod diff;
+pub mod canonical;
+pub mod collab;
 
 use crate::model::Model;
 
@@ -0,0 +1,524 @@
+use std::collections::HashMap;
+
+use crate::coordinate::Coordinate;
+use crate::driver::value_to_string;
+use crate::grammar::{Grammar, Interactive, Kind};
+use crate::grammar_map::MapEntry;
+use crate::html_export::escape_html;
+use crate::sheet::{cell_value, grid_size};
+use crate::util::non_zero_u32_tuple;
+
+// MARKDOWN TABLES
+// Grids are written as GitHub-flavored Markdown tables, the first row of the grid being the
+// header row of the table. Markdown tables can't be nested, so nested grids are written
+// either as HTML tables inline in their cell (`NestedTables::Inline`), or as sections after
+// the table, linked from their cell (`NestedTables::Sections`):
+//
+//   | name | address              |
+//   | ---- | -------------------- |
+//   | Ada  | [root-B2](#root-b2)  |
+//
+//   ## root-B2
+//
+//   | street | city |
+//   ...
+//
+// Reading a table back understands both, so what's copied as Markdown pastes back as the
+// same grid (values are read back as they are shown, e.g. "50%" for a percent format).
+
+// how deep tables can be nested when they're read, sections can link to each other
+const MAX_TABLE_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NestedTables {
+    Inline,
+    Sections,
+}
+
+// the grid at `grid` as a Markdown table, `None` if it's not a grid
+pub fn grid_markdown(
+    grammars: &HashMap<Coordinate, Grammar>,
+    grid: &Coordinate,
+    nested: NestedTables,
+) -> Option<String> {
+    match grammars.get(grid).map(|g| &g.kind) {
+        Some(Kind::Grid(_)) => (),
+        _ => return None,
+    }
+    let mut sections = vec![];
+    let mut markdown = table_markdown(grammars, grid, nested, &mut sections);
+    // sections are written in the order they're linked, along with their own sections
+    let mut i = 0;
+    while i < sections.len() {
+        let section: Coordinate = sections[i].clone();
+        let level = (section.row_cols.len() - grid.row_cols.len() + 1).min(6);
+        let mut nested_sections = vec![];
+        let table = table_markdown(grammars, &section, nested, &mut nested_sections);
+        markdown += &format! {"\n{} {}\n\n{}", "#".repeat(level), section.to_string(), table};
+        for (j, nested_section) in nested_sections.into_iter().enumerate() {
+            sections.insert(i + 1 + j, nested_section);
+        }
+        i += 1;
+    }
+    Some(markdown)
+}
+
+fn table_markdown(
+    grammars: &HashMap<Coordinate, Grammar>,
+    grid: &Coordinate,
+    nested: NestedTables,
+    sections: &mut Vec<Coordinate>,
+) -> String {
+    let rows = grid_rows(grammars, grid);
+    let mut markdown = String::new();
+    for (i, row) in rows.iter().enumerate() {
+        let cells: Vec<String> = row
+            .iter()
+            .map(|coord| match coord {
+                Some(coord) => cell_markdown(grammars, coord, nested, sections),
+                None => String::new(),
+            })
+            .collect();
+        markdown += &format! {"| {} |\n", cells.join(" | ")};
+        if i == 0 {
+            let separator: Vec<&str> = row.iter().map(|_| "---").collect();
+            markdown += &format! {"| {} |\n", separator.join(" | ")};
+        }
+    }
+    markdown
+}
+
+fn cell_markdown(
+    grammars: &HashMap<Coordinate, Grammar>,
+    coord: &Coordinate,
+    nested: NestedTables,
+    sections: &mut Vec<Coordinate>,
+) -> String {
+    match grammars.get(coord).map(|g| &g.kind) {
+        Some(Kind::Grid(_)) if nested == NestedTables::Sections => {
+            sections.push(coord.clone());
+            format! {"[{}](#{})", coord.to_string(), anchor(&coord.to_string())}
+        }
+        Some(Kind::Grid(_)) => escape_cell(&table_html(grammars, coord)),
+        _ => escape_cell(&cell_text(grammars, coord)).replace('\n', "<br>"),
+    }
+}
+
+// a nested grid on a single line, as HTML
+fn table_html(grammars: &HashMap<Coordinate, Grammar>, grid: &Coordinate) -> String {
+    let mut html = "<table>".to_string();
+    for row in grid_rows(grammars, grid) {
+        html += "<tr>";
+        for coord in row {
+            html += "<td>";
+            if let Some(coord) = coord {
+                match grammars.get(&coord).map(|g| &g.kind) {
+                    Some(Kind::Grid(_)) => html += &table_html(grammars, &coord),
+                    _ => html += &escape_html(&cell_text(grammars, &coord)).replace('\n', "<br>"),
+                }
+            }
+            html += "</td>";
+        }
+        html += "</tr>";
+    }
+    html + "</table>"
+}
+
+// the cells of a grid row by row, `None` for cells merged into another one
+fn grid_rows(
+    grammars: &HashMap<Coordinate, Grammar>,
+    grid: &Coordinate,
+) -> Vec<Vec<Option<Coordinate>>> {
+    let (rows, cols) = match grammars.get(grid).map(|g| &g.kind) {
+        Some(Kind::Grid(sub_coords)) => grid_size(sub_coords),
+        _ => (0, 0),
+    };
+    (1..=rows)
+        .map(|row| {
+            (1..=cols)
+                .map(|col| {
+                    let coord = Coordinate::child_of(grid, non_zero_u32_tuple((row, col)));
+                    match grammars.get(&coord) {
+                        Some(grammar) if grammar.style.display => Some(coord),
+                        _ => None,
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// the text of a cell the way it's shown in the sheet
+fn cell_text(grammars: &HashMap<Coordinate, Grammar>, coord: &Coordinate) -> String {
+    let grammar = match grammars.get(coord) {
+        Some(grammar) => grammar,
+        None => return String::new(),
+    };
+    match &grammar.kind {
+        Kind::Input(value) => match &grammar.style.format {
+            Some(format) => format.display(value),
+            None => value.clone(),
+        },
+        Kind::Text(value) | Kind::Editor(value, _, _) | Kind::Defn(value, _, _) => value.clone(),
+        Kind::Interactive(label, Interactive::Button()) => label.clone(),
+        _ => value_to_string(&cell_value(grammars, coord)),
+    }
+}
+
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+// the anchor GitHub gives a heading
+fn anchor(heading: &str) -> String {
+    heading
+        .trim()
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == '-' || *c == '_' || *c == ' ')
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
+
+// a table read from Markdown, its rows of cells as written
+struct Table {
+    rows: Vec<Vec<String>>,
+    // the anchor of the heading the table is under
+    section: Option<String>,
+}
+
+// whether the text has a Markdown table in it
+pub fn is_markdown_table(text: &str) -> bool {
+    !read_tables(text).is_empty()
+}
+
+// the first table of `text` as a grid of input cells, to be built with `build_grammar_map`
+pub fn parse_markdown_table(text: &str) -> Result<MapEntry, String> {
+    let tables = read_tables(text);
+    let table = tables
+        .first()
+        .ok_or_else(|| "there's no Markdown table in the text".to_string())?;
+    let sections: HashMap<&str, &Table> = tables
+        .iter()
+        .filter_map(|table| table.section.as_ref().map(|s| (s.as_str(), table)))
+        .collect();
+    Ok(table_entry(&table.rows, &sections, 0))
+}
+
+fn table_entry(rows: &[Vec<String>], sections: &HashMap<&str, &Table>, depth: usize) -> MapEntry {
+    MapEntry::Grid(
+        rows.iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| Box::new(cell_entry(cell, sections, depth)))
+                    .collect()
+            })
+            .collect(),
+    )
+}
+
+fn cell_entry(cell: &str, sections: &HashMap<&str, &Table>, depth: usize) -> MapEntry {
+    if depth < MAX_TABLE_DEPTH {
+        if let Some(table) = section_link(cell).and_then(|anchor| sections.get(anchor)) {
+            return table_entry(&table.rows, sections, depth + 1);
+        }
+        if cell.starts_with("<table>") {
+            if let Some((rows, _)) = read_html_table(cell) {
+                return html_entry(&rows);
+            }
+        }
+    }
+    MapEntry::G(Grammar::input("".to_string(), cell.replace("<br>", "\n")))
+}
+
+fn html_entry(rows: &[Vec<HtmlCell>]) -> MapEntry {
+    MapEntry::Grid(
+        rows.iter()
+            .map(|row| {
+                row.iter()
+                    .map(|cell| {
+                        Box::new(match cell {
+                            HtmlCell::Text(text) => MapEntry::G(Grammar::input("", text.as_str())),
+                            HtmlCell::Table(rows) => html_entry(rows),
+                        })
+                    })
+                    .collect()
+            })
+            .collect(),
+    )
+}
+
+// the anchor a cell links to, if it's only a link to a section, e.g. "[x](#root-b2)"
+fn section_link(cell: &str) -> Option<&str> {
+    let cell = cell.trim();
+    if !cell.starts_with('[') || !cell.ends_with(')') {
+        return None;
+    }
+    let start = cell.find("](#")?;
+    Some(&cell[start + 3..cell.len() - 1])
+}
+
+fn read_tables(text: &str) -> Vec<Table> {
+    let lines: Vec<&str> = text.lines().map(|line| line.trim()).collect();
+    let mut tables = vec![];
+    let mut section = None;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.starts_with('#') {
+            section = Some(anchor(line.trim_start_matches('#')));
+            i += 1;
+            continue;
+        }
+        let is_header =
+            line.contains('|') && lines.get(i + 1).map_or(false, |next| is_separator(next));
+        if !is_header {
+            i += 1;
+            continue;
+        }
+        let header = split_row(line);
+        let width = header.len();
+        let mut rows = vec![header];
+        i += 2;
+        while i < lines.len() && lines[i].contains('|') {
+            let mut row = split_row(lines[i]);
+            row.resize(width, String::new());
+            rows.push(row);
+            i += 1;
+        }
+        // a section has a single table
+        tables.push(Table {
+            rows,
+            section: section.take(),
+        });
+    }
+    tables
+}
+
+fn is_separator(line: &str) -> bool {
+    if !line.contains('-') {
+        return false;
+    }
+    split_row(line).iter().all(|cell| {
+        let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+        !dashes.is_empty() && dashes.chars().all(|c| c == '-')
+    })
+}
+
+// the cells of a table row, "| a | b \| c |" is "a" and "b | c"
+fn split_row(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = match line.strip_suffix('|') {
+        Some(rest) if !rest.ends_with('\\') => rest,
+        _ => line,
+    };
+    let mut cells = vec![];
+    let mut cell = String::new();
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                cell.push('|');
+                chars.next();
+            }
+            '|' => cells.push(std::mem::replace(&mut cell, String::new())),
+            c => cell.push(c),
+        }
+    }
+    cells.push(cell);
+    cells.into_iter().map(|c| c.trim().to_string()).collect()
+}
+
+// a cell of an HTML table written inline
+enum HtmlCell {
+    Text(String),
+    Table(Vec<Vec<HtmlCell>>),
+}
+
+// reads the `<table>` at the start of `html` as written by `table_html`, answers with its
+// rows and what follows the table
+fn read_html_table(html: &str) -> Option<(Vec<Vec<HtmlCell>>, &str)> {
+    let mut rest = html.strip_prefix("<table>")?;
+    let mut rows = vec![];
+    loop {
+        if let Some(after) = rest.strip_prefix("</table>") {
+            return Some((rows, after));
+        }
+        rest = rest.strip_prefix("<tr>")?;
+        let mut row = vec![];
+        while let Some(after) = rest.strip_prefix("<td>") {
+            if after.starts_with("<table>") {
+                let (table, after) = read_html_table(after)?;
+                row.push(HtmlCell::Table(table));
+                rest = after.strip_prefix("</td>")?;
+            } else {
+                let end = after.find("</td>")?;
+                row.push(HtmlCell::Text(unescape_html(
+                    &after[..end].replace("<br>", "\n"),
+                )));
+                rest = &after[end + "</td>".len()..];
+            }
+        }
+        rest = rest.strip_prefix("</tr>")?;
+        rows.push(row);
+    }
+}
+
+fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate::{CoordinateParser, Rule};
+    use crate::grammar_map::build_grammar_map;
+    use crate::{coord, g, grid};
+    use pest::Parser;
+    use std::num::NonZeroU32;
+
+    fn sheet() -> HashMap<Coordinate, Grammar> {
+        let mut map = HashMap::new();
+        build_grammar_map(
+            &mut map,
+            coord!("root"),
+            grid![
+                [
+                    g!(Grammar::input("", "name")),
+                    g!(Grammar::input("", "address"))
+                ],
+                [
+                    g!(Grammar::input("", "Ada | Lovelace")),
+                    grid![
+                        [
+                            g!(Grammar::input("", "street")),
+                            g!(Grammar::input("", "city"))
+                        ],
+                        [
+                            g!(Grammar::input("", "1 <Main>")),
+                            g!(Grammar::input("", "London"))
+                        ]
+                    ]
+                ]
+            ],
+        );
+        map
+    }
+
+    fn values(map: &HashMap<Coordinate, Grammar>) -> Vec<(String, String)> {
+        let mut values: Vec<(String, String)> = map
+            .iter()
+            .map(|(coord, grammar)| {
+                let value = match &grammar.kind {
+                    Kind::Input(value) => value.clone(),
+                    Kind::Grid(sub_coords) => format! {"{:?}", grid_size(sub_coords)},
+                    _ => String::new(),
+                };
+                (coord.to_string(), value)
+            })
+            .collect();
+        values.sort();
+        values
+    }
+
+    #[test]
+    fn test_grid_markdown() {
+        let map = sheet();
+        assert_eq!(
+            grid_markdown(&map, &coord!("root"), NestedTables::Sections),
+            Some(
+                "| name | address |\n\
+                 | --- | --- |\n\
+                 | Ada \\| Lovelace | [root-B2](#root-b2) |\n\
+                 \n\
+                 ## root-B2\n\
+                 \n\
+                 | street | city |\n\
+                 | --- | --- |\n\
+                 | 1 <Main> | London |\n"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            grid_markdown(&map, &coord!("root"), NestedTables::Inline).unwrap(),
+            "| name | address |\n\
+             | --- | --- |\n\
+             | Ada \\| Lovelace | <table><tr><td>street</td><td>city</td></tr>\
+             <tr><td>1 &lt;Main&gt;</td><td>London</td></tr></table> |\n"
+        );
+        assert_eq!(
+            grid_markdown(&map, &coord!("root-A1"), NestedTables::Inline),
+            None
+        );
+    }
+
+    #[test]
+    fn test_merged_cells() {
+        let mut map = sheet();
+        map.get_mut(&coord!("root-A1")).unwrap().style.col_span = (1, 2);
+        map.get_mut(&coord!("root-B1")).unwrap().style.display = false;
+        let markdown = grid_markdown(&map, &coord!("root"), NestedTables::Sections).unwrap();
+        assert!(markdown.starts_with("| name |  |\n"));
+    }
+
+    #[test]
+    fn test_parse_markdown_table() {
+        let text = "Some text\n\n\
+                    | a | b |\n\
+                    |:--|--:|\n\
+                    | 1 | 2 | 3 |\n\
+                    | x \\| y |\n\
+                    \n\
+                    more text";
+        assert!(is_markdown_table(text));
+        let mut map = HashMap::new();
+        build_grammar_map(
+            &mut map,
+            coord!("root"),
+            parse_markdown_table(text).unwrap(),
+        );
+        assert_eq!(
+            values(&map),
+            vec![
+                ("root".to_string(), "(3, 2)".to_string()),
+                ("root-A1".to_string(), "a".to_string()),
+                ("root-A2".to_string(), "1".to_string()),
+                ("root-A3".to_string(), "x | y".to_string()),
+                ("root-B1".to_string(), "b".to_string()),
+                ("root-B2".to_string(), "2".to_string()),
+                ("root-B3".to_string(), "".to_string()),
+            ]
+        );
+
+        assert!(!is_markdown_table("| not a table |\n| at all |"));
+        assert!(parse_markdown_table("a | b").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let map = sheet();
+        for nested in vec![NestedTables::Inline, NestedTables::Sections] {
+            let markdown = grid_markdown(&map, &coord!("root"), nested).unwrap();
+            let mut read = HashMap::new();
+            build_grammar_map(
+                &mut read,
+                coord!("root"),
+                parse_markdown_table(&markdown).unwrap(),
+            );
+            assert_eq!(values(&read), values(&map));
+        }
+    }
+
+    #[test]
+    fn test_anchor() {
+        assert_eq!(anchor(" root-A1-B2 "), "root-a1-b2");
+        assert_eq!(anchor("My Table (2)"), "my-table-2");
+        assert_eq!(section_link("[x](#root-b2)"), Some("root-b2"));
+        assert_eq!(section_link("see [x](#root-b2) here"), None);
+    }
+}
@@ -12,19 +12,60 @@ use stdweb::traits::IEvent;
 use stdweb::unstable::{TryFrom, TryInto};
 use stdweb::web::{document, IElement, INode, IParentNode};
 use wasm_bindgen::JsValue;
-use yew::events::{KeyDownEvent, KeyPressEvent, KeyUpEvent};
+use yew::events::{KeyDownEvent, KeyUpEvent};
 use yew::prelude::*;
 use yew::services::reader::{File, FileData, ReaderService, ReaderTask};
+use yew::format::{Json, Text};
+use yew::services::timeout::{TimeoutService, TimeoutTask};
+use yew::services::websocket::{WebSocketService, WebSocketStatus, WebSocketTask};
 use yew::services::ConsoleService;
 
-use crate::coordinate::{Col, Coordinate, Row};
+use crate::canonical::{canonical_json, Layout};
+use crate::codemirror::DEFAULT_EDITOR_MODE;
+use crate::collab::{self, default_relay_url, ClientMessage, Collab, Op, Received, RelayMessage};
+use crate::command::Command;
+use crate::comments::{self, Comment, Reply, DEFAULT_COMMENT_AUTHOR};
+use crate::coordinate::{Col, Coordinate, Line, Row};
+use crate::driver::{
+    parse_driver_manifest, validate_driver_files, value_to_string, DriverFileInfo, DriverInfo,
+    DriverIssue, DriverRegistry, DriverResponse, DriverValidationReport,
+};
+use crate::execution::{new_backend, BackendKind, ExecutionResult, Executions};
+use crate::diff::{
+    diff_sessions, empty_base, merge_sessions, split_conflict_markers, ConflictedText, Merge,
+    MergeConflicts, ShownDiff, Side,
+};
+use crate::explorer::{
+    duplicate_name, file_name, parse_listing, path_name, Explorer, SessionFile, EXPLORER_FILE,
+};
+use crate::formatting::{self, FormatRuleDraft, StyleOverride};
 use crate::grammar::{Grammar, Kind, Lookup};
 use crate::grammar_map::*;
+use crate::keymap::{Keymap, KEYMAP_FILE};
+use crate::names::{self, resolve, target_cell, target_to_string, validate_name, NameDraft};
+use crate::navigation::{navigate, Move, ViewHistory};
+use crate::palette::{
+    filter_entries, palette_commands, parse_command, Palette, PaletteCommand, PaletteEntry,
+    TEMPLATES,
+};
+use crate::html_export::export_html;
+use crate::json_grid::{grid_json, parse_json};
+use crate::workbook::{grid_workbook, sheet_entry, write_xlsx, WorkbookFile};
+use crate::markdown::{grid_markdown, parse_markdown_table, NestedTables};
 use crate::session::Session;
-use crate::style::Style;
-use crate::util::{move_grammar, non_zero_u32_tuple, resize, resize_diff};
+use crate::settings::{Setting, Settings, SETTINGS_FILE};
+use crate::sheet::{grid_csv, sheet_cells, spill, PYTHON_SHEET_PRELUDE};
+use crate::style::{Style, StyleContext};
+use crate::theme::Theme;
+use crate::util::{
+    merge_cells, merge_discards, move_grammar, non_zero_u32_tuple, resize, resize_diff,
+    unmerge_cells,
+};
+use crate::validation::{self, validate, ValidationDraft};
+use crate::value::Format;
 use crate::view::{
-    view_context_menu, view_file_popup, view_grammar, view_menu_bar, view_side_nav, view_tab_bar,
+    view_breadcrumbs, view_command_palette, view_context_menu, view_diff_styles, view_file_popup,
+    view_grammar, view_menu_bar, view_presence_styles, view_side_nav, view_tab_bar,
 };
 use crate::{coord, coord_col, coord_row, g, grid, row_col_vec};
 
@@ -37,8 +78,12 @@ pub struct CoordinateParser;
 pub struct Model {
     // Parts of the application state are described below:
 
-    // - `view_root` represents the parent grammar that the view starts rendering from
-    view_root: Coordinate,
+    // - `view_root` represents the parent grammar that the view starts rendering from,
+    //   `view_history` holds the view roots to go back and forward to, and `tab_views`
+    //   the view roots (and their histories) of the tabs that aren't shown
+    pub view_root: Coordinate,
+    pub view_history: ViewHistory,
+    tab_views: HashMap<usize, (Coordinate, ViewHistory)>,
 
     // - `active_cell`
     pub active_cell: Option<Coordinate>,
@@ -48,6 +93,8 @@ pub struct Model {
     // - `last_select_cell` is the bottom-rightmost cell in a selection
     pub first_select_cell: Option<Coordinate>,
     pub last_select_cell: Option<Coordinate>,
+    // - `select_cursor` is the cell the selection was last extended to from the keyboard
+    select_cursor: Option<Coordinate>,
 
     pub secondary_selections: HashSet<Coordinate>,
 
@@ -117,6 +164,68 @@ pub struct Model {
 
     // - `tasks` are used to store asynchronous requests to read/load files
     pub tasks: Vec<ReaderTask>,
+
+    // - `drivers` keeps track of the drivers registered through the driver API
+    //   (see static/driver_api.js) and of the evaluation requests sent to them
+    pub drivers: DriverRegistry,
+    // - `driver_report` is the result of validating the last selected driver directory
+    pub driver_report: Option<DriverValidationReport>,
+
+    // - `cell_errors` are errors (e.g. from evaluating a grammar) shown on the
+    //   corresponding cell until it is evaluated again
+    pub cell_errors: HashMap<Coordinate, String>,
+
+    // - `executions` runs code (e.g. python from an editor) on the selected execution
+    //   backend, `execution_timeouts` hold the timers of the running executions
+    pub executions: Executions,
+    execution_timeouts: HashMap<u32, TimeoutTask>,
+
+    // - `format_rule_draft` is the conditional formatting rule being filled in
+    pub format_rule_draft: FormatRuleDraft,
+
+    // - `validation_errors` are the messages of the input cells that failed validation,
+    //   `validation_draft` is the validation rule being filled in
+    pub validation_errors: HashMap<Coordinate, String>,
+    pub validation_draft: ValidationDraft,
+
+    // - `comment_author` is the name new comments and replies are written under
+    pub comment_author: String,
+
+    // - `name_draft` is the name being defined
+    pub name_draft: NameDraft,
+
+    // - `keymap` binds key combinations to commands, `keymap_issues` are the problems
+    //   found in the user's keymap file (see src/keymap.rs)
+    pub keymap: Keymap,
+    pub keymap_issues: Vec<String>,
+
+    // - `palette` is the command palette while it's open (see src/palette.rs)
+    pub palette: Option<Palette>,
+
+    // - `settings` are the application settings kept in the user config directory,
+    //   `settings_issues` the problems found reading or changing them (see src/settings.rs)
+    pub settings: Settings,
+    pub settings_issues: Vec<String>,
+
+    // - `explorer` is the workspace directory and the recent files of the "File Explorer",
+    //   `session_files` the session files of the workspace and `explorer_error` the last
+    //   thing that went wrong with them (see src/explorer.rs)
+    pub explorer: Explorer,
+    pub session_files: Vec<SessionFile>,
+    pub explorer_error: Option<String>,
+
+    // - `shown_diff` is the diff outlined in the grid, `merge_base` the session file merges
+    //   start from and `merge_conflicts` what's left to resolve of the last merge (see
+    //   src/diff.rs)
+    pub shown_diff: Option<ShownDiff>,
+    pub merge_base: Option<String>,
+    pub merge_conflicts: Option<MergeConflicts>,
+
+    // - `collab` is the tab edited with other people through a relay and `collab_task` the
+    //   connection to the relay, `relay_url` the relay to connect to (see src/collab.rs)
+    pub collab: Option<Collab>,
+    pub collab_task: Option<WebSocketTask>,
+    pub relay_url: String,
 }
 
 #[derive(Debug)]
@@ -177,8 +286,14 @@ pub enum Action {
     SetSessionTitle(String),
     ReadDriverFiles(Vec<File>),
     LoadDriverMainFile(FileData),
+    LoadDriverManifest(FileData),
     UploadDriverMiscFile(FileData),
 
+    // Driver API
+    RegisterDriver(DriverInfo),
+    EvaluateDriver(/* coord inside the grammar to evaluate */ Coordinate),
+    DriverResponse(DriverResponse),
+
     // Grid Operations
     AddNestedGrid(Coordinate, (u32 /*rows*/, u32 /*cols*/)),
 
@@ -190,6 +305,11 @@ pub enum Action {
     ZoomIn,
     ZoomOut,
     ZoomReset,
+    // renders the grid at the coordinate in place of the root grid
+    SetViewRoot(Coordinate),
+    ViewBack,
+    ViewForward,
+    SwitchTab(/* session index */ usize),
 
     NewEditor,
 
@@ -197,12 +317,18 @@ pub enum Action {
     SetCursorType(CursorType),
     Select(SelectMsg),
     RangeDelete(),
+    // moves the active cell from the cell, or extends the selection from it if the bool is
+    // set (see src/navigation.rs)
+    Navigate(Coordinate, Move, bool),
 
     Lookup(
         /* source: */ Coordinate,
         /* lookup_type: */ Lookup,
     ),
+    // merges the selected cells of a grid into the top-left one
     MergeCells(),
+    // splits the merged cells the active cell belongs to
+    UnmergeCells(),
 
     ChangeDefaultNestedGrid((NonZeroU32, NonZeroU32)),
 
@@ -224,10 +350,122 @@ pub enum Action {
     ReadCSVFile(File, Coordinate),
     LoadCSVFile(FileData, Coordinate),
 
-    RunPython(
-        String,     /* TODO: pass in sheet as well */
-        Coordinate, /* output_coord */
-    ),
+    RunPython(String, Coordinate /* output_coord */),
+    // runs the content of the editor at the coordinate, see `Model::editor_output`
+    RunEditor(Coordinate),
+    ChangeEditor(Coordinate, /* content */ String),
+    SetEditorMode(Coordinate, /* mode */ String),
+    SetEditorOutput(Coordinate, /* output coordinate, empty for the default */ String),
+
+    // Conditional formatting
+    UpdateFormatRuleDraft(FormatRuleDraft),
+    AddFormatRule(/* grid */ Coordinate),
+    RemoveFormatRule(/* grid */ Coordinate, /* index */ usize),
+
+    // Style classes and themes
+    SetTheme(/* name */ String),
+    // defines a class with the style of the active cell
+    DefineStyleClass(/* name */ String),
+    RemoveStyleClass(/* name */ String),
+    SetStyleClass(Coordinate, Option<String>),
+    ExecutionFinished(ExecutionResult),
+    ExecutionTimedOut(u32),
+    CancelExecution(u32),
+    SetExecutionBackend(BackendKind),
+
+    // Typed values
+    SetCellFormat(Coordinate, Option<Format>),
+    // changes the setting of the cell's format, see `Format::with_setting`
+    SetCellFormatSetting(Coordinate, String),
+    // writes the grid to "{title}-{grid}.csv"
+    ExportCSV(/* grid */ Coordinate),
+    // writes the cell to "{title}-{coord}.html", see src/html_export.rs
+    ExportHTML(Coordinate),
+    // copies the grid to the clipboard as a Markdown table, see src/markdown.rs
+    CopyMarkdown(/* grid */ Coordinate, NestedTables),
+    // replaces the active cell with a table pasted as Markdown
+    PasteMarkdown(MapEntry),
+    // replaces the active cell with a JSON document chosen by the user, see src/json_grid.rs
+    ImportJSON,
+    // saves the cell as a JSON document
+    ExportJSON(Coordinate),
+    // opens each sheet of an .xlsx or .ods workbook chosen by the user in a new tab, see
+    // src/workbook.rs
+    OpenWorkbook,
+    // saves the root grid of the session as an .xlsx workbook
+    ExportWorkbook,
+
+    // Comments
+    SetCommentAuthor(String),
+    AddComment(Coordinate, /* text */ String),
+    ReplyToComment(Coordinate, /* index */ usize, /* text */ String),
+    SetCommentResolved(Coordinate, /* index */ usize, bool),
+    RemoveComment(Coordinate, /* index */ usize),
+
+    // Data validation
+    UpdateValidationDraft(ValidationDraft),
+    // adds the validation being filled in, relative to the active cell
+    AddValidation(),
+    RemoveValidation(/* index */ usize),
+    // picks one of the allowed values of a cell from its dropdown
+    ChooseAllowedValue(Coordinate, String),
+
+    // Named ranges
+    UpdateNameDraft(NameDraft),
+    // defines the name being filled in, for the selection if no target was typed in
+    DefineName(),
+    RenameName(/* old */ String, /* new */ String),
+    RemoveName(String),
+    // activates the cell of a name or coordinate (the top-left cell of a range)
+    GoTo(String),
+
+    // Keymap
+    RunCommand(Command),
+    // reads the user's keymap file again
+    ReloadKeymap,
+
+    // Command palette
+    OpenPalette,
+    ClosePalette,
+    SetPaletteQuery(String),
+    // moves the highlighted entry of the palette up (negative) or down
+    MovePaletteSelection(i32),
+    RunPaletteCommand(PaletteCommand),
+
+    // Settings
+    SetSetting(Setting, /* value */ String),
+    ResetSettings,
+
+    // File Explorer
+    ChooseWorkspace,
+    RefreshSessionFiles,
+    OpenSessionFile(/* path */ String, /* in a new tab */ bool),
+    RenameSessionFile(/* path */ String, /* new file name */ String),
+    DuplicateSessionFile(/* path */ String),
+    DeleteSessionFile(/* path */ String),
+
+    // Diffs and merges (see src/diff.rs)
+    // outlines the cells of the current session that differ from a session file
+    DiffSessionFile(/* path */ String),
+    ClearDiff,
+    // sets the session file merges start from, `None` merges without one
+    SetMergeBase(Option<String>),
+    // merges a session file with the current session into a new tab
+    MergeSessionFile(/* path */ String),
+    // resolves a conflict of the last merge with the cell of one of its sides
+    ResolveConflict(/* index */ usize, Side),
+
+    // Collaboration (see src/collab.rs)
+    // starts a relay in the main process and connects to it
+    StartRelay,
+    SetRelayUrl(String),
+    // edits the current tab with the other sites of the relay
+    ConnectRelay,
+    DisconnectRelay,
+    // replaces the session of the other sites with the one of the shared tab
+    ShareSession,
+    RelayMessage(/* JSON */ String),
+    RelayClosed(/* because of an error */ bool),
 }
 
 impl Model {
@@ -250,6 +488,583 @@ impl Model {
         self.get_session_mut().root = session.root;
         self.get_session_mut().meta = session.meta;
         self.get_session_mut().grammars = session.grammars;
+        self.get_session_mut().formatting = session.formatting;
+        self.get_session_mut().style_classes = session.style_classes;
+        self.get_session_mut().validations = session.validations;
+        self.get_session_mut().comments = session.comments;
+        self.get_session_mut().names = session.names;
+        self.get_session_mut().theme = session.theme;
+        self.revalidate();
+        self.rebind_lookups();
+        // the grids zoomed into belonged to the previous session
+        self.view_root = coord!("root");
+        self.view_history = ViewHistory::default();
+    }
+
+    // the grid the view starts rendering from, the root grid if `view_root` is no longer
+    // a grid (e.g. its row was deleted)
+    pub fn shown_view_root(&self) -> Coordinate {
+        match self.get_session().grammars.get(&self.view_root).map(|g| &g.kind) {
+            Some(Kind::Grid(_)) => self.view_root.clone(),
+            _ => coord!("root"),
+        }
+    }
+
+    // replaces the cell at `coord` (and whatever was nested in it) with what's built from
+    // `entry`, keeping the name of the cell and the cells it's merged over
+    fn build_in_cell(&mut self, coord: &Coordinate, entry: MapEntry) {
+        let grammars = &mut self.get_session_mut().grammars;
+        let old = grammars.get(coord).cloned();
+        grammars.retain(|c, _| !c.row_cols.starts_with(&coord.row_cols));
+        build_grammar_map(grammars, coord.clone(), entry);
+        if let (Some(old), Some(grammar)) = (old, grammars.get_mut(coord)) {
+            grammar.name = old.name;
+            grammar.style.col_span = old.style.col_span;
+            grammar.style.row_span = old.style.row_span;
+        }
+        let first_cell = Coordinate::child_of(coord, non_zero_u32_tuple((1, 1)));
+        if grammars.contains_key(&first_cell) {
+            self.active_cell = Some(first_cell);
+        }
+    }
+
+    // the session in a session file, `Err` with what went wrong otherwise
+    fn read_session_file(&self, path: &str) -> Result<Session, String> {
+        let args: [JsValue; 1] = [JsValue::from_str(path)];
+        let contents = ipc_renderer.send_sync("read-session-file", Box::new(args));
+        let contents = contents
+            .as_string()
+            .ok_or_else(|| format! {"cannot read {}", path})?;
+        if split_conflict_markers(&contents).is_some() {
+            return Err(format! {"{} has merge conflicts, open it to resolve them", path});
+        }
+        serde_json::from_str::<Session>(&contents)
+            .map_err(|e| format! {"{} isn't a session: {}", path, e})
+    }
+
+    // the sides of a session file with merge conflicts, merged
+    fn merge_conflicted(&self, path: &str, sides: ConflictedText) -> Result<Merge, String> {
+        let parse = |side: &str, text: &str| {
+            serde_json::from_str::<Session>(text)
+                .map_err(|e| format! {"{} of {} isn't a session: {}", side, path, e})
+        };
+        let ours = parse("our side", &sides.ours)?;
+        let theirs = parse("their side", &sides.theirs)?;
+        let base = match sides.base {
+            Some(base) => parse("the base", &base)?,
+            None => empty_base(&theirs),
+        };
+        Ok(merge_sessions(&base, &ours, &theirs))
+    }
+
+    // opens a merged session in a new tab, its conflicts left to resolve
+    fn open_merge(&mut self, merge: Merge) {
+        self.sessions.push(merge.session);
+        let tab = self.sessions.len() - 1;
+        self.merge_conflicts = Some(MergeConflicts {
+            tab,
+            conflicts: merge.conflicts,
+        });
+        self.update(Action::SwitchTab(tab));
+    }
+
+    fn send_to_relay(&mut self, messages: Vec<ClientMessage>) {
+        if let Some(task) = self.collab_task.as_mut() {
+            for message in messages.iter() {
+                task.send(Json(message));
+            }
+        }
+    }
+
+    // sends the edits made to the shared tab since the last ones, and where the cursor is if
+    // it's the current tab
+    fn share_edits(&mut self) {
+        let collab = match self.collab.as_mut() {
+            Some(collab) if collab.is_connected() => collab,
+            _ => return,
+        };
+        let tab = collab.tab;
+        let mut messages = collab.local_edits(&self.sessions[tab].grammars);
+        if tab == self.current_session_index {
+            let selection = match (&self.first_select_cell, &self.last_select_cell) {
+                (Some(first), Some(last)) => Some((first.clone(), last.clone())),
+                _ => None,
+            };
+            messages.extend(collab.move_to(self.active_cell.clone(), selection));
+        }
+        self.send_to_relay(messages);
+    }
+
+    // sends `op` for an edit of the current tab that is about to be made, if it's shared
+    fn share_op(&mut self, op: Op) {
+        let collab = match self.collab.as_mut() {
+            Some(collab) if collab.is_connected() && collab.tab == self.current_session_index => {
+                collab
+            }
+            _ => return,
+        };
+        let tab = collab.tab;
+        let messages = collab.local_op(&self.sessions[tab].grammars, op);
+        self.send_to_relay(messages);
+    }
+
+    // the row or column appended to the grid of the active cell, when the current tab is
+    // shared: the grid is changed the same way on every site
+    fn shared_line_insert(&self, line: Line) -> Option<Op> {
+        self.collab
+            .as_ref()
+            .filter(|c| c.is_connected() && c.tab == self.current_session_index)?;
+        let parent = self.active_cell.as_ref()?.parent()?;
+        let last = match self.get_session().grammars.get(&parent).map(|g| &g.kind) {
+            Some(Kind::Grid(sub_coords)) => sub_coords
+                .iter()
+                .map(|(row, col)| match line {
+                    Line::Row => row.get(),
+                    Line::Col => col.get(),
+                })
+                .max()
+                .unwrap_or(0),
+            _ => return None,
+        };
+        Some(Op::InsertLine(parent, line, NonZeroU32::new(last + 1).unwrap()))
+    }
+
+    // the active cell when it's a nested grid, otherwise the grid it's in, as long as
+    // that grid can be zoomed into (i.e. isn't the root)
+    pub fn zoom_grid(&self) -> Option<Coordinate> {
+        self.active_cell
+            .clone()
+            .and_then(|c| match self.get_session().grammars.get(&c).map(|g| &g.kind) {
+                Some(Kind::Grid(_)) => Some(c),
+                _ => c.parent(),
+            })
+            .filter(|grid| grid.parent().is_some())
+    }
+
+    // the action a command runs with the current active cell and selection
+    pub fn command_action(&self, command: Command) -> Action {
+        let active_cell = self.active_cell.clone();
+        match command {
+            Command::NestGrid => {
+                let (rows, cols) = self.default_nested_row_cols;
+                active_cell.map_or(Action::Noop, |c| {
+                    Action::AddNestedGrid(c, (rows.get(), cols.get()))
+                })
+            }
+            Command::InsertRow => Action::InsertRow,
+            Command::InsertCol => Action::InsertCol,
+            Command::DeleteRow => Action::DeleteRow,
+            Command::DeleteCol => Action::DeleteCol,
+            Command::MergeCells => Action::MergeCells(),
+            Command::UnmergeCells => Action::UnmergeCells(),
+            Command::ZoomIn => Action::ZoomIn,
+            Command::ZoomOut => Action::ZoomOut,
+            Command::ZoomReset => Action::ZoomReset,
+            Command::Save => Action::AskFileName(),
+            Command::NewEditor => Action::NewEditor,
+            Command::RunEditor => active_cell.map_or(Action::Noop, Action::RunEditor),
+            Command::ToggleLookup => active_cell.map_or(Action::Noop, Action::ToggleLookup),
+            Command::Evaluate => active_cell.map_or(Action::Noop, Action::EvaluateDriver),
+            Command::ZoomIntoGrid => match self.zoom_grid() {
+                Some(grid) => Action::SetViewRoot(grid),
+                None => Action::Noop,
+            },
+            Command::ViewBack => Action::ViewBack,
+            Command::ViewForward => Action::ViewForward,
+            Command::AddDefinition => match self.definition_grid() {
+                Some(grid) => {
+                    let name = self.definition_name(&grid);
+                    Action::AddDefinition(grid, name)
+                }
+                None => Action::Noop,
+            },
+            Command::ExportCSV => Action::ExportCSV(self.formatting_grid()),
+            Command::ExportHTML => Action::ExportHTML(self.formatting_grid()),
+            Command::CopyMarkdown => {
+                Action::CopyMarkdown(self.formatting_grid(), NestedTables::Sections)
+            }
+            Command::ImportJSON => Action::ImportJSON,
+            Command::ExportJSON => Action::ExportJSON(self.formatting_grid()),
+            Command::OpenWorkbook => Action::OpenWorkbook,
+            Command::ExportWorkbook => Action::ExportWorkbook,
+            Command::Reset => Action::Recreate,
+            Command::CommandPalette => Action::OpenPalette,
+            Command::Navigate(movement, extend) => active_cell.map_or(Action::Noop, |c| {
+                Action::Navigate(c, movement, extend)
+            }),
+        }
+    }
+
+    // whether the command can run with the current active cell and selection
+    pub fn command_enabled(&self, command: Command) -> bool {
+        let active_grammar = self
+            .active_cell
+            .as_ref()
+            .and_then(|c| self.get_session().grammars.get(c));
+        match command {
+            Command::NestGrid
+            | Command::InsertRow
+            | Command::InsertCol
+            | Command::DeleteRow
+            | Command::DeleteCol
+            | Command::ImportJSON
+            | Command::Navigate(_, _) => self.active_cell.is_some(),
+            Command::MergeCells => match (&self.first_select_cell, &self.last_select_cell) {
+                (Some(first), Some(last)) => first.parent() == last.parent(),
+                _ => false,
+            },
+            Command::UnmergeCells => active_grammar.map_or(false, |g| g.style.col_span.0 != 0),
+            Command::NewEditor => match active_grammar.map(|g| &g.kind) {
+                Some(Kind::Input(_)) => true,
+                _ => false,
+            },
+            Command::RunEditor => match active_grammar.map(|g| &g.kind) {
+                Some(Kind::Editor(_, _, _)) => true,
+                _ => false,
+            },
+            Command::ToggleLookup => match active_grammar.map(|g| &g.kind) {
+                Some(Kind::Input(_)) | Some(Kind::Lookup(_, _)) => true,
+                _ => false,
+            },
+            Command::Evaluate => self.active_cell.is_some() && !self.drivers.drivers.is_empty(),
+            Command::ZoomIntoGrid => self.zoom_grid().is_some(),
+            Command::ViewBack => self.view_history.can_go_back(),
+            Command::ViewForward => self.view_history.can_go_forward(),
+            Command::AddDefinition => self.definition_grid().is_some(),
+            Command::ZoomIn
+            | Command::ZoomOut
+            | Command::ZoomReset
+            | Command::Save
+            | Command::ExportCSV
+            | Command::ExportHTML
+            | Command::CopyMarkdown
+            | Command::ExportJSON
+            | Command::OpenWorkbook
+            | Command::ExportWorkbook
+            | Command::Reset
+            | Command::CommandPalette => true,
+        }
+    }
+
+    // the grid a definition can be added from: the grid of the selection when the
+    // selection covers it from its first (top-left) to its last (bottom-right) cell
+    pub fn definition_grid(&self) -> Option<Coordinate> {
+        let (first, last) = match (&self.first_select_cell, &self.last_select_cell) {
+            (Some(first), Some(last)) if first.parent() == last.parent() => (first, last),
+            _ => return None,
+        };
+        let grid = first.parent()?;
+        let mut sub_coords = match self.get_session().grammars.get(&grid).map(|g| &g.kind) {
+            Some(Kind::Grid(sub_coords)) if !sub_coords.is_empty() => sub_coords.clone(),
+            _ => return None,
+        };
+        sub_coords.sort();
+        if sub_coords.first() == Some(&first.row_col()) && sub_coords.last() == Some(&last.row_col())
+        {
+            Some(grid)
+        } else {
+            None
+        }
+    }
+
+    // the name definitions get when none is typed in: `default_definition_name`, or else
+    // the coordinate of the grid, e.g. "root_A1"
+    pub fn definition_name(&self, grid: &Coordinate) -> String {
+        if self.default_definition_name == "" {
+            grid.to_string().replace("-", "_")
+        } else {
+            self.default_definition_name.clone()
+        }
+    }
+
+    pub fn palette_command_enabled(&self, command: &PaletteCommand) -> bool {
+        match command {
+            PaletteCommand::Run(command) => self.command_enabled(*command),
+            PaletteCommand::NestGrid(_, _) => self.active_cell.is_some(),
+            PaletteCommand::AddDefinition(_) => self.definition_grid().is_some(),
+            PaletteCommand::GoTo(text) => resolve(&self.get_session().names, text)
+                .as_ref()
+                .and_then(target_cell)
+                .map_or(false, |c| self.get_session().grammars.contains_key(&c)),
+            PaletteCommand::SetTheme(name) => Theme::by_name(name).is_some(),
+        }
+    }
+
+    fn palette_action(&self, command: PaletteCommand) -> Action {
+        match command {
+            PaletteCommand::Run(command) => self.command_action(command),
+            PaletteCommand::NestGrid(rows, cols) => self
+                .active_cell
+                .clone()
+                .map_or(Action::Noop, |c| Action::AddNestedGrid(c, (rows, cols))),
+            PaletteCommand::AddDefinition(name) => self
+                .definition_grid()
+                .map_or(Action::Noop, |grid| Action::AddDefinition(grid, name)),
+            PaletteCommand::GoTo(text) => Action::GoTo(text),
+            PaletteCommand::SetTheme(name) => Action::SetTheme(name),
+        }
+    }
+
+    // the entries of the palette matching its query: the command typed in with its
+    // parameter if any, then the commands and the templates waiting for a parameter
+    pub fn palette_entries(&self) -> Vec<PaletteEntry> {
+        let query = match &self.palette {
+            Some(palette) => palette.query.clone(),
+            None => return vec![],
+        };
+        let mut entries = vec![];
+        if let Some(command) = parse_command(&query) {
+            entries.push(PaletteEntry {
+                title: command.title(),
+                description: command.description(),
+                shortcut: None,
+                enabled: self.palette_command_enabled(&command),
+                command: Some(command),
+            });
+        }
+        let mut listed: Vec<PaletteEntry> = palette_commands()
+            .into_iter()
+            .map(|command| PaletteEntry {
+                title: command.title(),
+                description: command.description(),
+                shortcut: self.keymap.shortcut(command),
+                enabled: self.command_enabled(command),
+                command: Some(PaletteCommand::Run(command)),
+            })
+            .collect();
+        listed.extend(TEMPLATES.iter().map(|template| PaletteEntry {
+            title: format! {"{} …", template.title},
+            description: format! {"{}, e.g. \"{} {}\"", template.description, template.title, template.hint},
+            shortcut: None,
+            enabled: true,
+            command: None,
+        }));
+        entries.extend(filter_entries(&query, listed));
+        entries
+    }
+
+    // the default keymap with the overrides of the user's keymap file
+    fn load_keymap(&mut self) {
+        let mut keymap = Keymap::default();
+        self.keymap_issues = match read_config_file(KEYMAP_FILE) {
+            Some(contents) => keymap.apply_overrides(&contents),
+            None => vec![],
+        };
+        for issue in &self.keymap_issues {
+            info! {"[keymap] {}", issue};
+        }
+        self.keymap = keymap;
+    }
+
+    // the settings of the settings file, or the defaults if there's none
+    fn load_settings(&mut self) {
+        let (settings, issues) = match read_config_file(SETTINGS_FILE) {
+            Some(contents) => Settings::from_json(&contents),
+            None => (Settings::default(), vec![]),
+        };
+        for issue in &issues {
+            info! {"[settings] {}", issue};
+        }
+        self.settings = settings;
+        self.settings_issues = issues;
+        self.apply_settings();
+    }
+
+    // makes the settings take effect
+    fn apply_settings(&mut self) {
+        self.default_nested_row_cols = self.settings.nested_row_cols();
+        self.zoom = self.settings.zoom;
+        self.default_definition_name = self.settings.definition_name.clone();
+    }
+
+    fn save_settings(&mut self) {
+        if !write_config_file(SETTINGS_FILE, &self.settings.to_json()) {
+            self.settings_issues = vec![format! {"cannot write {}", SETTINGS_FILE}];
+        }
+    }
+
+    // the workspace and recent files of the explorer file, then the files of the workspace
+    fn load_explorer(&mut self) {
+        self.explorer = read_config_file(EXPLORER_FILE)
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        self.refresh_session_files();
+    }
+
+    fn save_explorer(&mut self) {
+        let contents = serde_json::to_string_pretty(&self.explorer).unwrap_or_default();
+        if !write_config_file(EXPLORER_FILE, &contents) {
+            self.explorer_error = Some(format! {"cannot write {}", EXPLORER_FILE});
+        }
+    }
+
+    // lists the session files of the workspace again
+    fn refresh_session_files(&mut self) {
+        let workspace = match self.explorer.workspace.clone() {
+            Some(workspace) => workspace,
+            None => {
+                self.session_files = vec![];
+                return;
+            }
+        };
+        let args: [JsValue; 1] = [JsValue::from_str(&workspace)];
+        let listing = ipc_renderer.send_sync("list-session-files", Box::new(args));
+        let files = match listing.as_string() {
+            Some(listing) => parse_listing(&listing),
+            None => Err(format! {"cannot read the workspace {}", workspace}),
+        };
+        match files {
+            Ok(files) => self.session_files = files,
+            Err(e) => {
+                self.session_files = vec![];
+                self.explorer_error = Some(e);
+            }
+        }
+    }
+
+    // binds every lookup cell to what its text refers to, either a name or a coordinate
+    fn rebind_lookups(&mut self) {
+        let session = self.get_session_mut();
+        let names = &session.names;
+        for grammar in session.grammars.values_mut() {
+            if let Kind::Lookup(text, _) = &grammar.kind {
+                grammar.kind = Kind::Lookup(text.clone(), resolve(names, text));
+            }
+        }
+    }
+
+    // the selected range of cells, or the active cell if nothing is selected
+    fn selection_target(&self) -> Option<Lookup> {
+        if let (Some(first), Some(last)) = (&self.first_select_cell, &self.last_select_cell) {
+            if first.parent().is_some() && first.parent() == last.parent() {
+                return Some(Lookup::Range {
+                    parent: first.parent().unwrap(),
+                    start: first.row_col(),
+                    end: last.row_col(),
+                });
+            }
+        }
+        self.active_cell.clone().map(Lookup::Cell)
+    }
+
+    // keeps everything that refers to cells by coordinate in step with a deleted row or
+    // column of the grid at `parent`
+    fn line_deleted(&mut self, parent: &Coordinate, line: Line, index: NonZeroU32) {
+        let session = self.get_session_mut();
+        comments::delete_line(&mut session.comments, parent, line, index);
+        names::delete_line(&mut session.names, parent, line, index);
+        formatting::delete_line(&mut session.formatting, parent, line, index);
+        validation::delete_line(&mut session.validations, parent, line, index);
+        self.rebind_lookups();
+        self.revalidate();
+    }
+
+    // checks every input cell against the validation rules again
+    fn revalidate(&mut self) {
+        let session = self.get_session();
+        let errors = session
+            .grammars
+            .iter()
+            .filter_map(|(coord, grammar)| match &grammar.kind {
+                Kind::Input(value) => validate(&session.validations, coord, value)
+                    .err()
+                    .map(|e| (coord.clone(), e.message)),
+                _ => None,
+            })
+            .collect();
+        self.validation_errors = errors;
+    }
+
+    // the size of the cells added to the sheet, see `Settings::cell_size`
+    fn cell_size(&self) -> (f64, f64) {
+        self.settings.cell_size(&self.get_session().theme)
+    }
+
+    // writes a value computed for a cell (by a driver for instance) while keeping the
+    // kind of the grammar, nested grids are never overwritten
+    fn set_cell_value(&mut self, coord: &Coordinate, value: String) {
+        match self.get_session_mut().grammars.get_mut(coord) {
+            Some(Grammar {
+                kind: Kind::Grid(_),
+                ..
+            }) => {
+                self.cell_errors.insert(
+                    coord.clone(),
+                    "cannot write a value into a nested grid".to_string(),
+                );
+            }
+            Some(
+                g
+                @
+                Grammar {
+                    kind: Kind::Text(_),
+                    ..
+                },
+            ) => {
+                g.kind = Kind::Text(value);
+            }
+            Some(g) => {
+                g.kind = Kind::Input(value);
+            }
+            None => {
+                info! {"[Model::set_cell_value] no grammar at {}", coord.to_string()};
+            }
+        }
+    }
+
+    // the grid conditional formatting rules are added to: the grid of the active cell
+    pub fn formatting_grid(&self) -> Coordinate {
+        self.active_cell
+            .as_ref()
+            .and_then(|c| c.parent())
+            .unwrap_or_else(|| coord!("root"))
+    }
+
+    // the formatting rules and validation state `style::get_style` needs for a cell
+    pub fn style_context(&self, coord: &Coordinate) -> StyleContext {
+        let session = self.get_session();
+        let formatting = &session.formatting;
+        StyleContext {
+            theme: Some(&session.theme),
+            classes: Some(&session.style_classes),
+            rules: (1..coord.row_cols.len())
+                .filter_map(|n| coord.truncate(n))
+                .filter_map(|grid| formatting.get(&grid))
+                .flatten()
+                .collect(),
+            invalid: self.validation_errors.contains_key(coord),
+        }
+    }
+
+    // where running the editor at `coord` writes its result: the configured output
+    // coordinate, or else the cell to the right of the editor (or below it)
+    pub fn editor_output(&self, coord: &Coordinate) -> Option<Coordinate> {
+        let grammars = &self.get_session().grammars;
+        if let Some(Grammar {
+            kind: Kind::Editor(_, _, Some(output)),
+            ..
+        }) = grammars.get(coord)
+        {
+            return Some(output.clone());
+        }
+        coord
+            .neighbor_right()
+            .filter(|c| grammars.contains_key(c))
+            .or_else(|| coord.neighbor_below().filter(|c| grammars.contains_key(c)))
+    }
+
+    // issues found while loading a driver (after the directory has been validated)
+    // are added to the same report
+    fn report_driver_issue(&mut self, issue: DriverIssue) {
+        info! {"[driver] {}", issue};
+        if let Some(report) = self.driver_report.as_mut() {
+            report.issues.push(issue);
+        } else {
+            self.driver_report = Some(DriverValidationReport {
+                issues: vec![issue],
+                ..DriverValidationReport::default()
+            });
+        }
     }
 
     fn query_parent(&self, coord_parent: Coordinate) -> Vec<Coordinate> {
@@ -431,6 +1246,8 @@ impl Component for Model {
         };
         let mut m = Model {
             view_root: coord!("root"),
+            view_history: ViewHistory::default(),
+            tab_views: HashMap::new(),
             col_widths: hashmap! {
                coord_col!("root","A") => 90.0,
                coord_col!("root","B") => 90.0,
@@ -458,6 +1275,7 @@ impl Component for Model {
             focus_cell: None,
             first_select_cell: None,
             last_select_cell: None,
+            select_cursor: None,
 
             secondary_selections: HashSet::new(),
 
@@ -539,6 +1357,12 @@ impl Component for Model {
                     assert!(map.contains_key(&(coord!("root"))));
                     map
                 },
+                formatting: HashMap::new(),
+                style_classes: HashMap::new(),
+                theme: Theme::light(),
+                validations: vec![],
+                comments: HashMap::new(),
+                names: names::Names::new(),
             }],
 
             current_session_index: 0,
@@ -552,6 +1376,14 @@ impl Component for Model {
                     name: "File Explorer".to_string(),
                     icon_path: "assets/folder_icon.png".to_string(),
                 },
+                SideMenu {
+                    name: "Comments".to_string(),
+                    icon_path: "assets/comments_icon.svg".to_string(),
+                },
+                SideMenu {
+                    name: "Collaborate".to_string(),
+                    icon_path: "assets/collaborate_icon.svg".to_string(),
+                },
                 SideMenu {
                     name: "Settings".to_string(),
                     icon_path: "assets/settings_icon.png".to_string(),
@@ -582,6 +1414,76 @@ impl Component for Model {
             mouse_cursor: CursorType::Default,
 
             lookups: vec![],
+
+            drivers: DriverRegistry::default(),
+            driver_report: None,
+            cell_errors: HashMap::new(),
+
+            executions: Executions::new(new_backend(BackendKind::Pyodide)),
+            execution_timeouts: HashMap::new(),
+
+            format_rule_draft: FormatRuleDraft::default(),
+
+            validation_errors: HashMap::new(),
+            validation_draft: ValidationDraft::default(),
+
+            comment_author: DEFAULT_COMMENT_AUTHOR.to_string(),
+
+            name_draft: NameDraft::default(),
+
+            keymap: Keymap::default(),
+            keymap_issues: vec![],
+
+            palette: None,
+
+            settings: Settings::default(),
+            settings_issues: vec![],
+
+            explorer: Explorer::default(),
+            session_files: vec![],
+            explorer_error: None,
+
+            shown_diff: None,
+            merge_base: None,
+            merge_conflicts: None,
+
+            collab: None,
+            collab_task: None,
+            relay_url: default_relay_url(),
+        };
+        m.load_keymap();
+        m.load_settings();
+        m.load_explorer();
+        m.executions.prelude = PYTHON_SHEET_PRELUDE.to_string();
+        // drivers register themselves by calling `ISE.registerDriver`, which reports
+        // back to us through `ISE.onRegister` (see static/driver_api.js)
+        let register_callback = m.link.callback(|info: String| {
+            match serde_json::from_str::<DriverInfo>(&info) {
+                Ok(driver) => Action::RegisterDriver(driver),
+                Err(e) => Action::Alert(format! {"could not register driver: {}", e}),
+            }
+        });
+        js! {
+            ISE.onRegister = @{move |info: String| register_callback.emit(info)};
+        };
+        // a Markdown table pasted anywhere replaces the active cell with a grid, anything
+        // else is pasted as usual
+        let paste_callback = m.link.callback(Action::PasteMarkdown);
+        let paste_markdown = move |text: String| match parse_markdown_table(&text) {
+            Ok(entry) => {
+                paste_callback.emit(entry);
+                true
+            }
+            Err(_) => false,
+        };
+        js! {
+            let paste_markdown = @{paste_markdown};
+            document.addEventListener("paste", (e) => {
+                let text = e.clipboardData ? e.clipboardData.getData("text/plain") : "";
+                if (paste_markdown(text)) {
+                    e.preventDefault();
+                }
+            });
         };
         // load suggestions from
         m.meta_suggestions = m
@@ -611,7 +1513,30 @@ impl Component for Model {
             }
 
             Action::ChangeInput(coord, new_value) => {
+                let old_value = match self.get_session().grammars.get(&coord).map(|g| &g.kind) {
+                    Some(Kind::Input(old_value)) => Some(old_value.clone()),
+                    _ => None,
+                };
+                let previous_error = self.validation_errors.get(&coord).cloned();
+                if let Some(old_value) = old_value {
+                    match validate(&self.get_session().validations, &coord, &new_value) {
+                        Err(e) if e.strict => {
+                            // the cell still shows what was typed, put the old value back
+                            restore_data_cell(&coord, old_value);
+                            return self.update(Action::Alert(format! {
+                                "{} was not changed: {}", coord.to_string(), e.message
+                            }));
+                        }
+                        Err(e) => {
+                            self.validation_errors.insert(coord.clone(), e.message);
+                        }
+                        Ok(()) => {
+                            self.validation_errors.remove(&coord);
+                        }
+                    }
+                }
                 set_data_cell(&coord.clone(), new_value.clone().to_string());
+                let binding = resolve(&self.get_session().names, &new_value);
                 if let Some(g) = self.get_session_mut().grammars.get_mut(&coord) {
                     match g {
                         Grammar {
@@ -621,16 +1546,19 @@ impl Component for Model {
                             g.kind = Kind::Input(new_value);
                         }
                         Grammar {
-                            kind: Kind::Lookup(_, lookup_type),
+                            kind: Kind::Lookup(_, _),
                             ..
                         } => {
-                            g.kind = Kind::Lookup(new_value, lookup_type.clone());
+                            // a lookup is bound to a name or coordinate as it's typed in
+                            g.kind = Kind::Lookup(new_value, binding);
                         }
                         _ => (),
                     }
                 }
-                
-                false
+
+                // the cell already shows the new value, it only needs to be rendered again
+                // when its validation marker changes
+                previous_error.as_ref() != self.validation_errors.get(&coord)
             }
 
             Action::SetActiveCell(coord) => {
@@ -720,6 +1648,7 @@ impl Component for Model {
             Action::Select(SelectMsg::Start(coord)) => {
                 self.first_select_cell = Some(coord.clone());
                 self.last_select_cell = None;
+                self.select_cursor = None;
                 true
             }
             Action::Select(SelectMsg::End(coord)) => {
@@ -767,6 +1696,7 @@ impl Component for Model {
                             if row_range.contains(&coord_row.get())
                                 && col_range.contains(&coord_col.get())
                                 && (coord_depth == depth_check)
+                                && coord.parent() == common_parent
                             {
                                 let col_span = grammar.clone().style.col_span;
                                 let row_span = grammar.clone().style.row_span;
@@ -802,6 +1732,37 @@ impl Component for Model {
                 true
             }
 
+            Action::Navigate(from, movement, extend) => {
+                let view_root = self.shown_view_root();
+                if extend {
+                    // the selection stays in the grid of the cell it started from
+                    let cursor = self
+                        .select_cursor
+                        .clone()
+                        .filter(|_| self.last_select_cell.is_some())
+                        .unwrap_or_else(|| from.clone());
+                    let next = navigate(&self.get_session().grammars, &view_root, &cursor, movement)
+                        .filter(|next| next.parent() == from.parent());
+                    match next {
+                        Some(next) => {
+                            self.update(Action::Select(SelectMsg::Start(from)));
+                            self.update(Action::Select(SelectMsg::End(next.clone())));
+                            self.select_cursor = Some(next);
+                            true
+                        }
+                        None => false,
+                    }
+                } else {
+                    match navigate(&self.get_session().grammars, &view_root, &from, movement) {
+                        Some(next) => {
+                            self.update(Action::Select(SelectMsg::Start(next.clone())));
+                            self.update(Action::SetActiveCell(next))
+                        }
+                        None => false,
+                    }
+                }
+            }
+
             Action::RangeDelete() => {
                 let (first_row, first_col) = self.first_select_cell.clone().unwrap().row_col();
                 let (last_row, last_col) = self.last_select_cell.clone().unwrap().row_col();
@@ -862,71 +1823,51 @@ impl Component for Model {
             }
 
             Action::MergeCells() => {
-                if self.first_select_cell.is_none() || self.last_select_cell.is_none() {
-                    info!("Expect for select of two coord");
-                    return false;
-                }
-                let (first_row, first_col) = self.first_select_cell.clone().unwrap().row_col();
-                let (last_row, last_col) = self.last_select_cell.clone().unwrap().row_col();
-
-                let depth_check = self.last_select_cell.clone().unwrap().row_cols.len();
-                let parent_check = self.last_select_cell.clone().unwrap().parent();
-
-                let row_range = first_row.get()..=last_row.get();
-                let col_range = first_col.get()..=last_col.get();
-
-                let mut merge_height = 0.00;
-                let mut merge_width = 0.00;
-                let mut max_coord = Coordinate::default();
-                let mut max_grammar = Grammar::default();
-                let mut ref_grammas = self.get_session_mut().grammars.clone();
-                for (coord, grammar) in ref_grammas.iter_mut() {
-                    if coord.to_string().contains("root-") {
-                        if row_range.contains(&coord.row().get())
-                            && col_range.contains(&coord.col().get())
-                            && coord.parent() == parent_check
-                        {
-                            let coord_style = grammar.style.clone();
-                            if coord_style.display != false {
-                                if coord.row().get() == last_row.get() {
-                                    merge_width = merge_width + coord_style.width;
-                                }
-                                if coord.col().get() == last_col.get() {
-                                    merge_height = merge_height + coord_style.height;
-                                }
-                                if coord.row().get() == last_row.get()
-                                    && (coord.col().get() == last_col.get())
-                                {
-                                    max_coord = coord.clone();
-                                    max_grammar = grammar.clone();
-                                } else {
-                                    grammar.style.display = false;
-                                }
-                            }
-                            grammar.kind = Kind::Input("".to_string());
-                            grammar.style.col_span.0 = first_col.get();
-                            grammar.style.col_span.1 = last_col.get();
-                            grammar.style.row_span.0 = first_row.get();
-                            grammar.style.row_span.1 = last_row.get();
-                            self.get_session_mut()
-                                .grammars
-                                .insert(coord.clone(), grammar.clone());
-                        }
+                let (first, last) = match (&self.first_select_cell, &self.last_select_cell) {
+                    (Some(first), Some(last)) => (first.clone(), last.clone()),
+                    _ => {
+                        info!("Expect for select of two coord");
+                        return false;
+                    }
+                };
+                let parent = match first.parent() {
+                    Some(parent) if last.parent().as_ref() == Some(&parent) => parent,
+                    _ => {
+                        return self.update(Action::Alert(
+                            "cannot merge cells of different grids".to_string(),
+                        ))
+                    }
+                };
+                let ((first_row, first_col), (last_row, last_col)) =
+                    (first.row_col(), last.row_col());
+                let start = (first_row.min(last_row), first_col.min(last_col));
+                let end = (first_row.max(last_row), first_col.max(last_col));
+                // only the top-left cell keeps its content
+                let discarded = merge_discards(&self.get_session().grammars, &parent, start, end);
+                if !discarded.is_empty() {
+                    let cells: Vec<String> = discarded.iter().map(|c| c.to_string()).collect();
+                    let message = format! {
+                        "Merging keeps the content of {} only, the content of {} will be lost. Merge anyway?",
+                        Coordinate::child_of(&parent, start).to_string(),
+                        cells.join(", "),
+                    };
+                    if !confirm(message) {
+                        return false;
                     }
                 }
-                max_grammar.kind = Kind::Input("".to_string());
-                max_grammar.style.width = merge_width;
-                max_grammar.style.height = merge_height;
-                max_grammar.style.col_span.0 = first_col.get();
-                max_grammar.style.col_span.1 = last_col.get();
-                max_grammar.style.row_span.0 = first_row.get();
-                max_grammar.style.row_span.1 = last_row.get();
-                self.get_session_mut()
-                    .grammars
-                    .insert(max_coord.clone(), max_grammar.clone());
+                self.share_op(Op::Merge(parent.clone(), start, end));
+                merge_cells(&mut self.get_session_mut().grammars, &parent, start, end);
+                self.revalidate();
                 true
             }
 
+            Action::UnmergeCells() => {
+                match self.active_cell.clone() {
+                    Some(coord) => unmerge_cells(&mut self.get_session_mut().grammars, &coord),
+                    None => false,
+                }
+            }
+
             Action::DoCompletion(source_coord, dest_coord) => {
                 move_grammar(self, source_coord, dest_coord.clone());
                 true
@@ -960,19 +1901,23 @@ impl Component for Model {
             // File Saving
             Action::SaveSession() => {
                 // Imports
-                use js_sys::{Function, JsString};
                 use node_sys::fs as node_fs;
-                use node_sys::Buffer;
                 // Session Copy
                 let current_session = self.to_session();
-                // File naming
-                let j = serde_json::to_string(&current_session.clone());
+                // File naming, the session is written in its canonical form (see
+                // src/canonical.rs) so saving it again gives the same file
+                let layout = if self.settings.cell_per_line {
+                    Layout::CellPerLine
+                } else {
+                    Layout::Indented
+                };
+                let j = canonical_json(&current_session, layout);
                 let filename = current_session.title.to_string() + ".json";
-                let jsfilename = JsString::from(filename);
-                let jsbuffer = Buffer::from_string(&JsString::from(j.unwrap()), None);
-                let jscallback = Function::new_no_args("{}");
-                // File append
-                node_fs::append_file(&jsfilename, &jsbuffer, None, &jscallback);
+                node_fs::write_file_sync(
+                    &JsValue::from_str(&filename),
+                    &JsValue::from_str(&j.unwrap()),
+                    None,
+                );
                 // Conditionnal Closing
                 if self.file_popup {self.update(Action::AskFileName());};
 
@@ -987,68 +1932,94 @@ impl Component for Model {
            
 
             Action::ReadDriverFiles(files_list) => {
-                // Get the main file and miscellaneous/additional files from the drivers list
-                let (main_file, misc_files) = {
-                    let (main_file_as_vec, misc_files) : (Vec<File>, Vec<File>) = files_list.iter().fold((Vec::new(), Vec::new()), |accum, file| { 
-                        // Iter::partition is used to divide a list into two given a certain condition.
-                        // Here the condition here is to separate the main file of the driver from the
-                        // addditional ones, where the main file's path looks like
-                        // '{directory_name}/{file_name}.js' and the directory_name == file_name
-                        let mut new_accum = accum.clone();
-                        // use `webkitRelativePath` as the `name`, if it's available
+                use stdweb::web::IBlob;
+                let files: Vec<(File, DriverFileInfo)> = files_list
+                    .into_iter()
+                    .map(|file| {
+                        // use `webkitRelativePath` as the path of the file, if it's available
                         // we'll call out to regular JS to do this using the `js!` macro.
                         // Note that yew::services::reader::File::name() calls "file.name" under the
                         // hood (https://docs.rs/stdweb/0.4.20/src/stdweb/webapi/file.rs.html#23)
-                        let full_file_name : String = js!(
+                        let path: String = js!(
                             if (!!@{&file}.webkitRelativePath) {
                                 return @{&file}.webkitRelativePath;
                             } else {
                                 console.log("couldn't get relative path of file: ", @{&file}.name);
                                 return @{&file}.name; // equivalent to yew::services::reader::File::name()
                             }
-                        ).try_into().unwrap();
-                        let path_parts : Vec<&str> = full_file_name.split("/").collect();
-                        match (path_parts.first(), path_parts.last(), path_parts.len()) {
-                            (Some(directory), Some(file_name), 2) if format!{"{}.js", directory} == file_name.to_string() => {
-                                if new_accum.0.len() == 0 {
-                                    new_accum.0.push(file.clone());
-                                } else {
-                                    panic!("[Action::ReadDriverFiles]: there shouldn't be more than 1 main file in the driver directory")
-                                }
-                            },
-                            _ => {
-                                new_accum.1.push(file.clone());
-                            },
-                        };
-                        new_accum
-                    });
-                    // the `partition` call above gives us a tuple of two Vecs (Vec, Vec) where the first Vec
-                    // should have only one element, so we'll convert it to a (Vec::Item, Vec).
-                    // If this has an error, then there's something wrong with how the driver
-                    // directory is organized.
-                    (
-                        main_file_as_vec.first().unwrap().clone(),
-                        misc_files.clone(),
-                    )
-                };
+                        )
+                        .try_into()
+                        .unwrap_or_else(|_| file.name());
+                        let size = file.len();
+                        (file, DriverFileInfo { path, size })
+                    })
+                    .collect();
+
+                // nothing gets loaded unless the whole driver directory is valid, the report
+                // is shown in the "Settings" menu either way
+                let report = validate_driver_files(
+                    &files
+                        .iter()
+                        .map(|(_, info)| info.clone())
+                        .collect::<Vec<DriverFileInfo>>(),
+                );
+                let main_file_path = report.main_file.clone();
+                let manifest_path = report.manifest.clone();
+                let is_valid = report.is_valid();
+                self.driver_report = Some(report);
+                if !is_valid {
+                    info! {"[Action::ReadDriverFiles] the driver directory is not valid"};
+                    return true;
+                }
 
                 // upload misc files so they can be served by electron to be used by main driver file
                 let upload_callback = self
                     .link
                     .callback(|file_data| Action::UploadDriverMiscFile(file_data));
-                for file in misc_files {
-                    let task = self.reader.read_file(file, upload_callback.clone());
-                    self.tasks.push(task);
+                let manifest_callback = self.link.callback(Action::LoadDriverManifest);
+                let mut main_file = None;
+                for (file, info) in files {
+                    if Some(&info.path) == main_file_path.as_ref() {
+                        main_file = Some(file);
+                    } else if Some(&info.path) == manifest_path.as_ref() {
+                        let task = self.reader.read_file(file, manifest_callback.clone());
+                        self.tasks.push(task);
+                    } else {
+                        let task = self.reader.read_file(file, upload_callback.clone());
+                        self.tasks.push(task);
+                    }
                 }
 
                 // Load main driver file. After this task has been scheduled and executed, the
                 // driver is ready for use.
-                self.tasks.push(
-                    self.reader
-                        .read_file(main_file, self.link.callback(Action::LoadDriverMainFile)),
-                );
+                if let Some(main_file) = main_file {
+                    self.tasks.push(
+                        self.reader
+                            .read_file(main_file, self.link.callback(Action::LoadDriverMainFile)),
+                    );
+                }
 
-                false
+                true
+            }
+
+            Action::LoadDriverManifest(file_data) => {
+                let path = self
+                    .driver_report
+                    .as_ref()
+                    .and_then(|r| r.manifest.clone())
+                    .unwrap_or(file_data.name.clone());
+                match std::str::from_utf8(&file_data.content)
+                    .map_err(|_| DriverIssue::UnreadableFile(path.clone()))
+                    .and_then(|contents| parse_driver_manifest(&path, contents))
+                {
+                    Ok(manifest) => {
+                        if let Some(report) = self.driver_report.as_mut() {
+                            report.manifest_info = Some(manifest);
+                        }
+                    }
+                    Err(issue) => self.report_driver_issue(issue),
+                }
+                true
             }
 
             Action::UploadDriverMiscFile(file_data) => {
@@ -1059,18 +2030,36 @@ impl Component for Model {
                 //   https://www.tutorialspoint.com/electron/electron_inter_process_communication.htm
                 // And here, for the documentation for the electon_sys Rust bindings for electron.ipcRenderer:
                 //   https://docs.rs/electron-sys/0.4.0/electron_sys/struct.IpcRenderer.html
-
+                let contents = match std::str::from_utf8(&file_data.content) {
+                    Ok(contents) => contents,
+                    Err(_) => {
+                        self.report_driver_issue(DriverIssue::UnreadableFile(file_data.name));
+                        return true;
+                    }
+                };
                 let args: [JsValue; 2] = [
                     JsValue::from_str(file_data.name.deref()),
-                    JsValue::from_str(std::str::from_utf8(&file_data.content).unwrap()),
+                    JsValue::from_str(contents),
                 ];
-                ipc_renderer.send_sync("upload-driver-misc-file", Box::new(args));
+                // the main process checks the file type and size again, and answers with
+                // `false` if it doesn't accept the file
+                let accepted = ipc_renderer.send_sync("upload-driver-misc-file", Box::new(args));
+                if accepted.as_bool() == Some(false) {
+                    self.report_driver_issue(DriverIssue::UploadRejected(file_data.name));
+                    return true;
+                }
                 false
             }
 
             Action::LoadDriverMainFile(main_file_data) => {
                 info! {"Loading Driver: {}", &main_file_data.name};
-                let file_contents = std::str::from_utf8(&main_file_data.content).unwrap();
+                let file_contents = match std::str::from_utf8(&main_file_data.content) {
+                    Ok(contents) => contents,
+                    Err(_) => {
+                        self.report_driver_issue(DriverIssue::UnreadableFile(main_file_data.name));
+                        return true;
+                    }
+                };
                 // dump file contents into script tag and attach to the DOM
                 let script = document().create_element("script").unwrap();
                 script.set_text_content(file_contents);
@@ -1082,6 +2071,66 @@ impl Component for Model {
                 true
             }
 
+            Action::RegisterDriver(driver) => {
+                info! {"Registered Driver: {} ({})", &driver.name, driver.definitions.join(", ")};
+                self.drivers.register(driver);
+                true
+            }
+
+            Action::EvaluateDriver(coord) => {
+                use stdweb::Once;
+                let grammars = self.get_session().grammars.clone();
+                // the value of a grammar is written to the cell on its right, if there's one
+                let output = self
+                    .drivers
+                    .find_root(&grammars, &coord)
+                    .and_then(|(root, _)| root.neighbor_right())
+                    .filter(|c| grammars.contains_key(c))
+                    .unwrap_or(coord.clone());
+                match self.drivers.request(&grammars, &coord, output) {
+                    Ok(request) => {
+                        self.cell_errors.remove(&request.root);
+                        let driver_name = request.driver.clone();
+                        let request_json = serde_json::to_string(&request).unwrap();
+                        let respond = self.link.callback(|response: String| {
+                            match serde_json::from_str::<DriverResponse>(&response) {
+                                Ok(response) => Action::DriverResponse(response),
+                                Err(e) => Action::Alert(
+                                    format! {"[Action::EvaluateDriver] unreadable driver response: {}", e},
+                                ),
+                            }
+                        });
+                        js! {
+                            ISE.evaluate(
+                                @{driver_name},
+                                @{request_json},
+                                @{Once(move |response: String| respond.emit(response))}
+                            );
+                        };
+                    }
+                    Err(e) => {
+                        self.cell_errors.insert(coord, e.to_string());
+                    }
+                }
+                true
+            }
+
+            Action::DriverResponse(response) => {
+                match self.drivers.resolve(response) {
+                    Ok((pending, value)) => {
+                        self.cell_errors.remove(&pending.root);
+                        self.set_cell_value(&pending.output, value_to_string(&value));
+                    }
+                    Err((Some(pending), e)) => {
+                        self.cell_errors.insert(pending.root, e.to_string());
+                    }
+                    Err((None, e)) => {
+                        info! {"[Action::DriverResponse] {}", e};
+                    }
+                }
+                true
+            }
+
             Action::AddNestedGrid(coord, (rows, cols)) => {
                 if self.active_cell.is_none() || self.focus_cell.is_none() {
                     info!("Expect a cell is select");
@@ -1091,8 +2140,7 @@ impl Component for Model {
                 
                 let mut change_active = false;
                 // height and width initial value
-                let mut tmp_heigth = 30.0;
-                let mut tmp_width = 90.0;
+                let (mut tmp_width, mut tmp_heigth) = self.cell_size();
 
                 let current_cell = self.active_cell.clone();
                 set_data_cell(&current_cell.clone().unwrap(), "".to_string());
@@ -1100,6 +2148,7 @@ impl Component for Model {
                 let current_grammar = ref_grammas.get(&current_cell.clone().unwrap()).unwrap();
 
                 let (r, c) = non_zero_u32_tuple((rows, cols));
+                self.share_op(Op::NestGrid(coord.clone(), r, c));
                 // info!("rorws {:?}, cols {:?}", rows.clone(), cols.clone());
                 let mut grammar = Grammar::as_grid(r, c);
                 if let Kind::Grid(sub_coords) = grammar.clone().kind {
@@ -1128,7 +2177,7 @@ impl Component for Model {
                         
                         self.get_session_mut()
                             .grammars
-                            .insert(new_coord.clone(), Grammar::default());
+                            .insert(new_coord.clone(), Grammar::sized(tmp_width, tmp_heigth));
                         if current_grammar.style.col_span.0 == 0
                             && current_grammar.style.row_span.0 == 0
                         {
@@ -1174,19 +2223,85 @@ impl Component for Model {
 
             Action::ZoomIn => {
                 self.zoom += 0.1;
+                self.settings.zoom = self.zoom;
+                self.save_settings();
                 true
             }
             Action::ZoomReset => {
                 self.zoom = 1.0;
+                self.settings.zoom = self.zoom;
+                self.save_settings();
+                true
+            }
+
+            Action::SetViewRoot(coord) => {
+                if coord == self.view_root {
+                    return false;
+                }
+                let is_grid = match self.get_session().grammars.get(&coord).map(|g| &g.kind) {
+                    Some(Kind::Grid(_)) => true,
+                    _ => false,
+                };
+                if !is_grid {
+                    return self.update(Action::Alert(format! {
+                        "cannot zoom into {}, it isn't a grid", coord.to_string()
+                    }));
+                }
+                self.view_history.visit(&self.view_root);
+                self.view_root = coord;
+                true
+            }
+
+            Action::ViewBack => match self.view_history.back(&self.view_root) {
+                Some(coord) => {
+                    self.view_root = coord;
+                    true
+                }
+                None => false,
+            },
+
+            Action::ViewForward => match self.view_history.forward(&self.view_root) {
+                Some(coord) => {
+                    self.view_root = coord;
+                    true
+                }
+                None => false,
+            },
+
+            Action::SwitchTab(index) => {
+                if index == self.current_session_index || index >= self.sessions.len() {
+                    return false;
+                }
+                // every tab comes back to the grid it was zoomed into
+                let view = (
+                    std::mem::replace(&mut self.view_root, coord!("root")),
+                    std::mem::replace(&mut self.view_history, ViewHistory::default()),
+                );
+                self.tab_views.insert(self.current_session_index, view);
+                if let Some((view_root, view_history)) = self.tab_views.remove(&index) {
+                    self.view_root = view_root;
+                    self.view_history = view_history;
+                }
+                self.current_session_index = index;
+                self.first_select_cell = None;
+                self.last_select_cell = None;
+                self.revalidate();
                 true
             }
 
             Action::ZoomOut => {
                 self.zoom -= 0.1;
+                self.settings.zoom = self.zoom;
+                self.save_settings();
                 true
             }
 
             Action::InsertCol => {
+                if let Some(op) = self.shared_line_insert(Line::Col) {
+                    self.share_op(op.clone());
+                    collab::apply(&mut self.get_session_mut().grammars, &op);
+                    return true;
+                }
                 if let Some(coord) = self.active_cell.clone() {
                     // find the bottom-most coord
                     let mut right_most_coord = coord.clone();
@@ -1204,6 +2319,7 @@ impl Component for Model {
                         .map(|c| (c.row(), NonZeroU32::new(c.col().get() + 1).unwrap()));
 
                     let parent = coord.parent().unwrap();
+                    let (width, height) = self.cell_size();
                     if let Some(Grammar {
                         kind: Kind::Grid(sub_coords),
                         name,
@@ -1215,7 +2331,7 @@ impl Component for Model {
                         for c in new_col_coords {
                             grammars.insert(
                                 Coordinate::child_of(&parent.clone(), c),
-                                Grammar::default(),
+                                Grammar::sized(width, height),
                             );
                             new_sub_coords.push(c);
                         }
@@ -1233,6 +2349,11 @@ impl Component for Model {
                 true
             }
             Action::InsertRow => {
+                if let Some(op) = self.shared_line_insert(Line::Row) {
+                    self.share_op(op.clone());
+                    collab::apply(&mut self.get_session_mut().grammars, &op);
+                    return true;
+                }
                 if let Some(coord) = self.active_cell.clone() {
                     // find the bottom-most coord
                     let mut bottom_most_coord = coord.clone();
@@ -1248,6 +2369,7 @@ impl Component for Model {
                         .iter()
                         .map(|c| (NonZeroU32::new(c.row().get() + 1).unwrap(), c.col()));
                     let parent = coord.parent().unwrap();
+                    let (width, height) = self.cell_size();
                     if let Some(Grammar {
                         kind: Kind::Grid(sub_coords),
                         name,
@@ -1260,7 +2382,7 @@ impl Component for Model {
                         for c in new_row_coords {
                             grammars.insert(
                                 Coordinate::child_of(&parent.clone(), c),
-                                Grammar::default(),
+                                Grammar::sized(width, height),
                             );
                             new_sub_coords.push(c);
                         }
@@ -1360,7 +2482,11 @@ impl Component for Model {
                     for (c, g) in temp_grammas.iter() {
                         self.get_session_mut().grammars.insert(c.clone(), g.clone());
                     }
-                               
+
+                    // comments and names move up along with their cells
+                    if let Some(parent) = &focus_coord_parent {
+                        self.line_deleted(parent, Line::Row, focus_coord.row());
+                    }
                 }
                 self.focus_cell = None;
                 true
@@ -1478,7 +2604,11 @@ impl Component for Model {
                     for (c, g) in temp_grammas.iter() {
                         self.get_session_mut().grammars.insert(c.clone(), g.clone());
                     }
-                                       
+
+                    // comments and names move left along with their cells
+                    if let Some(parent) = &focus_coord_parent {
+                        self.line_deleted(parent, Line::Col, focus_coord.col());
+                    }
                 }
                 self.focus_cell = None;  
                 true
@@ -1707,6 +2837,9 @@ impl Component for Model {
 
             Action::ChangeDefaultNestedGrid(row_col) => {
                 self.default_nested_row_cols = row_col;
+                self.settings.nested_rows = row_col.0.get();
+                self.settings.nested_cols = row_col.1.get();
+                self.save_settings();
                 false
             }
 
@@ -1722,7 +2855,9 @@ impl Component for Model {
             }
 
             Action::SetCurrentDefinitionName(name) => {
-                self.default_definition_name = name;
+                self.default_definition_name = name.clone();
+                self.settings.definition_name = name;
+                self.save_settings();
                 false
             }
 
@@ -1740,7 +2875,8 @@ impl Component for Model {
                             ..
                         },
                     ) => {
-                        g.kind = Kind::Editor("".to_string());
+                        g.kind =
+                            Kind::Editor("".to_string(), DEFAULT_EDITOR_MODE.to_string(), None);
                     }
                     _ => {
                         info! { "[Action::NewEditor] cannot create editor from non-Input kind of grammar" }
@@ -1749,47 +2885,974 @@ impl Component for Model {
                 true
             }
 
-            Action::RunPython(__code, output_coord) => {
-                let editor_id = format! {
-                    "codemirror-{}",
-                    self.active_cell.clone().map(|c| c.to_string()).unwrap_or(String::new()),
+            Action::RunPython(code, output_coord) => {
+                // scripts get the values of the sheet as `cells` and the named ranges as
+                // `names`, wrapped in `sheet` by the prelude (see src/sheet.rs)
+                let mut globals = serde_json::Map::new();
+                globals.insert(
+                    "cells".to_string(),
+                    sheet_cells(&self.get_session().grammars).into(),
+                );
+                globals.insert(
+                    "names".to_string(),
+                    self.get_session()
+                        .names
+                        .iter()
+                        .map(|(name, target)| (name.clone(), target_to_string(target).into()))
+                        .collect::<serde_json::Map<_, _>>()
+                        .into(),
+                );
+                let done = self.link.callback(Action::ExecutionFinished);
+                let id = self.executions.start(code, globals, output_coord, done);
+                if self.executions.is_running(id) {
+                    let timeout = TimeoutService::new().spawn(
+                        std::time::Duration::from_millis(self.executions.timeout_ms as u64),
+                        self.link.callback(move |_| Action::ExecutionTimedOut(id)),
+                    );
+                    self.execution_timeouts.insert(id, timeout);
+                }
+                true
+            }
+
+            Action::RunEditor(coord) => {
+                let content = match self.get_session().grammars.get(&coord) {
+                    Some(Grammar {
+                        kind: Kind::Editor(content, _, _),
+                        ..
+                    }) => content.clone(),
+                    _ => {
+                        info! {"[Action::RunEditor] no editor at {}", coord.to_string()};
+                        return false;
+                    }
+                };
+                match self.editor_output(&coord) {
+                    Some(output) => self.update(Action::RunPython(content, output)),
+                    None => {
+                        self.cell_errors
+                            .insert(coord, "there is no cell to write the output to".to_string());
+                        true
+                    }
+                }
+            }
+
+            Action::ChangeEditor(coord, new_content) => {
+                if let Some(Grammar {
+                    kind: Kind::Editor(content, _, _),
+                    ..
+                }) = self.get_session_mut().grammars.get_mut(&coord)
+                {
+                    *content = new_content;
+                }
+                // CodeMirror already shows the new content
+                false
+            }
+
+            Action::SetEditorMode(coord, new_mode) => {
+                if let Some(Grammar {
+                    kind: Kind::Editor(_, mode, _),
+                    ..
+                }) = self.get_session_mut().grammars.get_mut(&coord)
+                {
+                    *mode = new_mode;
+                }
+                true
+            }
+
+            Action::SetEditorOutput(coord, raw_output) => {
+                let new_output = if raw_output.trim().is_empty() {
+                    None
+                } else {
+                    match Coordinate::parse(&raw_output) {
+                        Some(output) => Some(output),
+                        None => {
+                            self.cell_errors.insert(
+                                coord,
+                                format! {"\"{}\" is not a coordinate", raw_output.trim()},
+                            );
+                            return true;
+                        }
+                    }
+                };
+                self.cell_errors.remove(&coord);
+                if let Some(Grammar {
+                    kind: Kind::Editor(_, _, output),
+                    ..
+                }) = self.get_session_mut().grammars.get_mut(&coord)
+                {
+                    *output = new_output;
+                }
+                true
+            }
+
+            Action::UpdateFormatRuleDraft(draft) => {
+                self.format_rule_draft = draft;
+                true
+            }
+
+            Action::AddFormatRule(grid) => {
+                match self.format_rule_draft.to_rule() {
+                    Ok(rule) => {
+                        self.get_session_mut()
+                            .formatting
+                            .entry(grid)
+                            .or_insert_with(Vec::new)
+                            .push(rule);
+                        self.format_rule_draft = FormatRuleDraft::default();
+                    }
+                    Err(e) => {
+                        return self.update(Action::Alert(format! {"cannot add rule: {}", e}));
+                    }
+                }
+                true
+            }
+
+            Action::RemoveFormatRule(grid, index) => {
+                let formatting = &mut self.get_session_mut().formatting;
+                if let Some(rules) = formatting.get_mut(&grid) {
+                    if index < rules.len() {
+                        rules.remove(index);
+                    }
+                    if rules.is_empty() {
+                        formatting.remove(&grid);
+                    }
+                }
+                true
+            }
+
+            Action::SetTheme(name) => match Theme::by_name(&name) {
+                Some(theme) => {
+                    self.get_session_mut().theme = theme;
+                    true
+                }
+                None => self.update(Action::Alert(format! {"no theme named \"{}\"", name})),
+            },
+
+            Action::DefineStyleClass(name) => {
+                let name = name.trim().to_string();
+                let style = self
+                    .active_cell
+                    .as_ref()
+                    .and_then(|c| self.get_session().grammars.get(c))
+                    .map(|g| g.style.clone());
+                match style {
+                    Some(style) if !name.is_empty() => {
+                        self.get_session_mut().style_classes.insert(
+                            name,
+                            StyleOverride {
+                                font_color: Some(style.font_color),
+                                font_weight: Some(style.font_weight),
+                                border_color: Some(style.border_color),
+                            },
+                        );
+                        true
+                    }
+                    _ => false,
+                }
+            }
+
+            Action::RemoveStyleClass(name) => {
+                let session = self.get_session_mut();
+                session.style_classes.remove(&name);
+                // cells keep their own style, they just stop referring to the class
+                for grammar in session.grammars.values_mut() {
+                    if grammar.style.class.as_ref() == Some(&name) {
+                        grammar.style.class = None;
+                    }
+                }
+                true
+            }
+
+            Action::SetStyleClass(coord, class) => {
+                if let Some(grammar) = self.get_session_mut().grammars.get_mut(&coord) {
+                    grammar.style.class = class;
+                }
+                true
+            }
+
+            Action::SetCommentAuthor(author) => {
+                // it's also the name the other sites see this site's cursor with
+                if let Some(collab) = self.collab.as_mut() {
+                    collab.name = author.clone();
+                }
+                self.comment_author = author;
+                true
+            }
+
+            Action::AddComment(coord, text) => {
+                if text.trim().is_empty() {
+                    return false;
+                }
+                let comment = Comment::new(self.comment_author.clone(), now(), text);
+                self.get_session_mut()
+                    .comments
+                    .entry(coord)
+                    .or_insert_with(Vec::new)
+                    .push(comment);
+                true
+            }
+
+            Action::ReplyToComment(coord, index, text) => {
+                if text.trim().is_empty() {
+                    return false;
+                }
+                let reply = Reply {
+                    author: self.comment_author.clone(),
+                    timestamp: now(),
+                    text,
+                };
+                if let Some(comment) = self
+                    .get_session_mut()
+                    .comments
+                    .get_mut(&coord)
+                    .and_then(|thread| thread.get_mut(index))
+                {
+                    comment.replies.push(reply);
+                }
+                true
+            }
+
+            Action::SetCommentResolved(coord, index, resolved) => {
+                if let Some(comment) = self
+                    .get_session_mut()
+                    .comments
+                    .get_mut(&coord)
+                    .and_then(|thread| thread.get_mut(index))
+                {
+                    comment.resolved = resolved;
+                }
+                true
+            }
+
+            Action::RemoveComment(coord, index) => {
+                let comments = &mut self.get_session_mut().comments;
+                if let Some(thread) = comments.get_mut(&coord) {
+                    if index < thread.len() {
+                        thread.remove(index);
+                    }
+                    if thread.is_empty() {
+                        comments.remove(&coord);
+                    }
+                }
+                true
+            }
+
+            Action::UpdateValidationDraft(draft) => {
+                self.validation_draft = draft;
+                true
+            }
+
+            Action::AddValidation() => {
+                let active_cell = match self.active_cell.clone() {
+                    Some(coord) => coord,
+                    None => return false,
                 };
-                // TODO: later, find a way to parse the grammar values into valid python
-                // expressions if that's what the grammars represent.
-                // We could also filter the entire map on that basis, no need to include
-                // irrelevant grammars
-                let string_map: HashMap<String, String> = self
+                match self.validation_draft.to_validation(&active_cell) {
+                    Ok(validation) => {
+                        self.get_session_mut().validations.push(validation);
+                        self.validation_draft = ValidationDraft::default();
+                        self.revalidate();
+                        true
+                    }
+                    Err(e) => self.update(Action::Alert(format! {"cannot add validation: {}", e})),
+                }
+            }
+
+            Action::RemoveValidation(index) => {
+                let validations = &mut self.get_session_mut().validations;
+                if index < validations.len() {
+                    validations.remove(index);
+                }
+                self.revalidate();
+                true
+            }
+
+            Action::ChooseAllowedValue(coord, value) => {
+                self.update(Action::ChangeInput(coord, value));
+                true
+            }
+
+            Action::UpdateNameDraft(draft) => {
+                self.name_draft = draft;
+                false
+            }
+
+            Action::DefineName() => {
+                let name = self.name_draft.name.trim().to_string();
+                let target = validate_name(&self.get_session().names, &name)
+                    .and_then(|_| self.name_draft.to_target(self.selection_target()));
+                match target {
+                    Ok(target) => {
+                        self.get_session_mut().names.insert(name, target);
+                        self.name_draft = NameDraft::default();
+                        self.rebind_lookups();
+                        true
+                    }
+                    Err(e) => self.update(Action::Alert(format! {"cannot define name: {}", e})),
+                }
+            }
+
+            Action::RenameName(old_name, new_name) => {
+                let new_name = new_name.trim().to_string();
+                if new_name == old_name {
+                    return false;
+                }
+                if let Err(e) = validate_name(&self.get_session().names, &new_name) {
+                    return self.update(Action::Alert(format! {"cannot rename: {}", e}));
+                }
+                let session = self.get_session_mut();
+                if let Some(target) = session.names.remove(&old_name) {
+                    session.names.insert(new_name.clone(), target);
+                    // lookups keep using the name under its new name
+                    for grammar in session.grammars.values_mut() {
+                        if let Kind::Lookup(text, binding) = &grammar.kind {
+                            if text.trim() == old_name {
+                                grammar.kind = Kind::Lookup(new_name.clone(), binding.clone());
+                            }
+                        }
+                    }
+                }
+                self.rebind_lookups();
+                true
+            }
+
+            Action::RemoveName(name) => {
+                self.get_session_mut().names.remove(&name);
+                self.rebind_lookups();
+                true
+            }
+
+            Action::GoTo(text) => {
+                let cell = resolve(&self.get_session().names, &text)
+                    .as_ref()
+                    .and_then(target_cell)
+                    .filter(|coord| self.get_session().grammars.contains_key(coord));
+                match cell {
+                    Some(coord) => self.update(Action::SetActiveCell(coord)),
+                    None => self.update(Action::Alert(format! {"cannot go to \"{}\"", text})),
+                }
+            }
+
+            Action::RunCommand(command) => {
+                let action = self.command_action(command);
+                self.update(action)
+            }
+
+            Action::ReloadKeymap => {
+                self.load_keymap();
+                true
+            }
+
+            Action::SetSetting(setting, value) => {
+                match self.settings.set(setting, &value) {
+                    Ok(()) => {
+                        self.settings_issues.clear();
+                        self.apply_settings();
+                        self.save_settings();
+                    }
+                    Err(e) => self.settings_issues = vec![e],
+                }
+                true
+            }
+
+            Action::ResetSettings => {
+                if !confirm("Reset every setting to its default?".to_string()) {
+                    return false;
+                }
+                self.settings = Settings::default();
+                self.settings_issues.clear();
+                self.apply_settings();
+                self.save_settings();
+                true
+            }
+
+            Action::ChooseWorkspace => {
+                let args: [JsValue; 0] = [];
+                let workspace = ipc_renderer.send_sync("choose-workspace", Box::new(args));
+                if let Some(workspace) = workspace.as_string() {
+                    self.explorer.workspace = Some(workspace);
+                    self.explorer_error = None;
+                    self.save_explorer();
+                    self.refresh_session_files();
+                }
+                true
+            }
+
+            Action::RefreshSessionFiles => {
+                self.explorer_error = None;
+                self.refresh_session_files();
+                true
+            }
+
+            Action::OpenSessionFile(path, new_tab) => {
+                let args: [JsValue; 1] = [JsValue::from_str(&path)];
+                let contents = ipc_renderer.send_sync("read-session-file", Box::new(args));
+                // files git couldn't merge are opened as a merge of their sides
+                if let Some(sides) = contents.as_string().and_then(|c| split_conflict_markers(&c)) {
+                    match self.merge_conflicted(&path, sides) {
+                        Ok(merge) => {
+                            self.explorer_error = None;
+                            self.open_merge(merge);
+                        }
+                        Err(e) => self.explorer_error = Some(e),
+                    }
+                    return true;
+                }
+                let session = match contents.as_string() {
+                    Some(contents) => serde_json::from_str::<Session>(&contents)
+                        .map_err(|e| format! {"{} isn't a session: {}", path, e}),
+                    None => Err(format! {"cannot read {}", path}),
+                };
+                let session = match session {
+                    Ok(session) => session,
+                    Err(e) => {
+                        // files that are gone aren't recent anymore
+                        if contents.is_null() {
+                            self.explorer.moved(&path, None);
+                            self.save_explorer();
+                        }
+                        self.explorer_error = Some(e);
+                        return true;
+                    }
+                };
+                self.explorer_error = None;
+                self.explorer.opened(&path);
+                self.save_explorer();
+                if new_tab {
+                    self.sessions.push(session);
+                    self.update(Action::SwitchTab(self.sessions.len() - 1));
+                } else {
+                    self.get_session_mut().title = session.title.clone();
+                    self.load_session(session);
+                }
+                true
+            }
+
+            Action::RenameSessionFile(path, name) => {
+                let name = match file_name(&name) {
+                    Ok(name) => name,
+                    Err(e) => {
+                        self.explorer_error = Some(e);
+                        return true;
+                    }
+                };
+                let args: [JsValue; 2] = [JsValue::from_str(&path), JsValue::from_str(&name)];
+                let renamed = ipc_renderer.send_sync("rename-session-file", Box::new(args));
+                if renamed.as_bool() == Some(true) {
+                    let new_path = format! {"{}{}", &path[..path.len() - path_name(&path).len()], name};
+                    self.explorer.moved(&path, Some(new_path));
+                    self.save_explorer();
+                    self.explorer_error = None;
+                } else {
+                    self.explorer_error = Some(format! {"cannot rename {} to {}", path, name});
+                }
+                self.refresh_session_files();
+                true
+            }
+
+            Action::DuplicateSessionFile(path) => {
+                let existing: Vec<String> = self.session_files.iter().map(|f| f.name.clone()).collect();
+                let name = duplicate_name(path_name(&path), &existing);
+                let args: [JsValue; 2] = [JsValue::from_str(&path), JsValue::from_str(&name)];
+                let duplicated = ipc_renderer.send_sync("duplicate-session-file", Box::new(args));
+                self.explorer_error = if duplicated.as_bool() == Some(true) {
+                    None
+                } else {
+                    Some(format! {"cannot duplicate {}", path})
+                };
+                self.refresh_session_files();
+                true
+            }
+
+            Action::DeleteSessionFile(path) => {
+                if !confirm(format! {"Delete {}? This can't be undone.", path}) {
+                    return false;
+                }
+                let args: [JsValue; 1] = [JsValue::from_str(&path)];
+                let deleted = ipc_renderer.send_sync("delete-session-file", Box::new(args));
+                if deleted.as_bool() == Some(true) {
+                    self.explorer.moved(&path, None);
+                    self.save_explorer();
+                    self.explorer_error = None;
+                } else {
+                    self.explorer_error = Some(format! {"cannot delete {}", path});
+                }
+                self.refresh_session_files();
+                true
+            }
+
+            Action::DiffSessionFile(path) => {
+                match self.read_session_file(&path) {
+                    Ok(session) => {
+                        self.explorer_error = None;
+                        self.shown_diff = Some(ShownDiff {
+                            tab: self.current_session_index,
+                            diff: diff_sessions(&session, self.get_session()),
+                            against: path,
+                        });
+                    }
+                    Err(e) => self.explorer_error = Some(e),
+                }
+                true
+            }
+
+            Action::ClearDiff => {
+                self.shown_diff = None;
+                true
+            }
+
+            Action::SetMergeBase(path) => {
+                self.merge_base = path;
+                true
+            }
+
+            Action::MergeSessionFile(path) => {
+                let theirs = match self.read_session_file(&path) {
+                    Ok(session) => session,
+                    Err(e) => {
+                        self.explorer_error = Some(e);
+                        return true;
+                    }
+                };
+                let ours = self.to_session();
+                let base = match self.merge_base.clone() {
+                    Some(base) => match self.read_session_file(&base) {
+                        Ok(session) => session,
+                        Err(e) => {
+                            self.explorer_error = Some(e);
+                            return true;
+                        }
+                    },
+                    None => empty_base(&theirs),
+                };
+                self.explorer_error = None;
+                let mut merge = merge_sessions(&base, &ours, &theirs);
+                merge.session.title = format! {"{} (merged with {})", ours.title, path_name(&path)};
+                self.open_merge(merge);
+                true
+            }
+
+            Action::ResolveConflict(index, side) => {
+                if let Some(merge) = &mut self.merge_conflicts {
+                    if index < merge.conflicts.len() {
+                        let conflict = merge.conflicts.remove(index);
+                        conflict.resolve(&mut self.sessions[merge.tab].grammars, side);
+                    }
+                }
+                true
+            }
+
+            Action::StartRelay => {
+                let args: [JsValue; 0] = [];
+                match ipc_renderer.send_sync("start-relay", Box::new(args)).as_string() {
+                    Some(url) => {
+                        self.relay_url = url;
+                        self.update(Action::ConnectRelay)
+                    }
+                    None => self.update(Action::Alert("cannot start the relay".to_string())),
+                }
+            }
+
+            Action::SetRelayUrl(url) => {
+                self.relay_url = url.trim().to_string();
+                true
+            }
+
+            Action::ConnectRelay => {
+                self.collab_task = None;
+                let callback = self.link.callback(|text: Text| match text {
+                    Ok(json) => Action::RelayMessage(json),
+                    Err(_) => Action::Noop,
+                });
+                let notification = self.link.callback(|status: WebSocketStatus| match status {
+                    WebSocketStatus::Opened => Action::Noop,
+                    WebSocketStatus::Closed => Action::RelayClosed(false),
+                    WebSocketStatus::Error => Action::RelayClosed(true),
+                });
+                let task = WebSocketService::new()
+                    .connect_text(&self.relay_url, callback, notification)
+                    .map_err(|e| e.to_string());
+                match task {
+                    Ok(task) => {
+                        self.collab_task = Some(task);
+                        self.collab = Some(Collab::new(
+                            self.relay_url.clone(),
+                            self.comment_author.clone(),
+                            self.current_session_index,
+                            &self.get_session().grammars,
+                        ));
+                        true
+                    }
+                    Err(e) => self.update(Action::Alert(format! {
+                        "cannot connect to the relay {}: {}", self.relay_url, e
+                    })),
+                }
+            }
+
+            Action::DisconnectRelay => {
+                self.collab = None;
+                self.collab_task = None;
+                true
+            }
+
+            Action::ShareSession => {
+                let message = match self.collab.as_mut() {
+                    Some(collab) if collab.is_connected() => {
+                        let tab = collab.tab;
+                        collab.share(&self.sessions[tab])
+                    }
+                    _ => return false,
+                };
+                self.send_to_relay(vec![message]);
+                true
+            }
+
+            Action::RelayMessage(json) => {
+                let message = match serde_json::from_str::<RelayMessage>(&json) {
+                    Ok(message) => message,
+                    Err(e) => {
+                        return self.update(Action::Alert(format! {
+                            "cannot read the message of the relay: {}", e
+                        }))
+                    }
+                };
+                // the edits made so far are sent before the ones of other sites are applied
+                self.share_edits();
+                let collab = match self.collab.as_mut() {
+                    Some(collab) => collab,
+                    None => return false,
+                };
+                let tab = collab.tab;
+                match collab.receive(message) {
+                    Received::Session(session) if tab == self.current_session_index => {
+                        let title = session.title.clone();
+                        self.load_session(*session);
+                        self.get_session_mut().title = title;
+                    }
+                    Received::Session(session) => self.sessions[tab] = *session,
+                    Received::Cells(cells) => {
+                        self.sessions[tab].grammars = cells;
+                        if tab == self.current_session_index {
+                            self.revalidate();
+                        }
+                    }
+                    Received::Presence => (),
+                    Received::Nothing => return false,
+                }
+                true
+            }
+
+            Action::RelayClosed(error) => {
+                let url = match self.collab.take() {
+                    Some(collab) => collab.url,
+                    None => return false,
+                };
+                self.collab_task = None;
+                let message = if error {
+                    format! {"cannot connect to the relay {}", url}
+                } else {
+                    format! {"disconnected from the relay {}", url}
+                };
+                self.update(Action::Alert(message))
+            }
+
+            Action::OpenPalette => {
+                self.palette = Some(Palette::default());
+                js! {
+                    setTimeout(() => {
+                        let input = document.getElementById("command-palette-input");
+                        if (input) {
+                            input.focus();
+                        }
+                    }, 0);
+                };
+                true
+            }
+
+            Action::ClosePalette => {
+                self.palette = None;
+                if let Some(coord) = self.active_cell.clone() {
+                    focus_on_cell(&coord);
+                }
+                true
+            }
+
+            Action::SetPaletteQuery(query) => {
+                if let Some(palette) = self.palette.as_mut() {
+                    palette.query = query;
+                    palette.selected = 0;
+                }
+                true
+            }
+
+            Action::MovePaletteSelection(delta) => {
+                let count = self.palette_entries().len() as i32;
+                if let Some(palette) = self.palette.as_mut() {
+                    if count > 0 {
+                        palette.selected = (palette.selected as i32 + delta).rem_euclid(count) as usize;
+                    }
+                }
+                true
+            }
+
+            Action::RunPaletteCommand(command) => {
+                if !self.palette_command_enabled(&command) {
+                    return self.update(Action::Alert(format! {
+                        "\"{}\" can't run with the current selection", command.title()
+                    }));
+                }
+                self.palette = None;
+                let action = self.palette_action(command);
+                self.update(action);
+                true
+            }
+
+            Action::SetCellFormat(coord, format) => {
+                if let Some(grammar) = self.get_session_mut().grammars.get_mut(&coord) {
+                    grammar.style.format = format;
+                }
+                true
+            }
+
+            Action::SetCellFormatSetting(coord, setting) => {
+                let format = self
                     .get_session()
                     .grammars
-                    .clone()
-                    .into_iter()
-                    .map(|(k, v)| (k.to_string(), serde_json::to_string(&v).unwrap()))
-                    .collect();
-                let grammars = stdweb::Object::try_from(string_map).expect(
-                    "[Action:RunPython] Grammar Map can be serialized into Javascript Object",
+                    .get(&coord)
+                    .and_then(|g| g.style.format.clone());
+                match format.map(|f| f.with_setting(&setting)) {
+                    Some(Ok(format)) => self.update(Action::SetCellFormat(coord, Some(format))),
+                    Some(Err(e)) => self.update(Action::Alert(format! {"cannot set format: {}", e})),
+                    None => false,
+                }
+            }
+
+            Action::ExportCSV(grid) => {
+                use node_sys::fs as node_fs;
+                let csv = match grid_csv(&self.get_session().grammars, &grid) {
+                    Some(csv) => csv,
+                    None => {
+                        return self.update(Action::Alert(format! {
+                            "cannot export {} as CSV, it's not a grid", grid.to_string()
+                        }))
+                    }
+                };
+                let filename = format! {"{}-{}.csv", self.get_session().title, grid.to_string()};
+                node_fs::write_file_sync(
+                    &JsValue::from_str(&filename),
+                    &JsValue::from_str(&csv),
+                    None,
                 );
-                let return_value: String = js! {
-                    let editorEl = document.getElementById(@{editor_id.clone()});
-                    let code = editorEl.value;
-                    pyodide.globals.grammars = @{grammars};
-                    return pyodide.runPython(code);
-                }
-                .try_into()
-                .unwrap();
-                if let Some(
-                    g
-                    @
-                    Grammar {
-                        kind: Kind::Input(_),
-                        ..
-                    },
-                ) = self.get_session_mut().grammars.get_mut(&output_coord)
-                {
-                    g.kind = Kind::Input(return_value);
+                false
+            }
+
+            Action::CopyMarkdown(grid, nested) => {
+                match grid_markdown(&self.get_session().grammars, &grid, nested) {
+                    Some(markdown) => {
+                        js! { navigator.clipboard.writeText(@{markdown}); };
+                        false
+                    }
+                    None => self.update(Action::Alert(format! {
+                        "cannot copy {} as Markdown, it's not a grid", grid.to_string()
+                    })),
                 }
+            }
+
+            Action::PasteMarkdown(entry) => {
+                let coord = match self.active_cell.clone() {
+                    Some(coord) => coord,
+                    None => {
+                        return self.update(Action::Alert(
+                            "select a cell to paste the table in".to_string(),
+                        ))
+                    }
+                };
+                self.build_in_cell(&coord, entry);
+                true
+            }
 
+            Action::ImportJSON => {
+                let coord = match self.active_cell.clone() {
+                    Some(coord) => coord,
+                    None => {
+                        return self.update(Action::Alert(
+                            "select a cell to import the JSON document in".to_string(),
+                        ))
+                    }
+                };
+                let args: [JsValue; 0] = [];
+                let contents = ipc_renderer.send_sync("open-json-file", Box::new(args));
+                if contents.as_bool() == Some(false) {
+                    return self.update(Action::Alert("cannot read the JSON file".to_string()));
+                }
+                let contents = match contents.as_string() {
+                    Some(contents) => contents,
+                    // no file was chosen
+                    None => return false,
+                };
+                match parse_json(&contents) {
+                    Ok(entry) => {
+                        self.build_in_cell(&coord, entry);
+                        true
+                    }
+                    Err(e) => self.update(Action::Alert(e)),
+                }
+            }
+
+            Action::ExportJSON(coord) => {
+                let session = self.get_session();
+                let json = serde_json::to_string_pretty(&grid_json(&session.grammars, &coord))
+                    .unwrap_or_default();
+                let filename = format! {"{}-{}.json", session.title, coord.to_string()};
+                let args: [JsValue; 2] = [JsValue::from_str(&filename), JsValue::from_str(&json)];
+                let saved = ipc_renderer.send_sync("save-json-file", Box::new(args));
+                if saved.as_bool() == Some(false) {
+                    return self.update(Action::Alert(format! {"cannot write {}", filename}));
+                }
                 false
             }
+
+            Action::OpenWorkbook => {
+                let args: [JsValue; 0] = [];
+                let file = ipc_renderer.send_sync("open-workbook-file", Box::new(args));
+                if file.as_bool() == Some(false) {
+                    return self.update(Action::Alert("cannot read the workbook".to_string()));
+                }
+                let file = match file.as_string() {
+                    Some(file) => file,
+                    // no file was chosen
+                    None => return false,
+                };
+                let workbook = serde_json::from_str::<WorkbookFile>(&file)
+                    .map_err(|e| format! {"cannot read the workbook: {}", e})
+                    .and_then(|file| Ok((file.stem().to_string(), file.workbook()?)));
+                let (stem, workbook) = match workbook {
+                    Ok(workbook) => workbook,
+                    Err(e) => return self.update(Action::Alert(e)),
+                };
+                // sheets become sessions of their own, with the meta grid and the look of
+                // the current session
+                let first_tab = self.sessions.len();
+                for sheet in workbook.sheets.iter() {
+                    let entry = match sheet_entry(sheet) {
+                        Ok(entry) => entry,
+                        Err(e) => {
+                            self.update(Action::Alert(e));
+                            continue;
+                        }
+                    };
+                    let mut session = self.to_session();
+                    session.title = format! {"{} - {}", stem, sheet.name};
+                    let root = coord!("root");
+                    session
+                        .grammars
+                        .retain(|c, _| !c.row_cols.starts_with(&root.row_cols));
+                    build_grammar_map(&mut session.grammars, root, entry);
+                    session.formatting.clear();
+                    session.validations.clear();
+                    session.comments.clear();
+                    session.names.clear();
+                    self.sessions.push(session);
+                }
+                if self.sessions.len() > first_tab {
+                    self.update(Action::SwitchTab(first_tab));
+                }
+                true
+            }
+
+            Action::ExportWorkbook => {
+                let session = self.get_session();
+                let workbook = grid_workbook(&session.grammars, &coord!("root"));
+                let data = match write_xlsx(&workbook) {
+                    Ok(bytes) => base64::encode(&bytes),
+                    Err(e) => return self.update(Action::Alert(e)),
+                };
+                let filename = format! {"{}.xlsx", session.title};
+                let args: [JsValue; 2] = [JsValue::from_str(&filename), JsValue::from_str(&data)];
+                let saved = ipc_renderer.send_sync("save-workbook-file", Box::new(args));
+                if saved.as_bool() == Some(false) {
+                    return self.update(Action::Alert(format! {"cannot write {}", filename}));
+                }
+                false
+            }
+
+            Action::ExportHTML(coord) => {
+                use node_sys::fs as node_fs;
+                let session = self.get_session();
+                let html = export_html(
+                    &session.grammars,
+                    &coord,
+                    &self.col_widths,
+                    &self.row_heights,
+                    &session.theme,
+                    &session.title,
+                    &|c| self.style_context(c),
+                );
+                let html = match html {
+                    Some(html) => html,
+                    None => {
+                        return self.update(Action::Alert(format! {
+                            "cannot export {} as HTML, there's no such cell", coord.to_string()
+                        }))
+                    }
+                };
+                let filename = format! {"{}-{}.html", session.title, coord.to_string()};
+                node_fs::write_file_sync(
+                    &JsValue::from_str(&filename),
+                    &JsValue::from_str(&html),
+                    None,
+                );
+                false
+            }
+
+            Action::ExecutionFinished(result) => {
+                self.execution_timeouts.remove(&result.id);
+                match self.executions.finish(result) {
+                    Some((pending, ExecutionResult { outcome, .. })) => match outcome {
+                        Ok(value) => {
+                            self.cell_errors.remove(&pending.output);
+                            if let Err(e) =
+                                spill(&mut self.get_session_mut().grammars, &pending.output, &value)
+                            {
+                                self.cell_errors.insert(pending.output, e.to_string());
+                            }
+                        }
+                        Err(e) => {
+                            self.cell_errors.insert(pending.output, e.to_string());
+                        }
+                    },
+                    None => {
+                        info! {"[Action::ExecutionFinished] ignoring result of an execution that was stopped"};
+                    }
+                }
+                true
+            }
+
+            Action::ExecutionTimedOut(id) => {
+                self.execution_timeouts.remove(&id);
+                if let Some((pending, result)) = self.executions.time_out(id) {
+                    if let Err(e) = result.outcome {
+                        self.cell_errors.insert(pending.output, e.to_string());
+                    }
+                }
+                true
+            }
+
+            Action::CancelExecution(id) => {
+                self.execution_timeouts.remove(&id);
+                if let Some((pending, _)) = self.executions.cancel(id) {
+                    // a cancelled execution leaves its output as it was
+                    self.cell_errors.remove(&pending.output);
+                }
+                true
+            }
+
+            Action::SetExecutionBackend(kind) => {
+                if self.executions.backend.kind() != kind {
+                    self.execution_timeouts.clear();
+                    self.executions.set_backend(new_backend(kind));
+                }
+                true
+            }
         };
 
         self.meta_suggestions = self
@@ -1809,6 +3872,8 @@ impl Component for Model {
             })
             .collect();
 
+        self.share_edits();
+
         should_render
     }
 
@@ -1822,49 +3887,55 @@ impl Component for Model {
             CursorType::EW => "ew-resize",
             CursorType::Default => "default",
         }};
-        let (default_row, default_col) = {
-            let (r, c) = self.default_nested_row_cols.clone();
-            (r.get(), c.get())
-        };
-        let active_cell = self.active_cell.clone().expect("active_cell should be set");
+        let keymap = self.keymap.clone();
+        let theme = &self.get_session().theme;
         html! {
             <div
+            class=format!{"theme-{}", theme.name}
+            style=theme.css_variables()
             onclick=self.link.callback(move |e: ClickEvent| {
                 Action::HideContextMenu
             })>
                 { view_file_popup(&self) }
 
+                { view_command_palette(&self) }
+
                 { view_side_nav(&self) }
 
                 { view_menu_bar(&self) }
 
                 { view_tab_bar(&self) }
+
+                { view_diff_styles(&self) }
+                { view_presence_styles(&self) }
                 <div class="main">
 
                     <div id="grammars" class="grid-wrapper" style={zoom}
-                        // Global Keyboard shortcuts
-                        onkeypress=self.link.callback(move |e : KeyPressEvent| {
-                            let keys = key_combination(&e);
-                            match keys.deref() {
-                                // Tab (navigation) is handled in onkeydown
-                                "Ctrl-g" => {
-                                    Action::AddNestedGrid(active_cell.clone(), (default_row, default_col))
-                                }
-                                _ => Action::Noop
-                            }
-                        })
                         // context menu
                         oncontextmenu=self.link.callback(move |e: ContextMenuEvent| {
                             e.prevent_default();
                             Action::ShowContextMenu((e.client_x() as f64, e.client_y() as f64))
                         })
-                        // Global Key togridles
+                        // Global Key togridles and keyboard shortcuts (see src/keymap.rs),
+                        // Tab is handled by the cells
                         onkeydown=self.link.callback(move |e: KeyDownEvent| {
                             if e.key() == "Shift" {
-                                Action::TogridleShiftKey(true)
-                            } else {
-                                Action::Noop
-
+                                return Action::TogridleShiftKey(true);
+                            }
+                            match keymap.command(&key_combination(&e)) {
+                                // editors and lookup suggestions keep their own arrows and Enter
+                                Some(Command::Navigate(_, _)) if !from_cell_data(&e) => Action::Noop,
+                                // the caret moves within the text until it reaches its end
+                                Some(Command::Navigate(movement, _))
+                                    if movement.moves_caret() && !caret_at_edge(movement.towards_start()) =>
+                                {
+                                    Action::Noop
+                                }
+                                Some(command) => {
+                                    e.prevent_default();
+                                    Action::RunCommand(command)
+                                }
+                                None => Action::Noop,
                             }
                         })
                         onkeyup=self.link.callback(move |e: KeyUpEvent| {
@@ -1896,7 +3967,8 @@ impl Component for Model {
                         /*onclick=self.link.callback(move |e: ClickEvent| {
                             Action::HideContextMenu
                         })*/>
-                        { view_grammar(&self, coord!{"root"}) }
+                        { view_breadcrumbs(&self) }
+                        { view_grammar(&self, self.shown_view_root()) }
                         { view_context_menu(&self) }
                     </div>
                 </div>
@@ -1932,6 +4004,75 @@ fn focus_on_cell(c: &Coordinate) {
     };
 }
 
+// the current time as an ISO 8601 string
+fn now() -> String {
+    let now = js! { return new Date().toISOString(); };
+    now.try_into().unwrap_or_default()
+}
+
+// whether a key was pressed in the editable text of a cell, rather than in an editor or a
+// suggestion nested in the cell
+fn from_cell_data(e: &KeyDownEvent) -> bool {
+    let target = match e.target() {
+        Some(target) => target,
+        None => return false,
+    };
+    let is_cell_data = js! {
+        let target = @{target};
+        return target.isContentEditable === true && target.classList.contains("cell-data");
+    };
+    is_cell_data.try_into().unwrap_or(false)
+}
+
+// whether the caret is at the start (or end) of the text of the focused cell, with nothing
+// selected
+fn caret_at_edge(start: bool) -> bool {
+    let at_edge = js! {
+        let selection = window.getSelection();
+        if (!selection || !selection.isCollapsed) {
+            return selection === null;
+        }
+        let text = selection.focusNode ? selection.focusNode.textContent || "" : "";
+        return @{start} ? selection.focusOffset === 0 : selection.focusOffset >= text.length;
+    };
+    at_edge.try_into().unwrap_or(true)
+}
+
+// the contents of a file in the user config directory, `None` if there's none (see
+// 'read-config-file' in static/main.js)
+fn read_config_file(name: &str) -> Option<String> {
+    let args: [JsValue; 1] = [JsValue::from_str(name)];
+    ipc_renderer
+        .send_sync("read-config-file", Box::new(args))
+        .as_string()
+}
+
+// writes a file in the user config directory, `false` if it couldn't be written
+fn write_config_file(name: &str, contents: &str) -> bool {
+    let args: [JsValue; 2] = [JsValue::from_str(name), JsValue::from_str(contents)];
+    ipc_renderer
+        .send_sync("write-config-file", Box::new(args))
+        .as_bool()
+        .unwrap_or(false)
+}
+
+// asks the user to confirm `message` before going on
+fn confirm(message: String) -> bool {
+    let confirmed = js! { return confirm(@{message}); };
+    confirmed.try_into().unwrap_or(false)
+}
+
+// sets the text of the cell's editable element without going through the virtual DOM
+fn restore_data_cell(c: &Coordinate, value: String) {
+    let cell_id = format! {"cell-{}", c.to_string()};
+    js! {
+        let cell = document.getElementById(@{cell_id});
+        if (cell && cell.firstChild) {
+            cell.firstChild.innerText = @{value};
+        }
+    };
+}
+
 fn set_data_cell(c: &Coordinate, value: String) {
     let cell_id = format! {"cell-{}", c.clone().to_string()}; 
     js! {
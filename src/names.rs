@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::num::NonZeroU32;
+
+use crate::coordinate::{Coordinate, Line};
+use crate::grammar::Lookup;
+
+// NAMED RANGES
+// Names are session-wide aliases for a cell (`Lookup::Cell`) or a rectangular range of
+// cells in one grid (`Lookup::Range`), stored in `Session::names`. A name can be used
+// wherever a coordinate is typed in: lookup cells, the go-to box and the `sheet` object of
+// python scripts (there are no formulas yet, they should resolve through `resolve` too).
+// Lookup cells keep the text that was typed in, so when a name is re-targeted or renamed
+// the lookups using it are re-bound (see `Model::rebind_lookups`), and when a row or column
+// is deleted the targets move along with their cells (see `delete_line`).
+
+pub type Names = BTreeMap<String, Lookup>;
+
+// checks that `name` can be used as a new name: it must look like an identifier and
+// mustn't be mistaken for a coordinate (e.g. "A1", "root" or "B12")
+pub fn validate_name(names: &Names, name: &str) -> Result<(), String> {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => (),
+        Some(_) => return Err(format! {"name \"{}\" must start with a letter or _", name}),
+        None => return Err("name can't be empty".to_string()),
+    }
+    if !chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format! {"name \"{}\" can only contain letters, digits and _", name});
+    }
+    if looks_like_coordinate(name) {
+        return Err(format! {"name \"{}\" looks like a coordinate", name});
+    }
+    if names.contains_key(name) {
+        return Err(format! {"name \"{}\" is already defined", name});
+    }
+    Ok(())
+}
+
+fn looks_like_coordinate(name: &str) -> bool {
+    let letters = name.trim_start_matches(|c: char| c.is_ascii_uppercase());
+    name == "root"
+        || name == "meta"
+        || Coordinate::parse(name).is_some()
+        || (letters.len() < name.len()
+            && !letters.is_empty()
+            && letters.chars().all(|c| c.is_ascii_digit()))
+}
+
+// parses a cell coordinate ("root-A1") or a range of cells in the same grid
+// ("root-A1:root-B3") into a lookup
+pub fn parse_target(text: &str) -> Option<Lookup> {
+    let text = text.trim();
+    match text.find(':') {
+        None => Coordinate::parse(text).map(Lookup::Cell),
+        Some(i) => {
+            let start = Coordinate::parse(text[..i].trim())?;
+            let end = Coordinate::parse(text[i + 1..].trim())?;
+            let parent = start.parent()?;
+            if end.parent().as_ref() != Some(&parent) {
+                return None;
+            }
+            let (start_row, start_col) = start.row_col();
+            let (end_row, end_col) = end.row_col();
+            Some(Lookup::Range {
+                parent,
+                start: (start_row.min(end_row), start_col.min(end_col)),
+                end: (start_row.max(end_row), start_col.max(end_col)),
+            })
+        }
+    }
+}
+
+// the lookup `text` refers to, whether it's a name or a coordinate
+pub fn resolve(names: &Names, text: &str) -> Option<Lookup> {
+    names
+        .get(text.trim())
+        .cloned()
+        .or_else(|| parse_target(text))
+}
+
+// the inverse of `parse_target`
+pub fn target_to_string(target: &Lookup) -> String {
+    match target {
+        Lookup::Cell(coord) => coord.to_string(),
+        Lookup::Range { parent, start, end } => format! {
+            "{}:{}",
+            Coordinate::child_of(parent, *start).to_string(),
+            Coordinate::child_of(parent, *end).to_string()
+        },
+        Lookup::Row(row) => {
+            Coordinate::child_of(&row.0, (row.1, NonZeroU32::new(1).unwrap())).row_to_string()
+        }
+        Lookup::Col(col) => {
+            Coordinate::child_of(&col.0, (NonZeroU32::new(1).unwrap(), col.1)).col_to_string()
+        }
+    }
+}
+
+// the top-left cell of the target, which is where go-to takes you
+pub fn target_cell(target: &Lookup) -> Option<Coordinate> {
+    match target {
+        Lookup::Cell(coord) => Some(coord.clone()),
+        Lookup::Range { parent, start, .. } => Some(Coordinate::child_of(parent, *start)),
+        _ => None,
+    }
+}
+
+// where the `target` ends up once row/column `index` of the grid at `parent` is deleted,
+// `None` if nothing is left of it
+pub fn target_after_line_deleted(
+    target: &Lookup,
+    parent: &Coordinate,
+    line: Line,
+    index: NonZeroU32,
+) -> Option<Lookup> {
+    match target {
+        Lookup::Cell(coord) => coord
+            .after_line_deleted(parent, line, index)
+            .map(Lookup::Cell),
+        Lookup::Range {
+            parent: range_parent,
+            start,
+            end,
+        } => {
+            if range_parent != parent {
+                let range_parent = range_parent.after_line_deleted(parent, line, index)?;
+                return Some(Lookup::Range {
+                    parent: range_parent,
+                    start: *start,
+                    end: *end,
+                });
+            }
+            let (mut start, mut end) = (*start, *end);
+            let (first, last) = match line {
+                Line::Row => (&mut start.0, &mut end.0),
+                Line::Col => (&mut start.1, &mut end.1),
+            };
+            if index < *first {
+                *first = NonZeroU32::new(first.get() - 1).unwrap();
+            }
+            if index <= *last {
+                if *first == *last && index == *last {
+                    return None;
+                }
+                *last = NonZeroU32::new(last.get() - 1).unwrap();
+            }
+            Some(Lookup::Range {
+                parent: parent.clone(),
+                start,
+                end,
+            })
+        }
+        _ => Some(target.clone()),
+    }
+}
+
+// moves the name targets along with their cells when a row or column is deleted,
+// dropping the names whose cells were all deleted
+pub fn delete_line(names: &mut Names, parent: &Coordinate, line: Line, index: NonZeroU32) {
+    let old_names = std::mem::take(names);
+    for (name, target) in old_names {
+        if let Some(target) = target_after_line_deleted(&target, parent, line, index) {
+            names.insert(name, target);
+        }
+    }
+}
+
+// the state of the "define name" form, the target defaults to the selection
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NameDraft {
+    pub name: String,
+    pub target: String,
+}
+
+impl NameDraft {
+    pub fn to_target(&self, selection: Option<Lookup>) -> Result<Lookup, String> {
+        if self.target.trim().is_empty() {
+            return selection.ok_or_else(|| "select the cells to name first".to_string());
+        }
+        parse_target(&self.target)
+            .ok_or_else(|| format! {"\"{}\" isn't a cell or a range of cells", self.target})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coord;
+    use crate::coordinate::{CoordinateParser, Rule};
+    use crate::util::non_zero_u32_tuple;
+    use pest::Parser;
+
+    fn range(parent: &str, start: (u32, u32), end: (u32, u32)) -> Lookup {
+        Lookup::Range {
+            parent: coord!(parent),
+            start: non_zero_u32_tuple(start),
+            end: non_zero_u32_tuple(end),
+        }
+    }
+
+    #[test]
+    fn test_validate_name() {
+        let mut names = Names::new();
+        names.insert("total".to_string(), Lookup::Cell(coord!("root-A1")));
+        assert!(validate_name(&names, "tax_rate").is_ok());
+        assert!(validate_name(&names, "_x2").is_ok());
+        assert!(validate_name(&names, "total").is_err());
+        assert!(validate_name(&names, "").is_err());
+        assert!(validate_name(&names, "2x").is_err());
+        assert!(validate_name(&names, "tax rate").is_err());
+        assert!(validate_name(&names, "A1").is_err());
+        assert!(validate_name(&names, "AB12").is_err());
+        assert!(validate_name(&names, "root").is_err());
+    }
+
+    #[test]
+    fn test_parse_target() {
+        assert_eq!(
+            parse_target("root-A1"),
+            Some(Lookup::Cell(coord!("root-A1")))
+        );
+        assert_eq!(
+            parse_target("root-B3:root-A1"),
+            Some(range("root", (1, 1), (3, 2)))
+        );
+        assert_eq!(parse_target("root-A1:meta-A2"), None);
+        assert_eq!(parse_target("total"), None);
+        let target = range("root-A1", (1, 1), (2, 2));
+        assert_eq!(parse_target(&target_to_string(&target)), Some(target));
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut names = Names::new();
+        names.insert("total".to_string(), Lookup::Cell(coord!("root-C1")));
+        assert_eq!(
+            resolve(&names, "total"),
+            Some(Lookup::Cell(coord!("root-C1")))
+        );
+        assert_eq!(
+            resolve(&names, "root-A2"),
+            Some(Lookup::Cell(coord!("root-A2")))
+        );
+        assert_eq!(resolve(&names, "missing"), None);
+    }
+
+    #[test]
+    fn test_delete_line() {
+        let mut names = Names::new();
+        names.insert("cell".to_string(), Lookup::Cell(coord!("root-A3")));
+        names.insert("gone".to_string(), Lookup::Cell(coord!("root-A2")));
+        names.insert("rows".to_string(), range("root", (1, 1), (3, 2)));
+        names.insert("after".to_string(), range("root", (3, 1), (4, 1)));
+        names.insert("single".to_string(), range("root", (2, 1), (2, 2)));
+        names.insert("nested".to_string(), range("root-B3", (1, 1), (2, 2)));
+        delete_line(
+            &mut names,
+            &coord!("root"),
+            Line::Row,
+            NonZeroU32::new(2).unwrap(),
+        );
+        assert_eq!(names["cell"], Lookup::Cell(coord!("root-A2")));
+        assert!(!names.contains_key("gone"));
+        assert!(!names.contains_key("single"));
+        assert_eq!(names["rows"], range("root", (1, 1), (2, 2)));
+        assert_eq!(names["after"], range("root", (2, 1), (3, 1)));
+        assert_eq!(names["nested"], range("root-B2", (1, 1), (2, 2)));
+    }
+}
@@ -0,0 +1,488 @@
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+
+use crate::coordinate::Coordinate;
+use crate::grammar::{Grammar, Kind};
+use crate::util::{non_zero_u32_tuple, row_col_to_string};
+
+// VIEW ROOT
+// The view can be zoomed into any nested grid, which is then rendered in place of the
+// root grid (see `Model::view_root`). Every tab keeps its own view root along with the
+// grids it was zoomed into before, so the user can go back and forward like in a browser.
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ViewHistory {
+    back: Vec<Coordinate>,
+    forward: Vec<Coordinate>,
+}
+
+impl ViewHistory {
+    // records that the view is leaving `current` for another grid
+    pub fn visit(&mut self, current: &Coordinate) {
+        self.back.push(current.clone());
+        self.forward.clear();
+    }
+
+    // the view root to go back to from `current`, if there is one
+    pub fn back(&mut self, current: &Coordinate) -> Option<Coordinate> {
+        let previous = self.back.pop()?;
+        self.forward.push(current.clone());
+        Some(previous)
+    }
+
+    pub fn forward(&mut self, current: &Coordinate) -> Option<Coordinate> {
+        let next = self.forward.pop()?;
+        self.back.push(current.clone());
+        Some(next)
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        !self.back.is_empty()
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        !self.forward.is_empty()
+    }
+}
+
+// the grids on the way from the outermost grid to `view_root` (included) along with
+// their labels, e.g. "root", "B2", "A1" for root-B2-A1
+pub fn breadcrumbs(view_root: &Coordinate) -> Vec<(String, Coordinate)> {
+    (1..=view_root.row_cols.len())
+        .map(|depth| {
+            let coord = Coordinate {
+                row_cols: view_root.row_cols[..depth].to_vec(),
+            };
+            let label = if depth == 1 {
+                coord.to_string()
+            } else {
+                let (row, col) = coord.row_col();
+                row_col_to_string((row.get(), col.get()))
+            };
+            (label, coord)
+        })
+        .collect()
+}
+
+// KEYBOARD NAVIGATION
+// The active cell moves within its grid, following these rules across nesting levels:
+// - stepping onto a nested grid enters it, on the side the step came from: its top-left
+//   cell going right or down, its last row going up and its last column going left
+// - stepping off the edge of a nested grid leaves it, continuing from the grid's own cell
+//   in the parent grid. The view root (see above) can't be left this way.
+// - Tab order (`Move::Next`/`Move::Previous`) reads the grid row by row, entering nested
+//   grids at their first cell going forward and at their last cell going backward
+// - moving onto a cell hidden by a merge lands on the visible top-left cell of the merge,
+//   and moving away from a merged cell starts from its far side
+// - jumping to the edge of the data (`Move::Edge`) and Home/End stay within the grid
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Move {
+    // to the neighboring cell (arrows, Enter)
+    Step(Direction),
+    // to the last non-empty cell before an empty one, or the next non-empty cell if the
+    // neighbor is empty (Ctrl+arrows)
+    Edge(Direction),
+    // to the first or last cell of the row (Home/End)
+    RowStart,
+    RowEnd,
+    // to the next or previous cell in reading order (Tab/Shift-Tab)
+    Next,
+    Previous,
+}
+
+impl Move {
+    // moves that also move the caret inside of the text of a cell
+    pub fn moves_caret(self) -> bool {
+        match self {
+            Move::Step(Direction::Left) | Move::Step(Direction::Right) => true,
+            Move::RowStart | Move::RowEnd => true,
+            _ => false,
+        }
+    }
+
+    // whether the caret has to be at the start (rather than the end) of the text
+    pub fn towards_start(self) -> bool {
+        match self {
+            Move::Step(Direction::Left) | Move::RowStart => true,
+            _ => false,
+        }
+    }
+}
+
+// the cell the active cell moves to from `from`, `None` if it can't move that way
+pub fn navigate(
+    grammars: &HashMap<Coordinate, Grammar>,
+    view_root: &Coordinate,
+    from: &Coordinate,
+    movement: Move,
+) -> Option<Coordinate> {
+    let parent = from.parent()?;
+    let (rows, cols) = grid_size(grammars, &parent)?;
+    match movement {
+        Move::Step(direction) => match neighbor(grammars, &parent, from, direction) {
+            Some(cell) => Some(enter(grammars, cell, direction)),
+            None if can_leave(view_root, &parent) => {
+                navigate(grammars, view_root, &parent, movement)
+            }
+            None => None,
+        },
+        Move::Edge(direction) => {
+            let mut current = neighbor(grammars, &parent, from, direction)?;
+            // moving through data stops at its last cell, otherwise at the first data
+            let through_data = !is_empty(grammars, from) && !is_empty(grammars, &current);
+            if !through_data && !is_empty(grammars, &current) {
+                return Some(current);
+            }
+            while let Some(next) = neighbor(grammars, &parent, &current, direction) {
+                if through_data && is_empty(grammars, &next) {
+                    break;
+                }
+                current = next;
+                if !through_data && !is_empty(grammars, &current) {
+                    break;
+                }
+            }
+            Some(current)
+        }
+        Move::RowStart => Some(visible_cell(
+            grammars,
+            Coordinate::child_of(&parent, (from.row(), NonZeroU32::new(1).unwrap())),
+        )),
+        Move::RowEnd => Some(visible_cell(
+            grammars,
+            Coordinate::child_of(&parent, (from.row(), NonZeroU32::new(cols).unwrap())),
+        )),
+        Move::Next | Move::Previous => {
+            let forward = movement == Move::Next;
+            let (mut row, mut col) = {
+                let (row, col) = from.row_col();
+                (row.get(), col.get())
+            };
+            loop {
+                if forward {
+                    col += 1;
+                    if col > cols {
+                        col = 1;
+                        row += 1;
+                    }
+                } else {
+                    col -= 1;
+                    if col < 1 {
+                        col = cols;
+                        row -= 1;
+                    }
+                }
+                if row < 1 || row > rows {
+                    return if can_leave(view_root, &parent) {
+                        navigate(grammars, view_root, &parent, movement)
+                    } else {
+                        None
+                    };
+                }
+                // hidden merged cells are skipped, their merge was reached before them
+                let cell = Coordinate::child_of(&parent, non_zero_u32_tuple((row, col)));
+                if is_visible(grammars, &cell) {
+                    return Some(enter_in_order(grammars, cell, forward));
+                }
+            }
+        }
+    }
+}
+
+// nested grids can be left unless they're shown as the view root
+fn can_leave(view_root: &Coordinate, grid: &Coordinate) -> bool {
+    grid != view_root && grid.parent().is_some()
+}
+
+// the number of rows and columns of the grid at `coord`, `None` if it isn't a grid
+fn grid_size(grammars: &HashMap<Coordinate, Grammar>, coord: &Coordinate) -> Option<(u32, u32)> {
+    match grammars.get(coord).map(|g| &g.kind) {
+        Some(Kind::Grid(sub_coords)) => {
+            Some(sub_coords.iter().fold((0, 0), |(rows, cols), (row, col)| {
+                (rows.max(row.get()), cols.max(col.get()))
+            }))
+        }
+        _ => None,
+    }
+}
+
+fn is_visible(grammars: &HashMap<Coordinate, Grammar>, coord: &Coordinate) -> bool {
+    grammars.get(coord).map_or(false, |g| g.style.display)
+}
+
+fn is_empty(grammars: &HashMap<Coordinate, Grammar>, coord: &Coordinate) -> bool {
+    match grammars.get(coord).map(|g| &g.kind) {
+        Some(Kind::Input(value)) | Some(Kind::Lookup(value, _)) => value.is_empty(),
+        Some(_) => false,
+        None => true,
+    }
+}
+
+// the top-left cell of the merge a hidden cell belongs to, other cells are visible already
+fn visible_cell(grammars: &HashMap<Coordinate, Grammar>, coord: Coordinate) -> Coordinate {
+    match (grammars.get(&coord), coord.parent()) {
+        (Some(grammar), Some(parent)) if !grammar.style.display => {
+            let (row_span, col_span) = (grammar.style.row_span, grammar.style.col_span);
+            Coordinate::child_of(&parent, non_zero_u32_tuple((row_span.0, col_span.0)))
+        }
+        _ => coord,
+    }
+}
+
+// the visible cell next to `from` in the grid at `parent`, starting from the far side of
+// `from` when it's merged
+fn neighbor(
+    grammars: &HashMap<Coordinate, Grammar>,
+    parent: &Coordinate,
+    from: &Coordinate,
+    direction: Direction,
+) -> Option<Coordinate> {
+    let (rows, cols) = grid_size(grammars, parent)?;
+    let (row, col) = {
+        let (row, col) = from.row_col();
+        let (row_span, col_span) = grammars
+            .get(from)
+            .map_or((0, 0), |g| (g.style.row_span.1, g.style.col_span.1));
+        (row.get().max(row_span), col.get().max(col_span))
+    };
+    let (row, col) = match direction {
+        Direction::Up => (from.row().get().checked_sub(1)?, from.col().get()),
+        Direction::Down => (row + 1, from.col().get()),
+        Direction::Left => (from.row().get(), from.col().get().checked_sub(1)?),
+        Direction::Right => (from.row().get(), col + 1),
+    };
+    if row < 1 || row > rows || col < 1 || col > cols {
+        return None;
+    }
+    let cell = visible_cell(
+        grammars,
+        Coordinate::child_of(parent, non_zero_u32_tuple((row, col))),
+    );
+    if cell == *from {
+        return None;
+    }
+    Some(cell)
+}
+
+// the cell that's stepped onto when stepping onto `cell`, which is inside of it if it's a
+// nested grid
+fn enter(
+    grammars: &HashMap<Coordinate, Grammar>,
+    cell: Coordinate,
+    direction: Direction,
+) -> Coordinate {
+    match grid_size(grammars, &cell) {
+        Some((rows, cols)) if rows > 0 && cols > 0 => {
+            let (row, col) = match direction {
+                Direction::Down | Direction::Right => (1, 1),
+                Direction::Up => (rows, 1),
+                Direction::Left => (1, cols),
+            };
+            let inner = Coordinate::child_of(&cell, non_zero_u32_tuple((row, col)));
+            enter(grammars, visible_cell(grammars, inner), direction)
+        }
+        _ => cell,
+    }
+}
+
+// same as `enter` for the reading order: the first cell of a nested grid going forward,
+// its last cell going backward
+fn enter_in_order(
+    grammars: &HashMap<Coordinate, Grammar>,
+    cell: Coordinate,
+    forward: bool,
+) -> Coordinate {
+    match grid_size(grammars, &cell) {
+        Some((rows, cols)) if rows > 0 && cols > 0 => {
+            let (row, col) = if forward { (1, 1) } else { (rows, cols) };
+            let inner = Coordinate::child_of(&cell, non_zero_u32_tuple((row, col)));
+            enter_in_order(grammars, visible_cell(grammars, inner), forward)
+        }
+        _ => cell,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coord;
+    use crate::coordinate::{CoordinateParser, Rule};
+    use crate::util::non_zero_u32_tuple;
+    use pest::Parser;
+
+    #[test]
+    fn test_history() {
+        let mut history = ViewHistory::default();
+        assert!(!history.can_go_back());
+        history.visit(&coord!("root"));
+        history.visit(&coord!("root-B2"));
+        let current = coord!("root-B2-A1");
+        assert_eq!(history.back(&current), Some(coord!("root-B2")));
+        assert_eq!(history.back(&coord!("root-B2")), Some(coord!("root")));
+        assert_eq!(history.back(&coord!("root")), None);
+        assert_eq!(history.forward(&coord!("root")), Some(coord!("root-B2")));
+        // zooming somewhere else drops the way forward
+        history.visit(&coord!("root-B2"));
+        assert!(!history.can_go_forward());
+    }
+
+    // a 3x3 root grid with a 2x2 grid nested at B2 and "x" in A1:A2
+    fn grammars() -> HashMap<Coordinate, Grammar> {
+        let mut grammars = HashMap::new();
+        let cells = |rows: u32, cols: u32| {
+            let mut cells = vec![];
+            for row in 1..=rows {
+                for col in 1..=cols {
+                    cells.push(non_zero_u32_tuple((row, col)));
+                }
+            }
+            cells
+        };
+        let mut grid = Grammar::default();
+        grid.kind = Kind::Grid(cells(3, 3));
+        grammars.insert(coord!("root"), grid.clone());
+        for cell in cells(3, 3) {
+            let coord = Coordinate::child_of(&coord!("root"), cell);
+            grammars.insert(coord, Grammar::input("", ""));
+        }
+        grid.kind = Kind::Grid(cells(2, 2));
+        grammars.insert(coord!("root-B2"), grid);
+        for cell in cells(2, 2) {
+            let coord = Coordinate::child_of(&coord!("root-B2"), cell);
+            grammars.insert(coord, Grammar::input("", ""));
+        }
+        grammars.insert(coord!("root-A1"), Grammar::input("", "x"));
+        grammars.insert(coord!("root-A2"), Grammar::input("", "x"));
+        grammars
+    }
+
+    #[test]
+    fn test_step_across_nested_grids() {
+        let grammars = grammars();
+        let root = coord!("root");
+        let step = |from: &str, direction| {
+            navigate(&grammars, &root, &coord!(from), Move::Step(direction))
+        };
+        // entering on the side the step came from
+        assert_eq!(
+            step("root-A2", Direction::Right),
+            Some(coord!("root-B2-A1"))
+        );
+        assert_eq!(step("root-C2", Direction::Left), Some(coord!("root-B2-B1")));
+        assert_eq!(step("root-B3", Direction::Up), Some(coord!("root-B2-A2")));
+        // leaving from the grid's own cell
+        assert_eq!(
+            step("root-B2-B1", Direction::Right),
+            Some(coord!("root-C2"))
+        );
+        assert_eq!(step("root-B2-A2", Direction::Down), Some(coord!("root-B3")));
+        // the root grid can't be left, and neither can the view root
+        assert_eq!(step("root-A1", Direction::Up), None);
+        assert_eq!(
+            navigate(
+                &grammars,
+                &coord!("root-B2"),
+                &coord!("root-B2-B1"),
+                Move::Step(Direction::Right)
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_reading_order() {
+        let grammars = grammars();
+        let root = coord!("root");
+        assert_eq!(
+            navigate(&grammars, &root, &coord!("root-C1"), Move::Next),
+            Some(coord!("root-A2"))
+        );
+        assert_eq!(
+            navigate(&grammars, &root, &coord!("root-A2"), Move::Next),
+            Some(coord!("root-B2-A1"))
+        );
+        // going backward enters nested grids at their last cell
+        assert_eq!(
+            navigate(&grammars, &root, &coord!("root-C2"), Move::Previous),
+            Some(coord!("root-B2-B2"))
+        );
+        assert_eq!(
+            navigate(&grammars, &root, &coord!("root-B2-B2"), Move::Next),
+            Some(coord!("root-C2"))
+        );
+    }
+
+    #[test]
+    fn test_edges_and_merges() {
+        let mut grammars = grammars();
+        let root = coord!("root");
+        assert_eq!(
+            navigate(
+                &grammars,
+                &root,
+                &coord!("root-A1"),
+                Move::Edge(Direction::Down)
+            ),
+            Some(coord!("root-A2"))
+        );
+        assert_eq!(
+            navigate(
+                &grammars,
+                &root,
+                &coord!("root-A3"),
+                Move::Edge(Direction::Up)
+            ),
+            Some(coord!("root-A2"))
+        );
+        assert_eq!(
+            navigate(&grammars, &root, &coord!("root-B1"), Move::RowEnd),
+            Some(coord!("root-C1"))
+        );
+        // B1:C1 merged, C1 is hidden
+        for coord in &[coord!("root-B1"), coord!("root-C1")] {
+            let grammar = grammars.get_mut(coord).unwrap();
+            grammar.style.row_span = (1, 1);
+            grammar.style.col_span = (2, 3);
+        }
+        grammars.get_mut(&coord!("root-C1")).unwrap().style.display = false;
+        assert_eq!(
+            navigate(&grammars, &root, &coord!("root-A1"), Move::RowEnd),
+            Some(coord!("root-B1"))
+        );
+        assert_eq!(
+            navigate(
+                &grammars,
+                &root,
+                &coord!("root-B1"),
+                Move::Step(Direction::Right)
+            ),
+            None
+        );
+        assert_eq!(
+            navigate(&grammars, &root, &coord!("root-B1"), Move::Next),
+            Some(coord!("root-A2"))
+        );
+    }
+
+    #[test]
+    fn test_breadcrumbs() {
+        let labels: Vec<String> = breadcrumbs(&coord!("root-B2-A1"))
+            .into_iter()
+            .map(|(label, _)| label)
+            .collect();
+        assert_eq!(labels, vec!["root", "B2", "A1"]);
+        assert_eq!(
+            breadcrumbs(&coord!("meta-A3")).last(),
+            Some(&("A3".to_string(), coord!("meta-A3")))
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use crate::command::Command;
+
+// COMMAND PALETTE
+// The palette (Ctrl-Shift-P by default, see src/keymap.rs) lists the commands along with
+// their shortcut, and whether they can run with the current selection. Typing filters
+// them with a fuzzy search on their title, e.g. "mc" finds "Merge cells". Some commands
+// take a parameter typed after their title (see `TEMPLATES`), e.g. "Nest grid 4x2".
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaletteCommand {
+    Run(Command),
+    NestGrid(u32, u32),
+    AddDefinition(/* name */ String),
+    GoTo(/* name or coordinate */ String),
+    SetTheme(/* name */ String),
+}
+
+pub struct Template {
+    pub title: &'static str,
+    pub hint: &'static str,
+    pub description: &'static str,
+}
+
+pub const TEMPLATES: &[Template] = &[
+    Template {
+        title: "Nest grid",
+        hint: "4x2",
+        description: "Nest a grid of the given size in the active cell",
+    },
+    Template {
+        title: "Add definition",
+        hint: "name",
+        description: "Define a grammar with the given name from the selected grid",
+    },
+    Template {
+        title: "Go to",
+        hint: "name or cell",
+        description: "Activate a named cell or a coordinate",
+    },
+    Template {
+        title: "Set theme",
+        hint: "name",
+        description: "Change the theme of the session",
+    },
+];
+
+// the palette while it's open
+#[derive(Debug, Clone, Default)]
+pub struct Palette {
+    pub query: String,
+    // the index of the highlighted entry
+    pub selected: usize,
+}
+
+// an entry of the palette, `command` is `None` for templates that wait for their parameter
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaletteEntry {
+    pub title: String,
+    pub description: String,
+    pub shortcut: Option<String>,
+    pub enabled: bool,
+    pub command: Option<PaletteCommand>,
+}
+
+impl PaletteEntry {
+    // what choosing the entry of a template types in, e.g. "Nest grid "
+    pub fn completion(&self) -> Option<String> {
+        match self.command {
+            Some(_) => None,
+            None => Some(format! {"{} ", self.title.trim_end_matches('…').trim_end()}),
+        }
+    }
+}
+
+impl PaletteCommand {
+    pub fn title(&self) -> String {
+        match self {
+            PaletteCommand::Run(command) => command.title(),
+            PaletteCommand::NestGrid(rows, cols) => format! {"Nest grid {}x{}", rows, cols},
+            PaletteCommand::AddDefinition(name) => format! {"Add definition {}", name},
+            PaletteCommand::GoTo(target) => format! {"Go to {}", target},
+            PaletteCommand::SetTheme(name) => format! {"Set theme {}", name},
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match self {
+            PaletteCommand::Run(command) => command.description(),
+            PaletteCommand::NestGrid(_, _) => TEMPLATES[0].description.to_string(),
+            PaletteCommand::AddDefinition(_) => TEMPLATES[1].description.to_string(),
+            PaletteCommand::GoTo(_) => TEMPLATES[2].description.to_string(),
+            PaletteCommand::SetTheme(_) => TEMPLATES[3].description.to_string(),
+        }
+    }
+}
+
+// the commands listed by the palette, moving the active cell is left to the keyboard
+pub fn palette_commands() -> Vec<Command> {
+    Command::all()
+        .into_iter()
+        .filter(|c| match c {
+            Command::Navigate(_, _) | Command::CommandPalette => false,
+            _ => true,
+        })
+        .collect()
+}
+
+// the command typed in with its parameter, e.g. "nest grid 4x2"
+pub fn parse_command(query: &str) -> Option<PaletteCommand> {
+    let query = query.trim();
+    let (template, parameter) = TEMPLATES.iter().find_map(|template| {
+        let title = template.title;
+        let prefix = query.get(..title.len())?;
+        let rest = query.get(title.len()..)?;
+        if prefix.eq_ignore_ascii_case(title) && rest.starts_with(' ') {
+            Some((title, rest.trim()))
+        } else {
+            None
+        }
+    })?;
+    match template {
+        "Nest grid" => {
+            parse_size(parameter).map(|(rows, cols)| PaletteCommand::NestGrid(rows, cols))
+        }
+        "Add definition" => Some(PaletteCommand::AddDefinition(parameter.to_string())),
+        "Go to" => Some(PaletteCommand::GoTo(parameter.to_string())),
+        "Set theme" => Some(PaletteCommand::SetTheme(parameter.to_string())),
+        _ => None,
+    }
+}
+
+// rows and columns written as "4x2", "4 x 2" or "4,2"
+fn parse_size(size: &str) -> Option<(u32, u32)> {
+    let mut parts = size.split(|c| c == 'x' || c == 'X' || c == ',');
+    let rows = parts.next()?.trim().parse::<u32>().ok()?;
+    let cols = parts.next()?.trim().parse::<u32>().ok()?;
+    if parts.next().is_some() || rows == 0 || cols == 0 {
+        return None;
+    }
+    Some((rows, cols))
+}
+
+// how well `text` matches `query` when the characters of the query appear in it in order,
+// higher for characters that follow each other or start words, `None` if it doesn't match
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let query: Vec<char> = query
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut matched = 0;
+    let mut previous: Option<usize> = None;
+    for (i, c) in text.iter().enumerate() {
+        if matched == query.len() {
+            break;
+        }
+        if *c != query[matched] {
+            continue;
+        }
+        score += 1;
+        if i > 0 && previous == Some(i - 1) {
+            score += 5;
+        }
+        if i == 0 || !text[i - 1].is_alphanumeric() {
+            score += 10;
+        }
+        previous = Some(i);
+        matched += 1;
+    }
+    if matched < query.len() {
+        return None;
+    }
+    // shorter titles first among equal matches
+    Some(score * 100 - text.len() as i32)
+}
+
+// the entries matching the query, best first, keeping their order when they match as well
+pub fn filter_entries(query: &str, entries: Vec<PaletteEntry>) -> Vec<PaletteEntry> {
+    let mut scored: Vec<(i32, PaletteEntry)> = entries
+        .into_iter()
+        .filter_map(|entry| fuzzy_score(query, &entry.title).map(|score| (score, entry)))
+        .collect();
+    if !query.trim().is_empty() {
+        scored.sort_by(|(a, _), (b, _)| b.cmp(a));
+    }
+    scored.into_iter().map(|(_, entry)| entry).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(title: &str) -> PaletteEntry {
+        PaletteEntry {
+            title: title.to_string(),
+            description: String::new(),
+            shortcut: None,
+            enabled: true,
+            command: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(
+            parse_command("Nest grid 4x2"),
+            Some(PaletteCommand::NestGrid(4, 2))
+        );
+        assert_eq!(
+            parse_command("nest GRID 3 x 5 "),
+            Some(PaletteCommand::NestGrid(3, 5))
+        );
+        assert_eq!(parse_command("nest grid 0x2"), None);
+        assert_eq!(parse_command("nest grid"), None);
+        assert_eq!(
+            parse_command("go to total"),
+            Some(PaletteCommand::GoTo("total".to_string()))
+        );
+        assert_eq!(parse_command("go to "), None);
+        assert_eq!(parse_command("goto total"), None);
+        assert_eq!(
+            parse_command("Add definition person"),
+            Some(PaletteCommand::AddDefinition("person".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_fuzzy_search() {
+        assert!(fuzzy_score("mc", "Merge cells").is_some());
+        assert!(fuzzy_score("cm", "Merge cells").is_none());
+        assert_eq!(fuzzy_score("", "Save"), Some(-4));
+        // word starts and consecutive characters are better matches
+        assert!(fuzzy_score("zi", "Zoom in") > fuzzy_score("zi", "Zoom into grid"));
+        assert!(fuzzy_score("ins", "Insert row") > fuzzy_score("ins", "Unmerge cells in"));
+
+        let entries = vec![
+            entry("Zoom out"),
+            entry("Merge cells"),
+            entry("Unmerge cells"),
+        ];
+        let titles: Vec<String> = filter_entries("merge", entries.clone())
+            .into_iter()
+            .map(|e| e.title)
+            .collect();
+        assert_eq!(titles, vec!["Merge cells", "Unmerge cells"]);
+        assert_eq!(filter_entries("", entries).len(), 3);
+    }
+
+    #[test]
+    fn test_palette_commands() {
+        let commands = palette_commands();
+        assert!(commands.contains(&Command::NewEditor));
+        assert!(commands.contains(&Command::ToggleLookup));
+        assert!(!commands.contains(&Command::CommandPalette));
+    }
+}
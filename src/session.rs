@@ -6,10 +6,15 @@ use serde::{
 use std::collections::HashMap;
 use std::option::Option;
 
+use crate::comments::Comments;
 use crate::coord;
 use crate::coordinate::Coordinate;
+use crate::formatting::{FormatRule, StyleOverride};
 use crate::grammar::{Grammar, Interactive, Kind};
+use crate::names::Names;
 use crate::style::Style;
+use crate::theme::Theme;
+use crate::validation::Validation;
 
 // Session encapsulates the serializable state of the application that gets stored to disk
 // in a .ise file (which is just a JSON file)
@@ -19,21 +24,65 @@ pub struct Session {
     pub root: Grammar,
     pub meta: Grammar,
     pub grammars: HashMap<Coordinate, Grammar>,
+    // conditional formatting rules by the coordinate of the grid they apply to
+    #[serde(default)]
+    pub formatting: HashMap<Coordinate, Vec<FormatRule>>,
+    // style classes cells can refer to by name through `Style::class`
+    #[serde(default)]
+    pub style_classes: HashMap<String, StyleOverride>,
+    #[serde(default = "Theme::light")]
+    pub theme: Theme,
+    #[serde(default)]
+    pub validations: Vec<Validation>,
+    #[serde(default)]
+    pub comments: Comments,
+    // named cells and ranges, see src/names.rs
+    #[serde(default)]
+    pub names: Names,
 }
 js_serializable!(Session);
 js_deserializable!(Session);
 
+// a session of `grammars` with nothing else in it, for tests
+#[cfg(test)]
+impl Session {
+    pub fn of_grammars(title: &str, grammars: HashMap<Coordinate, Grammar>) -> Session {
+        use crate::util::non_zero_u32_tuple;
+        use pest::Parser;
+        use std::num::NonZeroU32;
+
+        Session {
+            title: title.to_string(),
+            root: grammars[&coord!("root")].clone(),
+            meta: Grammar::input("", ""),
+            grammars,
+            formatting: HashMap::new(),
+            style_classes: HashMap::new(),
+            theme: Theme::light(),
+            validations: vec![],
+            comments: Comments::new(),
+            names: Names::new(),
+        }
+    }
+}
+
 // Session Custom Serialization
 impl Serialize for Session {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("Session", 3)?;
+        let mut state = serializer.serialize_struct("Session", 10)?;
         state.serialize_field("title", &self.title)?;
         state.serialize_field("root", &self.root)?;
         state.serialize_field("meta", &self.meta)?;
         state.serialize_field("grammars", &self.grammars)?;
+        state.serialize_field("formatting", &self.formatting)?;
+        state.serialize_field("style_classes", &self.style_classes)?;
+        state.serialize_field("theme", &self.theme)?;
+        state.serialize_field("validations", &self.validations)?;
+        state.serialize_field("comments", &self.comments)?;
+        state.serialize_field("names", &self.names)?;
         state.end()
     }
 }
@@ -75,6 +124,8 @@ impl Serialize for Style {
         state.serialize_field("col_span", &self.col_span)?;
         state.serialize_field("row_span", &self.row_span)?;
         state.serialize_field("display", &self.display)?;
+        state.serialize_field("class", &self.class)?;
+        state.serialize_field("format", &self.format)?;
         state.end()
     }
 }
@@ -161,9 +212,11 @@ impl Serialize for Kind {
                 sv.serialize_field("rules", rules)?;
                 sv.end()
             }
-            Kind::Editor(s) => {
-                let mut sv = serializer.serialize_struct_variant("Kind", 0, "Editor", 1)?;
-                sv.serialize_field("content", s)?;
+            Kind::Editor(content, mode, output) => {
+                let mut sv = serializer.serialize_tuple_variant("kind", 3, "Editor", 3)?;
+                sv.serialize_field(content)?;
+                sv.serialize_field(mode)?;
+                sv.serialize_field(output)?;
                 sv.end()
             }
         }
@@ -0,0 +1,289 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::num::NonZeroU32;
+
+use crate::theme::Theme;
+use crate::util::non_zero_u32_tuple;
+
+// SETTINGS
+// Application settings are kept in `SETTINGS_FILE` in the user config directory (read and
+// written through the Electron main process, see static/main.js), unlike the session
+// which is saved with the sheet. Every setting is edited in the "Settings" side menu and
+// applied as soon as it's changed (see `Model::apply_settings`).
+
+pub const SETTINGS_FILE: &str = "settings.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // the size of the grids nested with Ctrl-g or the "Nest Grid" button
+    pub nested_rows: u32,
+    pub nested_cols: u32,
+    pub zoom: f32,
+    // the size of new cells, the theme's size is used for the sides that aren't set
+    pub cell_width: Option<f64>,
+    pub cell_height: Option<f64>,
+    // the name of new definitions, the coordinate of their grid if empty
+    pub definition_name: String,
+    // whether sessions are saved with one cell per line, see src/canonical.rs
+    pub cell_per_line: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            nested_rows: 3,
+            nested_cols: 3,
+            zoom: 1.0,
+            cell_width: None,
+            cell_height: None,
+            definition_name: String::new(),
+            cell_per_line: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Setting {
+    NestedRows,
+    NestedCols,
+    Zoom,
+    CellWidth,
+    CellHeight,
+    DefinitionName,
+    CellPerLine,
+}
+
+impl Setting {
+    pub fn all() -> Vec<Setting> {
+        vec![
+            Setting::NestedRows,
+            Setting::NestedCols,
+            Setting::Zoom,
+            Setting::CellWidth,
+            Setting::CellHeight,
+            Setting::DefinitionName,
+            Setting::CellPerLine,
+        ]
+    }
+
+    // the key of the setting in the settings file
+    pub fn key(self) -> &'static str {
+        match self {
+            Setting::NestedRows => "nested_rows",
+            Setting::NestedCols => "nested_cols",
+            Setting::Zoom => "zoom",
+            Setting::CellWidth => "cell_width",
+            Setting::CellHeight => "cell_height",
+            Setting::DefinitionName => "definition_name",
+            Setting::CellPerLine => "cell_per_line",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Setting::NestedRows => "Nested grid rows",
+            Setting::NestedCols => "Nested grid columns",
+            Setting::Zoom => "Zoom",
+            Setting::CellWidth => "Cell width (px)",
+            Setting::CellHeight => "Cell height (px)",
+            Setting::DefinitionName => "Default definition name",
+            Setting::CellPerLine => "Save one cell per line",
+        }
+    }
+
+    // whether the setting is edited as a number
+    pub fn is_number(self) -> bool {
+        !self.is_text() && !self.is_toggle()
+    }
+
+    pub fn is_text(self) -> bool {
+        self == Setting::DefinitionName
+    }
+
+    // whether the setting is switched on and off, its text is "true" or "false"
+    pub fn is_toggle(self) -> bool {
+        self == Setting::CellPerLine
+    }
+
+    // whether the setting can be left empty
+    pub fn is_optional(self) -> bool {
+        self == Setting::CellWidth || self == Setting::CellHeight
+    }
+
+    fn range(self) -> (f64, f64) {
+        match self {
+            Setting::NestedRows | Setting::NestedCols => (1.0, 100.0),
+            Setting::Zoom => (0.1, 5.0),
+            Setting::CellWidth | Setting::CellHeight => (10.0, 1000.0),
+            Setting::DefinitionName | Setting::CellPerLine => (0.0, 0.0),
+        }
+    }
+}
+
+impl Settings {
+    pub fn get(&self, setting: Setting) -> String {
+        match setting {
+            Setting::NestedRows => self.nested_rows.to_string(),
+            Setting::NestedCols => self.nested_cols.to_string(),
+            Setting::Zoom => self.zoom.to_string(),
+            Setting::CellWidth => self.cell_width.map(|n| n.to_string()).unwrap_or_default(),
+            Setting::CellHeight => self.cell_height.map(|n| n.to_string()).unwrap_or_default(),
+            Setting::DefinitionName => self.definition_name.clone(),
+            Setting::CellPerLine => self.cell_per_line.to_string(),
+        }
+    }
+
+    // sets the setting from its text, numbers have to be within the range of the setting
+    pub fn set(&mut self, setting: Setting, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        if setting.is_text() {
+            self.definition_name = value.to_string();
+            return Ok(());
+        }
+        if setting.is_toggle() {
+            self.cell_per_line = value.parse::<bool>().map_err(
+                |_| format! {"{} should be true or false, not \"{}\"", setting.key(), value},
+            )?;
+            return Ok(());
+        }
+        if value.is_empty() && setting.is_optional() {
+            match setting {
+                Setting::CellWidth => self.cell_width = None,
+                _ => self.cell_height = None,
+            }
+            return Ok(());
+        }
+        let number = value
+            .parse::<f64>()
+            .map_err(|_| format! {"{} should be a number, not \"{}\"", setting.key(), value})?;
+        let (min, max) = setting.range();
+        if number < min || number > max || !number.is_finite() {
+            return Err(format! {"{} should be between {} and {}", setting.key(), min, max});
+        }
+        match setting {
+            Setting::NestedRows | Setting::NestedCols if number.fract() != 0.0 => {
+                return Err(format! {"{} should be a whole number", setting.key()});
+            }
+            Setting::NestedRows => self.nested_rows = number as u32,
+            Setting::NestedCols => self.nested_cols = number as u32,
+            Setting::Zoom => self.zoom = number as f32,
+            Setting::CellWidth => self.cell_width = Some(number),
+            Setting::CellHeight => self.cell_height = Some(number),
+            Setting::DefinitionName | Setting::CellPerLine => (),
+        }
+        Ok(())
+    }
+
+    // reads a settings file, the settings that are missing or can't be read keep their
+    // default, and are reported along with the keys that aren't settings
+    pub fn from_json(json: &str) -> (Settings, Vec<String>) {
+        let mut settings = Settings::default();
+        let values = match serde_json::from_str::<Value>(json) {
+            Ok(Value::Object(values)) => values,
+            Ok(_) => {
+                return (
+                    settings,
+                    vec!["the settings should be an object".to_string()],
+                )
+            }
+            Err(e) => return (settings, vec![format! {"cannot read the settings: {}", e}]),
+        };
+        let mut issues = vec![];
+        for (key, value) in values.iter() {
+            let setting = match Setting::all().into_iter().find(|s| s.key() == key) {
+                Some(setting) => setting,
+                None => {
+                    issues.push(format! {"unknown setting \"{}\"", key});
+                    continue;
+                }
+            };
+            let text = match value {
+                Value::String(text) if setting.is_text() => text.clone(),
+                Value::Bool(on) if setting.is_toggle() => on.to_string(),
+                Value::Number(number) if setting.is_number() => number.to_string(),
+                Value::Null if setting.is_optional() => String::new(),
+                _ => {
+                    issues.push(format! {"{} has the wrong type", key});
+                    continue;
+                }
+            };
+            if let Err(e) = settings.set(setting, &text) {
+                issues.push(e);
+            }
+        }
+        (settings, issues)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap_or_default()
+    }
+
+    pub fn nested_row_cols(&self) -> (NonZeroU32, NonZeroU32) {
+        non_zero_u32_tuple((self.nested_rows, self.nested_cols))
+    }
+
+    // the size new cells get with the theme of the session
+    pub fn cell_size(&self, theme: &Theme) -> (f64, f64) {
+        (
+            self.cell_width.unwrap_or(theme.cell_width),
+            self.cell_height.unwrap_or(theme.cell_height),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set() {
+        let mut settings = Settings::default();
+        assert_eq!(settings.set(Setting::NestedRows, " 4 "), Ok(()));
+        assert_eq!(settings.nested_rows, 4);
+        assert!(settings.set(Setting::NestedRows, "2.5").is_err());
+        assert!(settings.set(Setting::NestedCols, "0").is_err());
+        assert!(settings.set(Setting::Zoom, "fast").is_err());
+        assert_eq!(settings.set(Setting::Zoom, "1.5"), Ok(()));
+        assert_eq!(settings.get(Setting::Zoom), "1.5");
+        assert_eq!(settings.set(Setting::DefinitionName, "person"), Ok(()));
+        assert_eq!(settings.definition_name, "person");
+        assert!(settings.set(Setting::CellPerLine, "yes").is_err());
+        assert_eq!(settings.nested_row_cols(), non_zero_u32_tuple((4, 3)));
+    }
+
+    #[test]
+    fn test_cell_size() {
+        let mut settings = Settings::default();
+        let theme = Theme {
+            cell_width: 120.0,
+            ..Theme::dark()
+        };
+        assert_eq!(settings.cell_size(&theme), (120.0, 30.0));
+        settings.set(Setting::CellHeight, "40").unwrap();
+        assert_eq!(settings.get(Setting::CellHeight), "40");
+        assert_eq!(settings.cell_size(&theme), (120.0, 40.0));
+        settings.set(Setting::CellHeight, " ").unwrap();
+        assert_eq!(settings.get(Setting::CellHeight), "");
+        assert_eq!(settings.cell_size(&Theme::light()), (90.0, 30.0));
+        assert!(settings.set(Setting::Zoom, "").is_err());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let (settings, issues) = Settings::from_json(
+            r#"{"nested_rows": 2, "cell_width": 5000, "zoom": "big", "theme": "dark"}"#,
+        );
+        assert_eq!(settings.nested_rows, 2);
+        assert_eq!(settings.cell_width, None);
+        assert_eq!(settings.zoom, 1.0);
+        assert_eq!(issues.len(), 3);
+
+        // what's written is read back the same
+        let mut settings = Settings::default();
+        settings.set(Setting::CellHeight, "42").unwrap();
+        settings.set(Setting::DefinitionName, "row").unwrap();
+        settings.set(Setting::CellPerLine, "true").unwrap();
+        assert_eq!(Settings::from_json(&settings.to_json()), (settings, vec![]));
+    }
+}
@@ -0,0 +1,705 @@
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::num::NonZeroU32;
+
+use crate::coordinate::Coordinate;
+use crate::driver::value_to_string;
+use crate::grammar::{Grammar, Interactive, Kind, Lookup};
+use crate::util::non_zero_u32_tuple;
+use crate::value::CellValue;
+
+// SHEET VALUES
+// Scripts see the sheet as plain values instead of grammars:
+// - a cell is its value: the typed value of inputs (see `CellValue::to_json`), the text of
+//   text cells, a number for sliders and a bool for toggles
+// - a nested grid is a list of rows, each row a list of cell values
+// - a lookup is the value of whatever it looks up (ranges are lists of rows)
+// Merged away cells in a grid show up as `None`.
+
+// lookups can point at each other, so they're only followed this many times
+const MAX_LOOKUP_DEPTH: usize = 16;
+
+pub fn cell_value(grammars: &HashMap<Coordinate, Grammar>, coord: &Coordinate) -> Value {
+    cell_value_at_depth(grammars, coord, 0)
+}
+
+fn cell_value_at_depth(
+    grammars: &HashMap<Coordinate, Grammar>,
+    coord: &Coordinate,
+    depth: usize,
+) -> Value {
+    let grammar = match grammars.get(coord) {
+        Some(grammar) => grammar,
+        None => return Value::Null,
+    };
+    match &grammar.kind {
+        Kind::Input(s) => CellValue::parse(s).to_json(),
+        Kind::Text(s) | Kind::Editor(s, _, _) => Value::String(s.clone()),
+        Kind::Interactive(label, interactive) => match interactive {
+            Interactive::Button() => Value::String(label.clone()),
+            Interactive::Slider(value, _, _) => serde_json::json!(value),
+            Interactive::Toggle(on) => Value::Bool(*on),
+        },
+        Kind::Grid(sub_coords) => {
+            let (rows, cols) = grid_size(sub_coords);
+            let mut table = vec![vec![Value::Null; cols as usize]; rows as usize];
+            for (row, col) in sub_coords {
+                table[row.get() as usize - 1][col.get() as usize - 1] = cell_value_at_depth(
+                    grammars,
+                    &Coordinate::child_of(coord, (*row, *col)),
+                    depth,
+                );
+            }
+            Value::Array(table.into_iter().map(Value::Array).collect())
+        }
+        Kind::Lookup(value, lookup) => match lookup {
+            _ if depth >= MAX_LOOKUP_DEPTH => Value::Null,
+            Some(Lookup::Cell(target)) => cell_value_at_depth(grammars, target, depth + 1),
+            Some(Lookup::Range { parent, start, end }) => {
+                range_value_at_depth(grammars, parent, *start, *end, depth + 1)
+            }
+            Some(Lookup::Row(row)) => {
+                let (parent, row) = (&row.0, row.1);
+                match grammars.get(parent).map(|g| &g.kind) {
+                    Some(Kind::Grid(sub_coords)) => {
+                        let (_, cols) = grid_size(sub_coords);
+                        range_value_at_depth(
+                            grammars,
+                            parent,
+                            (row, NonZeroU32::new(1).unwrap()),
+                            (row, NonZeroU32::new(cols.max(1)).unwrap()),
+                            depth + 1,
+                        )
+                        .as_array()
+                        .and_then(|rows| rows.first().cloned())
+                        .unwrap_or(Value::Null)
+                    }
+                    _ => Value::Null,
+                }
+            }
+            Some(Lookup::Col(col)) => {
+                let (parent, col) = (&col.0, col.1);
+                match grammars.get(parent).map(|g| &g.kind) {
+                    Some(Kind::Grid(sub_coords)) => {
+                        let (rows, _) = grid_size(sub_coords);
+                        let column = range_value_at_depth(
+                            grammars,
+                            parent,
+                            (NonZeroU32::new(1).unwrap(), col),
+                            (NonZeroU32::new(rows.max(1)).unwrap(), col),
+                            depth + 1,
+                        );
+                        match column {
+                            Value::Array(rows) => Value::Array(
+                                rows.into_iter()
+                                    .filter_map(|row| {
+                                        row.as_array().and_then(|r| r.first().cloned())
+                                    })
+                                    .collect(),
+                            ),
+                            v => v,
+                        }
+                    }
+                    _ => Value::Null,
+                }
+            }
+            None => Value::String(value.clone()),
+        },
+        Kind::Defn(name, _, _) => Value::String(name.clone()),
+    }
+}
+
+// the cells from `start` to `end` (inclusive) in the grid at `parent`, as a list of rows
+pub fn range_value(
+    grammars: &HashMap<Coordinate, Grammar>,
+    parent: &Coordinate,
+    start: (NonZeroU32, NonZeroU32),
+    end: (NonZeroU32, NonZeroU32),
+) -> Value {
+    range_value_at_depth(grammars, parent, start, end, 0)
+}
+
+fn range_value_at_depth(
+    grammars: &HashMap<Coordinate, Grammar>,
+    parent: &Coordinate,
+    start: (NonZeroU32, NonZeroU32),
+    end: (NonZeroU32, NonZeroU32),
+    depth: usize,
+) -> Value {
+    let (first_row, last_row) = (start.0.min(end.0).get(), start.0.max(end.0).get());
+    let (first_col, last_col) = (start.1.min(end.1).get(), start.1.max(end.1).get());
+    Value::Array(
+        (first_row..=last_row)
+            .map(|row| {
+                Value::Array(
+                    (first_col..=last_col)
+                        .map(|col| {
+                            let coord =
+                                Coordinate::child_of(parent, non_zero_u32_tuple((row, col)));
+                            cell_value_at_depth(grammars, &coord, depth)
+                        })
+                        .collect(),
+                )
+            })
+            .collect(),
+    )
+}
+
+// every cell of the sheet by its coordinate string, this is the `cells` global of scripts
+pub fn sheet_cells(grammars: &HashMap<Coordinate, Grammar>) -> serde_json::Map<String, Value> {
+    grammars
+        .keys()
+        .map(|coord| (coord.to_string(), cell_value(grammars, coord)))
+        .collect()
+}
+
+// number of rows and columns of a grid
+pub fn grid_size(sub_coords: &[(NonZeroU32, NonZeroU32)]) -> (u32, u32) {
+    sub_coords.iter().fold((0, 0), |(rows, cols), (row, col)| {
+        (rows.max(row.get()), cols.max(col.get()))
+    })
+}
+
+// the rows of a grid as CSV, cells are written as the canonical text of their value (so
+// "1,200" is written as 1200 and "50%" as 0.5), nested grids are left empty
+pub fn grid_csv(grammars: &HashMap<Coordinate, Grammar>, grid: &Coordinate) -> Option<String> {
+    let rows = match cell_value(grammars, grid) {
+        Value::Array(rows) => rows,
+        _ => return None,
+    };
+    let mut writer = csv::Writer::from_writer(vec![]);
+    for row in rows {
+        let fields: Vec<String> = match row {
+            Value::Array(cells) => cells
+                .iter()
+                .map(|cell| match cell {
+                    Value::Array(_) => String::new(),
+                    value => value_to_string(value),
+                })
+                .collect(),
+            _ => vec![],
+        };
+        writer.write_record(&fields).ok()?;
+    }
+    String::from_utf8(writer.into_inner().ok()?).ok()
+}
+
+// PYTHON_SHEET_PRELUDE is run before every script, it wraps the `cells` global in `sheet`:
+//   sheet["root-A1"]           the value of a cell, nested grids are lists of rows
+//   sheet["root-A1:root-B3"]   a range, as a list of rows
+//   sheet.range("root-A1", "root-B3")
+//   sheet["total"]             a named cell or range, from the `names` global
+//                              (name -> "root-A1" or "root-A1:root-B3")
+pub const PYTHON_SHEET_PRELUDE: &str = r#"
+class Sheet:
+    def __init__(self, cells, names=None):
+        self.cells = cells
+        self.names = names or {}
+
+    def _resolve(self, key):
+        return self.names.get(key, key)
+
+    @staticmethod
+    def _split(coord):
+        parent, _, fragment = coord.rpartition("-")
+        if not parent or len(fragment) < 2 or not fragment[0].isalpha():
+            raise KeyError("{} is not a cell of a grid".format(coord))
+        return parent, int(fragment[1:]), ord(fragment[0].upper()) - 64
+
+    @staticmethod
+    def _join(parent, row, col):
+        return "{}-{}{}".format(parent, chr(col + 64), row)
+
+    def range(self, start, end):
+        start, end = self._resolve(start), self._resolve(end)
+        parent, start_row, start_col = self._split(start)
+        end_parent, end_row, end_col = self._split(end)
+        if parent != end_parent:
+            raise KeyError("{} and {} are not in the same grid".format(start, end))
+        return [
+            [self.cells.get(self._join(parent, row, col))
+             for col in range(min(start_col, end_col), max(start_col, end_col) + 1)]
+            for row in range(min(start_row, end_row), max(start_row, end_row) + 1)
+        ]
+
+    def __getitem__(self, coord):
+        coord = self._resolve(coord)
+        if ":" in coord:
+            return self.range(*coord.split(":", 1))
+        return self.cells[coord]
+
+    def get(self, coord, default=None):
+        try:
+            return self[coord]
+        except KeyError:
+            return default
+
+    def __contains__(self, coord):
+        coord = self._resolve(coord)
+        return ":" in coord or coord in self.cells
+
+    def __iter__(self):
+        return iter(self.cells)
+
+sheet = Sheet(cells, globals().get("names"))
+"#;
+
+// SPILLING RESULTS
+// The value a script returns is written back at its output coordinate:
+// - a scalar is written into the output cell
+// - a list spills into a range of the output's grid, starting at the output cell: a list
+//   of lists fills rows and columns, a flat list fills a column. The grid grows if the
+//   range doesn't fit.
+// - a dict becomes a new nested grid at the output cell, with a row per key
+// Values nested deeper than a range (e.g. a list inside a row) become nested grids.
+// Cells that hold a nested grid are only ever replaced by a new nested grid.
+// Nothing is written into the cells a merged range hides, and a value can't be spilled into
+// more than MAX_SPILL_CELLS cells.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpillError {
+    // a scalar can't be written into a nested grid
+    NestedGrid(Coordinate),
+    // a range needs a grid to spill into, the root doesn't have one
+    NoParentGrid(Coordinate),
+    // values aren't written into the cells hidden by a merged range
+    MergedCell(Coordinate),
+    // the value would make more than MAX_SPILL_CELLS cells
+    TooLarge(u64),
+}
+
+// the most cells a value returned by a script can be spilled into
+pub const MAX_SPILL_CELLS: u64 = 100_000;
+
+impl fmt::Display for SpillError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpillError::NestedGrid(coord) => write!(
+                f,
+                "cannot write a value into the nested grid at {}",
+                coord.to_string()
+            ),
+            SpillError::NoParentGrid(coord) => {
+                write!(f, "{} is not in a grid to spill into", coord.to_string())
+            }
+            SpillError::MergedCell(coord) => write!(
+                f,
+                "cannot write a value into {}, which is hidden by a merged range",
+                coord.to_string()
+            ),
+            SpillError::TooLarge(cells) => write!(
+                f,
+                "the value needs {} cells, more than the {} a value can be spilled into",
+                cells, MAX_SPILL_CELLS
+            ),
+        }
+    }
+}
+
+pub fn spill(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    output: &Coordinate,
+    value: &Value,
+) -> Result<(), SpillError> {
+    let cells = spilled_cells(value);
+    if cells > MAX_SPILL_CELLS {
+        return Err(SpillError::TooLarge(cells));
+    }
+    match value {
+        Value::Array(items) => spill_range(grammars, output, items),
+        Value::Object(_) => {
+            spill_grid(grammars, output, value);
+            Ok(())
+        }
+        scalar => set_value(grammars, output, value_to_string(scalar)),
+    }
+}
+
+// writes a value into a cell while keeping the kind of the grammar
+pub fn set_value(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    coord: &Coordinate,
+    value: String,
+) -> Result<(), SpillError> {
+    match grammars.get_mut(coord) {
+        Some(Grammar {
+            kind: Kind::Grid(_),
+            ..
+        }) => Err(SpillError::NestedGrid(coord.clone())),
+        Some(
+            g @ Grammar {
+                kind: Kind::Text(_),
+                ..
+            },
+        ) => {
+            g.kind = Kind::Text(value);
+            Ok(())
+        }
+        Some(g) => {
+            g.kind = Kind::Input(value);
+            Ok(())
+        }
+        None => {
+            grammars.insert(
+                coord.clone(),
+                Grammar {
+                    kind: Kind::Input(value),
+                    ..Grammar::default()
+                },
+            );
+            Ok(())
+        }
+    }
+}
+
+fn rows_of(items: &[Value]) -> Vec<Vec<Value>> {
+    items
+        .iter()
+        .map(|item| match item {
+            Value::Array(row) => row.clone(),
+            cell => vec![cell.clone()],
+        })
+        .collect()
+}
+
+fn spill_range(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    output: &Coordinate,
+    items: &[Value],
+) -> Result<(), SpillError> {
+    let parent = output
+        .parent()
+        .ok_or_else(|| SpillError::NoParentGrid(output.clone()))?;
+    let rows = rows_of(items);
+    let (row, col) = output.row_col();
+    let height = rows.len() as u32;
+    let width = rows.iter().map(|r| r.len()).max().unwrap_or(0) as u32;
+    if height == 0 || width == 0 {
+        return set_value(grammars, output, String::new());
+    }
+    // the grid grows to fit the range
+    let (grid_rows, grid_cols) = match grammars.get(&parent).map(|g| &g.kind) {
+        Some(Kind::Grid(sub_coords)) => grid_size(sub_coords),
+        _ => (0, 0),
+    };
+    let grown = u64::from(grid_rows.max(row.get() + height - 1))
+        * u64::from(grid_cols.max(col.get() + width - 1));
+    if grown > MAX_SPILL_CELLS {
+        return Err(SpillError::TooLarge(grown));
+    }
+    // check everything fits before writing anything
+    for (i, cells) in rows.iter().enumerate() {
+        for (j, cell) in cells.iter().enumerate() {
+            let coord = Coordinate::child_of(
+                &parent,
+                non_zero_u32_tuple((row.get() + i as u32, col.get() + j as u32)),
+            );
+            let is_grid = match grammars.get(&coord).map(|g| &g.kind) {
+                Some(Kind::Grid(_)) => true,
+                _ => false,
+            };
+            if is_grid && !is_nested(cell) {
+                return Err(SpillError::NestedGrid(coord));
+            }
+            if grammars.get(&coord).map_or(false, |g| !g.style.display) {
+                return Err(SpillError::MergedCell(coord));
+            }
+        }
+    }
+    grow_grid(
+        grammars,
+        &parent,
+        row.get() + height - 1,
+        col.get() + width - 1,
+    );
+    for (i, cells) in rows.iter().enumerate() {
+        for (j, cell) in cells.iter().enumerate() {
+            let coord = Coordinate::child_of(
+                &parent,
+                non_zero_u32_tuple((row.get() + i as u32, col.get() + j as u32)),
+            );
+            if is_nested(cell) {
+                spill_grid(grammars, &coord, cell);
+            } else {
+                set_value(grammars, &coord, value_to_string(cell))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// the number of cells spilling `value` writes, the cells of nested grids included
+fn spilled_cells(value: &Value) -> u64 {
+    let nested = |values: &mut dyn Iterator<Item = &Value>| {
+        values
+            .filter(|value| is_nested(value))
+            .fold(0u64, |cells, value| {
+                cells.saturating_add(spilled_cells(value))
+            })
+    };
+    match value {
+        Value::Array(items) => {
+            let width = items
+                .iter()
+                .map(|item| item.as_array().map_or(1, |row| row.len()))
+                .max()
+                .unwrap_or(0);
+            let cells = (items.len() as u64).saturating_mul(width as u64);
+            let mut values = items.iter().flat_map(|item| match item {
+                Value::Array(row) => row.iter().collect(),
+                cell => vec![cell],
+            });
+            cells.saturating_add(nested(&mut values))
+        }
+        Value::Object(map) => (map.len() as u64 * 2).saturating_add(nested(&mut map.values())),
+        _ => 1,
+    }
+}
+
+fn is_nested(value: &Value) -> bool {
+    match value {
+        Value::Array(_) | Value::Object(_) => true,
+        _ => false,
+    }
+}
+
+// replaces the cell at `coord` with a new nested grid holding `value`
+pub fn spill_grid(grammars: &mut HashMap<Coordinate, Grammar>, coord: &Coordinate, value: &Value) {
+    let rows: Vec<Vec<Value>> = match value {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, value)| vec![Value::String(key.clone()), value.clone()])
+            .collect(),
+        Value::Array(items) => rows_of(items),
+        scalar => vec![vec![scalar.clone()]],
+    };
+    let height = (rows.len() as u32).max(1);
+    let width = (rows.iter().map(|r| r.len()).max().unwrap_or(0) as u32).max(1);
+
+    // drop whatever was nested in the cell before
+    grammars.retain(|c, _| c == coord || !c.row_cols.starts_with(&coord.row_cols));
+    let name = grammars
+        .get(coord)
+        .map(|g| g.name.clone())
+        .unwrap_or_default();
+    let mut grid = Grammar::as_grid(
+        NonZeroU32::new(height).unwrap(),
+        NonZeroU32::new(width).unwrap(),
+    );
+    grid.name = name;
+    if let Some(old) = grammars.get(coord) {
+        grid.style = old.style.clone();
+    }
+    grammars.insert(coord.clone(), grid);
+
+    for i in 0..height {
+        for j in 0..width {
+            let cell_coord = Coordinate::child_of(coord, non_zero_u32_tuple((i + 1, j + 1)));
+            grammars.insert(cell_coord.clone(), Grammar::default());
+            match rows.get(i as usize).and_then(|r| r.get(j as usize)) {
+                Some(cell) if is_nested(cell) => spill_grid(grammars, &cell_coord, cell),
+                Some(cell) => {
+                    // the cell was just created as an Input, so this can't fail
+                    let _ = set_value(grammars, &cell_coord, value_to_string(cell));
+                }
+                None => (),
+            }
+        }
+    }
+}
+
+// makes the grid at `parent` at least `rows` x `cols`, filling it with empty cells
+fn grow_grid(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    parent: &Coordinate,
+    rows: u32,
+    cols: u32,
+) {
+    let sub_coords = match grammars.get(parent).map(|g| &g.kind) {
+        Some(Kind::Grid(sub_coords)) => sub_coords.clone(),
+        _ => return,
+    };
+    let (current_rows, current_cols) = grid_size(&sub_coords);
+    if rows <= current_rows && cols <= current_cols {
+        return;
+    }
+    let (rows, cols) = (rows.max(current_rows), cols.max(current_cols));
+    let mut new_sub_coords = sub_coords.clone();
+    for row in 1..=rows {
+        for col in 1..=cols {
+            if row <= current_rows && col <= current_cols {
+                continue;
+            }
+            let row_col = non_zero_u32_tuple((row, col));
+            new_sub_coords.push(row_col);
+            grammars.insert(Coordinate::child_of(parent, row_col), Grammar::default());
+        }
+    }
+    if let Some(g) = grammars.get_mut(parent) {
+        g.kind = Kind::Grid(new_sub_coords);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate::{CoordinateParser, Rule};
+    use crate::grammar_map::{build_grammar_map, MapEntry};
+    use crate::{coord, g, grid};
+    use pest::Parser;
+    use serde_json::json;
+
+    fn input(value: &str) -> Grammar {
+        Grammar {
+            kind: Kind::Input(value.to_string()),
+            ..Grammar::default()
+        }
+    }
+
+    fn sheet() -> HashMap<Coordinate, Grammar> {
+        let mut grammars = HashMap::new();
+        build_grammar_map(
+            &mut grammars,
+            coord!("root"),
+            grid![
+                [g!(input("a")), g!(input("b"))],
+                [
+                    g!(Grammar {
+                        kind: Kind::Lookup("".to_string(), Some(Lookup::Cell(coord!("root-B1")))),
+                        ..Grammar::default()
+                    }),
+                    grid![[g!(input("1")), g!(input("2"))]]
+                ]
+            ],
+        );
+        grammars
+    }
+
+    #[test]
+    fn test_cell_values() {
+        let grammars = sheet();
+        assert_eq!(cell_value(&grammars, &coord!("root-A1")), json!("a"));
+        assert_eq!(cell_value(&grammars, &coord!("root-A2")), json!("b"));
+        assert_eq!(cell_value(&grammars, &coord!("root-B2")), json!([[1, 2]]));
+        assert_eq!(
+            cell_value(&grammars, &coord!("root")),
+            json!([["a", "b"], ["b", [[1, 2]]]])
+        );
+        assert_eq!(
+            range_value(
+                &grammars,
+                &coord!("root"),
+                non_zero_u32_tuple((1, 1)),
+                non_zero_u32_tuple((2, 1))
+            ),
+            json!([["a"], ["b"]])
+        );
+        assert_eq!(sheet_cells(&grammars)["root-B1"], json!("b"));
+    }
+
+    #[test]
+    fn test_grid_csv() {
+        let mut grammars = sheet();
+        grammars.insert(coord!("root-A1"), input("1,200"));
+        grammars.insert(coord!("root-B1"), input("a, b"));
+        assert_eq!(
+            grid_csv(&grammars, &coord!("root")),
+            Some("1200,\"a, b\"\n\"a, b\",\n".to_string())
+        );
+        assert_eq!(grid_csv(&grammars, &coord!("root-A1")), None);
+    }
+
+    #[test]
+    fn test_spill_scalar() {
+        let mut grammars = sheet();
+        spill(&mut grammars, &coord!("root-A1"), &json!(3)).unwrap();
+        assert_eq!(cell_value(&grammars, &coord!("root-A1")), json!(3));
+        assert_eq!(
+            spill(&mut grammars, &coord!("root-B2"), &json!("x")),
+            Err(SpillError::NestedGrid(coord!("root-B2")))
+        );
+    }
+
+    #[test]
+    fn test_spill_range_grows_grid() {
+        let mut grammars = sheet();
+        assert_eq!(
+            spill(
+                &mut grammars,
+                &coord!("root-A1"),
+                &json!([[1, 2, 3], [4, 5, 6], [7]]),
+            ),
+            Err(SpillError::NestedGrid(coord!("root-B2")))
+        );
+        // nothing is written when part of the range is a nested grid
+        assert_eq!(cell_value(&grammars, &coord!("root-A1")), json!("a"));
+        assert_eq!(cell_value(&grammars, &coord!("root-B1")), json!("b"));
+
+        spill(&mut grammars, &coord!("root-C1"), &json!([[1, 2, 3], [4]])).unwrap();
+        assert_eq!(cell_value(&grammars, &coord!("root-C1")), json!(1));
+        assert_eq!(cell_value(&grammars, &coord!("root-E1")), json!(3));
+        assert_eq!(cell_value(&grammars, &coord!("root-C2")), json!(4));
+        match &grammars[&coord!("root")].kind {
+            Kind::Grid(sub_coords) => assert_eq!(grid_size(sub_coords), (2, 5)),
+            _ => panic!("root should be a grid"),
+        }
+
+        // a flat list fills a column
+        spill(&mut grammars, &coord!("root-C1"), &json!(["x", "y", "z"])).unwrap();
+        assert_eq!(cell_value(&grammars, &coord!("root-C3")), json!("z"));
+    }
+
+    #[test]
+    fn test_spill_limits() {
+        let mut grammars = sheet();
+        // the cells hidden by a merged range
+        grammars.get_mut(&coord!("root-B1")).unwrap().style.display = false;
+        assert_eq!(
+            spill(&mut grammars, &coord!("root-A1"), &json!([[1, 2]])),
+            Err(SpillError::MergedCell(coord!("root-B1")))
+        );
+        assert_eq!(cell_value(&grammars, &coord!("root-A1")), json!("a"));
+
+        // too many cells, nested ones included
+        let column: Vec<u32> = (0..MAX_SPILL_CELLS as u32 + 1).collect();
+        assert_eq!(
+            spill(&mut grammars, &coord!("root-A1"), &json!(column)),
+            Err(SpillError::TooLarge(MAX_SPILL_CELLS + 1))
+        );
+        let nested = json!([[[column]]]);
+        assert_eq!(
+            spill(&mut grammars, &coord!("root-A1"), &nested),
+            Err(SpillError::TooLarge(MAX_SPILL_CELLS + 2))
+        );
+        // or once the grid grows to fit the range
+        let row: Vec<u32> = (0..MAX_SPILL_CELLS as u32 / 2 + 1).collect();
+        assert_eq!(
+            spill(&mut grammars, &coord!("root-A2"), &json!([row])),
+            Err(SpillError::TooLarge(MAX_SPILL_CELLS + 2))
+        );
+        match &grammars[&coord!("root")].kind {
+            Kind::Grid(sub_coords) => assert_eq!(grid_size(sub_coords), (2, 2)),
+            _ => panic!("root should be a grid"),
+        }
+    }
+
+    #[test]
+    fn test_spill_dict_into_nested_grid() {
+        let mut grammars = sheet();
+        spill(
+            &mut grammars,
+            &coord!("root-B2"),
+            &json!({"a": 1, "b": [[true, null]]}),
+        )
+        .unwrap();
+        assert_eq!(
+            cell_value(&grammars, &coord!("root-B2")),
+            json!([["a", 1], ["b", [[true, null]]]])
+        );
+        assert_eq!(
+            spill(&mut grammars, &coord!("root"), &json!([1])),
+            Err(SpillError::NoParentGrid(coord!("root")))
+        );
+    }
+}
@@ -8,10 +8,13 @@ use std::vec::Vec;
 
 use crate::coordinate;
 use crate::coordinate::*;
+use crate::formatting::{FormatRule, StyleOverride};
 use crate::grammar;
 use crate::grammar::{Grammar, Interactive, Kind, Lookup};
 use crate::model::Model;
+use crate::theme::Theme;
 use crate::util::non_zero_u32_tuple;
+use crate::value::Format;
 use crate::{coord, coord_col, coord_row, row_col_vec};
 use yew::html::Component;
 
@@ -21,7 +24,7 @@ pub struct CoordinateParser;
 
 // Style contains the relevant CSS properties for styling
 // a grammar Cell or Grid
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Style {
     pub width: f64,            // CSS: width
     pub height: f64,           // CSS: height
@@ -32,36 +35,63 @@ pub struct Style {
     pub col_span: (u32, u32),
     pub row_span: (u32, u32),
     pub display: bool,
+    // name of a style class of the session (see `Session::style_classes`), the class
+    // applies wherever the fields above are left at their defaults
+    #[serde(default)]
+    pub class: Option<String>,
+    // how the value of the cell is shown, cells without a format show what was typed in
+    #[serde(default)]
+    pub format: Option<Format>,
 }
 js_serializable!(Style);
 js_deserializable!(Style);
 
+// cells are separated by the gaps of their grid, which already look like a border of
+// this color, so only other border colors are drawn
+pub const DEFAULT_BORDER_COLOR: &str = "grey";
+pub const DEFAULT_FONT_COLOR: &str = "black";
+pub const DEFAULT_FONT_WEIGHT: i32 = 400;
+pub const DEFAULT_CELL_WIDTH: f64 = 90.0;
+pub const DEFAULT_CELL_HEIGHT: f64 = 30.0;
+
 impl Style {
     pub fn default() -> Style {
+        Style::sized(DEFAULT_CELL_WIDTH, DEFAULT_CELL_HEIGHT)
+    }
+
+    // the style of a new cell of this size, the sheet sizes its cells with
+    // `Settings::cell_size`
+    pub fn sized(width: f64, height: f64) -> Style {
         Style {
-            width: 90.00,
-            height: 30.00,
-            border_color: "grey".to_string(),
+            width,
+            height,
+            border_color: DEFAULT_BORDER_COLOR.to_string(),
             border_collapse: false,
-            font_weight: 400,
-            font_color: "black".to_string(),
+            font_weight: DEFAULT_FONT_WEIGHT,
+            font_color: DEFAULT_FONT_COLOR.to_string(),
             col_span: (0, 0),
             row_span: (0, 0),
             display: true,
+            class: None,
+            format: None,
         }
     }
 
     pub fn to_string(&self) -> String {
+        let border = if self.border_color.is_empty() || self.border_color == DEFAULT_BORDER_COLOR {
+            String::new()
+        } else {
+            format! {"border: 1px solid {};\nbox-sizing: border-box;\n", self.border_color}
+        };
         format! {
-        "/* border: 1px; NOTE: ignoring Style::border_* for now */
-        border-collapse: {};
+        "{}border-collapse: {};
         font-weight: {};
         color: {};
         col_span: {};
         row_span: {};
         display: {};
         \n",
-        // self.border_color,
+        border,
         if self.border_collapse { "collapse" } else { "inherit" },
         self.font_weight,
         self.font_color,
@@ -72,12 +102,69 @@ impl Style {
     }
 }
 
+// StyleContext is what the style of a cell depends on besides its grammar
+#[derive(Debug, Default)]
+pub struct StyleContext<'a> {
+    // theme of the session, `None` leaves the style as it is
+    pub theme: Option<&'a Theme>,
+    // style classes of the session
+    pub classes: Option<&'a HashMap<String, StyleOverride>>,
+    // conditional formatting rules of the grids the cell is nested in, outermost first
+    pub rules: Vec<&'a FormatRule>,
+    // whether the cell failed validation
+    pub invalid: bool,
+}
+
+impl<'a> StyleContext<'a> {
+    // the style of `grammar` in this context, going from the theme to the cell's class,
+    // the cell's own style and finally the formatting rules that hold for it
+    pub fn apply(&self, grammar: &Grammar) -> Option<Style> {
+        let class = match (&grammar.style.class, self.classes) {
+            (Some(name), Some(classes)) => classes.get(name),
+            _ => None,
+        };
+        let matching: Vec<&&FormatRule> = self
+            .rules
+            .iter()
+            .filter(|rule| rule.applies_to(grammar, self.invalid))
+            .collect();
+        if self.theme.is_none() && class.is_none() && matching.is_empty() {
+            return None;
+        }
+        let mut inherited = self
+            .theme
+            .map(|theme| theme.style_override())
+            .unwrap_or_default();
+        if let Some(class) = class {
+            inherited.merge(class);
+        }
+        let mut style = grammar.style.clone();
+        inherited.apply_to_defaults(&mut style);
+        for rule in matching {
+            rule.style.apply(&mut style);
+        }
+        Some(style)
+    }
+}
+
 pub fn get_style(
     model_grammar: &Grammar,
     model_col_widths: &HashMap<coordinate::Col, f64>,
     model_row_heights: &HashMap<coordinate::Row, f64>,
     coord: &Coordinate,
+    context: &StyleContext,
 ) -> String {
+    let formatted;
+    let model_grammar = match context.apply(model_grammar) {
+        Some(style) => {
+            formatted = Grammar {
+                style,
+                ..model_grammar.clone()
+            };
+            &formatted
+        }
+        None => model_grammar,
+    };
     let grammar = model_grammar;
     // ignore root or meta
 
@@ -99,13 +186,13 @@ pub fn get_style(
         if n_col_span != 0 {
             s_col_span = format! {
                 "\ngrid-column-start: {}; grid-column: {} / span {};",
-                col_span.0.to_string(), col_span.0.to_string(), col_span.1.to_string(),
+                col_span.0.to_string(), col_span.0.to_string(), (n_col_span + 1).to_string(),
             };
         }
         if n_row_span != 0 {
             s_row_span = format! {
                 "\ngrid-row-start: {}; grid-row: {} / span {};",
-                row_span.0.to_string(), row_span.0.to_string(), row_span.1.to_string(),
+                row_span.0.to_string(), row_span.0.to_string(), (n_row_span + 1).to_string(),
             };
         }
         return format! {
@@ -154,33 +241,126 @@ mod tests {
 
     #[test]
     fn test_style_to_string() {
-        assert_eq!(Style::default().to_string(),  String::from("/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\n"));
-        // assert_ne!(Style::default().to_string(),  String::from("/* border: 1px; NOTE: ignoring Style::border_* for now */\n    border-collapse: inherit;\n    font-weight: 400;\n    color: black;\n" ));
+        assert_eq!(Style::default().to_string(),  String::from("border-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\n"));
+        // assert_ne!(Style::default().to_string(),  String::from("    border-collapse: inherit;\n    font-weight: 400;\n    color: black;\n" ));
     }
 
     #[test]
     fn test_get_style() {
         //Test type Grid
-        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Grid(row_col_vec![(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2)]),}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root-A1") ),
+        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Grid(row_col_vec![(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2)]),}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root-A1"), &StyleContext::default()),
         String::from("display: grid;\ngrid-area: cell-root-A1;\nheight: fit-content;\nwidth: fit-content !important;\ngrid-template-areas: \n\"cell-root-A1-A1 cell-root-A1-B1\"\n\"cell-root-A1-A2 cell-root-A1-B2\"\n\"cell-root-A1-A3 cell-root-A1-B3\";\n\nwidth: fit-content;\nheight: fit-content;\n"));
-        assert_ne!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Grid(row_col_vec![(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2)]),}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root-A1") ),
+        assert_ne!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Grid(row_col_vec![(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2)]),}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root-A1"), &StyleContext::default()),
         String::from("display: grid;\ngrid-area: cell-root-B1;\nheight: fit-content;\nwidth: fit-content !important;\ngrid-template-areas: \n\"cell-root-A1-A1 cell-root-A1-C1\"\n\"cell-root-A1-A2 cell-root-A1-B2\"\n\"cell-root-A1-A3 cell-root-A1-B3\";\n\nwidth: fit-content;\nheight: fit-content;\n"));
 
         //Test Row_cols length == 1
-        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Grid(row_col_vec![(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2)]),}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root") ),
+        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Grid(row_col_vec![(1, 1), (2, 1), (3, 1), (1, 2), (2, 2), (3, 2)]),}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root"), &StyleContext::default()),
         String::from("display: grid;\ngrid-area: cell-root;\nheight: fit-content;\nwidth: fit-content !important;\ngrid-template-areas: \n\"cell-root-A1 cell-root-B1\"\n\"cell-root-A2 cell-root-B2\"\n\"cell-root-A3 cell-root-B3\";\n"));
 
         //Test Kind input
-        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Input(String::default())}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root") ),
-        String::from("/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\ngrid-area: cell-root;\n"));
+        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Input(String::default())}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root"), &StyleContext::default()),
+        String::from("border-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\ngrid-area: cell-root;\n"));
 
         //Test Type interractive =>  Button as exemple
-        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Interactive(String::from("Test"), Interactive::Button())}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root") ),
-        String::from("/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\ngrid-area: cell-root;\n"));
+        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Interactive(String::from("Test"), Interactive::Button())}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root"), &StyleContext::default()),
+        String::from("border-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\ngrid-area: cell-root;\n"));
 
         // Test Type Lookup // Have to figureout the arguments
-        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Lookup(String::default(), std::option::Option::default())}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root") ),
-        String::from("/* border: 1px; NOTE: ignoring Style::border_* for now */\nborder-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\ndisplay: inline-flex; grid-area: cell-root; background: white;\n"));
+        assert_eq!(get_style(&grammar::Grammar {name: "root".to_string(), style: Style::default(), kind: Kind::Lookup(String::default(), std::option::Option::default())}, &hashmap! { coord_col!("root","A") => 90.0, coord_col!("root","B") => 90.0, coord_col!("meta","A") => 180.0, coord_col!("meta-A3","A") => 90.0, coord_col!("meta-A3","B") => 180.0,}, &hashmap! {coord_row!("root","1") => 30.0, coord_row!("root","2") => 30.0, coord_row!("root","3") => 30.0,coord_row!("meta","1") => 180.0,}, &coord!("root"), &StyleContext::default()),
+        String::from("border-collapse: inherit;\nfont-weight: 400;\ncolor: black;\n\ndisplay: inline-flex; grid-area: cell-root; background: white;\n"));
+    }
+
+    #[test]
+    fn test_border_color() {
+        assert!(!Style::default().to_string().contains("border:"));
+        let mut style = Style::default();
+        style.border_color = "red".to_string();
+        assert!(style.to_string().contains("border: 1px solid red;"));
+    }
+
+    #[test]
+    fn test_get_style_with_formatting_rules() {
+        use crate::formatting::{Condition, StyleOverride};
+        let bold = FormatRule {
+            condition: Condition::GreaterThan(10.0),
+            style: StyleOverride {
+                font_weight: Some(700),
+                border_color: Some("red".to_string()),
+                ..StyleOverride::default()
+            },
+        };
+        let blue = FormatRule {
+            condition: Condition::KindIs("Input".to_string()),
+            style: StyleOverride {
+                font_color: Some("blue".to_string()),
+                border_color: Some("blue".to_string()),
+                ..StyleOverride::default()
+            },
+        };
+        let context = StyleContext {
+            rules: vec![&bold, &blue],
+            invalid: false,
+            ..StyleContext::default()
+        };
+        let style = get_style(
+            &grammar::Grammar::input("", "11"),
+            &HashMap::new(),
+            &HashMap::new(),
+            &coord!("root-A1"),
+            &context,
+        );
+        assert!(style.contains("font-weight: 700;"));
+        assert!(style.contains("color: blue;"));
+        // later rules win
+        assert!(style.contains("border: 1px solid blue;"));
+
+        let style = get_style(
+            &grammar::Grammar::input("", "9"),
+            &HashMap::new(),
+            &HashMap::new(),
+            &coord!("root-A1"),
+            &context,
+        );
+        assert!(style.contains("font-weight: 400;"));
+    }
+
+    #[test]
+    fn test_style_classes_and_theme() {
+        let classes = hashmap! {
+            "header".to_string() => StyleOverride {
+                font_weight: Some(700),
+                font_color: Some("navy".to_string()),
+                ..StyleOverride::default()
+            },
+        };
+        let theme = Theme::dark();
+        let context = StyleContext {
+            theme: Some(&theme),
+            classes: Some(&classes),
+            ..StyleContext::default()
+        };
+
+        // the theme applies to cells without a class
+        let plain = grammar::Grammar::input("", "");
+        let style = context.apply(&plain).unwrap();
+        assert_eq!(style.font_color, theme.font_color);
+
+        // the class wins over the theme
+        let mut header = grammar::Grammar::input("", "");
+        header.style.class = Some("header".to_string());
+        let style = context.apply(&header).unwrap();
+        assert_eq!(style.font_weight, 700);
+        assert_eq!(style.font_color, "navy".to_string());
+
+        // and the cell's own style wins over the class
+        header.style.font_color = "red".to_string();
+        let style = context.apply(&header).unwrap();
+        assert_eq!(style.font_weight, 700);
+        assert_eq!(style.font_color, "red".to_string());
+
+        // unknown classes are ignored
+        header.style.class = Some("missing".to_string());
+        assert_eq!(context.apply(&header).unwrap().font_weight, 400);
     }
 
     #[test]
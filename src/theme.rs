@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+use crate::formatting::StyleOverride;
+use crate::style::{DEFAULT_CELL_HEIGHT, DEFAULT_CELL_WIDTH};
+
+// THEMES
+// A theme sets the look of a whole session: the colors and font of the sheet, and the
+// size new cells get, which the application settings override (see `Settings::cell_size`).
+// Cells only show the theme's font color and weight where their own style is left at the
+// defaults (see `StyleContext::apply`), everything else is applied through the CSS variables
+// in `Theme::css_variables` (see static/styles.css).
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub name: String,
+    pub font_family: String,
+    pub font_color: String,
+    pub font_weight: i32,
+    // background of the menus around the sheet
+    pub ui_background: String,
+    pub background: String,
+    pub cell_background: String,
+    // color of the lines between cells
+    pub grid_color: String,
+    pub cell_width: f64,
+    pub cell_height: f64,
+}
+
+pub const DEFAULT_THEME_NAME: &str = "light";
+
+impl Theme {
+    pub fn light() -> Theme {
+        Theme {
+            name: DEFAULT_THEME_NAME.to_string(),
+            font_family: "\"Lucida Console\", Monaco, monospace".to_string(),
+            font_color: "black".to_string(),
+            font_weight: 400,
+            ui_background: "#f1efef".to_string(),
+            background: "white".to_string(),
+            cell_background: "white".to_string(),
+            grid_color: "#aaa".to_string(),
+            cell_width: DEFAULT_CELL_WIDTH,
+            cell_height: DEFAULT_CELL_HEIGHT,
+        }
+    }
+
+    pub fn dark() -> Theme {
+        Theme {
+            name: "dark".to_string(),
+            font_color: "#e6e6e6".to_string(),
+            ui_background: "#2b2b2b".to_string(),
+            background: "#1e1e1e".to_string(),
+            cell_background: "#252526".to_string(),
+            grid_color: "#4a4a4a".to_string(),
+            ..Theme::light()
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Theme> {
+        themes().into_iter().find(|theme| theme.name == name)
+    }
+
+    // parts of a cell's style that come from the theme
+    pub fn style_override(&self) -> StyleOverride {
+        StyleOverride {
+            font_color: Some(self.font_color.clone()),
+            font_weight: Some(self.font_weight),
+            border_color: None,
+        }
+    }
+
+    pub fn css_variables(&self) -> String {
+        format! {
+            "--theme-font-family: {}; --theme-font-color: {}; --bg-light-grey: {}; \
+             --theme-background: {}; --theme-cell-background: {}; --theme-grid-color: {};",
+            self.font_family,
+            self.font_color,
+            self.ui_background,
+            self.background,
+            self.cell_background,
+            self.grid_color,
+        }
+    }
+}
+
+pub fn themes() -> Vec<Theme> {
+    vec![Theme::light(), Theme::dark()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_by_name() {
+        assert_eq!(Theme::by_name("dark"), Some(Theme::dark()));
+        assert_eq!(Theme::by_name(DEFAULT_THEME_NAME), Some(Theme::light()));
+        assert_eq!(Theme::by_name("solarized"), None);
+    }
+}
@@ -8,6 +8,7 @@ use stdweb::unstable::TryFrom;
 use stdweb::web::{document, HtmlElement, IHtmlElement, INonElementParentNode};
 use stdweb::Value;
 
+use crate::comments::copy_comments;
 use crate::coordinate::{Col, Coordinate, Row};
 use crate::grammar::{Grammar, Kind};
 use crate::grammar_map::*;
@@ -19,6 +20,7 @@ use crate::{g, grid, row_col_vec};
 // coordinate in the grid to another including:
 // - copying each nested grammar all the way to the innermost cell
 // - adjusting the sizes of the grammars in row_heights and col_widths
+// - copying the comments of the grammars
 //
 // TODO:
 // - add error return value that can be checked to see if grammar move was successful
@@ -34,6 +36,8 @@ pub fn move_grammar(m: &mut Model, source: Coordinate, dest: Coordinate) {
         let row_height = m.row_heights.get(&source.full_row()).unwrap_or(&30.0);
         let col_width = m.col_widths.get(&source.full_col()).unwrap_or(&90.0);
         resize(m, dest.clone(), *row_height, *col_width);
+        // comments go along with the cell
+        copy_comments(&mut m.get_session_mut().comments, &source, &dest);
         // copying over child grammar values
         if let Kind::Grid(sub_coords) = source_grammar.clone().kind {
             for sub_coord in sub_coords {
@@ -58,6 +62,18 @@ pub fn row_col_to_string((row, col): (u32, u32)) -> String {
     format! {"{}{}", col_str, row_str}
 }
 
+// the spreadsheet name of a column: A..Z, then AA, AB, ...
+pub fn col_letters(col: u32) -> String {
+    let mut letters = Vec::new();
+    let mut n = col;
+    while n > 0 {
+        n -= 1;
+        letters.push(from_u32(n % 26 + 65).unwrap());
+        n /= 26;
+    }
+    letters.iter().rev().collect()
+}
+
 pub fn coord_show(row_cols: Vec<(u32, u32)>) -> Option<String> {
     match row_cols.split_first() {
         Some((&(1, 1), rest)) => {
@@ -240,6 +256,139 @@ pub fn dom_resize(m: &mut Model, on: Coordinate) {
     */
 }
 
+// MERGING CELLS
+// A merged range of cells is shown by its top-left cell (the anchor), which takes the size
+// of the whole range. Every cell of the range remembers the range through
+// `Style::row_span` and `Style::col_span` (which are (0, 0) for cells that aren't merged),
+// and all cells but the anchor are hidden through `Style::display`.
+
+// the cells of the range (`start` to `end` of the grid at `parent`) that are about to lose
+// their content when the range is merged, i.e. every non-empty cell but the anchor
+pub fn merge_discards(
+    grammars: &HashMap<Coordinate, Grammar>,
+    parent: &Coordinate,
+    start: (NonZeroU32, NonZeroU32),
+    end: (NonZeroU32, NonZeroU32),
+) -> Vec<Coordinate> {
+    range_coords(parent, start, end)
+        .into_iter()
+        .skip(1)
+        .filter(|coord| match grammars.get(coord).map(|g| &g.kind) {
+            Some(Kind::Input(value)) => !value.is_empty(),
+            Some(_) => true,
+            None => false,
+        })
+        .collect()
+}
+
+// merges the range into its anchor, keeping the content of the anchor only. Ranges that
+// were merged before inside of it are unmerged first.
+pub fn merge_cells(
+    grammars: &mut HashMap<Coordinate, Grammar>,
+    parent: &Coordinate,
+    start: (NonZeroU32, NonZeroU32),
+    end: (NonZeroU32, NonZeroU32),
+) {
+    let coords = range_coords(parent, start, end);
+    for coord in coords.iter() {
+        unmerge_cells(grammars, coord);
+    }
+    let anchor = Coordinate::child_of(parent, start);
+    let size_of = |grammars: &HashMap<Coordinate, Grammar>, coord: &Coordinate| {
+        grammars
+            .get(coord)
+            .map_or((0.0, 0.0), |g| (g.style.width, g.style.height))
+    };
+    // the width of the first row of the range and the height of its first column
+    let mut width = 0.0;
+    let mut height = 0.0;
+    for coord in coords.iter() {
+        if coord.row() == start.0 {
+            width += size_of(grammars, coord).0;
+        }
+        if coord.col() == start.1 {
+            height += size_of(grammars, coord).1;
+        }
+    }
+    for coord in coords.iter() {
+        if let Some(grammar) = grammars.get_mut(coord) {
+            grammar.style.row_span = (start.0.get(), end.0.get());
+            grammar.style.col_span = (start.1.get(), end.1.get());
+            if *coord == anchor {
+                grammar.style.width = width;
+                grammar.style.height = height;
+            } else {
+                grammar.style.display = false;
+                grammar.kind = Kind::Input(String::new());
+            }
+        }
+        // hidden cells don't keep nested grids around
+        if *coord != anchor {
+            grammars.retain(|c, _| c == coord || !c.row_cols.starts_with(&coord.row_cols));
+        }
+    }
+}
+
+// splits the merged range `coord` belongs to back into its cells, returns false if `coord`
+// isn't merged
+pub fn unmerge_cells(grammars: &mut HashMap<Coordinate, Grammar>, coord: &Coordinate) -> bool {
+    let (row_span, col_span) = match grammars.get(coord) {
+        Some(grammar) if grammar.style.col_span.0 != 0 && grammar.style.row_span.0 != 0 => {
+            (grammar.style.row_span, grammar.style.col_span)
+        }
+        _ => return false,
+    };
+    let parent = match coord.parent() {
+        Some(parent) => parent,
+        None => return false,
+    };
+    let start = non_zero_u32_tuple((row_span.0, col_span.0));
+    let end = non_zero_u32_tuple((row_span.1, col_span.1));
+    let anchor = Coordinate::child_of(&parent, start);
+    // the hidden cells kept their sizes, so the anchor gets back the size that's left
+    let (mut width, mut height) = grammars
+        .get(&anchor)
+        .map_or((0.0, 0.0), |g| (g.style.width, g.style.height));
+    let coords = range_coords(&parent, start, end);
+    for c in coords.iter().filter(|c| **c != anchor) {
+        if let Some(grammar) = grammars.get(c) {
+            if c.row() == start.0 {
+                width -= grammar.style.width;
+            }
+            if c.col() == start.1 {
+                height -= grammar.style.height;
+            }
+        }
+    }
+    for c in coords.iter() {
+        if let Some(grammar) = grammars.get_mut(c) {
+            grammar.style.row_span = (0, 0);
+            grammar.style.col_span = (0, 0);
+            grammar.style.display = true;
+            if *c == anchor {
+                grammar.style.width = width;
+                grammar.style.height = height;
+            }
+        }
+    }
+    true
+}
+
+// the coordinates of the cells of a range, row by row starting with the anchor
+pub fn range_coords(
+    parent: &Coordinate,
+    start: (NonZeroU32, NonZeroU32),
+    end: (NonZeroU32, NonZeroU32),
+) -> Vec<Coordinate> {
+    let mut coords = Vec::new();
+    for row in start.0.get()..=end.0.get() {
+        for col in start.1.get()..=end.1.get() {
+            coords.push(Coordinate::child_of(parent, non_zero_u32_tuple((row, col))));
+        }
+    }
+    coords
+}
+
 // macro for easily defining a vector of non-zero tuples
 // used in Coordinate::root() below
 #[macro_export]
@@ -258,6 +407,9 @@ macro_rules! row_col_vec {
 /* TODO: get this working so w can color code lookups */
 mod tests {
     use super::*;
+    use crate::coord;
+    use crate::coordinate::{CoordinateParser, Rule};
+    use pest::Parser;
 
     #[test]
     fn test_non_zero_u32_tuple() {
@@ -277,6 +429,64 @@ mod tests {
         assert_ne!(row_col_to_string((2, 2)), "A2");
     }
 
+    fn merge_grid() -> HashMap<Coordinate, Grammar> {
+        let mut grammars = HashMap::new();
+        for row in 1..=2 {
+            for col in 1..=2 {
+                let coord =
+                    Coordinate::child_of(&coord!("meta-A1"), non_zero_u32_tuple((row, col)));
+                let mut grammar = Grammar::input(String::new(), format! {"{}{}", row, col});
+                grammar.style.width = 90.0;
+                grammar.style.height = 30.0;
+                grammars.insert(coord, grammar);
+            }
+        }
+        grammars
+    }
+
+    #[test]
+    fn test_merge_cells() {
+        let mut grammars = merge_grid();
+        let parent = coord!("meta-A1");
+        let (start, end) = (non_zero_u32_tuple((1, 1)), non_zero_u32_tuple((2, 2)));
+        assert_eq!(
+            merge_discards(&grammars, &parent, start, end),
+            vec![
+                coord!("meta-A1-B1"),
+                coord!("meta-A1-A2"),
+                coord!("meta-A1-B2")
+            ]
+        );
+        merge_cells(&mut grammars, &parent, start, end);
+        let anchor = &grammars[&coord!("meta-A1-A1")];
+        assert_eq!(anchor.kind, Kind::Input("11".to_string()));
+        assert_eq!((anchor.style.width, anchor.style.height), (180.0, 60.0));
+        assert_eq!(anchor.style.col_span, (1, 2));
+        let hidden = &grammars[&coord!("meta-A1-B2")];
+        assert!(!hidden.style.display);
+        assert_eq!(hidden.kind, Kind::Input(String::new()));
+        assert!(merge_discards(&grammars, &parent, start, end).is_empty());
+    }
+
+    #[test]
+    fn test_unmerge_cells() {
+        let mut grammars = merge_grid();
+        let parent = coord!("meta-A1");
+        merge_cells(
+            &mut grammars,
+            &parent,
+            non_zero_u32_tuple((1, 1)),
+            non_zero_u32_tuple((1, 2)),
+        );
+        assert!(!unmerge_cells(&mut grammars, &coord!("meta-A1-A2")));
+        assert!(unmerge_cells(&mut grammars, &coord!("meta-A1-B1")));
+        for grammar in grammars.values() {
+            assert!(grammar.style.display);
+            assert_eq!(grammar.style.col_span, (0, 0));
+            assert_eq!((grammar.style.width, grammar.style.height), (90.0, 30.0));
+        }
+    }
+
     #[test]
     fn test_coord_show() {
         assert_eq!(coord_show(vec![(1, 1), (1, 1)]).unwrap(), "root-A1");
@@ -0,0 +1,537 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::num::NonZeroU32;
+
+use crate::coordinate::{Col, Coordinate, Line, Row};
+use crate::formatting::regex;
+use crate::util::col_letters;
+use crate::value::CellValue;
+
+// DATA VALIDATION
+// Validation rules are stored in `Session::validations` and apply to a cell, a row or a
+// column of a grid, or every cell nested in a grid. Each time an input cell is edited, its
+// new value is checked against every rule that applies to it. Values that fail are marked
+// on the cell, or aren't accepted at all if one of the failing rules is strict. Empty
+// cells are always valid, so a cell can be cleared.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ValidationTarget {
+    Cell(Coordinate),
+    Row(Row),
+    Col(Col),
+    // every cell nested in the grid, at any depth
+    Grid(Coordinate),
+}
+
+impl ValidationTarget {
+    pub fn contains(&self, coord: &Coordinate) -> bool {
+        match self {
+            ValidationTarget::Cell(cell) => cell == coord,
+            ValidationTarget::Row(Row(parent, row)) => {
+                coord.parent().as_ref() == Some(parent) && coord.row() == *row
+            }
+            ValidationTarget::Col(Col(parent, col)) => {
+                coord.parent().as_ref() == Some(parent) && coord.col() == *col
+            }
+            ValidationTarget::Grid(grid) => {
+                coord.row_cols.len() > grid.row_cols.len()
+                    && coord.row_cols.starts_with(&grid.row_cols)
+            }
+        }
+    }
+}
+
+// where the target ends up once a row/column is deleted from the grid at `parent`, or None
+// if it's deleted with it
+pub fn target_after_line_deleted(
+    target: &ValidationTarget,
+    parent: &Coordinate,
+    line: Line,
+    index: NonZeroU32,
+) -> Option<ValidationTarget> {
+    // the row/column of a grid moves up by one if it's after the deleted one
+    let shifted = |target_parent: &Coordinate, target_line: Line, position: NonZeroU32| {
+        if target_parent != parent || target_line != line || position < index {
+            Some(position)
+        } else if position == index {
+            None
+        } else {
+            NonZeroU32::new(position.get() - 1)
+        }
+    };
+    match target {
+        ValidationTarget::Cell(cell) => cell
+            .after_line_deleted(parent, line, index)
+            .map(ValidationTarget::Cell),
+        ValidationTarget::Row(Row(row_parent, row)) => {
+            let row = shifted(row_parent, Line::Row, *row)?;
+            let row_parent = row_parent.after_line_deleted(parent, line, index)?;
+            Some(ValidationTarget::Row(Row(row_parent, row)))
+        }
+        ValidationTarget::Col(Col(col_parent, col)) => {
+            let col = shifted(col_parent, Line::Col, *col)?;
+            let col_parent = col_parent.after_line_deleted(parent, line, index)?;
+            Some(ValidationTarget::Col(Col(col_parent, col)))
+        }
+        ValidationTarget::Grid(grid) => grid
+            .after_line_deleted(parent, line, index)
+            .map(ValidationTarget::Grid),
+    }
+}
+
+// moves the validations along with their targets once a row/column is deleted, dropping
+// the ones whose target is deleted
+pub fn delete_line(
+    validations: &mut Vec<Validation>,
+    parent: &Coordinate,
+    line: Line,
+    index: NonZeroU32,
+) {
+    let old_validations = std::mem::take(validations);
+    for mut validation in old_validations {
+        if let Some(target) = target_after_line_deleted(&validation.target, parent, line, index) {
+            validation.target = target;
+            validations.push(validation);
+        }
+    }
+}
+
+impl fmt::Display for ValidationTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationTarget::Cell(cell) => write!(f, "cell {}", cell.to_string()),
+            ValidationTarget::Row(Row(parent, row)) => {
+                write!(f, "row {} of {}", row, parent.to_string())
+            }
+            ValidationTarget::Col(Col(parent, col)) => write!(
+                f,
+                "column {} of {}",
+                col_letters(col.get()),
+                parent.to_string()
+            ),
+            ValidationTarget::Grid(grid) => write!(f, "grid {}", grid.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ValidationRule {
+    // a number, within the bounds that are set
+    NumberRange { min: Option<f64>, max: Option<f64> },
+    // a whole number
+    Integer,
+    // text matching the regular expression
+    Matches(String),
+    // at most this many characters
+    MaxLength(usize),
+    // one of a fixed list of values, offered as a dropdown in the cell
+    OneOf(Vec<String>),
+}
+
+impl ValidationRule {
+    pub fn check(&self, raw: &str) -> Result<(), String> {
+        let value = CellValue::parse(raw);
+        if value == CellValue::Empty {
+            return Ok(());
+        }
+        match self {
+            ValidationRule::NumberRange { min, max } => match value.as_number() {
+                Some(n) if min.map_or(false, |min| n < min) => {
+                    Err(format! {"{} is less than {}", n, min.unwrap()})
+                }
+                Some(n) if max.map_or(false, |max| n > max) => {
+                    Err(format! {"{} is more than {}", n, max.unwrap()})
+                }
+                Some(_) => Ok(()),
+                None => Err(format! {"\"{}\" is not a number", raw}),
+            },
+            ValidationRule::Integer => match value.as_number() {
+                Some(n) if n.fract() == 0.0 => Ok(()),
+                _ => Err(format! {"\"{}\" is not a whole number", raw}),
+            },
+            ValidationRule::Matches(pattern) => match regex(pattern) {
+                Ok(re) if re.is_match(raw) => Ok(()),
+                Ok(_) => Err(format! {"\"{}\" doesn't match /{}/", raw, pattern}),
+                Err(e) => Err(e),
+            },
+            ValidationRule::MaxLength(length) if raw.chars().count() > *length => {
+                Err(format! {"longer than {} characters", length})
+            }
+            ValidationRule::MaxLength(_) => Ok(()),
+            ValidationRule::OneOf(values) if values.iter().any(|v| v == raw.trim()) => Ok(()),
+            ValidationRule::OneOf(values) => {
+                Err(format! {"\"{}\" is not one of {}", raw, values.join(", ")})
+            }
+        }
+    }
+}
+
+impl fmt::Display for ValidationRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationRule::NumberRange { min, max } => write!(
+                f,
+                "number in {}..{}",
+                min.map(|n| n.to_string()).unwrap_or_default(),
+                max.map(|n| n.to_string()).unwrap_or_default()
+            ),
+            ValidationRule::Integer => write!(f, "whole number"),
+            ValidationRule::Matches(pattern) => write!(f, "matches /{}/", pattern),
+            ValidationRule::MaxLength(length) => write!(f, "at most {} characters", length),
+            ValidationRule::OneOf(values) => write!(f, "one of {}", values.join(", ")),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Validation {
+    pub target: ValidationTarget,
+    pub rule: ValidationRule,
+    // values failing a strict rule aren't accepted, others are only marked as invalid
+    #[serde(default)]
+    pub strict: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub message: String,
+    pub strict: bool,
+}
+
+// checks the value typed into the cell at `coord` against every rule that applies to it
+pub fn validate(
+    validations: &[Validation],
+    coord: &Coordinate,
+    raw: &str,
+) -> Result<(), ValidationError> {
+    let failed: Vec<(&Validation, String)> = validations
+        .iter()
+        .filter(|v| v.target.contains(coord))
+        .filter_map(|v| v.rule.check(raw).err().map(|message| (v, message)))
+        .collect();
+    if failed.is_empty() {
+        return Ok(());
+    }
+    Err(ValidationError {
+        message: failed
+            .iter()
+            .map(|(_, message)| message.clone())
+            .collect::<Vec<String>>()
+            .join("; "),
+        strict: failed.iter().any(|(v, _)| v.strict),
+    })
+}
+
+// the values the cell can be set to from a dropdown, if a `OneOf` rule applies to it
+pub fn allowed_values(validations: &[Validation], coord: &Coordinate) -> Option<Vec<String>> {
+    validations
+        .iter()
+        .filter(|v| v.target.contains(coord))
+        .find_map(|v| match &v.rule {
+            ValidationRule::OneOf(values) => Some(values.clone()),
+            _ => None,
+        })
+}
+
+pub const TARGET_NAMES: [&str; 4] = ["Cell", "Row", "Col", "Grid"];
+
+pub const RULE_NAMES: [&str; 5] = ["NumberRange", "Integer", "Matches", "MaxLength", "OneOf"];
+
+// ValidationDraft holds the fields of the "add validation" form while they're filled in,
+// the target is taken relative to the active cell
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationDraft {
+    // one of TARGET_NAMES
+    pub target: String,
+    // one of RULE_NAMES
+    pub rule: String,
+    // "min..max" for number ranges (either side can be left out), the pattern, the
+    // length, or a comma separated list of values
+    pub value: String,
+    pub strict: bool,
+}
+
+impl Default for ValidationDraft {
+    fn default() -> ValidationDraft {
+        ValidationDraft {
+            target: TARGET_NAMES[0].to_string(),
+            rule: RULE_NAMES[0].to_string(),
+            value: String::new(),
+            strict: false,
+        }
+    }
+}
+
+impl ValidationDraft {
+    pub fn to_validation(&self, active_cell: &Coordinate) -> Result<Validation, String> {
+        let not_in_grid = || format! {"{} is not in a grid", active_cell.to_string()};
+        let target = match self.target.as_str() {
+            "Cell" => ValidationTarget::Cell(active_cell.clone()),
+            "Row" => {
+                let parent = active_cell.parent().ok_or_else(not_in_grid)?;
+                ValidationTarget::Row(Row(parent, active_cell.row()))
+            }
+            "Col" => {
+                let parent = active_cell.parent().ok_or_else(not_in_grid)?;
+                ValidationTarget::Col(Col(parent, active_cell.col()))
+            }
+            "Grid" => ValidationTarget::Grid(active_cell.parent().ok_or_else(not_in_grid)?),
+            other => return Err(format! {"unknown target \"{}\"", other}),
+        };
+        let value = self.value.trim();
+        let bound = |s: &str| -> Result<Option<f64>, String> {
+            let s = s.trim();
+            if s.is_empty() {
+                return Ok(None);
+            }
+            s.parse::<f64>()
+                .map(Some)
+                .map_err(|_| format! {"\"{}\" is not a number", s})
+        };
+        let rule = match self.rule.as_str() {
+            "NumberRange" => {
+                let (min, max) = match value.find("..") {
+                    Some(i) => (bound(&value[..i])?, bound(&value[i + 2..])?),
+                    None => return Err("expected a range like 0..100".to_string()),
+                };
+                if let (Some(min), Some(max)) = (min, max) {
+                    if min > max {
+                        return Err(format! {"{} is more than {}", min, max});
+                    }
+                }
+                ValidationRule::NumberRange { min, max }
+            }
+            "Integer" => ValidationRule::Integer,
+            "Matches" => {
+                regex(value)?;
+                ValidationRule::Matches(value.to_string())
+            }
+            "MaxLength" => ValidationRule::MaxLength(
+                value
+                    .parse()
+                    .map_err(|_| format! {"\"{}\" is not a length", value})?,
+            ),
+            "OneOf" => {
+                let values: Vec<String> = value
+                    .split(',')
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty())
+                    .collect();
+                if values.is_empty() {
+                    return Err("expected a comma separated list of values".to_string());
+                }
+                ValidationRule::OneOf(values)
+            }
+            other => return Err(format! {"unknown rule \"{}\"", other}),
+        };
+        Ok(Validation {
+            target,
+            rule,
+            strict: self.strict,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coord;
+    use crate::coordinate::{CoordinateParser, Rule};
+    use crate::util::non_zero_u32_tuple;
+    use pest::Parser;
+    use std::num::NonZeroU32;
+
+    fn validation(target: ValidationTarget, rule: ValidationRule, strict: bool) -> Validation {
+        Validation {
+            target,
+            rule,
+            strict,
+        }
+    }
+
+    #[test]
+    fn test_targets() {
+        let cell = coord!("root-A1-B2");
+        assert!(ValidationTarget::Cell(coord!("root-A1-B2")).contains(&cell));
+        assert!(
+            ValidationTarget::Row(Row(coord!("root-A1"), NonZeroU32::new(2).unwrap()))
+                .contains(&cell)
+        );
+        assert!(
+            !ValidationTarget::Col(Col(coord!("root-A1"), NonZeroU32::new(1).unwrap()))
+                .contains(&cell)
+        );
+        assert!(ValidationTarget::Grid(coord!("root")).contains(&cell));
+        assert!(!ValidationTarget::Grid(coord!("root-A1-B2")).contains(&cell));
+    }
+
+    #[test]
+    fn test_rules() {
+        let range = ValidationRule::NumberRange {
+            min: Some(0.0),
+            max: Some(10.0),
+        };
+        assert!(range.check("10").is_ok());
+        assert!(range.check("-1").is_err());
+        assert!(range.check("ten").is_err());
+        assert!(range.check("").is_ok());
+        assert!(ValidationRule::Integer.check("1,000").is_ok());
+        assert!(ValidationRule::Integer.check("1.5").is_err());
+        assert!(ValidationRule::Matches("^[a-z]+$".to_string())
+            .check("abc")
+            .is_ok());
+        assert!(ValidationRule::MaxLength(3).check("abcd").is_err());
+        let one_of = ValidationRule::OneOf(vec!["yes".to_string(), "no".to_string()]);
+        assert!(one_of.check("no").is_ok());
+        assert!(one_of.check("maybe").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let validations = vec![
+            validation(
+                ValidationTarget::Grid(coord!("root")),
+                ValidationRule::MaxLength(2),
+                false,
+            ),
+            validation(
+                ValidationTarget::Cell(coord!("root-A1")),
+                ValidationRule::Integer,
+                true,
+            ),
+        ];
+        assert_eq!(validate(&validations, &coord!("root-A1"), "12"), Ok(()));
+        assert_eq!(
+            validate(&validations, &coord!("root-B1"), "abc"),
+            Err(ValidationError {
+                message: "longer than 2 characters".to_string(),
+                strict: false,
+            })
+        );
+        let error = validate(&validations, &coord!("root-A1"), "1.5").unwrap_err();
+        assert!(error.strict);
+        assert_eq!(allowed_values(&validations, &coord!("root-A1")), None);
+    }
+
+    #[test]
+    fn test_delete_line() {
+        let two = NonZeroU32::new(2).unwrap();
+        let three = NonZeroU32::new(3).unwrap();
+        let mut validations = vec![
+            validation(
+                ValidationTarget::Cell(coord!("root-A3")),
+                ValidationRule::Integer,
+                false,
+            ),
+            validation(
+                ValidationTarget::Row(Row(coord!("root"), two)),
+                ValidationRule::Integer,
+                false,
+            ),
+            validation(
+                ValidationTarget::Row(Row(coord!("root"), three)),
+                ValidationRule::Integer,
+                false,
+            ),
+            validation(
+                ValidationTarget::Col(Col(coord!("root-A3"), two)),
+                ValidationRule::Integer,
+                false,
+            ),
+            validation(
+                ValidationTarget::Grid(coord!("root-B4")),
+                ValidationRule::Integer,
+                false,
+            ),
+        ];
+        delete_line(&mut validations, &coord!("root"), Line::Row, two);
+        let targets: Vec<ValidationTarget> = validations.iter().map(|v| v.target.clone()).collect();
+        assert_eq!(
+            targets,
+            vec![
+                ValidationTarget::Cell(coord!("root-A2")),
+                ValidationTarget::Row(Row(coord!("root"), two)),
+                ValidationTarget::Col(Col(coord!("root-A2"), two)),
+                ValidationTarget::Grid(coord!("root-B3")),
+            ]
+        );
+        delete_line(&mut validations, &coord!("root-A2"), Line::Col, two);
+        assert_eq!(validations.len(), 3);
+        delete_line(&mut validations, &coord!("root"), Line::Col, two);
+        assert_eq!(
+            validations.last().map(|v| v.target.clone()),
+            Some(ValidationTarget::Row(Row(coord!("root"), two)))
+        );
+    }
+
+    #[test]
+    fn test_target_names() {
+        let col = |n| Col(coord!("root"), NonZeroU32::new(n).unwrap());
+        assert_eq!(
+            ValidationTarget::Col(col(26)).to_string(),
+            "column Z of root"
+        );
+        assert_eq!(
+            ValidationTarget::Col(col(27)).to_string(),
+            "column AA of root"
+        );
+        assert_eq!(
+            ValidationTarget::Col(col(703)).to_string(),
+            "column AAA of root"
+        );
+    }
+
+    #[test]
+    fn test_validation_serialization() {
+        let validation = validation(
+            ValidationTarget::Row(Row(coord!("root-A1"), NonZeroU32::new(2).unwrap())),
+            ValidationRule::OneOf(vec!["yes".to_string(), "no".to_string()]),
+            true,
+        );
+        let json = serde_json::to_string(&validation).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Validation>(&json).unwrap(),
+            validation
+        );
+    }
+
+    #[test]
+    fn test_draft_to_validation() {
+        let draft = ValidationDraft {
+            target: "Col".to_string(),
+            value: "..100".to_string(),
+            ..ValidationDraft::default()
+        };
+        assert_eq!(
+            draft.to_validation(&coord!("root-B3")),
+            Ok(validation(
+                ValidationTarget::Col(Col(coord!("root"), NonZeroU32::new(2).unwrap())),
+                ValidationRule::NumberRange {
+                    min: None,
+                    max: Some(100.0),
+                },
+                false,
+            ))
+        );
+        assert!(draft.to_validation(&coord!("root")).is_err());
+        assert!(ValidationDraft {
+            value: "10..1".to_string(),
+            ..draft.clone()
+        }
+        .to_validation(&coord!("root-B3"))
+        .is_err());
+        assert_eq!(
+            ValidationDraft {
+                rule: "OneOf".to_string(),
+                value: "a, b,".to_string(),
+                ..draft
+            }
+            .to_validation(&coord!("root-B3"))
+            .map(|v| v.rule),
+            Ok(ValidationRule::OneOf(vec![
+                "a".to_string(),
+                "b".to_string()
+            ]))
+        );
+    }
+}
@@ -0,0 +1,520 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// TYPED VALUES
+// Input cells keep exactly what was typed in (`Kind::Input`), which is what gets stored in
+// the session, so nothing is lost by saving and loading. The typed value of a cell is
+// parsed from that text whenever it's needed: by lookups and scripts (see
+// `sheet::cell_value`), formatting rules, CSV export and the display format of the cell
+// (`Style::format`). Starting the text with an apostrophe keeps it as text.
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum CellValue {
+    Empty,
+    Number(f64),
+    Bool(bool),
+    Date(Date),
+    Text(String),
+}
+
+// a calendar date, optionally with a time of day
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+    pub time: Option<(u32, u32, u32)>,
+}
+
+// currency symbols that may prefix a number, e.g. "$1,200"
+const CURRENCY_SYMBOLS: [&str; 4] = ["$", "€", "£", "¥"];
+
+impl CellValue {
+    pub fn parse(raw: &str) -> CellValue {
+        if let Some(text) = raw.strip_prefix('\'') {
+            return CellValue::Text(text.to_string());
+        }
+        let trimmed = raw.trim();
+        if trimmed.is_empty() {
+            return CellValue::Empty;
+        }
+        if trimmed.eq_ignore_ascii_case("true") {
+            return CellValue::Bool(true);
+        }
+        if trimmed.eq_ignore_ascii_case("false") {
+            return CellValue::Bool(false);
+        }
+        if let Some(n) = parse_number(trimmed) {
+            return CellValue::Number(n);
+        }
+        if let Some(date) = Date::parse(trimmed) {
+            return CellValue::Date(date);
+        }
+        CellValue::Text(raw.to_string())
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            CellValue::Empty => "empty",
+            CellValue::Number(_) => "number",
+            CellValue::Bool(_) => "boolean",
+            CellValue::Date(_) => "date",
+            CellValue::Text(_) => "text",
+        }
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            CellValue::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    // the value handed to scripts, dates become ISO 8601 strings and whole numbers are
+    // integers, so "1,200" is 1200 rather than 1200.0
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            CellValue::Empty => serde_json::Value::Null,
+            CellValue::Number(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => {
+                serde_json::Value::from(*n as i64)
+            }
+            CellValue::Number(n) => serde_json::Number::from_f64(*n)
+                .map(serde_json::Value::Number)
+                .unwrap_or(serde_json::Value::Null),
+            CellValue::Bool(b) => serde_json::Value::Bool(*b),
+            CellValue::Date(date) => serde_json::Value::String(date.to_string()),
+            CellValue::Text(s) => serde_json::Value::String(s.clone()),
+        }
+    }
+}
+
+// the canonical text of a value, which parses back to the same value
+impl fmt::Display for CellValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CellValue::Empty => Ok(()),
+            CellValue::Number(n) => write!(f, "{}", n),
+            CellValue::Bool(b) => write!(f, "{}", b),
+            CellValue::Date(date) => write!(f, "{}", date),
+            CellValue::Text(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+// parses numbers like "-1.5", "1e3", "1,234.5", "12%" and "$20", numbers with leading
+// zeros ("007") are left as text since they're usually identifiers
+fn parse_number(s: &str) -> Option<f64> {
+    let (negative, s) = match s.chars().next() {
+        Some('-') => (true, &s[1..]),
+        Some('+') => (false, &s[1..]),
+        _ => (false, s),
+    };
+    let s = CURRENCY_SYMBOLS
+        .iter()
+        .find_map(|symbol| s.strip_prefix(symbol))
+        .unwrap_or(s);
+    let (percent, s) = match s.strip_suffix('%') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+    let (integer, fraction) = match mantissa.find('.') {
+        Some(i) => (&mantissa[..i], Some(&mantissa[i + 1..])),
+        None => (mantissa, None),
+    };
+    let digits = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+    let integer = if integer.contains(',') {
+        // thousands separators have to group the digits by three
+        let groups: Vec<&str> = integer.split(',').collect();
+        if groups[0].len() > 3 || groups[1..].iter().any(|group| group.len() != 3) {
+            return None;
+        }
+        groups.concat()
+    } else {
+        integer.to_string()
+    };
+    if !(integer.is_empty() || digits(&integer))
+        || (integer.len() > 1 && integer.starts_with('0'))
+        || fraction.map_or(false, |f| !digits(f))
+        || (integer.is_empty() && fraction.is_none())
+    {
+        return None;
+    }
+    if let Some(exponent) = exponent {
+        let unsigned = exponent.strip_prefix(['-', '+']).unwrap_or(exponent);
+        if !digits(unsigned) {
+            return None;
+        }
+    }
+    let mut number: f64 = format! {
+        "{}.{}e{}",
+        if integer.is_empty() { "0" } else { &integer },
+        fraction.unwrap_or("0"),
+        exponent.unwrap_or("0"),
+    }
+    .parse()
+    .ok()?;
+    if percent {
+        number /= 100.0;
+    }
+    if !number.is_finite() {
+        return None;
+    }
+    Some(if negative { -number } else { number })
+}
+
+impl Date {
+    // parses "YYYY-MM-DD", optionally followed by "THH:MM" or "THH:MM:SS" (a space
+    // works in place of the "T")
+    pub fn parse(s: &str) -> Option<Date> {
+        let (date, time) = match s.find(['T', ' ']) {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => (s, None),
+        };
+        let parts: Vec<&str> = date.split('-').collect();
+        if parts.len() != 3 || parts[0].len() != 4 || parts[1].len() != 2 || parts[2].len() != 2 {
+            return None;
+        }
+        let number = |part: &str| -> Option<u32> {
+            if part.chars().all(|c| c.is_ascii_digit()) {
+                part.parse().ok()
+            } else {
+                None
+            }
+        };
+        let year = number(parts[0])? as i32;
+        let month = number(parts[1])?;
+        let day = number(parts[2])?;
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+            return None;
+        }
+        let time = match time {
+            Some(time) => {
+                let parts: Vec<&str> = time.split(':').collect();
+                if parts.len() < 2 || parts.len() > 3 || parts.iter().any(|p| p.len() != 2) {
+                    return None;
+                }
+                let hour = number(parts[0])?;
+                let minute = number(parts[1])?;
+                let second = parts.get(2).map_or(Some(0), |p| number(p))?;
+                if hour > 23 || minute > 59 || second > 59 {
+                    return None;
+                }
+                Some((hour, minute, second))
+            }
+            None => None,
+        };
+        Some(Date {
+            year,
+            month,
+            day,
+            time,
+        })
+    }
+
+    // writes the date with a pattern where YYYY, MM, DD, hh, mm and ss are replaced by
+    // the year, month, day, hour, minute and second
+    pub fn format(&self, pattern: &str) -> String {
+        let (hour, minute, second) = self.time.unwrap_or((0, 0, 0));
+        let fields = [
+            ("YYYY", format! {"{:04}", self.year}),
+            ("MM", format! {"{:02}", self.month}),
+            ("DD", format! {"{:02}", self.day}),
+            ("hh", format! {"{:02}", hour}),
+            ("mm", format! {"{:02}", minute}),
+            ("ss", format! {"{:02}", second}),
+        ];
+        let mut formatted = String::new();
+        let mut rest = pattern;
+        'outer: while let Some(c) = rest.chars().next() {
+            for (token, value) in fields.iter() {
+                if rest.starts_with(token) {
+                    formatted += value;
+                    rest = &rest[token.len()..];
+                    continue 'outer;
+                }
+            }
+            formatted.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+        formatted
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.time {
+            Some(_) => write!(f, "{}", self.format("YYYY-MM-DDThh:mm:ss")),
+            None => write!(f, "{}", self.format("YYYY-MM-DD")),
+        }
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Format is how a cell shows its value (see `Style::format`), values of other types
+// than the format expects are shown as they were typed in
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Format {
+    Number { decimals: u32, thousands: bool },
+    Percent { decimals: u32 },
+    Currency { symbol: String, decimals: u32 },
+    Date(/* pattern, see `Date::format` */ String),
+}
+
+pub const FORMAT_NAMES: [&str; 4] = ["Number", "Percent", "Currency", "Date"];
+
+pub const DEFAULT_DATE_PATTERN: &str = "YYYY-MM-DD";
+
+impl Format {
+    // the format called `name` with its default settings
+    pub fn with_name(name: &str) -> Option<Format> {
+        match name {
+            "Number" => Some(Format::Number {
+                decimals: 2,
+                thousands: true,
+            }),
+            "Percent" => Some(Format::Percent { decimals: 0 }),
+            "Currency" => Some(Format::Currency {
+                symbol: "$".to_string(),
+                decimals: 2,
+            }),
+            "Date" => Some(Format::Date(DEFAULT_DATE_PATTERN.to_string())),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Number { .. } => "Number",
+            Format::Percent { .. } => "Percent",
+            Format::Currency { .. } => "Currency",
+            Format::Date(_) => "Date",
+        }
+    }
+
+    // the setting of the format that can be edited as text: the number of decimal places,
+    // followed by "," if thousands are separated, or the currency symbol and decimal
+    // places ("$2"), or the date pattern
+    pub fn setting(&self) -> String {
+        match self {
+            Format::Number {
+                decimals,
+                thousands,
+            } => format! {"{}{}", decimals, if *thousands { "," } else { "" }},
+            Format::Percent { decimals } => decimals.to_string(),
+            Format::Currency { symbol, decimals } => format! {"{}{}", symbol, decimals},
+            Format::Date(pattern) => pattern.clone(),
+        }
+    }
+
+    pub fn with_setting(&self, setting: &str) -> Result<Format, String> {
+        let setting = setting.trim();
+        let decimals = |s: &str| {
+            s.parse::<u32>()
+                .ok()
+                .filter(|d| *d <= 20)
+                .ok_or(format! {"\"{}\" is not a number of decimal places", s})
+        };
+        match self {
+            Format::Number { .. } => {
+                let (digits, thousands) = match setting.strip_suffix(',') {
+                    Some(digits) => (digits, true),
+                    None => (setting, false),
+                };
+                Ok(Format::Number {
+                    decimals: decimals(digits)?,
+                    thousands,
+                })
+            }
+            Format::Percent { .. } => Ok(Format::Percent {
+                decimals: decimals(setting)?,
+            }),
+            Format::Currency { .. } => {
+                let split = setting
+                    .find(|c: char| c.is_ascii_digit())
+                    .unwrap_or(setting.len());
+                let (symbol, digits) = setting.split_at(split);
+                Ok(Format::Currency {
+                    symbol: symbol.to_string(),
+                    decimals: if digits.is_empty() {
+                        0
+                    } else {
+                        decimals(digits)?
+                    },
+                })
+            }
+            Format::Date(_) if setting.is_empty() => Err("the date pattern is empty".to_string()),
+            Format::Date(_) => Ok(Format::Date(setting.to_string())),
+        }
+    }
+
+    // the text shown for a cell that has `raw` typed in
+    pub fn display(&self, raw: &str) -> String {
+        match (self, CellValue::parse(raw)) {
+            (
+                Format::Number {
+                    decimals,
+                    thousands,
+                },
+                CellValue::Number(n),
+            ) => format_number(n, *decimals, *thousands),
+            (Format::Percent { decimals }, CellValue::Number(n)) => {
+                format! {"{}%", format_number(n * 100.0, *decimals, false)}
+            }
+            (Format::Currency { symbol, decimals }, CellValue::Number(n)) => {
+                let formatted = format_number(n.abs(), *decimals, true);
+                if n < 0.0 {
+                    format! {"-{}{}", symbol, formatted}
+                } else {
+                    format! {"{}{}", symbol, formatted}
+                }
+            }
+            (Format::Date(pattern), CellValue::Date(date)) => date.format(pattern),
+            _ => raw.to_string(),
+        }
+    }
+}
+
+fn format_number(n: f64, decimals: u32, thousands: bool) -> String {
+    let formatted = format! {"{:.*}", decimals as usize, n.abs()};
+    let (integer, fraction) = match formatted.find('.') {
+        Some(i) => formatted.split_at(i),
+        None => (formatted.as_str(), ""),
+    };
+    let mut grouped = String::new();
+    for (i, digit) in integer.chars().enumerate() {
+        if thousands && i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    // rounding may leave nothing but zeros, which shouldn't show as negative
+    let is_zero = formatted.chars().all(|c| c == '0' || c == '.');
+    format! {"{}{}{}", if n < 0.0 && !is_zero { "-" } else { "" }, grouped, fraction}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_values() {
+        assert_eq!(CellValue::parse(""), CellValue::Empty);
+        assert_eq!(CellValue::parse(" 42 "), CellValue::Number(42.0));
+        assert_eq!(CellValue::parse("-1.5e3"), CellValue::Number(-1500.0));
+        assert_eq!(CellValue::parse(".5"), CellValue::Number(0.5));
+        assert_eq!(CellValue::parse("1,234.5"), CellValue::Number(1234.5));
+        assert_eq!(CellValue::parse("12.5%"), CellValue::Number(0.125));
+        assert_eq!(CellValue::parse("-$20"), CellValue::Number(-20.0));
+        assert_eq!(CellValue::parse("TRUE"), CellValue::Bool(true));
+        assert_eq!(CellValue::parse("false"), CellValue::Bool(false));
+        // not numbers
+        for text in &["007", "1,23", "1.", "inf", "NaN", "12 apples", "1e", "'42"] {
+            assert_eq!(
+                CellValue::parse(text).type_name(),
+                "text",
+                "{} should be text",
+                text
+            );
+        }
+        assert_eq!(CellValue::parse("'42"), CellValue::Text("42".to_string()));
+    }
+
+    #[test]
+    fn test_parse_dates() {
+        assert_eq!(
+            CellValue::parse("2020-02-29"),
+            CellValue::Date(Date {
+                year: 2020,
+                month: 2,
+                day: 29,
+                time: None,
+            })
+        );
+        assert_eq!(
+            Date::parse("2020-03-01 09:30").unwrap().time,
+            Some((9, 30, 0))
+        );
+        assert_eq!(Date::parse("2019-02-29"), None);
+        assert_eq!(Date::parse("2020-13-01"), None);
+        assert_eq!(Date::parse("2020-01-01T24:00"), None);
+        assert_eq!(Date::parse("20-01-01"), None);
+    }
+
+    #[test]
+    fn test_values_round_trip() {
+        for raw in &[
+            "42",
+            "-0.1",
+            "1e300",
+            "true",
+            "2020-01-31",
+            "2020-01-31T08:05:09",
+        ] {
+            let value = CellValue::parse(raw);
+            assert_eq!(CellValue::parse(&value.to_string()), value);
+        }
+        assert_eq!(CellValue::parse("0.1").to_json(), serde_json::json!(0.1));
+        assert_eq!(CellValue::parse("1,200").to_json(), serde_json::json!(1200));
+        assert_eq!(CellValue::parse("1e300").to_json(), serde_json::json!(1e300));
+        assert_eq!(
+            CellValue::parse("2020-01-31").to_json(),
+            serde_json::json!("2020-01-31")
+        );
+        assert_eq!(CellValue::parse("").to_json(), serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_display_formats() {
+        let number = Format::with_name("Number").unwrap();
+        assert_eq!(number.display("1234567.891"), "1,234,567.89");
+        assert_eq!(number.display("-999.999"), "-1,000.00");
+        assert_eq!(number.display("-0.001"), "0.00");
+        assert_eq!(number.display("apples"), "apples");
+        assert_eq!(Format::Percent { decimals: 1 }.display("0.1234"), "12.3%");
+        assert_eq!(
+            Format::with_name("Currency").unwrap().display("-1200"),
+            "-$1,200.00"
+        );
+        assert_eq!(
+            Format::Date("DD/MM/YYYY hh:mm".to_string()).display("2020-03-01T09:30"),
+            "01/03/2020 09:30"
+        );
+    }
+
+    #[test]
+    fn test_format_settings() {
+        let number = Format::with_name("Number").unwrap();
+        assert_eq!(number.setting(), "2,");
+        assert_eq!(
+            number.with_setting("0"),
+            Ok(Format::Number {
+                decimals: 0,
+                thousands: false,
+            })
+        );
+        assert!(number.with_setting("two").is_err());
+        assert_eq!(
+            Format::with_name("Currency").unwrap().with_setting("€1"),
+            Ok(Format::Currency {
+                symbol: "€".to_string(),
+                decimals: 1,
+            })
+        );
+        assert!(Format::with_name("Date")
+            .unwrap()
+            .with_setting(" ")
+            .is_err());
+    }
+}
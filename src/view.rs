@@ -1,6 +1,5 @@
 #![recursion_limit = "1024"]
 use pest::Parser;
-use std::num::NonZeroU32;
 use std::ops::Deref;
 use stdweb::traits::IEvent;
 use stdweb::unstable::TryFrom;
@@ -13,12 +12,28 @@ use yew::services::reader::File;
 use yew::virtual_dom::vlist::VList;
 use yew::{html, ChangeData, Html, InputData};
 
-use crate::codemirror::CodeMirror;
+use crate::codemirror::{CodeMirror, EDITOR_MODES};
+use crate::collab::peer_color;
+use crate::command::Command;
+use crate::comments::has_open_comments;
 use crate::coordinate::Coordinate;
+use crate::diff::{cell_summary, Side};
+use crate::execution::BackendKind;
+use crate::explorer::{format_modified, path_name};
+use crate::formatting::{FormatRuleDraft, CONDITION_NAMES};
 use crate::grammar::{Grammar, Interactive, Kind, Lookup};
+use crate::keymap::KEYMAP_FILE;
+use crate::markdown::NestedTables;
 use crate::model::{Action, CursorType, Model, ResizeMsg, SelectMsg, SideMenu};
+use crate::names::{target_to_string, NameDraft};
+use crate::navigation::{breadcrumbs, Move};
+use crate::palette::PaletteCommand;
+use crate::settings::{Setting, SETTINGS_FILE};
 use crate::style::get_style;
-use crate::util::non_zero_u32_tuple;
+use crate::theme::themes;
+use crate::util::{non_zero_u32_tuple, range_coords};
+use crate::validation::{allowed_values, ValidationDraft, RULE_NAMES, TARGET_NAMES};
+use crate::value::{CellValue, Format, FORMAT_NAMES};
 use crate::{coord};
 
 #[derive(Parser)]
@@ -99,12 +114,105 @@ pub fn view_file_popup(m: &Model) -> Html {
     }
 }
 
+// the command palette (see src/palette.rs), Enter runs the highlighted entry, or types in
+// the title of a template so its parameter can follow
+pub fn view_command_palette(m: &Model) -> Html {
+    let palette = match &m.palette {
+        Some(palette) => palette,
+        None => return html! { <></> },
+    };
+    let entries = m.palette_entries();
+    let selected = palette.selected.min(entries.len().saturating_sub(1));
+    let selected_choice = entries
+        .get(selected)
+        .map(|entry| (entry.command.clone(), entry.completion()));
+
+    let mut entry_nodes = VList::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let choice = (entry.command.clone(), entry.completion());
+        let class = format! {"palette-entry{}{}",
+            if index == selected { " selected" } else { "" },
+            if entry.enabled { "" } else { " disabled" },
+        };
+        entry_nodes.add_child(html! {
+            <li class=class onclick=m.link.callback(move |e: ClickEvent| {
+                e.stop_propagation();
+                palette_choice(choice.clone())
+            })>
+                { entry.title.clone() }
+                {
+                    match &entry.shortcut {
+                        Some(shortcut) => html! { <span class="shortcut">{ shortcut.clone() }</span> },
+                        None => html! {},
+                    }
+                }
+                <div class="palette-description">{ entry.description.clone() }</div>
+            </li>
+        });
+    }
+    html! {
+        <div class="command-palette-overlay" onclick=m.link.callback(|_| Action::ClosePalette)>
+            <div class="command-palette" onclick=m.link.callback(|e: ClickEvent| {
+                e.stop_propagation();
+                Action::Noop
+            })>
+                <input
+                    id="command-palette-input"
+                    placeholder="Type a command, e.g. Nest grid 4x2"
+                    value=palette.query.clone()
+                    oninput=m.link.callback(|e: InputData| Action::SetPaletteQuery(e.value))
+                    onkeydown=m.link.callback(move |e: KeyDownEvent| {
+                        match e.key().deref() {
+                            "ArrowDown" => { e.prevent_default(); Action::MovePaletteSelection(1) }
+                            "ArrowUp" => { e.prevent_default(); Action::MovePaletteSelection(-1) }
+                            "Escape" => Action::ClosePalette,
+                            "Enter" => {
+                                e.prevent_default();
+                                selected_choice.clone().map_or(Action::Noop, palette_choice)
+                            }
+                            _ => Action::Noop,
+                        }
+                    })>
+                </input>
+                <ul class="palette-entries">{ entry_nodes }</ul>
+            </div>
+        </div>
+    }
+}
+
+// runs the command of a palette entry, or types in the title of its template
+fn palette_choice((command, completion): (Option<PaletteCommand>, Option<String>)) -> Action {
+    match (command, completion) {
+        (Some(command), _) => Action::RunPaletteCommand(command),
+        (None, Some(completion)) => Action::SetPaletteQuery(completion),
+        (None, None) => Action::Noop,
+    }
+}
+
 pub fn view_side_menu(m: &Model, side_menu: &SideMenu) -> Html {
     match side_menu.name.deref() {
         "Home" => {
             html! {
                 <div class="side-menu-section">
                     {"THIS IS Home MENU"}
+
+                    <h3>{"output"}</h3>
+                    { view_execution_output(m) }
+
+                    <h3>{"conditional formatting"}</h3>
+                    { view_formatting_rules(m) }
+
+                    <h3>{"style classes"}</h3>
+                    { view_style_classes(m) }
+
+                    <h3>{"value format"}</h3>
+                    { view_cell_format(m) }
+
+                    <h3>{"validation"}</h3>
+                    { view_validations(m) }
+
+                    <h3>{"names"}</h3>
+                    { view_names(m) }
                 </div>
             }
         }
@@ -115,6 +223,18 @@ pub fn view_side_menu(m: &Model, side_menu: &SideMenu) -> Html {
                         {"File Explorer"}
                     </h1>
 
+                    <h3>{"workspace"}</h3>
+                    { view_workspace(m) }
+
+                    <h3>{"recent files"}</h3>
+                    { view_recent_files(m) }
+
+                    <h3>{"changes"}</h3>
+                    { view_session_diff(m) }
+
+                    <h3>{"merge conflicts"}</h3>
+                    { view_merge_conflicts(m) }
+
                     <h3>{"load session"}</h3>
                     <br></br>
                     <input type="file" onchange=m.link.callback(|value| {
@@ -142,6 +262,14 @@ pub fn view_side_menu(m: &Model, side_menu: &SideMenu) -> Html {
                     </input>
                     <input type="button" value="Save" onclick=m.link.callback(|_| Action::SaveSession())>
                     </input>
+
+                    <h3>{"workbooks"}</h3>
+                    <button onclick=m.link.callback(|_| Action::OpenWorkbook)>
+                        { "Open .xlsx or .ods workbook" }
+                    </button>
+                    <button onclick=m.link.callback(|_| Action::ExportWorkbook)>
+                        { "Save session as .xlsx workbook" }
+                    </button>
                 </div>
             }
         }
@@ -152,6 +280,9 @@ pub fn view_side_menu(m: &Model, side_menu: &SideMenu) -> Html {
                         {"Settings"}
                     </h1>
 
+                    <h3>{"preferences"}</h3>
+                    { view_settings(m) }
+
                     <h3>{"load driver"}</h3>
                     <br></br>
                     // drivers will be represented as directories, so we use "webkitdirectory"
@@ -177,6 +308,37 @@ pub fn view_side_menu(m: &Model, side_menu: &SideMenu) -> Html {
                         Action::Noop
                     })>
                     </input>
+
+                    { view_driver_report(m) }
+
+                    <h3>{"loaded drivers"}</h3>
+                    { view_driver_list(m) }
+
+                    <h3>{"execution backend"}</h3>
+                    { view_execution_backends(m) }
+
+                    <h3>{"theme"}</h3>
+                    { view_themes(m) }
+
+                    <h3>{"keyboard shortcuts"}</h3>
+                    { view_keymap(m) }
+                </div>
+            }
+        }
+        "Comments" => {
+            html! {
+                <div class="side-menu-section">
+                    { view_comments(m) }
+                </div>
+            }
+        }
+        "Collaborate" => {
+            html! {
+                <div class="side-menu-section">
+                    <h1>
+                        {"Collaborate"}
+                    </h1>
+                    { view_collaboration(m) }
                 </div>
             }
         }
@@ -192,9 +354,1044 @@ pub fn view_side_menu(m: &Model, side_menu: &SideMenu) -> Html {
     }
 }
 
+pub fn view_comments(m: &Model) -> Html {
+    let comments = &m.get_session().comments;
+    let mut coords: Vec<&Coordinate> = comments.keys().collect();
+    coords.sort_by_key(|c| c.to_string());
+    let mut cell_nodes = VList::new();
+    for coord in coords {
+        let open = comments[coord].iter().filter(|c| !c.resolved).count();
+        let select_coord = coord.clone();
+        cell_nodes.add_child(html! {
+            <li>
+                <a onclick=m.link.callback(move |_| Action::SetActiveCell(select_coord.clone()))>
+                    { coord.to_string() }
+                </a>
+                { format!{" {} comment(s), {} open", comments[coord].len(), open} }
+            </li>
+        });
+    }
+
+    let active_cell = match m.active_cell.clone() {
+        Some(coord) => coord,
+        None => {
+            return html! {
+                <div class="comments">
+                    <ul>{ cell_nodes }</ul>
+                    <p>{ "select a cell to comment on it" }</p>
+                </div>
+            }
+        }
+    };
+    let mut thread_nodes = VList::new();
+    for (index, comment) in comments.get(&active_cell).into_iter().flatten().enumerate() {
+        let mut reply_nodes = VList::new();
+        for reply in comment.replies.iter() {
+            reply_nodes.add_child(html! {
+                <li class="comment-reply">
+                    <b>{ reply.author.clone() }</b>
+                    <span class="comment-timestamp">{ format!{" {}", reply.timestamp} }</span>
+                    <p>{ reply.text.clone() }</p>
+                </li>
+            });
+        }
+        let resolve_coord = active_cell.clone();
+        let remove_coord = active_cell.clone();
+        let reply_coord = active_cell.clone();
+        let resolved = comment.resolved;
+        thread_nodes.add_child(html! {
+            <li class=format!{"comment {}", if resolved { "resolved" } else { "" }}>
+                <b>{ comment.author.clone() }</b>
+                <span class="comment-timestamp">{ format!{" {}", comment.timestamp} }</span>
+                <p>{ comment.text.clone() }</p>
+                <ul>{ reply_nodes }</ul>
+                <input placeholder="reply"
+                    onchange=m.link.callback(move |value| {
+                        if let ChangeData::Value(text) = value {
+                            return Action::ReplyToComment(reply_coord.clone(), index, text);
+                        }
+                        Action::Noop
+                    })>
+                </input>
+                <button onclick=m.link.callback(move |_| Action::SetCommentResolved(resolve_coord.clone(), index, !resolved))>
+                    { if resolved { "Unresolve" } else { "Resolve" } }
+                </button>
+                <button onclick=m.link.callback(move |_| Action::RemoveComment(remove_coord.clone(), index))>
+                    { "Remove" }
+                </button>
+            </li>
+        });
+    }
+    let comment_coord = active_cell.clone();
+    html! {
+        <div class="comments">
+            <ul>{ cell_nodes }</ul>
+            <label>
+                { "comment as " }
+                <input value=m.comment_author.clone()
+                    onchange=m.link.callback(|value| {
+                        if let ChangeData::Value(author) = value {
+                            return Action::SetCommentAuthor(author);
+                        }
+                        Action::Noop
+                    })>
+                </input>
+            </label>
+            <h3>{ format!{"comments on {}", active_cell.to_string()} }</h3>
+            <ul>{ thread_nodes }</ul>
+            <textarea placeholder="new comment"
+                onchange=m.link.callback(move |value| {
+                    if let ChangeData::Value(text) = value {
+                        return Action::AddComment(comment_coord.clone(), text);
+                    }
+                    Action::Noop
+                })>
+            </textarea>
+        </div>
+    }
+}
+
+// the corner marker of cells with comments, it's faded once every comment is resolved
+pub fn view_comment_marker(m: &Model, coord: &Coordinate) -> Html {
+    let comments = &m.get_session().comments;
+    let thread = match comments.get(coord) {
+        Some(thread) if !thread.is_empty() => thread,
+        _ => return html! { <></> },
+    };
+    let title = thread
+        .iter()
+        .map(|c| format! {"{}: {}", c.author, c.text})
+        .collect::<Vec<String>>()
+        .join("\n");
+    html! {
+        <span
+            class=format!{
+                "comment-marker {}",
+                if has_open_comments(comments, coord) { "open" } else { "resolved" },
+            }
+            title=title>
+        </span>
+    }
+}
+
+pub fn view_driver_report(m: &Model) -> Html {
+    let report = match &m.driver_report {
+        Some(report) => report,
+        None => return html! { <></> },
+    };
+    if report.is_valid() {
+        let manifest = report
+            .manifest_info
+            .as_ref()
+            .map(|manifest| format! {" ({} {})", manifest.name, manifest.version})
+            .unwrap_or_default();
+        return html! {
+            <div class="driver-report valid">
+                { format!{
+                    "\"{}\"{} is valid: main file {}, {} other file(s)",
+                    report.directory,
+                    manifest,
+                    report.main_file.clone().unwrap_or_default(),
+                    report.misc_files.len(),
+                } }
+            </div>
+        };
+    }
+    let mut issue_nodes = VList::new();
+    for issue in report.issues.iter() {
+        issue_nodes.add_child(html! {
+            <li>{ issue.to_string() }</li>
+        });
+    }
+    html! {
+        <div class="driver-report invalid">
+            { format!{"\"{}\" could not be loaded:", report.directory} }
+            <ul>
+                { issue_nodes }
+            </ul>
+        </div>
+    }
+}
+
+pub fn view_driver_list(m: &Model) -> Html {
+    if m.drivers.drivers.is_empty() {
+        return html! { <p>{"no drivers loaded"}</p> };
+    }
+    let mut driver_nodes = VList::new();
+    for driver in m.drivers.drivers.iter() {
+        driver_nodes.add_child(html! {
+            <li>
+                <b>{ driver.name.clone() }</b>
+                { format!{" ({})", driver.definitions.join(", ")} }
+            </li>
+        });
+    }
+    html! {
+        <ul class="driver-list">
+            { driver_nodes }
+        </ul>
+    }
+}
+
+pub fn view_execution_backends(m: &Model) -> Html {
+    let current = m.executions.backend.kind();
+    let mut option_nodes = VList::new();
+    for kind in [BackendKind::Pyodide, BackendKind::NodePython].iter() {
+        option_nodes.add_child(html! {
+            <option value=format!{"{:?}", kind} selected=(*kind == current)>
+                { kind.name() }
+            </option>
+        });
+    }
+    html! {
+        <select onchange=m.link.callback(|value| {
+            if let ChangeData::Select(select) = value {
+                match select.value().as_ref().map(|v| v.deref()) {
+                    Some("Pyodide") => return Action::SetExecutionBackend(BackendKind::Pyodide),
+                    Some("NodePython") => return Action::SetExecutionBackend(BackendKind::NodePython),
+                    _ => (),
+                }
+            }
+            Action::Noop
+        })>
+            { option_nodes }
+        </select>
+    }
+}
+
+// the session files of the workspace, each with the title of its session and the last
+// time it was modified
+pub fn view_workspace(m: &Model) -> Html {
+    let workspace = m.explorer.workspace.clone();
+    let error = match &m.explorer_error {
+        Some(error) => html! { <p class="explorer-error">{ error.clone() }</p> },
+        None => html! {},
+    };
+    let mut file_nodes = VList::new();
+    for file in &m.session_files {
+        let open_path = file.path.clone();
+        let tab_path = file.path.clone();
+        let rename_path = file.path.clone();
+        let duplicate_path = file.path.clone();
+        let delete_path = file.path.clone();
+        let diff_path = file.path.clone();
+        let merge_path = file.path.clone();
+        let is_merge_base = m.merge_base.as_ref() == Some(&file.path);
+        let base_path = if is_merge_base { None } else { Some(file.path.clone()) };
+        file_nodes.add_child(html! {
+            <li class="session-file">
+                <div class="session-file-title">
+                    { file.title.clone().unwrap_or_else(|| "(not a session)".to_string()) }
+                </div>
+                <input
+                    class="session-file-name"
+                    value=file.name.clone()
+                    title="rename"
+                    onchange=m.link.callback(move |e: ChangeData| {
+                        if let ChangeData::Value(name) = e {
+                            return Action::RenameSessionFile(rename_path.clone(), name);
+                        }
+                        Action::Noop
+                    })>
+                </input>
+                <span class="session-file-modified">{ format_modified(file.modified) }</span>
+                <div>
+                    <button onclick=m.link.callback(move |_| Action::OpenSessionFile(open_path.clone(), false))>
+                        { "Open" }
+                    </button>
+                    <button onclick=m.link.callback(move |_| Action::OpenSessionFile(tab_path.clone(), true))>
+                        { "Open in new tab" }
+                    </button>
+                    <button onclick=m.link.callback(move |_| Action::DuplicateSessionFile(duplicate_path.clone()))>
+                        { "Duplicate" }
+                    </button>
+                    <button onclick=m.link.callback(move |_| Action::DeleteSessionFile(delete_path.clone()))>
+                        { "Delete" }
+                    </button>
+                </div>
+                <div>
+                    <button
+                        title="outline the cells of the current session that differ from this file"
+                        onclick=m.link.callback(move |_| Action::DiffSessionFile(diff_path.clone()))>
+                        { "Diff" }
+                    </button>
+                    <button
+                        title="merge this file with the current session into a new tab"
+                        onclick=m.link.callback(move |_| Action::MergeSessionFile(merge_path.clone()))>
+                        { "Merge" }
+                    </button>
+                    <button
+                        class={ if is_merge_base { "merge-base" } else { "" } }
+                        title="the version both sides of a merge started from"
+                        onclick=m.link.callback(move |_| Action::SetMergeBase(base_path.clone()))>
+                        { if is_merge_base { "Merge base ✓" } else { "Use as merge base" } }
+                    </button>
+                </div>
+            </li>
+        });
+    }
+    let files = match (&workspace, m.session_files.is_empty()) {
+        (None, _) => html! { <p>{ "choose a directory to see its sessions" }</p> },
+        (Some(_), true) => html! { <p>{ "no session files" }</p> },
+        (Some(_), false) => html! { <ul class="session-files">{ file_nodes }</ul> },
+    };
+    html! {
+        <div class="explorer">
+            <span>{ workspace.unwrap_or_default() }</span>
+            <button onclick=m.link.callback(|_| Action::ChooseWorkspace)>{ "Choose…" }</button>
+            <button onclick=m.link.callback(|_| Action::RefreshSessionFiles)>{ "Refresh" }</button>
+            { error }
+            { files }
+        </div>
+    }
+}
+
+// the cells of the current tab that differ from the session file it's compared with
+pub fn view_session_diff(m: &Model) -> Html {
+    let shown = match &m.shown_diff {
+        Some(shown) if shown.tab == m.current_session_index => shown,
+        _ => return html! { <p>{ "diff a session file of the workspace to see what changed" }</p> },
+    };
+    let mut change_nodes = VList::new();
+    for (coord, change) in shown.diff.cells.iter() {
+        let select_coord = coord.clone();
+        change_nodes.add_child(html! {
+            <li class=change.class()>
+                <a onclick=m.link.callback(move |_| Action::SetActiveCell(select_coord.clone()))>
+                    { coord.to_string() }
+                </a>
+                { format!{" {}", change.describe()} }
+            </li>
+        });
+    }
+    let refresh_path = shown.against.clone();
+    html! {
+        <div class="session-diff">
+            <span>{ format!{"against {}", path_name(&shown.against)} }</span>
+            <button onclick=m.link.callback(move |_| Action::DiffSessionFile(refresh_path.clone()))>
+                { "Refresh" }
+            </button>
+            <button onclick=m.link.callback(|_| Action::ClearDiff)>{ "Clear" }</button>
+            {
+                if shown.diff.is_empty() {
+                    html! { <p>{ "no changes" }</p> }
+                } else {
+                    html! { <ul class="diff-changes">{ change_nodes }</ul> }
+                }
+            }
+        </div>
+    }
+}
+
+// the conflicts left from the last merge, when it's in the current tab
+pub fn view_merge_conflicts(m: &Model) -> Html {
+    let conflicts = match &m.merge_conflicts {
+        Some(merge) if merge.tab == m.current_session_index && !merge.conflicts.is_empty() => {
+            &merge.conflicts
+        }
+        _ => return html! { <p>{ "no conflicts" }</p> },
+    };
+    let mut conflict_nodes = VList::new();
+    for (index, conflict) in conflicts.iter().enumerate() {
+        let select_coord = conflict.coord.clone();
+        let mut side_nodes = VList::new();
+        for &(side, label) in [(Side::Ours, "ours"), (Side::Theirs, "theirs"), (Side::Base, "base")].iter() {
+            side_nodes.add_child(html! {
+                <button onclick=m.link.callback(move |_| Action::ResolveConflict(index, side))>
+                    { format!{"{}: {}", label, cell_summary(conflict.side(side))} }
+                </button>
+            });
+        }
+        conflict_nodes.add_child(html! {
+            <li class="diff-conflict">
+                <a onclick=m.link.callback(move |_| Action::SetActiveCell(select_coord.clone()))>
+                    { conflict.coord.to_string() }
+                </a>
+                { side_nodes }
+            </li>
+        });
+    }
+    html! {
+        <ul class="merge-conflicts">{ conflict_nodes }</ul>
+    }
+}
+
+// outlines the cells of the current tab that are in the diff or in conflict, through their
+// ids since cells of every kind are drawn their own way (see `view_grammar`)
+pub fn view_diff_styles(m: &Model) -> Html {
+    let mut css = String::new();
+    let mut outline = |coord: &Coordinate, class: &str| {
+        css.push_str(&format! {
+            "#cell-{} {{ outline: 2px solid var(--{}); outline-offset: -2px; }}\n",
+            coord.to_string(), class,
+        });
+    };
+    if let Some(shown) = m.shown_diff.as_ref().filter(|d| d.tab == m.current_session_index) {
+        for (coord, change) in shown.diff.cells.iter() {
+            outline(coord, change.class());
+        }
+    }
+    if let Some(merge) = m.merge_conflicts.as_ref().filter(|c| c.tab == m.current_session_index) {
+        for conflict in merge.conflicts.iter() {
+            outline(&conflict.coord, "diff-conflict");
+        }
+    }
+    html! { <style>{ css }</style> }
+}
+
+// the cursors and selections of the other sites editing the current tab
+pub fn view_presence_styles(m: &Model) -> Html {
+    let collab = match m.collab.as_ref() {
+        Some(collab) if collab.tab == m.current_session_index => collab,
+        _ => return html! {},
+    };
+    let mut css = String::new();
+    for (site, presence) in collab.peers.iter() {
+        let color = peer_color(*site);
+        if let Some((first, last)) = &presence.selection {
+            match (first.parent(), last.parent()) {
+                (Some(parent), Some(last_parent)) if parent == last_parent => {
+                    let ((first_row, first_col), (last_row, last_col)) =
+                        (first.row_col(), last.row_col());
+                    let start = (first_row.min(last_row), first_col.min(last_col));
+                    let end = (first_row.max(last_row), first_col.max(last_col));
+                    for coord in range_coords(&parent, start, end) {
+                        css.push_str(&format! {
+                            "#cell-{} {{ background-color: {}33; }}\n", coord.to_string(), color,
+                        });
+                    }
+                }
+                _ => (),
+            }
+        }
+        if let Some(cursor) = &presence.cursor {
+            css.push_str(&format! {
+                "#cell-{} {{ outline: 2px solid {}; outline-offset: -2px; }}\n",
+                cursor.to_string(), color,
+            });
+        }
+    }
+    html! { <style>{ css }</style> }
+}
+
+// connecting to a relay, sharing the session and the other sites editing it
+pub fn view_collaboration(m: &Model) -> Html {
+    let collab = match m.collab.as_ref() {
+        Some(collab) => collab,
+        None => {
+            return html! {
+                <div class="collaboration">
+                    <p>{ "Edit the current tab with other people through a relay." }</p>
+                    <label>
+                        { "relay " }
+                        <input value=m.relay_url.clone()
+                            onchange=m.link.callback(|value| {
+                                if let ChangeData::Value(url) = value {
+                                    return Action::SetRelayUrl(url);
+                                }
+                                Action::Noop
+                            })>
+                        </input>
+                    </label>
+                    <button onclick=m.link.callback(|_| Action::ConnectRelay)>{ "Connect" }</button>
+                    <button onclick=m.link.callback(|_| Action::StartRelay)>
+                        { "Start a local relay" }
+                    </button>
+                </div>
+            };
+        }
+    };
+    let site = match collab.site {
+        Some(site) => site,
+        None => {
+            return html! {
+                <div class="collaboration">
+                    <p>{ format!{"connecting to {}...", collab.url} }</p>
+                    <button onclick=m.link.callback(|_| Action::DisconnectRelay)>{ "Cancel" }</button>
+                </div>
+            };
+        }
+    };
+    let tab_title = m.sessions.get(collab.tab).map_or("", |s| s.title.as_str());
+    let mut peer_nodes = VList::new();
+    for (peer, presence) in collab.peers.iter() {
+        let cursor = presence.cursor.as_ref().map_or(String::new(), |c| c.to_string());
+        peer_nodes.add_child(html! {
+            <li>
+                <span class="peer-color" style=format!{"background-color: {};", peer_color(*peer)}></span>
+                { presence.name.clone() }
+                <span class="peer-cursor">{ cursor }</span>
+            </li>
+        });
+    }
+    html! {
+        <div class="collaboration">
+            <p>{ format!{"connected to {} as {} (site {})", collab.url, collab.name, site} }</p>
+            <p>{ format!{"editing \"{}\"", tab_title} }</p>
+            <label>
+                { "name " }
+                <input value=m.comment_author.clone()
+                    onchange=m.link.callback(|value| {
+                        if let ChangeData::Value(author) = value {
+                            return Action::SetCommentAuthor(author);
+                        }
+                        Action::Noop
+                    })>
+                </input>
+            </label>
+            <button title="the other sites open this session instead of theirs"
+                onclick=m.link.callback(|_| Action::ShareSession)>
+                { "Share this session" }
+            </button>
+            <button onclick=m.link.callback(|_| Action::DisconnectRelay)>{ "Disconnect" }</button>
+            { if collab.pending() > 0 {
+                html! { <p>{ format!{"{} edits waiting for the relay", collab.pending()} }</p> }
+            } else {
+                html! {}
+            } }
+            <h3>{ "editing with" }</h3>
+            <ul class="peers">{ peer_nodes }</ul>
+        </div>
+    }
+}
+
+pub fn view_recent_files(m: &Model) -> Html {
+    if m.explorer.recent.is_empty() {
+        return html! { <p>{ "nothing opened yet" }</p> };
+    }
+    let mut recent_nodes = VList::new();
+    for path in &m.explorer.recent {
+        let open_path = path.clone();
+        let tab_path = path.clone();
+        recent_nodes.add_child(html! {
+            <li title=path.clone()>
+                <a onclick=m.link.callback(move |_| Action::OpenSessionFile(open_path.clone(), false))>
+                    { path_name(path) }
+                </a>
+                <button onclick=m.link.callback(move |_| Action::OpenSessionFile(tab_path.clone(), true))>
+                    { "Open in new tab" }
+                </button>
+            </li>
+        });
+    }
+    html! {
+        <ul class="recent-files">{ recent_nodes }</ul>
+    }
+}
+
+// every setting with its current value, changing one applies and saves it
+pub fn view_settings(m: &Model) -> Html {
+    let mut setting_nodes = VList::new();
+    for setting in Setting::all() {
+        if setting.is_toggle() {
+            let on = m.settings.get(setting) == "true";
+            setting_nodes.add_child(html! {
+                <label class="setting">
+                    <span>{ setting.label() }</span>
+                    <input
+                        type="checkbox"
+                        checked=on
+                        onclick=m.link.callback(move |_| Action::SetSetting(setting, (!on).to_string()))>
+                    </input>
+                </label>
+            });
+            continue;
+        }
+        setting_nodes.add_child(html! {
+            <label class="setting">
+                <span>{ setting.label() }</span>
+                <input
+                    type={ if setting.is_number() { "number" } else { "text" } }
+                    step="any"
+                    placeholder={
+                        if setting.is_optional() {
+                            "the theme's"
+                        } else if setting.is_number() {
+                            ""
+                        } else {
+                            "the coordinate of the grid"
+                        }
+                    }
+                    value=m.settings.get(setting)
+                    onchange=m.link.callback(move |e: ChangeData| {
+                        if let ChangeData::Value(value) = e {
+                            return Action::SetSetting(setting, value);
+                        }
+                        Action::Noop
+                    })>
+                </input>
+            </label>
+        });
+    }
+    let mut issue_nodes = VList::new();
+    for issue in &m.settings_issues {
+        issue_nodes.add_child(html! {
+            <li class="settings-issue">{ issue.clone() }</li>
+        });
+    }
+    html! {
+        <div class="settings">
+            { setting_nodes }
+            <ul>{ issue_nodes }</ul>
+            <p>{ format!{"saved to {} in the user config directory", SETTINGS_FILE} }</p>
+            <button onclick=m.link.callback(|_| Action::ResetSettings)>{ "Reset to defaults" }</button>
+        </div>
+    }
+}
+
+// the active key bindings, and the problems found in the user's keymap file
+pub fn view_keymap(m: &Model) -> Html {
+    let mut binding_nodes = VList::new();
+    for (keys, command) in m.keymap.bindings() {
+        binding_nodes.add_child(html! {
+            <li title=command.name()>
+                <span class="shortcut">{ keys.clone() }</span>
+                { command.description() }
+            </li>
+        });
+    }
+    let mut issue_nodes = VList::new();
+    for issue in &m.keymap_issues {
+        issue_nodes.add_child(html! {
+            <li class="keymap-issue">{ issue.clone() }</li>
+        });
+    }
+    html! {
+        <div class="keymap">
+            <p>{ format!{"overridden by {} in the user config directory", KEYMAP_FILE} }</p>
+            <button onclick=m.link.callback(|_| Action::ReloadKeymap)>{ "Reload" }</button>
+            <ul>{ issue_nodes }</ul>
+            <ul>{ binding_nodes }</ul>
+        </div>
+    }
+}
+
+pub fn view_execution_output(m: &Model) -> Html {
+    let mut running_nodes = VList::new();
+    for id in m.executions.running() {
+        running_nodes.add_child(html! {
+            <div class="execution-running">
+                { format!{"running #{} ", id} }
+                <button onclick=m.link.callback(move |_| Action::CancelExecution(id))>
+                    { "Cancel" }
+                </button>
+            </div>
+        });
+    }
+    let result = match &m.executions.last_result {
+        Some(result) => result,
+        None => return html! { <>{ running_nodes }<p>{"nothing run yet"}</p></> },
+    };
+    let outcome = match &result.outcome {
+        Ok(value) => html! {
+            <pre class="execution-value">{ value.to_string() }</pre>
+        },
+        Err(e) => html! {
+            <pre class="execution-error">{ e.to_string() }{ "\n" }{ e.traceback.clone() }</pre>
+        },
+    };
+    html! {
+        <div class="execution-output">
+            { running_nodes }
+            <pre class="execution-stdout">{ result.stdout.clone() }</pre>
+            <pre class="execution-stderr">{ result.stderr.clone() }</pre>
+            { outcome }
+        </div>
+    }
+}
+
+pub fn view_themes(m: &Model) -> Html {
+    let current = m.get_session().theme.name.clone();
+    let mut option_nodes = VList::new();
+    for theme in themes() {
+        option_nodes.add_child(html! {
+            <option value=theme.name.clone() selected=(theme.name == current)>
+                { theme.name.clone() }
+            </option>
+        });
+    }
+    html! {
+        <select onchange=m.link.callback(|value| {
+            if let ChangeData::Select(select) = value {
+                if let Some(name) = select.value() {
+                    return Action::SetTheme(name);
+                }
+            }
+            Action::Noop
+        })>
+            { option_nodes }
+        </select>
+    }
+}
+
+pub fn view_style_classes(m: &Model) -> Html {
+    let active_cell = m.active_cell.clone();
+    let active_class = active_cell
+        .as_ref()
+        .and_then(|c| m.get_session().grammars.get(c))
+        .and_then(|g| g.style.class.clone());
+    let mut names: Vec<&String> = m.get_session().style_classes.keys().collect();
+    names.sort();
+
+    let mut class_nodes = VList::new();
+    for name in names {
+        let class = &m.get_session().style_classes[name];
+        let apply_cell = active_cell.clone();
+        let apply_name = name.clone();
+        let remove_name = name.clone();
+        let is_active = active_class.as_ref() == Some(name);
+        class_nodes.add_child(html! {
+            <li>
+                <span style=format!{
+                    "color: {}; font-weight: {};",
+                    class.font_color.clone().unwrap_or_default(),
+                    class.font_weight.unwrap_or(400),
+                }>
+                    { name.clone() }
+                </span>
+                <button disabled=(apply_cell.is_none() || is_active)
+                    onclick=m.link.callback(move |_| match &apply_cell {
+                        Some(coord) => Action::SetStyleClass(coord.clone(), Some(apply_name.clone())),
+                        None => Action::Noop,
+                    })>
+                    { "Apply" }
+                </button>
+                <button onclick=m.link.callback(move |_| Action::RemoveStyleClass(remove_name.clone()))>
+                    { "Remove" }
+                </button>
+            </li>
+        });
+    }
+    let clear_cell = active_cell.clone();
+    html! {
+        <div class="style-classes">
+            <ul>
+                { class_nodes }
+            </ul>
+            <input placeholder="new class from the active cell"
+                onchange=m.link.callback(|value| {
+                    if let ChangeData::Value(name) = value {
+                        return Action::DefineStyleClass(name);
+                    }
+                    Action::Noop
+                })>
+            </input>
+            <button disabled=active_class.is_none()
+                onclick=m.link.callback(move |_| match &clear_cell {
+                    Some(coord) => Action::SetStyleClass(coord.clone(), None),
+                    None => Action::Noop,
+                })>
+                { "Clear class" }
+            </button>
+        </div>
+    }
+}
+
+pub fn view_cell_format(m: &Model) -> Html {
+    let active_cell = match m.active_cell.clone() {
+        Some(coord) => coord,
+        None => return html! { <p>{ "select a cell to format its value" }</p> },
+    };
+    let grammar = m.get_session().grammars.get(&active_cell);
+    let format = grammar.and_then(|g| g.style.format.clone());
+    let value_type = match grammar.map(|g| &g.kind) {
+        Some(Kind::Input(raw)) => CellValue::parse(raw).type_name(),
+        _ => "not an input",
+    };
+    let selected = format.as_ref().map_or("General", |f| f.name());
+    let mut option_nodes = VList::new();
+    for name in std::iter::once(&"General").chain(FORMAT_NAMES.iter()) {
+        option_nodes.add_child(html! {
+            <option value=name.to_string() selected=(*name == selected)>
+                { name }
+            </option>
+        });
+    }
+    let format_cell = active_cell.clone();
+    let setting_cell = active_cell.clone();
+    let grid = m.formatting_grid();
+    let html_grid = grid.clone();
+    let markdown_grid = grid.clone();
+    let inline_grid = grid.clone();
+    let json_grid = grid.clone();
+    html! {
+        <div class="cell-format">
+            <span>{ format! {"{}: {}", active_cell.to_string(), value_type} }</span>
+            <select onchange=m.link.callback(move |value| {
+                if let ChangeData::Select(select) = value {
+                    if let Some(name) = select.value() {
+                        return Action::SetCellFormat(format_cell.clone(), Format::with_name(&name));
+                    }
+                }
+                Action::Noop
+            })>
+                { option_nodes }
+            </select>
+            <input placeholder="decimals, symbol or pattern"
+                disabled=format.is_none()
+                value=format.as_ref().map(|f| f.setting()).unwrap_or_default()
+                onchange=m.link.callback(move |value| {
+                    if let ChangeData::Value(setting) = value {
+                        return Action::SetCellFormatSetting(setting_cell.clone(), setting);
+                    }
+                    Action::Noop
+                })>
+            </input>
+            <button onclick=m.link.callback(move |_| Action::ExportCSV(grid.clone()))>
+                { "Export grid as CSV" }
+            </button>
+            <button onclick=m.link.callback(move |_| Action::ExportHTML(html_grid.clone()))>
+                { "Export grid as HTML" }
+            </button>
+            <button onclick=m.link.callback(move |_| Action::CopyMarkdown(markdown_grid.clone(), NestedTables::Sections))>
+                { "Copy grid as Markdown" }
+            </button>
+            <button onclick=m.link.callback(move |_| Action::CopyMarkdown(inline_grid.clone(), NestedTables::Inline))>
+                { "Copy grid as Markdown (inline nested tables)" }
+            </button>
+            <button onclick=m.link.callback(move |_| Action::ExportJSON(json_grid.clone()))>
+                { "Save grid as JSON" }
+            </button>
+            <button onclick=m.link.callback(|_| Action::ImportJSON)>
+                { "Open JSON in cell" }
+            </button>
+        </div>
+    }
+}
+
+pub fn view_formatting_rules(m: &Model) -> Html {
+    let grid = m.formatting_grid();
+    let mut rule_nodes = VList::new();
+    if let Some(rules) = m.get_session().formatting.get(&grid) {
+        for (index, rule) in rules.iter().enumerate() {
+            let remove_grid = grid.clone();
+            let mut overrides = vec![];
+            if let Some(color) = &rule.style.font_color {
+                overrides.push(format! {"color {}", color});
+            }
+            if let Some(weight) = rule.style.font_weight {
+                overrides.push(format! {"weight {}", weight});
+            }
+            if let Some(color) = &rule.style.border_color {
+                overrides.push(format! {"border {}", color});
+            }
+            rule_nodes.add_child(html! {
+                <li>
+                    { format!{"{}: {} ", rule.condition, overrides.join(", ")} }
+                    <button onclick=m.link.callback(move |_| Action::RemoveFormatRule(remove_grid.clone(), index))>
+                        { "Remove" }
+                    </button>
+                </li>
+            });
+        }
+    }
+
+    // each field of the form updates its own copy of the draft
+    let draft = &m.format_rule_draft;
+    let mut condition_options = VList::new();
+    for name in CONDITION_NAMES.iter() {
+        condition_options.add_child(html! {
+            <option value=name.to_string() selected=(*name == draft.condition)>
+                { name.to_string() }
+            </option>
+        });
+    }
+    let condition_draft = draft.clone();
+    let value_draft = draft.clone();
+    let font_color_draft = draft.clone();
+    let font_weight_draft = draft.clone();
+    let border_color_draft = draft.clone();
+    let add_grid = grid.clone();
+    html! {
+        <div class="formatting-rules">
+            { format!{"rules of {}", grid.to_string()} }
+            <ul>
+                { rule_nodes }
+            </ul>
+            <select onchange=m.link.callback(move |value| {
+                if let ChangeData::Select(select) = value {
+                    if let Some(condition) = select.value() {
+                        return Action::UpdateFormatRuleDraft(FormatRuleDraft { condition, ..condition_draft.clone() });
+                    }
+                }
+                Action::Noop
+            })>
+                { condition_options }
+            </select>
+            <input placeholder="value" value=draft.value.clone()
+                oninput=m.link.callback(move |e: InputData| Action::UpdateFormatRuleDraft(FormatRuleDraft { value: e.value, ..value_draft.clone() }))>
+            </input>
+            <input placeholder="font color" value=draft.font_color.clone()
+                oninput=m.link.callback(move |e: InputData| Action::UpdateFormatRuleDraft(FormatRuleDraft { font_color: e.value, ..font_color_draft.clone() }))>
+            </input>
+            <input placeholder="font weight" value=draft.font_weight.clone()
+                oninput=m.link.callback(move |e: InputData| Action::UpdateFormatRuleDraft(FormatRuleDraft { font_weight: e.value, ..font_weight_draft.clone() }))>
+            </input>
+            <input placeholder="border color" value=draft.border_color.clone()
+                oninput=m.link.callback(move |e: InputData| Action::UpdateFormatRuleDraft(FormatRuleDraft { border_color: e.value, ..border_color_draft.clone() }))>
+            </input>
+            <button onclick=m.link.callback(move |_| Action::AddFormatRule(add_grid.clone()))>
+                { "Add rule" }
+            </button>
+        </div>
+    }
+}
+
+pub fn view_validations(m: &Model) -> Html {
+    let mut validation_nodes = VList::new();
+    for (index, validation) in m.get_session().validations.iter().enumerate() {
+        validation_nodes.add_child(html! {
+            <li>
+                { format!{
+                    "{}: {}{} ",
+                    validation.target,
+                    validation.rule,
+                    if validation.strict { " (strict)" } else { "" },
+                } }
+                <button onclick=m.link.callback(move |_| Action::RemoveValidation(index))>
+                    { "Remove" }
+                </button>
+            </li>
+        });
+    }
+
+    // each field of the form updates its own copy of the draft
+    let draft = &m.validation_draft;
+    let mut target_options = VList::new();
+    for name in TARGET_NAMES.iter() {
+        target_options.add_child(html! {
+            <option value=name.to_string() selected=(*name == draft.target)>
+                { name.to_string() }
+            </option>
+        });
+    }
+    let mut rule_options = VList::new();
+    for name in RULE_NAMES.iter() {
+        rule_options.add_child(html! {
+            <option value=name.to_string() selected=(*name == draft.rule)>
+                { name.to_string() }
+            </option>
+        });
+    }
+    let target_draft = draft.clone();
+    let rule_draft = draft.clone();
+    let value_draft = draft.clone();
+    let strict_draft = draft.clone();
+    html! {
+        <div class="validations">
+            <ul>
+                { validation_nodes }
+            </ul>
+            <select onchange=m.link.callback(move |value| {
+                if let ChangeData::Select(select) = value {
+                    if let Some(target) = select.value() {
+                        return Action::UpdateValidationDraft(ValidationDraft { target, ..target_draft.clone() });
+                    }
+                }
+                Action::Noop
+            })>
+                { target_options }
+            </select>
+            <select onchange=m.link.callback(move |value| {
+                if let ChangeData::Select(select) = value {
+                    if let Some(rule) = select.value() {
+                        return Action::UpdateValidationDraft(ValidationDraft { rule, ..rule_draft.clone() });
+                    }
+                }
+                Action::Noop
+            })>
+                { rule_options }
+            </select>
+            <input placeholder="0..100, pattern, length or a, b, c" value=draft.value.clone()
+                oninput=m.link.callback(move |e: InputData| Action::UpdateValidationDraft(ValidationDraft { value: e.value, ..value_draft.clone() }))>
+            </input>
+            <label>
+                <input type="checkbox" checked=draft.strict
+                    onclick=m.link.callback(move |_| Action::UpdateValidationDraft(ValidationDraft { strict: !strict_draft.strict, ..strict_draft.clone() }))>
+                </input>
+                { "reject invalid values" }
+            </label>
+            <button disabled=m.active_cell.is_none()
+                onclick=m.link.callback(|_| Action::AddValidation())>
+                { "Add validation for the active cell" }
+            </button>
+        </div>
+    }
+}
+
+pub fn view_names(m: &Model) -> Html {
+    let mut name_nodes = VList::new();
+    for (name, target) in m.get_session().names.iter() {
+        let old_name = name.clone();
+        let go_to = name.clone();
+        let removed = name.clone();
+        name_nodes.add_child(html! {
+            <li>
+                <input value=name.clone()
+                    onchange=m.link.callback(move |value| {
+                        if let ChangeData::Value(new_name) = value {
+                            return Action::RenameName(old_name.clone(), new_name);
+                        }
+                        Action::Noop
+                    })>
+                </input>
+                <a onclick=m.link.callback(move |_| Action::GoTo(go_to.clone()))>
+                    { target_to_string(target) }
+                </a>
+                <button onclick=m.link.callback(move |_| Action::RemoveName(removed.clone()))>
+                    { "Remove" }
+                </button>
+            </li>
+        });
+    }
+
+    let draft = &m.name_draft;
+    let name_draft = draft.clone();
+    let target_draft = draft.clone();
+    html! {
+        <div class="names">
+            <ul>
+                { name_nodes }
+            </ul>
+            <input placeholder="name" value=draft.name.clone()
+                oninput=m.link.callback(move |e: InputData| Action::UpdateNameDraft(NameDraft { name: e.value, ..name_draft.clone() }))>
+            </input>
+            <input placeholder="root-A1:root-B3 (the selection if empty)" value=draft.target.clone()
+                oninput=m.link.callback(move |e: InputData| Action::UpdateNameDraft(NameDraft { target: e.value, ..target_draft.clone() }))>
+            </input>
+            <button onclick=m.link.callback(|_| Action::DefineName())>
+                { "Define name" }
+            </button>
+            <input placeholder="go to a name or coordinate"
+                onchange=m.link.callback(|value| {
+                    if let ChangeData::Value(text) = value {
+                        return Action::GoTo(text);
+                    }
+                    Action::Noop
+                })>
+            </input>
+        </div>
+    }
+}
+
+// the tooltip of a menu item running the command: its description and shortcut
+fn command_title(m: &Model, command: Command) -> String {
+    match m.keymap.shortcut(command) {
+        Some(shortcut) => format! {"{} ({})", command.description(), shortcut},
+        None => command.description(),
+    }
+}
+
 pub fn view_menu_bar(m: &Model) -> Html {
     let active_cell = m.active_cell.clone();
     let nest_active_cell = m.active_cell.clone();
+    // "Run Python" runs the active cell when it's an editor
+    let run_active_cell = m.active_cell.clone().filter(|c| {
+        match m.get_session().grammars.get(c).map(|g| &g.kind) {
+            Some(Kind::Editor(_, _, _)) => true,
+            _ => false,
+        }
+    });
     let (default_row, default_col) = {
         let (r, c) = m.default_nested_row_cols.clone();
         (r.get(), c.get())
@@ -205,7 +1402,7 @@ pub fn view_menu_bar(m: &Model) -> Html {
 <<<<<<< HEAD
             * it contains fields for the variable size of the button
             */
-        <button class="menu-bar-button" id="nest" onclick=m.link.callback(move |_| {
+        <button class="menu-bar-button" id="nest" title=command_title(m, Command::NestGrid) onclick=m.link.callback(move |_| {
             if let Some(current) = &active_cell {
                 Action::AddNestedGrid(current.clone(), (default_row, default_col))
             } else { Action::Noop }
@@ -213,7 +1410,7 @@ pub fn view_menu_bar(m: &Model) -> Html {
 =======
          * it contains fields for the variable size of the button
          */
-        <button class="menu-bar-button" id="nest" 
+        <button class="menu-bar-button" id="nest" title=command_title(m, Command::NestGrid)
             onmousedown=m.link.callback(move |e : MouseDownEvent| {
                 if let Some(current) = &active_cell {
                     Action::AddNestedGrid(current.clone(), (default_row, default_col))            
@@ -268,65 +1465,17 @@ pub fn view_menu_bar(m: &Model) -> Html {
     };
 
     let add_definition_button = {
-        let (can_add_definition, default_name, callback) = match (
-            m.first_select_cell.clone(),
-            m.last_select_cell.clone(),
-        ) {
-            // definitions can occur when a range of coordinates are selected where:
-            // - the first (top-leftmost) and last (bottom-rightmost) selected cells have the same parent
-            // - the first selected cell is the first (top-leftmost) child of the parent
-            // - the last selected cell is the last (bottom-rightmost) child of the parent
-            // cell, which should be a Kind::Grid grammar
-            (Some(first), Some(last)) if first.parent() == last.parent() => {
-                if let Some((Kind::Grid(sub_coords))) = /* get the coordinate of the parent, lookup the grammar, then get the grammar.kind */
-                    first
-                        .parent()
-                        .and_then(|c| m.get_session().grammars.get(&c))
-                        .map(|g| (g.kind.clone()))
-                {
-                    use std::cmp::Ordering;
-                    let mut sc = sub_coords.clone();
-                    sc.sort_by(|(a_row, a_col), (b_row, b_col)| {
-                        if a_row > b_row {
-                            Ordering::Greater
-                        } else if a_row < b_row {
-                            Ordering::Less
-                        } else {
-                            if a_col > b_col {
-                                Ordering::Greater
-                            } else if a_col < b_col {
-                                Ordering::Less
-                            } else {
-                                Ordering::Equal
-                            }
-                        }
-                    });
-                    let first_sc = sc.first().expect(
-                        "add_definition_button: expect selection parent sub_coords.len > 1",
-                    );
-                    let last_sc = sc.last().expect(
-                        "add_definition_button: expect selection parent sub_coords.len > 1",
-                    );
-                    let defn_name = if m.default_definition_name == "" {
-                        first.parent().unwrap().to_string().replace("-", "_")
-                    } else {
-                        m.default_definition_name.clone()
-                    };
-                    (
-                        // can add definition?
-                        *first_sc == first.row_col() && *last_sc == last.row_col(),
-                        // definition name
-                        defn_name.clone(),
-                        // callback
-                        m.link.callback(move |_| {
-                            Action::AddDefinition(first.parent().unwrap(), defn_name.clone())
-                        }),
-                    )
-                } else {
-                    (false, "".to_string(), m.link.callback(|_| Action::Noop))
-                }
+        // definitions can be added when the selection covers a whole grid (see
+        // `Model::definition_grid`)
+        let (can_add_definition, callback) = match m.definition_grid() {
+            Some(grid) => {
+                let defn_name = m.definition_name(&grid);
+                (
+                    true,
+                    m.link.callback(move |_| Action::AddDefinition(grid.clone(), defn_name.clone())),
+                )
             }
-            _ => (false, "".to_string(), m.link.callback(|_| Action::Noop)),
+            None => (false, m.link.callback(|_| Action::Noop)),
         };
         /*
         let suggestions: Vec<_> = m
@@ -344,7 +1493,7 @@ pub fn view_menu_bar(m: &Model) -> Html {
         */
 
         html! {
-            <button class="menu-bar-button" disabled={ !can_add_definition } onclick=callback>
+            <button class="menu-bar-button" title=command_title(m, Command::AddDefinition) disabled={ !can_add_definition } onclick=callback>
                 { "Add Definition  " }
                 <input
                     class="active-cell-indicator"
@@ -380,19 +1529,22 @@ pub fn view_menu_bar(m: &Model) -> Html {
                     }
                 }>
             </input>
-            <button id="SaveSession" class="menu-bar-button" onclick=m.link.callback(|_| Action::AskFileName()) >
+            <button id="CommandPalette" class="menu-bar-button" title=command_title(m, Command::CommandPalette) onclick=m.link.callback(|_| Action::OpenPalette)>
+                { "Commands" }
+            </button>
+            <button id="SaveSession" class="menu-bar-button" title=command_title(m, Command::Save) onclick=m.link.callback(|_| Action::AskFileName()) >
                 { "Save" }
             </button>
             <button class="menu-bar-button">
                 { "Git" }
             </button>
-            <button id="ZoomIn" class="menu-bar-button" onclick=m.link.callback(|_| Action::ZoomIn)>
+            <button id="ZoomIn" class="menu-bar-button" title=command_title(m, Command::ZoomIn) onclick=m.link.callback(|_| Action::ZoomIn)>
                 { "Zoom In (+)" }
             </button>
-            <button id="ZoomReset" class="menu-bar-button" onclick=m.link.callback(|_| Action::ZoomReset)>
+            <button id="ZoomReset" class="menu-bar-button" title=command_title(m, Command::ZoomReset) onclick=m.link.callback(|_| Action::ZoomReset)>
                 { "Zoom Reset" }
             </button>
-            <button id="ZoomOut" class="menu-bar-button" onclick=m.link.callback(|_| Action::ZoomOut)>
+            <button id="ZoomOut" class="menu-bar-button" title=command_title(m, Command::ZoomOut) onclick=m.link.callback(|_| Action::ZoomOut)>
                 { "Zoom Out (-)" }
             </button>
             <button id="Reset" class="menu-bar-button" onclick=m.link.callback(|_| Action::Recreate)>
@@ -402,25 +1554,33 @@ pub fn view_menu_bar(m: &Model) -> Html {
                 { nest_grid_button }
             //</>
                 { nest_row_input } { nest_col_input }
-            <button id="InsertRow" class="menu-bar-button" onclick=m.link.callback(|_| Action::InsertRow)>
+            <button id="InsertRow" class="menu-bar-button" title=command_title(m, Command::InsertRow) onclick=m.link.callback(|_| Action::InsertRow)>
                 { "Insert Row" }
             </button>
-            <button id="InsertCol" class="menu-bar-button" onclick=m.link.callback(|_| Action::InsertCol)>
+            <button id="InsertCol" class="menu-bar-button" title=command_title(m, Command::InsertCol) onclick=m.link.callback(|_| Action::InsertCol)>
                 { "Insert Column" }
             </button>
-            <button id="Merge" class="menu-bar-button" onclick=m.link.callback(move |_ : ClickEvent| Action::MergeCells())>
+            <button id="Merge" class="menu-bar-button" title=command_title(m, Command::MergeCells) onclick=m.link.callback(move |_ : ClickEvent| Action::MergeCells())>
                 { "Merge" }
             </button>
-            <button id="DeleteRow" class="menu-bar-button" onclick=m.link.callback(|_| Action::DeleteRow)>
+            <button id="Unmerge" class="menu-bar-button" title=command_title(m, Command::UnmergeCells) onclick=m.link.callback(move |_ : ClickEvent| Action::UnmergeCells())>
+                { "Unmerge" }
+            </button>
+            <button id="DeleteRow" class="menu-bar-button" title=command_title(m, Command::DeleteRow) onclick=m.link.callback(|_| Action::DeleteRow)>
                 { "Delete Row" }
             </button>
-            <button id="DeleteCol" class="menu-bar-button" onclick=m.link.callback(|_| Action::DeleteCol)>
+            <button id="DeleteCol" class="menu-bar-button" title=command_title(m, Command::DeleteCol) onclick=m.link.callback(|_| Action::DeleteCol)>
                 { "Delete Column" }
             </button>
-            <button id="NewEditor" class="menu-bar-button" onclick=m.link.callback(|_| Action::NewEditor)>
+            <button id="NewEditor" class="menu-bar-button" title=command_title(m, Command::NewEditor) onclick=m.link.callback(|_| Action::NewEditor)>
                 { "New Editor" }
             </button>
-            <button id="RunPython" class="menu-bar-button" onclick=m.link.callback(|_| Action::RunPython("import sys\nsys.version\nprint(1+2)".to_string(), coord!("root-A1")))>
+            <button id="RunPython" class="menu-bar-button" title=command_title(m, Command::RunEditor) onclick=m.link.callback(move |_| {
+                match &run_active_cell {
+                    Some(coord) => Action::RunEditor(coord.clone()),
+                    None => Action::Alert("select an editor to run".to_string()),
+                }
+            })>
                 { "Run Python" }
             </button>
             //<>
@@ -439,7 +1599,9 @@ pub fn view_tab_bar(m: &Model) -> Html {
             });
         } else {
             tabs.add_child(html! {
-                <button class="tab">{ tab.title.clone() }</button>
+                <button class="tab" onclick=m.link.callback(move |_| Action::SwitchTab(index))>
+                    { tab.title.clone() }
+                </button>
             });
         }
     }
@@ -453,6 +1615,40 @@ pub fn view_tab_bar(m: &Model) -> Html {
     }
 }
 
+// the path from the outermost grid to the grid the view is zoomed into, e.g.
+// "root › B2 › A1", each step zooms the view back out to that grid
+pub fn view_breadcrumbs(m: &Model) -> Html {
+    let view_root = m.shown_view_root();
+    let mut crumbs = VList::new();
+    for (index, (label, coord)) in breadcrumbs(&view_root).into_iter().enumerate() {
+        if index > 0 {
+            crumbs.add_child(html! { <span class="breadcrumb-separator">{ "›" }</span> });
+        }
+        if coord == view_root {
+            crumbs.add_child(html! { <span class="breadcrumb current">{ label }</span> });
+        } else {
+            crumbs.add_child(html! {
+                <a class="breadcrumb" onclick=m.link.callback(move |_| Action::SetViewRoot(coord.clone()))>
+                    { label }
+                </a>
+            });
+        }
+    }
+    html! {
+        <div class="breadcrumb-bar">
+            <button disabled=!m.view_history.can_go_back()
+                onclick=m.link.callback(|_| Action::ViewBack)>
+                { "←" }
+            </button>
+            <button disabled=!m.view_history.can_go_forward()
+                onclick=m.link.callback(|_| Action::ViewForward)>
+                { "→" }
+            </button>
+            { crumbs }
+        </div>
+    }
+}
+
 pub fn view_grammar(m: &Model, coord: Coordinate) -> Html {
     let is_active = m.active_cell.clone() == Some(coord.clone());
     if let Some(grammar) = m.get_session().grammars.get(&coord) {
@@ -487,7 +1683,7 @@ pub fn view_grammar(m: &Model, coord: Coordinate) -> Html {
                     <div
                         class=format!{"cell interactive row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
                         id=format!{"cell-{}", coord.to_string()}
-                        style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
+                        style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights, &coord, &m.style_context(&coord)) }>
                         <button>
                             { name }
                         </button>
@@ -501,7 +1697,7 @@ pub fn view_grammar(m: &Model, coord: Coordinate) -> Html {
                         class=format!{"cell interactive row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
                         id=format!{"cell-{}", coord.to_string()}
                         // style={ get_style(&m, &coord) }>
-                        style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
+                        style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights, &coord, &m.style_context(&coord)) }>
                         <input type="range" min={min} max={max} value={value}>
                             { name }
                         </input>
@@ -515,7 +1711,7 @@ pub fn view_grammar(m: &Model, coord: Coordinate) -> Html {
                         class=format!{"cell interactive row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
                         id=format!{"cell-{}", coord.to_string()}
                         // style={ get_style(&m, &coord) }>
-                        style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
+                        style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights, &coord, &m.style_context(&coord)) }>
                         <input type="checkbox" checked={checked}>
                             { name }
                         </input>
@@ -548,17 +1744,79 @@ pub fn view_grammar(m: &Model, coord: Coordinate) -> Html {
             Kind::Defn(name, defn_coord, sub_grammars) => {
                 view_defn_grammar(m, &coord, &defn_coord, name, sub_grammars)
             }
-            Kind::Editor(content) => view_editor_grammar(m, &coord, content),
+            Kind::Editor(content, mode, output) => {
+                view_editor_grammar(m, &coord, content, mode, output)
+            }
         }
     } else {
         html! { <></> }
     }
 }
 
-pub fn view_editor_grammar(m: &Model, coord: &Coordinate, content: String) -> Html {
+pub fn view_editor_grammar(
+    m: &Model,
+    coord: &Coordinate,
+    content: String,
+    mode: String,
+    output: Option<Coordinate>,
+) -> Html {
+    let run_coord = coord.clone();
+    let change_coord = coord.clone();
+    let mode_coord = coord.clone();
+    let output_coord = coord.clone();
+    let mut mode_options = VList::new();
+    for editor_mode in EDITOR_MODES.iter() {
+        mode_options.add_child(html! {
+            <option value=editor_mode.to_string() selected=(*editor_mode == mode.deref())>
+                { editor_mode.to_string() }
+            </option>
+        });
+    }
+    let default_output = m
+        .editor_output(coord)
+        .map(|c| c.to_string())
+        .unwrap_or_default();
+    let error = m.cell_errors.get(coord).cloned();
     html! {
-        <CodeMirror content={content} coordinate={coord.clone()}>
-        </CodeMirror>
+        <div
+            class=format!{"cell editor row-{} col-{}{}", coord.row_to_string(), coord.col_to_string(), if error.is_some() { " cell-error" } else { "" }}
+            id=format!{"cell-{}", coord.to_string()}
+            title=error.unwrap_or_default()
+            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights, &coord, &m.style_context(&coord)) }>
+            <div class="editor-toolbar">
+                <button onclick=m.link.callback(move |_| Action::RunEditor(run_coord.clone()))>
+                    { "Run" }
+                </button>
+                <select onchange=m.link.callback(move |value| {
+                    if let ChangeData::Select(select) = value {
+                        if let Some(mode) = select.value() {
+                            return Action::SetEditorMode(mode_coord.clone(), mode);
+                        }
+                    }
+                    Action::Noop
+                })>
+                    { mode_options }
+                </select>
+                { "output: " }
+                <input
+                    class="editor-output"
+                    placeholder=default_output
+                    value=output.map(|c| c.to_string()).unwrap_or_default()
+                    onchange=m.link.callback(move |value| {
+                        if let ChangeData::Value(output) = value {
+                            return Action::SetEditorOutput(output_coord.clone(), output);
+                        }
+                        Action::Noop
+                    })>
+                </input>
+            </div>
+            <CodeMirror
+                content={content}
+                mode={mode}
+                coordinate={coord.clone()}
+                onchange=m.link.callback(move |content| Action::ChangeEditor(change_coord.clone(), content))>
+            </CodeMirror>
+        </div>
     }
 }
 
@@ -590,7 +1848,7 @@ pub fn view_defn_grammar(
             onclick=m.link.callback(|_| Action::HideContextMenu)
             class=format!{"cell grid row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
             id=format!{"cell-{}", coord.to_string()}
-            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
+            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights, &coord, &m.style_context(&coord)) }>
             <input
                 class="cell"
                 value={name}>
@@ -618,7 +1876,7 @@ pub fn view_defn_variant_grammar(
             class=format!{"cell variant row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
             id=format!{"cell-{}", coord.to_string()}
             // style={ get_style(&m, &coord) }>
-            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
+            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights, &coord, &m.style_context(&coord)) }>
             { nodes }
             <button onclick=m.link.callback(|_| Action::InsertCol)>
                 {"+"}
@@ -647,6 +1905,20 @@ pub fn view_lookup_grammar(
                 </a>
             })
         }
+        // names are suggested along with the coordinates
+        for name in m.get_session().names.keys() {
+            if !name.contains(value.deref()) {
+                continue;
+            }
+            let dest = coord.clone();
+            let source = name.clone();
+            suggestions_nodes.add_child(html!{
+                <a tabindex=2 class="name-suggestion"
+                    onclick=m.link.callback(move |_ : ClickEvent| Action::ChangeInput(dest.clone(), source.clone()))>
+                    { name.clone() }
+                </a>
+            })
+        }
         html! {
             <div
                 onclick=m.link.callback(|_| Action::HideContextMenu)
@@ -665,7 +1937,7 @@ pub fn view_lookup_grammar(
             onclick=m.link.callback(|_| Action::HideContextMenu)
             class=format!{"cell suggestion lookup row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
             id=format!{"cell-{}", coord.to_string()}
-            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
+            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights, &coord, &m.style_context(&coord)) }>
             <b style=format!{"font-size: 20px; color: {};", random_color()}>{ "$" }</b>
             <div contenteditable=true
                 class=format!{
@@ -693,6 +1965,7 @@ pub fn view_lookup_grammar(
                 >
             </div>
             { value }
+            { view_comment_marker(m, coord) }
             { suggestions_div }
         </div>
     }
@@ -771,56 +2044,63 @@ pub fn view_input_grammar(
     let cell_classes =
         format! {"cell suggestion row-{} col-{}", coord.row_to_string(), coord.col_to_string()};
     let cell_data_classes = format! {
-        "cell-data {} {}",
+        "cell-data {} {} {} {}",
         if is_active { "cell-active " } else { "cell-inactive" },
-        if is_selected { "selection" } else { "" }
+        if is_selected { "selection" } else { "" },
+        if m.cell_errors.contains_key(&coord) { "cell-error" } else { "" },
+        if m.validation_errors.contains_key(&coord) { "cell-invalid" } else { "" },
     };
-
-    // relevant coordinates for navigation purposes
-    let neighbor_left = current_coord
-        .neighbor_left()
-        .and_then(|c| {
-            // check if grammar corresponding to left neighbor coord exists...
-            m.get_session().grammars.get(&c).map(|g| {
-                // ... and if it's a grid, select it's first cell
-                if let Kind::Grid(_) = g.kind {
-                    Coordinate::child_of(&c, non_zero_u32_tuple((1, 1)))
-                } else {
-                    c.clone()
-                }
-            })
-        })
-        .clone();
-    let neighbor_right = current_coord
-        .neighbor_right()
-        .and_then(|c| {
-            // check if grammar corresponding to right neighbor coord exists...
-            m.get_session().grammars.get(&c).map(|g| {
-                // ... and if it's a grid, select it's first cell
-                // TODO: make this select the last (bottom-rightmost) nested cell
-                if let Kind::Grid(_) = g.kind {
-                    Coordinate::child_of(&c, non_zero_u32_tuple((1, 1)))
-                } else {
-                    c.clone()
-                }
-            })
-        })
-        .clone();
-    let first_col_next_row = {
-        let temp = &mut current_coord.neighbor_below();
-        if let Some(t) = temp {
-            let col = t.col_mut();
-            *col = NonZeroU32::new(1).unwrap();
-            if m.get_session().grammars.contains_key(&t) {
-                Some(t.clone())
-            } else {
-                None
+    let cell_title = m
+        .cell_errors
+        .get(&coord)
+        .into_iter()
+        .chain(m.validation_errors.get(&coord))
+        .cloned()
+        .collect::<Vec<String>>()
+        .join("\n");
+    // cells with a fixed list of allowed values get a dropdown while they're active
+    let allowed_values = if is_active {
+        allowed_values(&m.get_session().validations, &coord)
+    } else {
+        None
+    };
+    let dropdown = match allowed_values {
+        Some(values) => {
+            let mut option_nodes = VList::new();
+            option_nodes.add_child(html! { <option value="" selected=true disabled=true>{ "choose" }</option> });
+            for allowed in values {
+                option_nodes.add_child(html! {
+                    <option value=allowed.clone()>{ allowed }</option>
+                });
+            }
+            let choose_coord = coord.clone();
+            html! {
+                <select class="allowed-values"
+                    onchange=m.link.callback(move |value| {
+                        if let ChangeData::Select(select) = value {
+                            if let Some(allowed) = select.value() {
+                                return Action::ChooseAllowedValue(choose_coord.clone(), allowed);
+                            }
+                        }
+                        Action::Noop
+                    })>
+                    { option_nodes }
+                </select>
             }
-        } else {
-            None
         }
+        None => html! { <></> },
     };
-    let last_col_prev_row = /* TODO: get the correct value of this */ current_coord.neighbor_above();
+    // the active cell shows what was typed in, so it can be edited
+    let format = m
+        .get_session()
+        .grammars
+        .get(&coord)
+        .and_then(|g| g.style.format.as_ref());
+    let shown_value = match format {
+        Some(format) if !is_active => format.display(&value),
+        _ => value.clone(),
+    };
+    let comment_marker = view_comment_marker(m, &coord);
 
     let keydownhandler = m.link.callback(move |e: KeyDownEvent| {
         // info! {"suggestion len {}", suggestions_len}
@@ -829,19 +2109,8 @@ pub fn view_input_grammar(
             if suggestions_len > 0 {
                 return Action::NextSuggestion(tab_coord.clone(), 1);
             }
-            let next_active_cell = if e.shift_key() {
-                neighbor_left
-                    .clone()
-                    .or(last_col_prev_row.clone())
-                    .or(tab_coord.parent().and_then(|c| c.neighbor_left()))
-            } else {
-                neighbor_right
-                    .clone()
-                    .or(first_col_next_row.clone())
-                    .or(tab_coord.parent().and_then(|c| c.neighbor_right()))
-            };
-            // info! {"next_active_cell {}", next_active_cell.clone().unwrap().to_string()};
-            return next_active_cell.map_or(Action::Noop, |c| Action::SetActiveCell(c));
+            let movement = if e.shift_key() { Move::Previous } else { Move::Next };
+            return Action::Navigate(tab_coord.clone(), movement, false);
         } 
         if is_selected && (e.code() == "Backspace" || e.code() == "Delete") {       
             return Action::RangeDelete();
@@ -863,15 +2132,16 @@ pub fn view_input_grammar(
             class=cell_classes
             id=format!{"cell-{}", coord.to_string()}
 <<<<<<< HEAD
-            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
+            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights, &coord, &m.style_context(&coord)) }>
 =======
             // style={ get_style(&m, &coord) }>
-            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }
+            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights, &coord, &m.style_context(&coord)) }
             >
 >>>>>>> hieule/fix_bug
             <div contenteditable=true
 
                 class=cell_data_classes
+                title=cell_title
                 onkeydown=keydownhandler
                 onkeypress=m.link.callback(move |e : KeyPressEvent| {
                     if e.code() == "Space" && has_lookup_prefix {
@@ -934,12 +2204,14 @@ pub fn view_input_grammar(
 <<<<<<< HEAD
                 })
                 ondrop=drophandler >
-                { value }
+                { shown_value }
 =======
                 })>
                 // { value }
 >>>>>>> hieule/fix_bug
             </div>
+            { comment_marker }
+            { dropdown }
             { suggestions }
         </div>
     }
@@ -953,15 +2225,17 @@ pub fn view_text_grammar(m: &Model, coord: &Coordinate, value: String, is_active
             class=format!{"cell suggestion row-{} col-{}", coord.row_to_string(), coord.col_to_string(),}
             id=format!{"cell-{}", coord.to_string()}
             // style={ get_style(&m, &coord) }>
-            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
+            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights, &coord, &m.style_context(&coord)) }>
             <div
                 class={
                     format!{
-                        "cell-data {} {}",
+                        "cell-data {} {} {}",
                         if is_active { "cell-active" } else { "cell-inactive" },
-                        if is_selected { "selection" } else { "" }
+                        if is_selected { "selection" } else { "" },
+                        if m.cell_errors.contains_key(coord) { "cell-error" } else { "" },
                     }
                 },
+                title={ m.cell_errors.get(coord).cloned().unwrap_or_default() },
                 ref={
                     if is_active {
                         m.focus_node_ref.clone()
@@ -969,6 +2243,7 @@ pub fn view_text_grammar(m: &Model, coord: &Coordinate, value: String, is_active
                 }>
                 { value }
             </div>
+            { view_comment_marker(m, coord) }
         </div>
     }
 }
@@ -981,79 +2256,149 @@ pub fn view_grid_grammar(m: &Model, coord: &Coordinate, sub_coords: Vec<Coordina
     html! {
         <div
             onclick=m.link.callback(|_| Action::HideContextMenu)
-            class=format!{"\ncell grid row-{} col-{}", coord.row_to_string(), coord.col_to_string()}
+            class=format!{
+                "\ncell grid row-{} col-{} {}",
+                coord.row_to_string(), coord.col_to_string(),
+                if m.cell_errors.contains_key(coord) { "cell-error" } else { "" },
+            }
+            title={ m.cell_errors.get(coord).cloned().unwrap_or_default() }
             id=format!{"cell-{}", coord.to_string()}
-            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights,  &coord) }>
+            style={ get_style(m.get_session().grammars.get(&coord).expect("no grammar with this coordinate"), &m.col_widths, &m.row_heights, &coord, &m.style_context(&coord)) }>
             { nodes }
         </div>
     }
 }
 
 pub fn view_context_menu(m: &Model) -> Html {
+    let evaluate_cell = m.active_cell.clone();
+    let zoom_grid = m.zoom_grid();
+    let can_zoom = zoom_grid.is_some();
     let default_options = vec![
         (
             "Insert Row",
             m.link.callback(|_| Action::InsertRow),
             true,
             1,
+            Some(Command::InsertRow),
         ),
         (
             "Insert Col",
             m.link.callback(|_| Action::InsertCol),
             true,
             1,
+            Some(Command::InsertCol),
         ),
         (
             "Delete Row",
             m.link.callback(|_| Action::DeleteRow),
             true,
             1,
+            Some(Command::DeleteRow),
         ),
         (
             "Delete Col",
             m.link.callback(|_| Action::DeleteCol),
             true,
             1,
+            Some(Command::DeleteCol),
         ),
         (
             "----------",
             m.link.callback(|_| Action::HideContextMenu),
             true,
             0,
+            None,
+        ),
+        (
+            "Zoom In (+)",
+            m.link.callback(|_| Action::ZoomIn),
+            true,
+            2,
+            Some(Command::ZoomIn),
         ),
-        ("Zoom In (+)", m.link.callback(|_| Action::ZoomIn), true, 2),
         (
             "Zoom Reset",
             m.link.callback(|_| Action::ZoomReset),
             true,
             2,
+            Some(Command::ZoomReset),
         ),
         (
             "Zoom Out (-)",
             m.link.callback(|_| Action::ZoomOut),
             true,
             2,
+            Some(Command::ZoomOut),
         ),
         (
             "----------",
             m.link.callback(|_| Action::HideContextMenu),
             true,
             0,
+            None,
         ),
 
-        ("Save", m.link.callback(|_| Action::AskFileName()), true, 3),
-        ("Reset", m.link.callback(|_| Action::Recreate), true, 3),
-        ("Merge", m.link.callback(|_| Action::MergeCells()), false, 3),
+        (
+            "Save",
+            m.link.callback(|_| Action::AskFileName()),
+            true,
+            3,
+            Some(Command::Save),
+        ),
+        ("Reset", m.link.callback(|_| Action::Recreate), true, 3, None),
+        (
+            "Merge",
+            m.link.callback(|_| Action::MergeCells()),
+            false,
+            3,
+            Some(Command::MergeCells),
+        ),
+        (
+            "Unmerge",
+            m.link.callback(|_| Action::UnmergeCells()),
+            false,
+            3,
+            Some(Command::UnmergeCells),
+        ),
+        (
+            "Zoom Into Grid",
+            m.link.callback(move |_| match zoom_grid.clone() {
+                Some(grid) => Action::SetViewRoot(grid),
+                None => Action::Noop,
+            }),
+            false,
+            3,
+            Some(Command::ZoomIntoGrid),
+        ),
+        (
+            "----------",
+            m.link.callback(|_| Action::HideContextMenu),
+            true,
+            0,
+            None,
+        ),
+        (
+            "Evaluate",
+            m.link.callback(move |_| {
+                evaluate_cell
+                    .clone()
+                    .map_or(Action::Noop, |c| Action::EvaluateDriver(c))
+            }),
+            false,
+            4,
+            Some(Command::Evaluate),
+        ),
     ];
     /*option Name and action are what their name means
     option_param represents the default or conditionnal render of an option
     option_layer represents the visual layer of the option on the context menu that for now only helps the break
         But will evolve in the future
+    option_command is the command the option runs, whose shortcut is shown next to it
     */
     let option_nodes = {
         let mut v = VList::new();
 
-        for (option_name, option_action, option_param, option_layer) in default_options {
+        for (option_name, option_action, option_param, option_layer, option_command) in default_options {
             let mut should_render = true;
 
             //Conditional for the options that should only show under certain circumstances
@@ -1066,6 +2411,26 @@ pub fn view_context_menu(m: &Model) -> Html {
                             should_render = true;
                         }
                     }
+                    "Unmerge" => {
+                        let is_merged = m
+                            .active_cell
+                            .as_ref()
+                            .and_then(|c| m.get_session().grammars.get(c))
+                            .map_or(false, |g| g.style.col_span.0 != 0);
+                        if is_merged {
+                            should_render = true;
+                        }
+                    }
+                    "Zoom Into Grid" => {
+                        if can_zoom {
+                            should_render = true;
+                        }
+                    }
+                    "Evaluate" => {
+                        if !m.drivers.drivers.is_empty() {
+                            should_render = true;
+                        }
+                    }
                     _ => info!("Parameter not managed {:?}", option_name),
                 }
             }
@@ -1074,6 +2439,12 @@ pub fn view_context_menu(m: &Model) -> Html {
                 v.add_child(html! {
                     <li class="context-menu-option" onclick=option_action>
                         { option_name }
+                        {
+                            match option_command.and_then(|c| m.keymap.shortcut(c)) {
+                                Some(shortcut) => html! { <span class="shortcut">{ shortcut }</span> },
+                                None => html! {},
+                            }
+                        }
                     </li>
                 });
             }
@@ -0,0 +1,1532 @@
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Cursor, Read, Write};
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::coordinate::Coordinate;
+use crate::grammar::{Grammar, Kind};
+use crate::grammar_map::MapEntry;
+use crate::sheet::cell_value;
+use crate::style::{Style, DEFAULT_FONT_COLOR, DEFAULT_FONT_WEIGHT};
+use crate::value::{CellValue, Date};
+
+// WORKBOOKS
+// Spreadsheets made with other applications come as .xlsx (Office Open XML) or .ods
+// (OpenDocument) workbooks, both zip archives of XML files. A workbook is read into a
+// `Workbook`, which only keeps what a grid can show: the values of the cells (formulas are
+// left out, their last result is kept), merged ranges, column widths and whether the font
+// is bold or colored. Each sheet is opened as the root grid of a new tab (see
+// `sheet_entry`).
+// Going the other way, a grid is exported as an .xlsx workbook with a sheet for the grid
+// and one for each grid nested in it, named after the coordinate of the nested grid. The
+// cell the grid is nested in shows that name and links to its sheet.
+
+// sheets with more cells than this aren't opened, grids that big can't be edited anyway
+pub const MAX_SHEET_CELLS: u32 = 100_000;
+// the last row and column of an .xlsx or .ods sheet
+const MAX_ROWS: u32 = 1_048_576;
+const MAX_COLS: u32 = 16384;
+// the longest sheet name .xlsx allows
+const MAX_SHEET_NAME: usize = 31;
+// the largest XML file read out of a workbook, so a small archive can't unpack into
+// gigabytes of memory
+const MAX_XML_SIZE: u64 = 64 * 1024 * 1024;
+// .xlsx column widths are in characters of the default font, about 7 pixels wide
+const XLSX_CHAR_WIDTH: f64 = 7.0;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Workbook {
+    pub sheets: Vec<WorkbookSheet>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WorkbookSheet {
+    pub name: String,
+    // cells with a value by (row, column), both starting at 1
+    pub cells: BTreeMap<(u32, u32), WorkbookCell>,
+    // merged ranges, from their top left cell to their bottom right one
+    pub merges: Vec<((u32, u32), (u32, u32))>,
+    // widths of the columns in pixels, columns that aren't here have the default width
+    pub col_widths: BTreeMap<u32, f64>,
+    // cells linking to another sheet, by the name of the sheet
+    pub links: BTreeMap<(u32, u32), String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkbookCell {
+    pub value: CellValue,
+    pub bold: bool,
+    // CSS color of the font, `None` for the default color
+    pub color: Option<String>,
+}
+
+// a workbook file as read by the main process, see 'open-workbook-file' in static/main.js
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WorkbookFile {
+    pub name: String,
+    // the contents of the file, base64 encoded
+    pub data: String,
+}
+
+impl WorkbookFile {
+    pub fn workbook(&self) -> Result<Workbook, String> {
+        let bytes =
+            base64::decode(&self.data).map_err(|e| format! {"cannot read {}: {}", self.name, e})?;
+        read_workbook(&bytes).map_err(|e| format! {"cannot read {}: {}", self.name, e})
+    }
+
+    // the name of the file without its extension, e.g. "budget" for "budget.xlsx"
+    pub fn stem(&self) -> &str {
+        match self.name.rfind('.') {
+            Some(i) if i > 0 => &self.name[..i],
+            _ => &self.name,
+        }
+    }
+}
+
+impl WorkbookCell {
+    pub fn new(value: CellValue) -> WorkbookCell {
+        WorkbookCell {
+            value,
+            bold: false,
+            color: None,
+        }
+    }
+}
+
+impl WorkbookSheet {
+    // the number of rows and columns of the sheet, at least one of each
+    pub fn size(&self) -> (u32, u32) {
+        let corners = self
+            .cells
+            .keys()
+            .chain(self.merges.iter().map(|(_, end)| end));
+        corners.fold((1, 1), |(rows, cols), &(row, col)| {
+            (rows.max(row), cols.max(col))
+        })
+    }
+
+    fn add_cell(&mut self, row: u32, col: u32, cell: WorkbookCell) -> Result<(), String> {
+        if self.cells.len() as u32 >= MAX_SHEET_CELLS {
+            return Err(too_big(&self.name));
+        }
+        self.cells.insert((row, col), cell);
+        Ok(())
+    }
+}
+
+fn too_big(name: &str) -> String {
+    format! {"the sheet \"{}\" has more than {} cells", name, MAX_SHEET_CELLS}
+}
+
+// GRIDS
+
+// the grid of a sheet, to be built with `build_grammar_map`
+pub fn sheet_entry(sheet: &WorkbookSheet) -> Result<MapEntry, String> {
+    let (rows, cols) = sheet.size();
+    if rows as u64 * cols as u64 > MAX_SHEET_CELLS as u64 {
+        return Err(too_big(&sheet.name));
+    }
+    let default = Style::default();
+    let width = |col: u32| sheet.col_widths.get(&col).cloned().unwrap_or(default.width);
+    let mut cells: Vec<Vec<Grammar>> = (1..=rows)
+        .map(|row| {
+            (1..=cols)
+                .map(|col| {
+                    let mut grammar = match sheet.cells.get(&(row, col)) {
+                        Some(cell) => cell_grammar(cell),
+                        None => Grammar::default(),
+                    };
+                    grammar.style.width = width(col);
+                    grammar
+                })
+                .collect()
+        })
+        .collect();
+    // merged ranges are laid out the way `util::merge_cells` does it
+    for &((r1, c1), (r2, c2)) in sheet.merges.iter() {
+        for row in r1..=r2 {
+            for col in c1..=c2 {
+                let grammar = &mut cells[row as usize - 1][col as usize - 1];
+                grammar.style.row_span = (r1, r2);
+                grammar.style.col_span = (c1, c2);
+                if (row, col) == (r1, c1) {
+                    grammar.style.width = (c1..=c2).map(width).sum();
+                    grammar.style.height = default.height * (r2 - r1 + 1) as f64;
+                } else {
+                    grammar.style.display = false;
+                    grammar.kind = Kind::Input(String::new());
+                }
+            }
+        }
+    }
+    Ok(MapEntry::Grid(
+        cells
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|grammar| Box::new(MapEntry::G(grammar)))
+                    .collect()
+            })
+            .collect(),
+    ))
+}
+
+fn cell_grammar(cell: &WorkbookCell) -> Grammar {
+    // text that would be read as another value is kept as text with an apostrophe
+    let input = match &cell.value {
+        CellValue::Text(text) if CellValue::parse(text) != cell.value => format! {"'{}", text},
+        value => value.to_string(),
+    };
+    let mut grammar = Grammar::input("".to_string(), input);
+    if cell.bold {
+        grammar.style.font_weight = 700;
+    }
+    if let Some(color) = &cell.color {
+        grammar.style.font_color = color.clone();
+    }
+    grammar
+}
+
+// the workbook of the grid at `grid`, with a sheet for it and one for each grid nested in
+// it, nested grids coming after the grid they're in
+pub fn grid_workbook(grammars: &HashMap<Coordinate, Grammar>, grid: &Coordinate) -> Workbook {
+    let mut names = HashSet::new();
+    let mut grids = vec![(grid.clone(), sheet_name(grid, &mut names))];
+    let mut sheets = vec![];
+    let mut i = 0;
+    while i < grids.len() {
+        let (coord, name) = grids[i].clone();
+        let mut sheet = WorkbookSheet {
+            name,
+            ..WorkbookSheet::default()
+        };
+        let mut sub_coords = match grammars.get(&coord).map(|g| &g.kind) {
+            Some(Kind::Grid(sub_coords)) => sub_coords.clone(),
+            _ => vec![],
+        };
+        sub_coords.sort();
+        for row_col in sub_coords {
+            let cell_coord = Coordinate::child_of(&coord, row_col);
+            let grammar = match grammars.get(&cell_coord) {
+                Some(grammar) if grammar.style.display => grammar,
+                _ => continue,
+            };
+            let (row, col) = (row_col.0.get(), row_col.1.get());
+            let style = &grammar.style;
+            if style.col_span.0 != 0 && style.row_span.0 != 0 {
+                let (start, end) = (
+                    (style.row_span.0, style.col_span.0),
+                    (style.row_span.1, style.col_span.1),
+                );
+                if start == (row, col) && start != end {
+                    sheet.merges.push((start, end));
+                }
+            } else {
+                sheet.col_widths.entry(col).or_insert(style.width);
+            }
+            let value = match &grammar.kind {
+                Kind::Grid(_) => {
+                    let name = sheet_name(&cell_coord, &mut names);
+                    sheet.links.insert((row, col), name.clone());
+                    grids.push((cell_coord.clone(), name.clone()));
+                    CellValue::Text(name)
+                }
+                Kind::Input(raw) => CellValue::parse(raw),
+                _ => match cell_value(grammars, &cell_coord) {
+                    serde_json::Value::Null => CellValue::Empty,
+                    serde_json::Value::Bool(b) => CellValue::Bool(b),
+                    serde_json::Value::Number(n) => {
+                        n.as_f64().map_or(CellValue::Empty, CellValue::Number)
+                    }
+                    serde_json::Value::String(s) => CellValue::Text(s),
+                    value => CellValue::Text(value.to_string()),
+                },
+            };
+            if value == CellValue::Empty {
+                continue;
+            }
+            let color = Some(style.font_color.clone())
+                .filter(|c| !c.is_empty() && c.as_str() != DEFAULT_FONT_COLOR);
+            sheet.cells.insert(
+                (row, col),
+                WorkbookCell {
+                    value,
+                    bold: style.font_weight > DEFAULT_FONT_WEIGHT,
+                    color,
+                },
+            );
+        }
+        sheets.push(sheet);
+        i += 1;
+    }
+    Workbook { sheets }
+}
+
+// the name of the sheet of a grid, its coordinate cut to the length .xlsx allows and made
+// unique among `names`
+fn sheet_name(coord: &Coordinate, names: &mut HashSet<String>) -> String {
+    let full: String = coord.to_string().chars().take(MAX_SHEET_NAME).collect();
+    let mut name = full.clone();
+    let mut n = 2;
+    while names.contains(&name) {
+        let suffix = format! {"~{}", n};
+        let kept = full.chars().take(MAX_SHEET_NAME - suffix.len());
+        name = kept.chain(suffix.chars()).collect();
+        n += 1;
+    }
+    names.insert(name.clone());
+    name
+}
+
+// READING
+
+pub fn read_workbook(bytes: &[u8]) -> Result<Workbook, String> {
+    let mut archive =
+        ZipArchive::new(Cursor::new(bytes)).map_err(|_| "it isn't a workbook".to_string())?;
+    let workbook = if archive.by_name("xl/workbook.xml").is_ok() {
+        read_xlsx(&mut archive)?
+    } else if archive.by_name("content.xml").is_ok() {
+        read_ods(&mut archive)?
+    } else {
+        return Err("it isn't an .xlsx or .ods workbook".to_string());
+    };
+    if workbook.sheets.is_empty() {
+        return Err("the workbook has no sheets".to_string());
+    }
+    Ok(workbook)
+}
+
+type Archive<'a> = ZipArchive<Cursor<&'a [u8]>>;
+
+fn archive_xml(archive: &mut Archive, path: &str) -> Result<Option<Element>, String> {
+    let mut file = match archive.by_name(path) {
+        Ok(file) => file,
+        Err(_) => return Ok(None),
+    };
+    if file.size() > MAX_XML_SIZE {
+        return Err(format! {"{} is larger than {} bytes", path, MAX_XML_SIZE});
+    }
+    // the size written in the archive can be wrong, so no more than the limit is read
+    let mut xml = String::new();
+    file.by_ref()
+        .take(MAX_XML_SIZE + 1)
+        .read_to_string(&mut xml)
+        .map_err(|e| format! {"{}: {}", path, e})?;
+    if xml.len() as u64 > MAX_XML_SIZE {
+        return Err(format! {"{} is larger than {} bytes", path, MAX_XML_SIZE});
+    }
+    parse_xml(&xml)
+        .map(Some)
+        .map_err(|e| format! {"{}: {}", path, e})
+}
+
+fn required_xml(archive: &mut Archive, path: &str) -> Result<Element, String> {
+    archive_xml(archive, path)?.ok_or_else(|| format! {"{} is missing", path})
+}
+
+// the font and number format of the cell formats of an .xlsx workbook (`cellXfs`)
+#[derive(Default)]
+struct XlsxStyles {
+    // bold and color of each font
+    fonts: Vec<(bool, Option<String>)>,
+    // font and whether the number format is a date, for each cell format
+    formats: Vec<(usize, bool)>,
+}
+
+fn read_xlsx(archive: &mut Archive) -> Result<Workbook, String> {
+    let workbook = required_xml(archive, "xl/workbook.xml")?;
+    let rels = required_xml(archive, "xl/_rels/workbook.xml.rels")?;
+    let targets: HashMap<&str, String> = rels
+        .children("Relationship")
+        .filter_map(|rel| {
+            let target = rel.attr("Target")?;
+            let path = match target.strip_prefix('/') {
+                Some(path) => path.to_string(),
+                None => format! {"xl/{}", target},
+            };
+            Some((rel.attr("Id")?, path))
+        })
+        .collect();
+    let shared: Vec<String> = match archive_xml(archive, "xl/sharedStrings.xml")? {
+        Some(sst) => sst.children("si").map(xlsx_text).collect(),
+        None => vec![],
+    };
+    let styles = match archive_xml(archive, "xl/styles.xml")? {
+        Some(styles) => xlsx_styles(&styles),
+        None => XlsxStyles::default(),
+    };
+
+    let mut sheets = vec![];
+    for sheet in workbook
+        .children("sheets")
+        .flat_map(|s| s.children("sheet"))
+    {
+        let name = sheet.attr("name").unwrap_or_default().to_string();
+        // the relationship id is `r:id`
+        let path = match sheet.attr("id").and_then(|id| targets.get(id)) {
+            Some(path) => path,
+            None => return Err(format! {"the sheet \"{}\" is missing", name}),
+        };
+        let xml = required_xml(archive, path)?;
+        sheets.push(read_xlsx_sheet(name, &xml, &shared, &styles)?);
+    }
+    Ok(Workbook { sheets })
+}
+
+fn xlsx_styles(styles: &Element) -> XlsxStyles {
+    let fonts = styles
+        .children("fonts")
+        .flat_map(|f| f.children("font"))
+        .map(|font| {
+            let bold = font.child("b").map_or(false, |b| {
+                !matches!(b.attr("val"), Some("0") | Some("false"))
+            });
+            let color = font
+                .child("color")
+                .and_then(|c| c.attr("rgb"))
+                .and_then(hex_css_color);
+            (bold, color)
+        })
+        .collect();
+    let number_formats: HashMap<&str, &str> = styles
+        .children("numFmts")
+        .flat_map(|f| f.children("numFmt"))
+        .filter_map(|f| Some((f.attr("numFmtId")?, f.attr("formatCode")?)))
+        .collect();
+    let formats = styles
+        .children("cellXfs")
+        .flat_map(|x| x.children("xf"))
+        .map(|xf| {
+            let font = xf.attr("fontId").and_then(|f| f.parse().ok()).unwrap_or(0);
+            let date = match xf.attr("numFmtId") {
+                Some(id) => match number_formats.get(id) {
+                    Some(code) => is_date_format(code),
+                    None => is_builtin_date_format(id),
+                },
+                None => false,
+            };
+            (font, date)
+        })
+        .collect();
+    XlsxStyles { fonts, formats }
+}
+
+fn is_builtin_date_format(id: &str) -> bool {
+    match id.parse::<u32>() {
+        Ok(id) => (14..=22).contains(&id) || (45..=47).contains(&id),
+        Err(_) => false,
+    }
+}
+
+// whether a format code shows days or years, leaving out quoted text, escaped characters
+// and sections in brackets (colors, locales)
+fn is_date_format(code: &str) -> bool {
+    let mut chars = code.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => while chars.next().map_or(false, |c| c != '"') {},
+            '[' => while chars.next().map_or(false, |c| c != ']') {},
+            '\\' | '_' | '*' => {
+                chars.next();
+            }
+            'd' | 'D' | 'y' | 'Y' => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+// the text of a shared or inline string, leaving out phonetic runs
+fn xlsx_text(string: &Element) -> String {
+    string
+        .elements()
+        .filter_map(|e| match e.name.as_str() {
+            "t" => Some(e.text()),
+            "r" => e.child("t").map(Element::text),
+            _ => None,
+        })
+        .collect()
+}
+
+fn read_xlsx_sheet(
+    name: String,
+    xml: &Element,
+    shared: &[String],
+    styles: &XlsxStyles,
+) -> Result<WorkbookSheet, String> {
+    let mut sheet = WorkbookSheet {
+        name,
+        ..WorkbookSheet::default()
+    };
+    for col in xml.children("cols").flat_map(|c| c.children("col")) {
+        let number = |name: &str| col.attr(name).and_then(|n| n.parse::<u32>().ok());
+        let width = col.attr("width").and_then(|w| w.parse::<f64>().ok());
+        if let (Some(min), Some(max), Some(width)) = (number("min"), number("max"), width) {
+            for c in min.max(1)..=max.min(MAX_COLS) {
+                sheet
+                    .col_widths
+                    .insert(c, (width * XLSX_CHAR_WIDTH).round());
+            }
+        }
+    }
+
+    let mut row = 0;
+    for row_xml in xml.children("sheetData").flat_map(|d| d.children("row")) {
+        row = row_xml
+            .attr("r")
+            .and_then(|r| r.parse().ok())
+            .unwrap_or(row + 1);
+        let mut col = 0;
+        for c in row_xml.children("c") {
+            col = match c.attr("r").and_then(parse_cell_ref) {
+                Some((_, c)) => c,
+                None => col + 1,
+            };
+            let v = c.child("v").map(Element::text);
+            let (font, date) = c
+                .attr("s")
+                .and_then(|s| s.parse::<usize>().ok())
+                .and_then(|s| styles.formats.get(s))
+                .cloned()
+                .unwrap_or((0, false));
+            let value = match (c.attr("t").unwrap_or("n"), v) {
+                ("inlineStr", _) => {
+                    CellValue::Text(c.child("is").map(xlsx_text).unwrap_or_default())
+                }
+                ("s", Some(v)) => {
+                    match v.trim().parse::<usize>().ok().and_then(|i| shared.get(i)) {
+                        Some(text) => CellValue::Text(text.clone()),
+                        None => continue,
+                    }
+                }
+                ("b", Some(v)) => CellValue::Bool(v.trim() == "1"),
+                ("d", Some(v)) => Date::parse(&v).map_or(CellValue::Text(v), CellValue::Date),
+                ("n", Some(v)) => match v.trim().parse::<f64>() {
+                    Ok(n) if date => serial_date(n).map_or(CellValue::Number(n), CellValue::Date),
+                    Ok(n) => CellValue::Number(n),
+                    Err(_) => CellValue::Text(v),
+                },
+                // formula strings ("str") and errors ("e")
+                (_, Some(v)) => CellValue::Text(v),
+                (_, None) => continue,
+            };
+            if value == CellValue::Text(String::new()) {
+                continue;
+            }
+            let (bold, color) = styles.fonts.get(font).cloned().unwrap_or((false, None));
+            sheet.add_cell(row, col, WorkbookCell { value, bold, color })?;
+        }
+    }
+
+    for merge in xml
+        .children("mergeCells")
+        .flat_map(|m| m.children("mergeCell"))
+    {
+        let range = merge.attr("ref").unwrap_or_default();
+        let mut ends = range.split(':').map(parse_cell_ref);
+        if let (Some(Some(start)), Some(Some(end))) = (ends.next(), ends.next()) {
+            sheet.merges.push((start, end));
+        }
+    }
+    Ok(sheet)
+}
+
+// the elements columns and rows of an .ods table can be grouped in
+const COLUMN_GROUPS: [&str; 3] = [
+    "table-columns",
+    "table-header-columns",
+    "table-column-group",
+];
+const ROW_GROUPS: [&str; 3] = ["table-rows", "table-header-rows", "table-row-group"];
+
+// an OpenDocument style, as far as cells and columns go
+#[derive(Default)]
+struct OdsStyle {
+    width: Option<f64>,
+    bold: bool,
+    color: Option<String>,
+}
+
+fn read_ods(archive: &mut Archive) -> Result<Workbook, String> {
+    let content = required_xml(archive, "content.xml")?;
+    let styles: HashMap<&str, OdsStyle> = content
+        .children("automatic-styles")
+        .flat_map(|s| s.children("style"))
+        .filter_map(|style| {
+            let mut ods_style = OdsStyle::default();
+            if let Some(column) = style.child("table-column-properties") {
+                ods_style.width = column.attr("column-width").and_then(length_px);
+            }
+            if let Some(text) = style.child("text-properties") {
+                ods_style.bold = matches!(
+                    text.attr("font-weight"),
+                    Some("bold") | Some("600") | Some("700") | Some("800") | Some("900")
+                );
+                ods_style.color = text.attr("color").and_then(hex_css_color);
+            }
+            Some((style.attr("name")?, ods_style))
+        })
+        .collect();
+    let spreadsheet = content
+        .child("body")
+        .and_then(|b| b.child("spreadsheet"))
+        .ok_or_else(|| "it isn't a spreadsheet".to_string())?;
+    let sheets = spreadsheet
+        .children("table")
+        .map(|table| read_ods_table(table, &styles))
+        .collect::<Result<_, _>>()?;
+    Ok(Workbook { sheets })
+}
+
+fn read_ods_table(
+    table: &Element,
+    styles: &HashMap<&str, OdsStyle>,
+) -> Result<WorkbookSheet, String> {
+    let mut sheet = WorkbookSheet {
+        name: table.attr("name").unwrap_or_default().to_string(),
+        ..WorkbookSheet::default()
+    };
+    let repeated = |e: &Element, name: &str| {
+        e.attr(name)
+            .and_then(|n| n.parse::<u32>().ok())
+            .unwrap_or(1)
+            .max(1)
+    };
+
+    let mut columns = vec![];
+    ods_elements(table, "table-column", &COLUMN_GROUPS, &mut columns);
+    let mut cell_styles: HashMap<u32, &str> = HashMap::new();
+    let mut col = 1;
+    for column in columns {
+        let width = column
+            .attr("style-name")
+            .and_then(|s| styles.get(s))
+            .and_then(|s| s.width);
+        for _ in 0..repeated(column, "number-columns-repeated") {
+            if col > MAX_COLS {
+                break;
+            }
+            if let Some(width) = width {
+                sheet.col_widths.insert(col, width);
+            }
+            if let Some(style) = column.attr("default-cell-style-name") {
+                cell_styles.insert(col, style);
+            }
+            col += 1;
+        }
+    }
+
+    let mut rows = vec![];
+    ods_elements(table, "table-row", &ROW_GROUPS, &mut rows);
+    let mut row: u32 = 1;
+    for row_xml in rows {
+        // the cells of the row and the ranges merged from them
+        let mut cells = vec![];
+        let mut merges = vec![];
+        let mut col: u32 = 1;
+        for cell in row_xml.elements() {
+            let covered = match cell.name.as_str() {
+                "table-cell" => false,
+                "covered-table-cell" => true,
+                _ => continue,
+            };
+            let repeat = repeated(cell, "number-columns-repeated");
+            let value = if covered { None } else { ods_value(cell) };
+            if let Some(value) = value {
+                let style = cell
+                    .attr("style-name")
+                    .or_else(|| cell_styles.get(&col).cloned())
+                    .and_then(|s| styles.get(s));
+                let (bold, color) = style.map_or((false, None), |s| (s.bold, s.color.clone()));
+                for c in col..(col + repeat).min(MAX_COLS + 1) {
+                    let cell = WorkbookCell {
+                        value: value.clone(),
+                        bold,
+                        color: color.clone(),
+                    };
+                    cells.push((c, cell));
+                }
+            }
+            let (rows_spanned, cols_spanned) = (
+                repeated(cell, "number-rows-spanned"),
+                repeated(cell, "number-columns-spanned"),
+            );
+            if !covered && (rows_spanned > 1 || cols_spanned > 1) {
+                merges.push((col, rows_spanned, cols_spanned));
+            }
+            col = col.saturating_add(repeat);
+        }
+        // the rows after the last one are usually there as many empty repeated rows
+        let repeat = repeated(row_xml, "number-rows-repeated");
+        if cells.is_empty() && merges.is_empty() {
+            row = row.saturating_add(repeat);
+            continue;
+        }
+        for _ in 0..repeat {
+            if row > MAX_ROWS {
+                break;
+            }
+            for (c, cell) in cells.iter() {
+                sheet.add_cell(row, *c, cell.clone())?;
+            }
+            for &(c, rows_spanned, cols_spanned) in merges.iter().filter(|m| m.0 <= MAX_COLS) {
+                // spans can be any number in the file, they end at the last row and column
+                let end = (
+                    row.saturating_add(rows_spanned - 1).min(MAX_ROWS),
+                    c.saturating_add(cols_spanned - 1).min(MAX_COLS),
+                );
+                sheet.merges.push(((row, c), end));
+            }
+            row += 1;
+        }
+    }
+    Ok(sheet)
+}
+
+// the elements named `name` in `e`, looking into the `groups` they may be in
+fn ods_elements<'a>(e: &'a Element, name: &str, groups: &[&str], found: &mut Vec<&'a Element>) {
+    for child in e.elements() {
+        if child.name == name {
+            found.push(child);
+        } else if groups.contains(&child.name.as_str()) {
+            ods_elements(child, name, groups, found);
+        }
+    }
+}
+
+fn ods_value(cell: &Element) -> Option<CellValue> {
+    let text = || {
+        let paragraphs: Vec<String> = cell.children("p").map(ods_text).collect();
+        paragraphs.join("\n")
+    };
+    let value = match cell.attr("value-type") {
+        Some("float") | Some("percentage") | Some("currency") => {
+            match cell.attr("value").and_then(|v| v.parse::<f64>().ok()) {
+                Some(n) => CellValue::Number(n),
+                None => CellValue::Text(text()),
+            }
+        }
+        Some("date") => match cell.attr("date-value").and_then(Date::parse) {
+            Some(date) => CellValue::Date(date),
+            None => CellValue::Text(text()),
+        },
+        Some("boolean") => CellValue::Bool(cell.attr("boolean-value") == Some("true")),
+        _ => CellValue::Text(text()),
+    };
+    match value {
+        CellValue::Text(text) if text.is_empty() => None,
+        value => Some(value),
+    }
+}
+
+// the text of a paragraph, spaces and tabs being elements of their own
+fn ods_text(e: &Element) -> String {
+    let mut text = String::new();
+    for node in e.children.iter() {
+        match node {
+            Node::Text(t) => text.push_str(t),
+            Node::Element(child) => match child.name.as_str() {
+                "s" => {
+                    let spaces = child.attr("c").and_then(|c| c.parse().ok()).unwrap_or(1);
+                    text.push_str(&" ".repeat(spaces));
+                }
+                "tab" => text.push('\t'),
+                "line-break" => text.push('\n'),
+                "annotation" | "note" => {}
+                _ => text.push_str(&ods_text(child)),
+            },
+        }
+    }
+    text
+}
+
+// a length like "2.258cm" in pixels
+fn length_px(length: &str) -> Option<f64> {
+    let i = length.find(|c: char| c.is_ascii_alphabetic())?;
+    let (number, unit) = length.split_at(i);
+    let px_per_unit = match unit {
+        "px" => 1.0,
+        "in" => 96.0,
+        "cm" => 96.0 / 2.54,
+        "mm" => 96.0 / 25.4,
+        "pt" => 96.0 / 72.0,
+        "pc" => 16.0,
+        _ => return None,
+    };
+    Some((number.trim().parse::<f64>().ok()? * px_per_unit).round())
+}
+
+// "FFFF0000" (ARGB) or "#ff0000" as a CSS color, `None` for black which is the default
+fn hex_css_color(hex: &str) -> Option<String> {
+    let hex = hex.trim_start_matches('#');
+    // the slicing below is by bytes
+    if !hex.is_ascii() {
+        return None;
+    }
+    let rgb = match hex.len() {
+        8 => &hex[2..],
+        6 => hex,
+        _ => return None,
+    };
+    if !rgb.chars().all(|c| c.is_ascii_hexdigit()) || rgb == "000000" {
+        return None;
+    }
+    Some(format! {"#{}", rgb.to_lowercase()})
+}
+
+// "B12" as (12, 2)
+fn parse_cell_ref(cell_ref: &str) -> Option<(u32, u32)> {
+    let cell_ref = cell_ref.trim().replace('$', "");
+    let i = cell_ref.find(|c: char| c.is_ascii_digit())?;
+    let (letters, digits) = cell_ref.split_at(i);
+    if letters.is_empty() || !letters.chars().all(|c| c.is_ascii_uppercase()) {
+        return None;
+    }
+    let col = letters.chars().try_fold(0u32, |col, c| {
+        col.checked_mul(26)?.checked_add(c as u32 - 'A' as u32 + 1)
+    })?;
+    let row = digits.parse::<u32>().ok()?;
+    if row == 0 || col > MAX_COLS {
+        return None;
+    }
+    Some((row, col))
+}
+
+// (12, 2) as "B12"
+fn cell_ref((row, col): (u32, u32)) -> String {
+    let mut letters = vec![];
+    let mut col = col;
+    while col > 0 {
+        letters.push((b'A' + ((col - 1) % 26) as u8) as char);
+        col = (col - 1) / 26;
+    }
+    letters
+        .into_iter()
+        .rev()
+        .chain(row.to_string().chars())
+        .collect()
+}
+
+// DATES
+// .xlsx dates are numbers of days since 1899-12-30, the time of day being the fraction
+
+// days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year } as i64;
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year as i32, month as u32, day as u32)
+}
+
+const SERIAL_EPOCH: i64 = -25569; // 1899-12-30
+
+fn serial_date(serial: f64) -> Option<Date> {
+    if !serial.is_finite() || !(0.0..=2958466.0).contains(&serial) {
+        return None;
+    }
+    let seconds = (serial * 86400.0).round() as i64;
+    let (year, month, day) = civil_from_days(SERIAL_EPOCH + seconds.div_euclid(86400));
+    let time = seconds.rem_euclid(86400) as u32;
+    Some(Date {
+        year,
+        month,
+        day,
+        time: Some((time / 3600, time % 3600 / 60, time % 60)).filter(|_| time > 0),
+    })
+}
+
+fn date_serial(date: &Date) -> f64 {
+    let days = days_from_civil(date.year, date.month, date.day) - SERIAL_EPOCH;
+    let (hour, minute, second) = date.time.unwrap_or((0, 0, 0));
+    days as f64 + (hour * 3600 + minute * 60 + second) as f64 / 86400.0
+}
+
+// WRITING
+
+const MAIN_NS: &str = "http://schemas.openxmlformats.org/spreadsheetml/2006/main";
+const REL_NS: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+const PACKAGE_REL_NS: &str = "http://schemas.openxmlformats.org/package/2006/relationships";
+const XML_HEADER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n";
+
+// the fonts and cell formats used by the cells of a workbook being written, the first
+// ones being the defaults
+struct StyleTable {
+    fonts: Vec<(bool, Option<String>)>,
+    // font and number format
+    formats: Vec<(usize, u32)>,
+}
+
+impl StyleTable {
+    fn new() -> StyleTable {
+        StyleTable {
+            fonts: vec![(false, None)],
+            formats: vec![(0, 0)],
+        }
+    }
+
+    // the cell format of a cell
+    fn format(&mut self, cell: &WorkbookCell) -> usize {
+        let font = (cell.bold, cell.color.as_deref().and_then(argb_color));
+        let font = position_or_push(&mut self.fonts, font);
+        let number_format = match &cell.value {
+            // m/d/yyyy h:mm and m/d/yyyy
+            CellValue::Date(date) if date.time.is_some() => 22,
+            CellValue::Date(_) => 14,
+            _ => 0,
+        };
+        position_or_push(&mut self.formats, (font, number_format))
+    }
+
+    fn xml(&self) -> String {
+        let fonts: String = self
+            .fonts
+            .iter()
+            .map(|(bold, color)| {
+                format! {
+                    "<font>{}<sz val=\"11\"/>{}<name val=\"Calibri\"/></font>",
+                    if *bold { "<b/>" } else { "" },
+                    color.as_ref().map_or(String::new(), |c| format! {"<color rgb=\"{}\"/>", c}),
+                }
+            })
+            .collect();
+        let formats: String = self
+            .formats
+            .iter()
+            .map(|(font, number_format)| {
+                format! {
+                    "<xf numFmtId=\"{}\" fontId=\"{}\" fillId=\"0\" borderId=\"0\" xfId=\"0\"{}{}/>",
+                    number_format,
+                    font,
+                    if *font > 0 { " applyFont=\"1\"" } else { "" },
+                    if *number_format > 0 { " applyNumberFormat=\"1\"" } else { "" },
+                }
+            })
+            .collect();
+        format! {
+            "{}<styleSheet xmlns=\"{}\"><fonts count=\"{}\">{}</fonts>\
+             <fills count=\"2\"><fill><patternFill patternType=\"none\"/></fill>\
+             <fill><patternFill patternType=\"gray125\"/></fill></fills>\
+             <borders count=\"1\"><border><left/><right/><top/><bottom/><diagonal/></border></borders>\
+             <cellStyleXfs count=\"1\"><xf numFmtId=\"0\" fontId=\"0\" fillId=\"0\" borderId=\"0\"/></cellStyleXfs>\
+             <cellXfs count=\"{}\">{}</cellXfs></styleSheet>",
+            XML_HEADER, MAIN_NS, self.fonts.len(), fonts, self.formats.len(), formats,
+        }
+    }
+}
+
+fn position_or_push<T: PartialEq>(items: &mut Vec<T>, item: T) -> usize {
+    match items.iter().position(|i| *i == item) {
+        Some(i) => i,
+        None => {
+            items.push(item);
+            items.len() - 1
+        }
+    }
+}
+
+// a CSS color as ARGB, e.g. "FFFF0000" for "red" or "#f00", `None` for colors that aren't
+// hex colors or one of the basic named ones
+fn argb_color(color: &str) -> Option<String> {
+    let color = color.trim().to_lowercase();
+    let rgb = match color.as_str() {
+        "black" => "000000",
+        "white" => "ffffff",
+        "red" => "ff0000",
+        "green" => "008000",
+        "blue" => "0000ff",
+        "yellow" => "ffff00",
+        "orange" => "ffa500",
+        "purple" => "800080",
+        "gray" | "grey" => "808080",
+        "silver" => "c0c0c0",
+        "maroon" => "800000",
+        "olive" => "808000",
+        "lime" => "00ff00",
+        "aqua" | "cyan" => "00ffff",
+        "teal" => "008080",
+        "navy" => "000080",
+        "fuchsia" | "magenta" => "ff00ff",
+        hex => hex.strip_prefix('#')?,
+    };
+    let rgb: String = match rgb.len() {
+        3 => rgb.chars().flat_map(|c| vec![c, c]).collect(),
+        6 => rgb.to_string(),
+        _ => return None,
+    };
+    if !rgb.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(format! {"FF{}", rgb.to_uppercase()})
+}
+
+// text as XML character data, leaving out the control characters XML can't have
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn sheet_xml(sheet: &WorkbookSheet, styles: &mut StyleTable) -> String {
+    let mut xml =
+        format! {"{}<worksheet xmlns=\"{}\" xmlns:r=\"{}\">", XML_HEADER, MAIN_NS, REL_NS};
+    if !sheet.col_widths.is_empty() {
+        xml.push_str("<cols>");
+        for (col, width) in sheet.col_widths.iter() {
+            xml.push_str(&format! {
+                "<col min=\"{0}\" max=\"{0}\" width=\"{1:.2}\" customWidth=\"1\"/>",
+                col, width / XLSX_CHAR_WIDTH,
+            });
+        }
+        xml.push_str("</cols>");
+    }
+    xml.push_str("<sheetData>");
+    let mut row = 0;
+    for (&(r, c), cell) in sheet.cells.iter() {
+        if r != row {
+            if row != 0 {
+                xml.push_str("</row>");
+            }
+            xml.push_str(&format! {"<row r=\"{}\">", r});
+            row = r;
+        }
+        let format = match styles.format(cell) {
+            0 => String::new(),
+            format => format! {" s=\"{}\"", format},
+        };
+        let cell_ref = cell_ref((r, c));
+        let number = |n: f64| format! {"<c r=\"{}\"{}><v>{}</v></c>", cell_ref, format, n};
+        xml.push_str(&match &cell.value {
+            CellValue::Number(n) if n.is_finite() => number(*n),
+            CellValue::Date(date) => number(date_serial(date)),
+            CellValue::Bool(b) => format! {
+                "<c r=\"{}\"{} t=\"b\"><v>{}</v></c>", cell_ref, format, if *b { 1 } else { 0 },
+            },
+            value => format! {
+                "<c r=\"{}\"{} t=\"inlineStr\"><is><t xml:space=\"preserve\">{}</t></is></c>",
+                cell_ref, format, escape_xml(&value.to_string()),
+            },
+        });
+    }
+    if row != 0 {
+        xml.push_str("</row>");
+    }
+    xml.push_str("</sheetData>");
+    if !sheet.merges.is_empty() {
+        xml.push_str(&format! {"<mergeCells count=\"{}\">", sheet.merges.len()});
+        for &(start, end) in sheet.merges.iter() {
+            xml.push_str(&format! {"<mergeCell ref=\"{}:{}\"/>", cell_ref(start), cell_ref(end)});
+        }
+        xml.push_str("</mergeCells>");
+    }
+    if !sheet.links.is_empty() {
+        xml.push_str("<hyperlinks>");
+        for (&at, name) in sheet.links.iter() {
+            xml.push_str(&format! {
+                "<hyperlink ref=\"{}\" location=\"'{}'!A1\" display=\"{}\"/>",
+                cell_ref(at), escape_xml(&name.replace('\'', "''")), escape_xml(name),
+            });
+        }
+        xml.push_str("</hyperlinks>");
+    }
+    xml.push_str("</worksheet>");
+    xml
+}
+
+// the contents of an .xlsx file of the workbook
+pub fn write_xlsx(workbook: &Workbook) -> Result<Vec<u8>, String> {
+    if workbook.sheets.is_empty() {
+        return Err("a workbook needs at least one sheet".to_string());
+    }
+    let mut styles = StyleTable::new();
+    let sheets: Vec<String> = workbook
+        .sheets
+        .iter()
+        .map(|sheet| sheet_xml(sheet, &mut styles))
+        .collect();
+    let numbered = || (1..=sheets.len()).zip(workbook.sheets.iter());
+
+    let sheet_types: String = (1..=sheets.len())
+        .map(|i| {
+            format! {
+                "<Override PartName=\"/xl/worksheets/sheet{}.xml\" ContentType=\"application/\
+                 vnd.openxmlformats-officedocument.spreadsheetml.worksheet+xml\"/>",
+                i,
+            }
+        })
+        .collect();
+    let content_types = format! {
+        "{}<Types xmlns=\"http://schemas.openxmlformats.org/package/2006/content-types\">\
+         <Default Extension=\"rels\" ContentType=\"application/vnd.openxmlformats-package.relationships+xml\"/>\
+         <Default Extension=\"xml\" ContentType=\"application/xml\"/>\
+         <Override PartName=\"/xl/workbook.xml\" ContentType=\"application/\
+         vnd.openxmlformats-officedocument.spreadsheetml.sheet.main+xml\"/>\
+         <Override PartName=\"/xl/styles.xml\" ContentType=\"application/\
+         vnd.openxmlformats-officedocument.spreadsheetml.styles+xml\"/>{}</Types>",
+        XML_HEADER, sheet_types,
+    };
+    let package_rels = format! {
+        "{}<Relationships xmlns=\"{}\"><Relationship Id=\"rId1\" Type=\"{}/officeDocument\" \
+         Target=\"xl/workbook.xml\"/></Relationships>",
+        XML_HEADER, PACKAGE_REL_NS, REL_NS,
+    };
+    let sheet_entries: String = numbered()
+        .map(|(i, sheet)| {
+            format! {
+                "<sheet name=\"{}\" sheetId=\"{}\" r:id=\"rId{}\"/>", escape_xml(&sheet.name), i, i,
+            }
+        })
+        .collect();
+    let workbook_xml = format! {
+        "{}<workbook xmlns=\"{}\" xmlns:r=\"{}\"><sheets>{}</sheets></workbook>",
+        XML_HEADER, MAIN_NS, REL_NS, sheet_entries,
+    };
+    let sheet_rels: String = (1..=sheets.len())
+        .map(|i| format! {
+            "<Relationship Id=\"rId{0}\" Type=\"{1}/worksheet\" Target=\"worksheets/sheet{0}.xml\"/>",
+            i, REL_NS,
+        })
+        .collect();
+    let workbook_rels = format! {
+        "{}<Relationships xmlns=\"{}\">{}<Relationship Id=\"rIdStyles\" Type=\"{}/styles\" \
+         Target=\"styles.xml\"/></Relationships>",
+        XML_HEADER, PACKAGE_REL_NS, sheet_rels, REL_NS,
+    };
+
+    let mut files = vec![
+        ("[Content_Types].xml".to_string(), content_types),
+        ("_rels/.rels".to_string(), package_rels),
+        ("xl/workbook.xml".to_string(), workbook_xml),
+        ("xl/_rels/workbook.xml.rels".to_string(), workbook_rels),
+        ("xl/styles.xml".to_string(), styles.xml()),
+    ];
+    for (i, sheet) in sheets.into_iter().enumerate() {
+        files.push((format! {"xl/worksheets/sheet{}.xml", i + 1}, sheet));
+    }
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (path, contents) in files {
+        zip.start_file(path, FileOptions::default())
+            .and_then(|_| zip.write_all(contents.as_bytes()).map_err(Into::into))
+            .map_err(|e| format! {"cannot write the workbook: {}", e})?;
+    }
+    zip.finish()
+        .map(Cursor::into_inner)
+        .map_err(|e| format! {"cannot write the workbook: {}", e})
+}
+
+// XML
+// Workbooks are read into a tree of elements, which are small enough to keep in memory.
+// Names are kept without their namespace prefix ("table:table-cell" is "table-cell").
+
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Element(Element),
+    Text(String),
+}
+
+impl Element {
+    fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(e) => Some(e),
+            Node::Text(_) => None,
+        })
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.elements().filter(move |e| e.name == name)
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.elements().find(|e| e.name == name)
+    }
+
+    // all the text in the element
+    fn text(&self) -> String {
+        self.children
+            .iter()
+            .map(|node| match node {
+                Node::Element(e) => e.text(),
+                Node::Text(text) => text.clone(),
+            })
+            .collect()
+    }
+}
+
+fn local_name(name: &[u8]) -> String {
+    let name = String::from_utf8_lossy(name);
+    match name.rfind(':') {
+        Some(i) => name[i + 1..].to_string(),
+        None => name.to_string(),
+    }
+}
+
+fn parse_xml(xml: &str) -> Result<Element, String> {
+    let mut reader = Reader::from_str(xml);
+    let mut buf = Vec::new();
+    // the elements being read, the first one holding the root element
+    let mut open = vec![Element::default()];
+    let start = |reader: &Reader<&[u8]>, e: &BytesStart| -> Result<Element, String> {
+        let attributes = e
+            .attributes()
+            .map(|a| {
+                let a = a.map_err(|e| e.to_string())?;
+                let value = a
+                    .unescape_and_decode_value(reader)
+                    .map_err(|e| e.to_string())?;
+                Ok((local_name(a.key), value))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Element {
+            name: local_name(e.name()),
+            attributes,
+            children: vec![],
+        })
+    };
+    loop {
+        let node = match reader.read_event(&mut buf) {
+            Ok(Event::Start(e)) => {
+                open.push(start(&reader, &e)?);
+                None
+            }
+            Ok(Event::Empty(e)) => Some(Node::Element(start(&reader, &e)?)),
+            Ok(Event::End(_)) => match open.pop() {
+                Some(element) if !open.is_empty() => Some(Node::Element(element)),
+                _ => return Err("unexpected end of an element".to_string()),
+            },
+            Ok(Event::Text(e)) => Some(Node::Text(
+                e.unescape_and_decode(&reader).map_err(|e| e.to_string())?,
+            )),
+            Ok(Event::CData(e)) => Some(Node::Text(String::from_utf8_lossy(&e).to_string())),
+            Ok(Event::Eof) => break,
+            Ok(_) => None,
+            Err(e) => return Err(format! {"{} at {}", e, reader.buffer_position()}),
+        };
+        if let (Some(node), Some(parent)) = (node, open.last_mut()) {
+            parent.children.push(node);
+        }
+        buf.clear();
+    }
+    match open.pop() {
+        Some(document) if open.is_empty() => document
+            .elements()
+            .next()
+            .cloned()
+            .ok_or_else(|| "there's no element".to_string()),
+        _ => Err("unexpected end of the document".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coordinate::{CoordinateParser, Rule};
+    use crate::grammar_map::build_grammar_map;
+    use crate::util::non_zero_u32_tuple;
+    use crate::{coord, g, grid};
+    use pest::Parser;
+    use std::num::NonZeroU32;
+
+    fn zip_files(files: &[(&str, &str)]) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (path, contents) in files {
+            zip.start_file(*path, FileOptions::default()).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn text(s: &str) -> CellValue {
+        CellValue::Text(s.to_string())
+    }
+
+    #[test]
+    fn test_read_xlsx() {
+        let bytes = zip_files(&[
+            (
+                "xl/workbook.xml",
+                r#"<workbook xmlns:r="rels"><sheets>
+                    <sheet name="Budget" sheetId="1" r:id="rId1"/>
+                </sheets></workbook>"#,
+            ),
+            (
+                "xl/_rels/workbook.xml.rels",
+                r#"<Relationships>
+                    <Relationship Id="rId1" Target="worksheets/sheet1.xml"/>
+                </Relationships>"#,
+            ),
+            (
+                "xl/sharedStrings.xml",
+                r#"<sst><si><t>Item &amp; cost</t></si><si><r><t>fo</t></r><r><t>od</t></r></si>
+                    <si><t>true</t></si></sst>"#,
+            ),
+            (
+                "xl/styles.xml",
+                r#"<styleSheet><numFmts><numFmt numFmtId="164" formatCode="[Red]dd/mm/yyyy"/></numFmts>
+                    <fonts><font/><font><b/><color rgb="FFFF0000"/></font></fonts>
+                    <cellXfs><xf fontId="0"/><xf fontId="1"/><xf numFmtId="164"/></cellXfs>
+                </styleSheet>"#,
+            ),
+            (
+                "xl/worksheets/sheet1.xml",
+                r#"<worksheet><cols><col min="2" max="3" width="20"/></cols><sheetData>
+                    <row r="1"><c r="A1" t="s" s="1"><v>0</v></c><c r="B1" t="s"><v>1</v></c></row>
+                    <row r="3"><c r="A3"><f>1+1</f><v>2.5</v></c><c t="b"><v>1</v></c>
+                        <c r="D3" s="2"><v>43968.5</v></c>
+                        <c r="E3" t="inlineStr"><is><t>inline</t></is></c></row>
+                    <row r="4"><c r="A4" t="s"><v>2</v></c></row>
+                </sheetData><mergeCells><mergeCell ref="A1:A2"/></mergeCells></worksheet>"#,
+            ),
+        ]);
+        let workbook = read_workbook(&bytes).unwrap();
+        assert_eq!(workbook.sheets.len(), 1);
+        let sheet = &workbook.sheets[0];
+        assert_eq!(sheet.name, "Budget");
+        assert_eq!(
+            sheet.cells[&(1, 1)],
+            WorkbookCell {
+                value: text("Item & cost"),
+                bold: true,
+                color: Some("#ff0000".to_string()),
+            }
+        );
+        assert_eq!(sheet.cells[&(1, 2)], WorkbookCell::new(text("food")));
+        assert_eq!(sheet.cells[&(3, 1)].value, CellValue::Number(2.5));
+        assert_eq!(sheet.cells[&(3, 2)].value, CellValue::Bool(true));
+        assert_eq!(
+            sheet.cells[&(3, 4)].value,
+            CellValue::Date(Date::parse("2020-05-17T12:00:00").unwrap())
+        );
+        assert_eq!(sheet.cells[&(3, 5)].value, text("inline"));
+        assert_eq!(sheet.merges, vec![((1, 1), (2, 1))]);
+        assert_eq!(sheet.col_widths.get(&2), Some(&140.0));
+        assert_eq!(sheet.col_widths.get(&1), None);
+        assert_eq!(sheet.size(), (4, 5));
+
+        // text that reads as another value stays text
+        let mut map = HashMap::new();
+        build_grammar_map(&mut map, coord!("root"), sheet_entry(sheet).unwrap());
+        assert_eq!(
+            map[&coord!("root-A4")].kind,
+            Kind::Input("'true".to_string())
+        );
+        assert_eq!(map[&coord!("root-A1")].style.row_span, (1, 2));
+        assert_eq!(map[&coord!("root-A1")].style.font_weight, 700);
+        assert!(!map[&coord!("root-A2")].style.display);
+        assert_eq!(map[&coord!("root-B1")].style.width, 140.0);
+        assert_eq!(
+            map[&coord!("root-D3")].kind,
+            Kind::Input("2020-05-17T12:00:00".to_string())
+        );
+    }
+
+    #[test]
+    fn test_read_ods() {
+        let bytes = zip_files(&[(
+            "content.xml",
+            r##"<office:document-content xmlns:office="o" xmlns:table="t" xmlns:text="x"
+                xmlns:style="s" xmlns:fo="f">
+              <office:automatic-styles>
+                <style:style style:name="co1"><style:table-column-properties style:column-width="2.54cm"/></style:style>
+                <style:style style:name="ce1"><style:text-properties fo:font-weight="bold" fo:color="#0000FF"/></style:style>
+              </office:automatic-styles>
+              <office:body><office:spreadsheet>
+                <table:table table:name="Sheet 1">
+                  <table:table-column table:style-name="co1" table:number-columns-repeated="2"/>
+                  <table:table-column table:default-cell-style-name="ce1"/>
+                  <table:table-header-rows><table:table-row>
+                    <table:table-cell table:number-columns-spanned="2" office:value-type="string">
+                      <text:p>a<text:s text:c="2"/>b</text:p><text:p>c</text:p>
+                    </table:table-cell>
+                    <table:covered-table-cell/>
+                    <table:table-cell office:value-type="float" office:value="0.5"><text:p>50%</text:p></table:table-cell>
+                  </table:table-row></table:table-header-rows>
+                  <table:table-row table:number-rows-repeated="2">
+                    <table:table-cell table:number-columns-repeated="2" office:value-type="boolean" office:boolean-value="true"/>
+                    <table:table-cell office:value-type="date" office:date-value="2020-05-17"/>
+                  </table:table-row>
+                  <table:table-row table:number-rows-repeated="1048570"><table:table-cell/></table:table-row>
+                </table:table>
+                <table:table table:name="Empty"/>
+              </office:spreadsheet></office:body>
+            </office:document-content>"##,
+        )]);
+        let workbook = read_workbook(&bytes).unwrap();
+        let names: Vec<&str> = workbook.sheets.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["Sheet 1", "Empty"]);
+        let sheet = &workbook.sheets[0];
+        assert_eq!(sheet.cells[&(1, 1)].value, text("a  b\nc"));
+        assert_eq!(sheet.merges, vec![((1, 1), (1, 2))]);
+        assert_eq!(
+            sheet.cells[&(1, 3)],
+            WorkbookCell {
+                value: CellValue::Number(0.5),
+                bold: true,
+                color: Some("#0000ff".to_string()),
+            }
+        );
+        for &at in [(2, 1), (2, 2), (3, 1), (3, 2)].iter() {
+            assert_eq!(sheet.cells[&at].value, CellValue::Bool(true));
+        }
+        assert_eq!(
+            sheet.cells[&(3, 3)].value,
+            CellValue::Date(Date::parse("2020-05-17").unwrap())
+        );
+        assert_eq!(sheet.size(), (3, 3));
+        assert_eq!(sheet.col_widths.get(&2), Some(&96.0));
+        assert_eq!(workbook.sheets[1].size(), (1, 1));
+
+        assert!(read_workbook(b"not a zip").is_err());
+        assert!(read_workbook(&zip_files(&[("other.xml", "<a/>")])).is_err());
+
+        // spans past the end of the sheet stop at its last row and column
+        let bytes = zip_files(&[(
+            "content.xml",
+            r##"<office:document-content xmlns:office="o" xmlns:table="t" xmlns:text="x">
+              <office:body><office:spreadsheet><table:table table:name="Spans">
+                <table:table-row>
+                  <table:table-cell table:number-rows-spanned="4294967295"
+                    table:number-columns-spanned="4294967295" office:value-type="string">
+                    <text:p>a</text:p>
+                  </table:table-cell>
+                </table:table-row>
+              </table:table></office:spreadsheet></office:body>
+            </office:document-content>"##,
+        )]);
+        let workbook = read_workbook(&bytes).unwrap();
+        assert_eq!(
+            workbook.sheets[0].merges,
+            vec![((1, 1), (MAX_ROWS, MAX_COLS))]
+        );
+    }
+
+    #[test]
+    fn test_write_xlsx() {
+        let mut map = HashMap::new();
+        build_grammar_map(
+            &mut map,
+            coord!("root"),
+            grid![
+                [
+                    g!(Grammar::input("", "Total")),
+                    g!(Grammar::input("", "1,200"))
+                ],
+                [
+                    g!(Grammar::input("", "2020-05-17")),
+                    grid![[g!(Grammar::input("", "true")), g!(Grammar::text("", "<b>"))]]
+                ]
+            ],
+        );
+        let total = map.get_mut(&coord!("root-A1")).unwrap();
+        total.style.font_weight = 700;
+        total.style.font_color = "red".to_string();
+        total.style.col_span = (1, 1);
+        total.style.row_span = (1, 2);
+
+        let workbook = grid_workbook(&map, &coord!("root"));
+        let names: Vec<&str> = workbook.sheets.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["root", "root-B2"]);
+        assert_eq!(workbook.sheets[0].merges, vec![((1, 1), (2, 1))]);
+        assert_eq!(
+            workbook.sheets[0].links.get(&(2, 2)),
+            Some(&"root-B2".to_string())
+        );
+
+        // what's written reads back the same
+        let bytes = write_xlsx(&workbook).unwrap();
+        let read = read_workbook(&bytes).unwrap();
+        assert_eq!(read.sheets.len(), 2);
+        let (root, nested) = (&read.sheets[0], &read.sheets[1]);
+        assert_eq!(
+            root.cells[&(1, 1)],
+            WorkbookCell {
+                value: text("Total"),
+                bold: true,
+                color: Some("#ff0000".to_string()),
+            }
+        );
+        assert_eq!(root.cells[&(1, 2)].value, CellValue::Number(1200.0));
+        assert_eq!(
+            root.cells[&(2, 1)].value,
+            CellValue::Date(Date::parse("2020-05-17").unwrap())
+        );
+        assert_eq!(root.cells[&(2, 2)].value, text("root-B2"));
+        assert_eq!(root.merges, vec![((1, 1), (2, 1))]);
+        assert_eq!(
+            root.col_widths.get(&2).cloned(),
+            Some(map[&coord!("root-B1")].style.width)
+        );
+        assert_eq!(nested.cells[&(1, 1)].value, CellValue::Bool(true));
+        assert_eq!(nested.cells[&(1, 2)].value, text("<b>"));
+    }
+
+    #[test]
+    fn test_helpers() {
+        assert_eq!(parse_cell_ref("B12"), Some((12, 2)));
+        assert_eq!(parse_cell_ref("$AA$3"), Some((3, 27)));
+        assert_eq!(parse_cell_ref("A0"), None);
+        assert_eq!(cell_ref((3, 27)), "AA3");
+        assert_eq!(cell_ref((1, 16384)), "XFD1");
+        assert_eq!(argb_color("#f00"), Some("FFFF0000".to_string()));
+        assert_eq!(argb_color("Navy"), Some("FF000080".to_string()));
+        assert_eq!(argb_color("rgb(1, 2, 3)"), None);
+        assert_eq!(hex_css_color("FF0000FF"), Some("#0000ff".to_string()));
+        assert_eq!(hex_css_color("aé00000"), None);
+        assert_eq!(length_px("1in"), Some(96.0));
+        assert!(is_date_format("yyyy-mm-dd") && !is_date_format("\"days\" 0.00"));
+        assert_eq!(date_serial(&Date::parse("1970-01-01").unwrap()), 25569.0);
+
+        let mut names = HashSet::new();
+        let long = coord!("root-A1-A1-A1-A1-A1-A1-A1-A1-A1-A1");
+        assert_eq!(
+            sheet_name(&long, &mut names),
+            "root-A1-A1-A1-A1-A1-A1-A1-A1-A1"
+        );
+        assert_eq!(
+            sheet_name(&long, &mut names),
+            "root-A1-A1-A1-A1-A1-A1-A1-A1-~2"
+        );
+    }
+}
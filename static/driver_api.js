@@ -0,0 +1,60 @@
+// Driver API
+//
+// Drivers are loaded from a driver directory (see Settings > load driver). The main file
+// of a driver registers the driver with the sheet by calling:
+//
+//   ISE.registerDriver({
+//     name: "js",                    // unique name of the driver
+//     definitions: ["js_grammar"],   // names of the definitions this driver evaluates
+//     evaluate: function (request) { // request: { id, driver, definition, root, grammars }
+//       return value;                // any JSON value, or a Promise of one
+//     },
+//   });
+//
+// The Rust side (src/driver.rs) listens to registrations through `ISE.onRegister` and
+// sends evaluation requests through `ISE.evaluate`.
+window.ISE = window.ISE || {
+  drivers: {},
+
+  // set by the Rust side when the app starts
+  onRegister: null,
+
+  registerDriver: function (driver) {
+    if (!driver || typeof driver.name !== "string" || driver.name === "") {
+      throw new Error("ISE.registerDriver: a driver needs a `name`");
+    }
+    if (!Array.isArray(driver.definitions)) {
+      throw new Error("ISE.registerDriver: driver \"" + driver.name + "\" needs a list of `definitions`");
+    }
+    if (typeof driver.evaluate !== "function") {
+      throw new Error("ISE.registerDriver: driver \"" + driver.name + "\" needs an `evaluate` function");
+    }
+    this.drivers[driver.name] = driver;
+    if (this.onRegister) {
+      this.onRegister(JSON.stringify({
+        name: driver.name,
+        definitions: driver.definitions.map(String),
+      }));
+    }
+  },
+
+  // `respond` is called exactly once with a JSON encoded DriverResponse
+  evaluate: function (name, requestJson, respond) {
+    let request = JSON.parse(requestJson);
+    let driver = this.drivers[name];
+    let reply = function (result) {
+      respond(JSON.stringify({ id: request.id, result: result }));
+    };
+    if (!driver) {
+      reply({ Error: "driver \"" + name + "\" is not loaded" });
+      return;
+    }
+    Promise.resolve()
+      .then(function () { return driver.evaluate(request); })
+      .then(function (value) {
+        reply({ Value: value === undefined ? null : value });
+      }, function (error) {
+        reply({ Error: String(error && error.message ? error.message : error) });
+      });
+  },
+};
@@ -55,15 +55,397 @@ function createProtocol (scheme, normalize = true) {
 
 // IPC: Communication between Electron main.js and Rust src/lib.rs
 const { ipcMain } = require('electron');
+
+// keep in sync with DRIVER_FILE_TYPES and MAX_DRIVER_FILE_SIZE in src/driver.rs
+const DRIVER_FILE_TYPES = [".js", ".json", ".css", ".html", ".txt", ".md", ".py", ".svg", ".csv"];
+const MAX_DRIVER_FILE_SIZE = 5 * 1024 * 1024;
+
 ipcMain.on('upload-driver-misc-file', (event, args) => {
   console.log(args);
 
+  if (!Array.isArray(args) || typeof args[0] !== "string" || typeof args[1] !== "string"
+    || !DRIVER_FILE_TYPES.includes(extname(args[0]).toLowerCase())
+    || Buffer.byteLength(args[1]) > MAX_DRIVER_FILE_SIZE) {
+    event.returnValue = false;
+    return;
+  }
+
   driverMiscFiles[args[0]] = args[1];
 
   // respond with success or failure (true/false)
   event.returnValue = true;
 });
 
+// Settings files (e.g. the keymap, see src/keymap.rs) in the user config directory,
+// answers with the contents of the file, or null if there's none
+const { join, basename } = require("path");
+const { readFileSync, writeFileSync } = require("fs");
+
+function isPlainFileName(name) {
+  return typeof name === "string" && name !== "" && basename(name) === name;
+}
+
+// what the File Explorer below may touch, the renderer can't read or write it
+const EXPLORER_ACCESS_FILE = "explorer-access.json";
+
+function isConfigFile(name) {
+  return isPlainFileName(name) && name !== EXPLORER_ACCESS_FILE;
+}
+
+ipcMain.on('read-config-file', (event, args) => {
+  // only plain file names, so nothing outside of the config directory can be read
+  if (!Array.isArray(args) || !isConfigFile(args[0])) {
+    event.returnValue = null;
+    return;
+  }
+  try {
+    event.returnValue = readFileSync(join(app.getPath("userData"), args[0]), "utf8");
+  } catch (e) {
+    event.returnValue = null;
+  }
+});
+
+// writes a settings file, answers with false if it couldn't be written
+ipcMain.on('write-config-file', (event, args) => {
+  if (!Array.isArray(args) || !isConfigFile(args[0]) || typeof args[1] !== "string") {
+    event.returnValue = false;
+    return;
+  }
+  try {
+    writeFileSync(join(app.getPath("userData"), args[0]), args[1], "utf8");
+    event.returnValue = true;
+  } catch (e) {
+    event.returnValue = false;
+  }
+});
+
+// File Explorer (see src/explorer.rs): session files in the workspace directory the user
+// chose, and the ones opened recently. The workspace and the recent files are kept here
+// rather than taken from the renderer, and only session files in the workspace or among
+// the recent files can be read, renamed, duplicated or deleted through these.
+const { dialog } = require("electron");
+const { readdirSync, statSync, renameSync, copyFileSync, unlinkSync, existsSync } = require("fs");
+const { dirname, resolve } = require("path");
+
+// keep in sync with SESSION_FILE_TYPES and MAX_RECENT_FILES in src/explorer.rs
+const SESSION_FILE_TYPES = [".ise", ".json"];
+const MAX_RECENT_FILES = 10;
+
+function isSessionFile(path) {
+  return typeof path === "string" && SESSION_FILE_TYPES.includes(extname(path).toLowerCase());
+}
+
+let explorerAccess = null;
+
+function accessFilePath() {
+  return join(app.getPath("userData"), EXPLORER_ACCESS_FILE);
+}
+
+// the workspace and the recent files, read from EXPLORER_ACCESS_FILE the first time
+function access() {
+  if (explorerAccess === null) {
+    explorerAccess = { workspace: null, recent: [] };
+    try {
+      let saved = JSON.parse(readFileSync(accessFilePath(), "utf8"));
+      if (typeof saved.workspace === "string") {
+        explorerAccess.workspace = saved.workspace;
+      }
+      if (Array.isArray(saved.recent)) {
+        explorerAccess.recent = saved.recent.filter((p) => typeof p === "string");
+      }
+    } catch (e) {}
+  }
+  return explorerAccess;
+}
+
+function saveAccess() {
+  try {
+    writeFileSync(accessFilePath(), JSON.stringify(access()), "utf8");
+  } catch (e) {}
+}
+
+// the full path of a session file the explorer may touch, or null
+function allowedSessionFile(path) {
+  if (!isSessionFile(path)) {
+    return null;
+  }
+  let full = resolve(path);
+  let { workspace, recent } = access();
+  return (workspace !== null && dirname(full) === workspace) || recent.includes(full) ? full : null;
+}
+
+// like `Explorer::opened` and `Explorer::moved` in src/explorer.rs
+function openedRecent(path) {
+  let recent = access().recent.filter((p) => p !== path);
+  access().recent = [path].concat(recent).slice(0, MAX_RECENT_FILES);
+  saveAccess();
+}
+
+function movedRecent(path, newPath) {
+  access().recent = access().recent
+    .map((p) => (p === path ? newPath : p))
+    .filter((p) => p !== null);
+  saveAccess();
+}
+
+function sessionTitle(path) {
+  try {
+    let title = JSON.parse(readFileSync(path, "utf8")).title;
+    return typeof title === "string" ? title : null;
+  } catch (e) {
+    return null;
+  }
+}
+
+// answers with the chosen directory, or null if none was chosen
+ipcMain.on('choose-workspace', (event) => {
+  let paths = dialog.showOpenDialogSync(win, { properties: ["openDirectory"] });
+  if (paths && paths.length > 0) {
+    access().workspace = resolve(paths[0]);
+    saveAccess();
+  }
+  event.returnValue = paths && paths.length > 0 ? paths[0] : null;
+});
+
+// answers with the JSON encoded session files of the workspace, or null if it can't be read
+ipcMain.on('list-session-files', (event, args) => {
+  if (!Array.isArray(args) || typeof args[0] !== "string"
+    || resolve(args[0]) !== access().workspace) {
+    event.returnValue = null;
+    return;
+  }
+  try {
+    let files = readdirSync(args[0])
+      .map((name) => join(args[0], name))
+      .filter((path) => isSessionFile(path) && statSync(path).isFile())
+      .map((path) => ({
+        name: basename(path),
+        path,
+        modified: statSync(path).mtimeMs,
+        title: sessionTitle(path),
+      }));
+    event.returnValue = JSON.stringify(files);
+  } catch (e) {
+    event.returnValue = null;
+  }
+});
+
+// answers with the contents of the session file, or null if it can't be read
+ipcMain.on('read-session-file', (event, args) => {
+  let path = Array.isArray(args) ? allowedSessionFile(args[0]) : null;
+  if (path === null) {
+    event.returnValue = null;
+    return;
+  }
+  try {
+    event.returnValue = readFileSync(path, "utf8");
+    openedRecent(path);
+  } catch (e) {
+    event.returnValue = null;
+  }
+});
+
+// renames (or copies, when duplicating) a session file to another name in its directory,
+// never overwriting a file, answers with false if it couldn't
+function toSibling(args, action) {
+  let path = Array.isArray(args) ? allowedSessionFile(args[0]) : null;
+  if (path === null || !isPlainFileName(args[1]) || !isSessionFile(args[1])) {
+    return null;
+  }
+  let target = join(dirname(path), args[1]);
+  if (existsSync(target)) {
+    return null;
+  }
+  try {
+    action(path, target);
+    return [path, target];
+  } catch (e) {
+    return null;
+  }
+}
+
+ipcMain.on('rename-session-file', (event, args) => {
+  let renamed = toSibling(args, renameSync);
+  if (renamed !== null) {
+    movedRecent(renamed[0], renamed[1]);
+  }
+  event.returnValue = renamed !== null;
+});
+
+ipcMain.on('duplicate-session-file', (event, args) => {
+  event.returnValue = toSibling(args, copyFileSync) !== null;
+});
+
+ipcMain.on('delete-session-file', (event, args) => {
+  let path = Array.isArray(args) ? allowedSessionFile(args[0]) : null;
+  if (path === null) {
+    event.returnValue = false;
+    return;
+  }
+  try {
+    unlinkSync(path);
+    movedRecent(path, null);
+    event.returnValue = true;
+  } catch (e) {
+    event.returnValue = false;
+  }
+});
+
+// JSON documents edited as grids (see src/json_grid.rs)
+ipcMain.on('open-json-file', (event) => {
+  let paths = dialog.showOpenDialogSync(win, {
+    properties: ["openFile"],
+    filters: [{ name: "JSON", extensions: ["json"] }],
+  });
+  // null if no file was chosen, false if it can't be read
+  if (!paths || paths.length === 0) {
+    event.returnValue = null;
+    return;
+  }
+  try {
+    event.returnValue = readFileSync(paths[0], "utf8");
+  } catch (e) {
+    event.returnValue = false;
+  }
+});
+
+ipcMain.on('save-json-file', (event, args) => {
+  if (!Array.isArray(args) || typeof args[0] !== "string" || typeof args[1] !== "string") {
+    event.returnValue = false;
+    return;
+  }
+  let path = dialog.showSaveDialogSync(win, {
+    defaultPath: args[0],
+    filters: [{ name: "JSON", extensions: ["json"] }],
+  });
+  if (!path) {
+    event.returnValue = null;
+    return;
+  }
+  try {
+    writeFileSync(path, args[1], "utf8");
+    event.returnValue = true;
+  } catch (e) {
+    event.returnValue = false;
+  }
+});
+
+// workbooks are handed to the renderer base64 encoded, see WorkbookFile in src/workbook.rs
+ipcMain.on('open-workbook-file', (event) => {
+  let paths = dialog.showOpenDialogSync(win, {
+    properties: ["openFile"],
+    filters: [{ name: "Workbooks", extensions: ["xlsx", "ods"] }],
+  });
+  // null if no file was chosen, false if it can't be read
+  if (!paths || paths.length === 0) {
+    event.returnValue = null;
+    return;
+  }
+  try {
+    event.returnValue = JSON.stringify({
+      name: basename(paths[0]),
+      data: readFileSync(paths[0]).toString("base64"),
+    });
+  } catch (e) {
+    event.returnValue = false;
+  }
+});
+
+ipcMain.on('save-workbook-file', (event, args) => {
+  if (!Array.isArray(args) || typeof args[0] !== "string" || typeof args[1] !== "string") {
+    event.returnValue = false;
+    return;
+  }
+  let file = dialog.showSaveDialogSync(win, {
+    defaultPath: args[0],
+    filters: [{ name: "Excel workbook", extensions: ["xlsx"] }],
+  });
+  if (!file) {
+    event.returnValue = null;
+    return;
+  }
+  try {
+    writeFileSync(file, Buffer.from(args[1], "base64"));
+    event.returnValue = true;
+  } catch (e) {
+    event.returnValue = false;
+  }
+});
+
+// Python execution backend (NodePythonBackend in src/execution.rs)
+// `runner` defines `__ise_run`, which reads the JSON encoded request from stdin and
+// returns the JSON encoded result, so stdout of the process is only ever the result.
+const { spawn } = require("child_process");
+const PYTHON = process.env.ISE_PYTHON || "python3";
+let pythonProcesses = {};
+
+function pythonError(id, kind, message, stderr) {
+  return JSON.stringify({
+    id,
+    stdout: "",
+    stderr: stderr || "",
+    outcome: { Err: { kind, message, traceback: "" } },
+  });
+}
+
+ipcMain.handle('run-python', (event, requestJson, runner) => new Promise((resolve) => {
+  let request = JSON.parse(requestJson);
+  let python = spawn(PYTHON, [
+    "-c",
+    runner + "\nimport sys\nsys.__stdout__.write(__ise_run(sys.stdin.read()))",
+  ]);
+  pythonProcesses[request.id] = python;
+  let out = "";
+  let err = "";
+  python.stdout.on("data", (data) => { out += data; });
+  python.stderr.on("data", (data) => { err += data; });
+  python.on("error", (e) => {
+    delete pythonProcesses[request.id];
+    resolve(pythonError(request.id, "Backend", `could not start ${PYTHON}: ${e.message}`));
+  });
+  python.on("close", (code, signal) => {
+    delete pythonProcesses[request.id];
+    if (signal) {
+      resolve(pythonError(request.id, "Cancelled", `killed by ${signal}`, err));
+    } else if (code !== 0) {
+      resolve(pythonError(request.id, "Backend", `${PYTHON} exited with code ${code}`, err));
+    } else {
+      resolve(out);
+    }
+  });
+  python.stdin.end(requestJson);
+}));
+
+ipcMain.on('cancel-python', (event, id) => {
+  if (pythonProcesses[id]) {
+    pythonProcesses[id].kill();
+  }
+});
+
+
+// Collaboration (see src/collab.rs and relay.js): a relay started from the app runs in the
+// main process until the app quits and listens on this computer only. It accepts the
+// sockets with the random token it's started with, which is only handed to the renderer,
+// in the URL of the relay this answers with. If the relay can't listen the connection
+// fails.
+const { startRelay, relayToken, relayUrl, DEFAULT_RELAY_PORT, DEFAULT_RELAY_HOST } =
+  require("./relay.js");
+let startedRelayUrl = null;
+
+ipcMain.on('start-relay', (event, args) => {
+  if (!startedRelayUrl) {
+    const token = relayToken();
+    startedRelayUrl = relayUrl(DEFAULT_RELAY_PORT, DEFAULT_RELAY_HOST, token);
+    startRelay(DEFAULT_RELAY_PORT, DEFAULT_RELAY_HOST, token, error => {
+      if (error) {
+        console.error(`cannot start the relay: ${error.message}`);
+        startedRelayUrl = null;
+      }
+    });
+  }
+  event.returnValue = startedRelayUrl;
+});
+
 
 // standard scheme must be registered before the app is ready
 // https://gist.github.com/dbkr/e898624be6d53590ebf494521d868fec
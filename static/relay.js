@@ -0,0 +1,242 @@
+// RELAY
+// A small WebSocket server for editing a session with other people (see src/collab.rs). It
+// numbers the apps connected to it ("sites"), puts the operations they send in one order
+// and forwards them to every site. The session that was shared last and the operations
+// since are kept for the sites that connect later. It doesn't know about grids, every site
+// applies the operations itself.
+// It's started from the "Collaborate" menu of the app, or on its own with
+// `npm run relay -- [port] [host]`. It only listens on this computer unless it's given
+// another host, e.g. `npm run relay -- 8765 0.0.0.0` to edit with people on the same
+// network. Listening on this computer isn't enough to keep the session private though: any
+// web page open in a browser can connect to a port of this computer. So the relay only
+// accepts sockets whose URL has the random token it was started with, anyone given the URL
+// with the token can read the shared session and edit it.
+const http = require("http");
+const crypto = require("crypto");
+const { URL } = require("url");
+
+const DEFAULT_RELAY_PORT = 8765;
+const DEFAULT_RELAY_HOST = "127.0.0.1";
+// the largest message a site can send, sockets sending more are closed
+const MAX_MESSAGE_BYTES = 64 * 1024 * 1024;
+const WEBSOCKET_GUID = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+// a token for `startRelay`
+function relayToken() {
+  return crypto.randomBytes(16).toString("hex");
+}
+
+// the URL the sites connect to
+function relayUrl(port, host, token) {
+  return `ws://${host}:${port}/?token=${token}`;
+}
+
+// whether the URL of a request has the token, compared in constant time
+function hasToken(request, token) {
+  let given;
+  try {
+    given = new URL(request.url, "http://relay").searchParams.get("token") || "";
+  } catch (e) {
+    return false;
+  }
+  const expected = Buffer.from(token);
+  const actual = Buffer.from(given);
+  return actual.length === expected.length && crypto.timingSafeEqual(actual, expected);
+}
+
+// starts a relay on `port` of `host` accepting the sites connecting with `token`,
+// `callback` gets an error if it can't listen
+function startRelay(port, host, token, callback) {
+  let nextSite = 1;
+  const sockets = new Map();
+  const presences = new Map();
+  let shared = null;
+  let ops = [];
+
+  function send(socket, message) {
+    if (socket.writable) {
+      socket.write(encodeFrame(JSON.stringify(message)));
+    }
+  }
+
+  function broadcast(message) {
+    sockets.forEach(socket => send(socket, message));
+  }
+
+  function receive(site, text) {
+    let message;
+    try {
+      message = JSON.parse(text);
+    } catch (e) {
+      return;
+    }
+    if (message.Share) {
+      shared = { Shared: { site, session: message.Share } };
+      ops = [];
+      broadcast(shared);
+    } else if (message.Op) {
+      const op = { Op: { seq: ops.length + 1, site, base: message.Op.base, op: message.Op.op } };
+      ops.push(op);
+      broadcast(op);
+    } else if (message.Presence) {
+      const presence = { Presence: { site, presence: message.Presence } };
+      presences.set(site, presence);
+      broadcast(presence);
+    }
+  }
+
+  const server = http.createServer((request, response) => {
+    response.writeHead(426, { "Content-Type": "text/plain" });
+    response.end("this is a WebSocket relay\n");
+  });
+
+  server.on("upgrade", (request, socket) => {
+    const key = request.headers["sec-websocket-key"];
+    if (!key) {
+      socket.destroy();
+      return;
+    }
+    if (!hasToken(request, token)) {
+      socket.end("HTTP/1.1 403 Forbidden\r\nConnection: close\r\n\r\n");
+      return;
+    }
+    const accept = crypto.createHash("sha1").update(key + WEBSOCKET_GUID).digest("base64");
+    socket.write(
+      "HTTP/1.1 101 Switching Protocols\r\n" +
+      "Upgrade: websocket\r\n" +
+      "Connection: Upgrade\r\n" +
+      `Sec-WebSocket-Accept: ${accept}\r\n\r\n`
+    );
+    const site = nextSite++;
+    sockets.set(site, socket);
+    send(socket, { Welcome: { site } });
+    if (shared) {
+      send(socket, shared);
+      ops.forEach(op => send(socket, op));
+    }
+    presences.forEach(presence => send(socket, presence));
+
+    let buffer = Buffer.alloc(0);
+    let fragments = [];
+    let fragmentsLength = 0;
+    socket.on("data", data => {
+      buffer = Buffer.concat([buffer, data]);
+      if (buffer.length + fragmentsLength > MAX_MESSAGE_BYTES) {
+        tooBig(socket);
+        return;
+      }
+      let frame;
+      while ((frame = decodeFrame(buffer))) {
+        buffer = buffer.slice(frame.length);
+        if (frame.opcode === 0x8) {
+          socket.end(encodeFrame(Buffer.alloc(0), 0x8));
+          return;
+        } else if (frame.opcode === 0x9) {
+          socket.write(encodeFrame(frame.payload, 0xa));
+        } else if (frame.opcode === 0x1 || frame.opcode === 0x0) {
+          fragments.push(frame.payload);
+          fragmentsLength += frame.payload.length;
+          if (frame.fin) {
+            receive(site, Buffer.concat(fragments).toString("utf8"));
+            fragments = [];
+            fragmentsLength = 0;
+          }
+        }
+      }
+    });
+    const leave = () => {
+      if (sockets.delete(site)) {
+        presences.delete(site);
+        broadcast({ Left: { site } });
+      }
+    };
+    socket.on("close", leave);
+    socket.on("error", leave);
+  });
+
+  server.on("error", error => callback(error));
+  server.listen(port, host, () => callback(null));
+  return server;
+}
+
+// closes a socket that sent more than MAX_MESSAGE_BYTES, with the "message too big" status
+function tooBig(socket) {
+  const status = Buffer.alloc(2);
+  status.writeUInt16BE(1009);
+  socket.end(encodeFrame(status, 0x8));
+  socket.destroy();
+}
+
+// the first frame in `buffer`, or null if it isn't all there yet
+function decodeFrame(buffer) {
+  if (buffer.length < 2) {
+    return null;
+  }
+  const fin = (buffer[0] & 0x80) !== 0;
+  const opcode = buffer[0] & 0x0f;
+  const masked = (buffer[1] & 0x80) !== 0;
+  let length = buffer[1] & 0x7f;
+  let offset = 2;
+  if (length === 126) {
+    if (buffer.length < 4) {
+      return null;
+    }
+    length = buffer.readUInt16BE(2);
+    offset = 4;
+  } else if (length === 127) {
+    if (buffer.length < 10) {
+      return null;
+    }
+    length = buffer.readUInt32BE(2) * 2 ** 32 + buffer.readUInt32BE(6);
+    offset = 10;
+  }
+  const mask = masked ? buffer.slice(offset, offset + 4) : null;
+  if (masked) {
+    offset += 4;
+  }
+  if (buffer.length < offset + length) {
+    return null;
+  }
+  const payload = Buffer.from(buffer.slice(offset, offset + length));
+  if (mask) {
+    for (let i = 0; i < payload.length; i++) {
+      payload[i] ^= mask[i % 4];
+    }
+  }
+  return { fin, opcode, payload, length: offset + length };
+}
+
+// an unmasked frame, as servers send them
+function encodeFrame(payload, opcode = 0x1) {
+  const data = Buffer.isBuffer(payload) ? payload : Buffer.from(payload, "utf8");
+  let header;
+  if (data.length < 126) {
+    header = Buffer.from([0x80 | opcode, data.length]);
+  } else if (data.length < 65536) {
+    header = Buffer.alloc(4);
+    header[1] = 126;
+    header.writeUInt16BE(data.length, 2);
+  } else {
+    header = Buffer.alloc(10);
+    header[1] = 127;
+    header.writeUInt32BE(Math.floor(data.length / 2 ** 32), 2);
+    header.writeUInt32BE(data.length % 2 ** 32, 6);
+  }
+  header[0] = 0x80 | opcode;
+  return Buffer.concat([header, data]);
+}
+
+module.exports = { startRelay, relayToken, relayUrl, DEFAULT_RELAY_PORT, DEFAULT_RELAY_HOST };
+
+if (require.main === module) {
+  const port = Number(process.argv[2]) || DEFAULT_RELAY_PORT;
+  const host = process.argv[3] || DEFAULT_RELAY_HOST;
+  const token = relayToken();
+  startRelay(port, host, token, error => {
+    if (error) {
+      console.error(`cannot start the relay: ${error.message}`);
+      process.exit(1);
+    }
+    console.log(`relay listening, connect to ${relayUrl(port, host, token)}`);
+  });
+}